@@ -0,0 +1,282 @@
+//! Criterion benchmarks of the scanner, the string hash, the weather station
+//! table, and whole builds. Inputs are generated in memory from the bundled
+//! station list, so no input files are needed. Run with `cargo bench`, or
+//! e.g. `cargo bench -- scanner` to run one group.
+
+use std::{
+  hash::{BuildHasher, BuildHasherDefault, DefaultHasher},
+  hint::black_box,
+  time::Duration,
+};
+
+#[cfg(feature = "multithreaded")]
+use barse::{barse::build_temperature_reading_table_with_options, session::Session};
+use barse::{
+  barse::{PaddedInput, Table},
+  build_report::Strategy,
+  error::BarseResult,
+  generate::{default_stations, Generator, Station},
+  options::{BuildConfig, Options},
+  scanner::Scanner,
+  soa_table::SoaWeatherStationTable,
+  str_hash::str_hash,
+  temperature_reading::TemperatureReading,
+};
+use criterion::{
+  criterion_group, criterion_main, measurement::WallTime, BenchmarkGroup, BenchmarkId, Criterion,
+  Throughput,
+};
+
+const SEED: u64 = 0xbe9c4;
+/// The number of rows in the inputs of the scanner and table benchmarks.
+const ROWS: u64 = 1 << 20;
+/// The number of rows in the inputs of whole builds, which is large enough
+/// for every thread of a multithreaded build to get a few chunks.
+const BUILD_ROWS: u64 = 1 << 22;
+/// The number of rows in each of the small inputs summarized by a session.
+#[cfg(feature = "multithreaded")]
+const SESSION_ROWS: u64 = 1 << 15;
+
+fn stations() -> Vec<Station> {
+  default_stations().expect("Failed to read the bundled station list")
+}
+
+/// Generates `rows` rows over `unique_stations` of `stations` into a padded
+/// buffer.
+fn generate_input(stations: &[Station], unique_stations: usize, rows: u64) -> PaddedInput {
+  let mut input = Vec::new();
+  Generator::new(stations, unique_stations, SEED)
+    .and_then(|generator| generator.write(rows, &mut input))
+    .expect("Failed to generate input");
+  PaddedInput::new(&input).expect("Failed to allocate input")
+}
+
+/// Returns the longest prefix of `name` of at most `len` bytes which ends on
+/// a character boundary.
+fn truncate_name(name: &str, len: usize) -> &str {
+  let len = (0..=len.min(name.len()))
+    .rev()
+    .find(|&len| name.is_char_boundary(len))
+    .unwrap_or(0);
+  &name[..len]
+}
+
+/// Returns `stations` with their names cut to at most `max_len` bytes, and
+/// names shorter than `min_len` bytes repeated until they are at least that
+/// long.
+fn with_name_lengths(stations: &[Station], min_len: usize, max_len: usize) -> Vec<Station> {
+  stations
+    .iter()
+    .map(|station| {
+      let mut name = station.name.clone();
+      while name.len() < min_len {
+        name = format!("{name} {}", station.name);
+      }
+      Station {
+        name: truncate_name(&name, max_len).to_owned(),
+        mean: station.mean,
+      }
+    })
+    .collect()
+}
+
+/// Scans every record of `input`.
+fn scan(input: &PaddedInput) -> Vec<(&str, TemperatureReading)> {
+  Scanner::from_start_with_len(input.padded_slice(), input.len()).collect()
+}
+
+fn bench_scanner(c: &mut Criterion) {
+  let stations = stations();
+  let distributions = [
+    ("short", with_name_lengths(&stations, 2, 8)),
+    ("bundled", stations.clone()),
+    ("long", with_name_lengths(&stations, 32, 50)),
+  ];
+
+  let mut group = c.benchmark_group("scanner");
+  group.sample_size(20);
+  for (name, stations) in &distributions {
+    let input = generate_input(stations, stations.len(), ROWS);
+    group.throughput(Throughput::Bytes(input.len() as u64));
+    group.bench_with_input(BenchmarkId::from_parameter(name), &input, |b, input| {
+      b.iter(|| {
+        Scanner::from_start_with_len(black_box(input.padded_slice()), input.len())
+          .fold(0i64, |sum, (_, reading)| sum + reading.reading() as i64)
+      })
+    });
+  }
+  group.finish();
+}
+
+fn bench_hash(c: &mut Criterion) {
+  let stations = stations();
+  // Each station appears once, and is hashed from the padded input, which
+  // `str_hash` may read past the end of names in.
+  let input = generate_input(&stations, stations.len(), stations.len() as u64);
+  let names = scan(&input)
+    .into_iter()
+    .map(|(name, _)| name)
+    .collect::<Vec<_>>();
+
+  let mut group = c.benchmark_group("hash");
+  group.throughput(Throughput::Elements(names.len() as u64));
+  group.bench_function("str_hash", |b| {
+    b.iter(|| {
+      names
+        .iter()
+        .fold(0, |acc, name| acc ^ str_hash(black_box(name.as_bytes())))
+    })
+  });
+  let default_hasher = BuildHasherDefault::<DefaultHasher>::default();
+  group.bench_function("DefaultHasher", |b| {
+    b.iter(|| {
+      names.iter().fold(0, |acc, name| {
+        acc ^ default_hasher.hash_one(black_box(name))
+      })
+    })
+  });
+  group.finish();
+}
+
+/// Benchmarks adding every one of `records` to an empty table made by
+/// `new_table`.
+fn bench_inserts<T>(
+  group: &mut BenchmarkGroup<'_, WallTime>,
+  id: BenchmarkId,
+  records: &[(&str, TemperatureReading)],
+  new_table: impl Fn() -> BarseResult<T>,
+  add_reading: impl Fn(&mut T, &str, TemperatureReading),
+) {
+  group.bench_with_input(id, records, |b, records| {
+    b.iter_batched_ref(
+      || new_table().expect("Failed to allocate table"),
+      |table| {
+        for &(station, reading) in records {
+          add_reading(table, station, reading);
+        }
+      },
+      criterion::BatchSize::PerIteration,
+    )
+  });
+}
+
+fn bench_table(c: &mut Criterion) {
+  let stations = stations();
+
+  let mut group = c.benchmark_group("add_reading");
+  group.sample_size(20);
+  for unique_stations in [413, 10_000] {
+    let input = generate_input(&stations, unique_stations, ROWS);
+    let records = scan(&input);
+    let capacity = BuildConfig::table_capacity(unique_stations);
+    group.throughput(Throughput::Elements(records.len() as u64));
+    bench_inserts(
+      &mut group,
+      BenchmarkId::new("interleaved", unique_stations),
+      &records,
+      || Table::with_capacity(capacity),
+      Table::add_reading,
+    );
+    bench_inserts(
+      &mut group,
+      BenchmarkId::new("soa", unique_stations),
+      &records,
+      || SoaWeatherStationTable::with_capacity(capacity),
+      SoaWeatherStationTable::add_reading,
+    );
+  }
+  group.finish();
+}
+
+fn bench_build(c: &mut Criterion) {
+  let stations = stations();
+  let input = generate_input(&stations, 10_000, BUILD_ROWS);
+  // Named as with `--strategy`.
+  #[cfg(not(feature = "multithreaded"))]
+  let strategies = [("single", Strategy::SingleThreaded)];
+  #[cfg(feature = "multithreaded")]
+  let strategies = [
+    ("single", Strategy::SingleThreaded),
+    ("shared-table", Strategy::MultiThreaded),
+    ("per-thread-table", Strategy::PerThreadTables),
+  ];
+
+  let mut group = c.benchmark_group("build");
+  group.sample_size(10);
+  group.measurement_time(Duration::from_secs(5));
+  group.throughput(Throughput::Bytes(input.len() as u64));
+  for (name, strategy) in strategies {
+    let mut options = Options::default();
+    options.strategy = Some(strategy);
+    group.bench_with_input(BenchmarkId::from_parameter(name), &options, |b, options| {
+      b.iter(|| input.build(options).expect("Failed to build table"))
+    });
+  }
+  group.finish();
+}
+
+/// Summarizes `SESSION_FILES` small files one after another, through a
+/// `Session` and through the one-shot builder, which sets up new tables for
+/// each file.
+#[cfg(feature = "multithreaded")]
+fn bench_session(c: &mut Criterion) {
+  const SESSION_FILES: u64 = 100;
+  let stations = stations();
+  let dir = std::env::temp_dir().join(format!("barse_bench_{}_session", std::process::id()));
+  std::fs::create_dir_all(&dir).expect("Failed to create input directory");
+  let paths = (0..SESSION_FILES)
+    .map(|i| {
+      let mut input = Vec::new();
+      Generator::new(&stations, 10_000, SEED + i)
+        .and_then(|generator| generator.write(SESSION_ROWS, &mut input))
+        .expect("Failed to generate input");
+      let path = dir.join(format!("{i}.txt"));
+      std::fs::write(&path, input).expect("Failed to write input");
+      path.to_str().expect("temp paths are UTF-8").to_owned()
+    })
+    .collect::<Vec<_>>();
+  let bytes = paths
+    .iter()
+    .map(|path| std::fs::metadata(path).map_or(0, |metadata| metadata.len()))
+    .sum();
+
+  let options = Options::default();
+  let mut group = c.benchmark_group("session");
+  group.sample_size(10);
+  group.measurement_time(Duration::from_secs(5));
+  group.throughput(Throughput::Bytes(bytes));
+  group.bench_function("one-shot", |b| {
+    b.iter(|| {
+      for path in &paths {
+        let (table, _) = build_temperature_reading_table_with_options(path, &options)
+          .expect("Failed to build table");
+        black_box(table);
+      }
+    })
+  });
+  let mut session = Session::new(&options).expect("Failed to start session");
+  group.bench_function("session", |b| {
+    b.iter(|| {
+      for path in &paths {
+        let summary = session.aggregate(path).expect("Failed to build table");
+        black_box(summary.report().records);
+      }
+    })
+  });
+  group.finish();
+  drop(session);
+  std::fs::remove_dir_all(&dir).expect("Failed to remove input directory");
+}
+
+#[cfg(not(feature = "multithreaded"))]
+criterion_group!(benches, bench_scanner, bench_hash, bench_table, bench_build);
+#[cfg(feature = "multithreaded")]
+criterion_group!(
+  benches,
+  bench_scanner,
+  bench_hash,
+  bench_table,
+  bench_build,
+  bench_session
+);
+criterion_main!(benches);
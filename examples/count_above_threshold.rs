@@ -0,0 +1,80 @@
+//! Counts the readings of each station above a threshold, e.g.
+//!
+//! ```text
+//! cargo run --release --example count_above_threshold -- measurements.txt 30.0
+//! ```
+
+use std::process::ExitCode;
+
+use barse::{
+  aggregator::{Aggregator, InPlaceInitializable, Merge},
+  barse::build_aggregated,
+  error::{BarseError, BarseResult},
+  temperature_reading::DecimalSeparator,
+  Options, TemperatureReading,
+};
+
+/// The threshold used if none is given, in degrees.
+const DEFAULT_THRESHOLD: &str = "30.0";
+
+#[derive(Clone, Copy)]
+struct CountAbove {
+  above: u32,
+  total: u32,
+}
+
+// SAFETY: `CountAbove` is made of integers, and zeroed memory is an
+// initialized summary of no readings.
+unsafe impl InPlaceInitializable for CountAbove {
+  fn initialize(&mut self) {}
+}
+
+impl Merge for CountAbove {
+  fn merge(&mut self, other: &Self) {
+    self.above += other.above;
+    self.total += other.total;
+  }
+}
+
+#[derive(Clone)]
+struct CountAboveThreshold {
+  threshold: TemperatureReading,
+}
+
+impl Aggregator for CountAboveThreshold {
+  type Summary = CountAbove;
+
+  fn add(&self, summary: &mut CountAbove, reading: TemperatureReading) {
+    summary.above += (reading > self.threshold) as u32;
+    summary.total += 1;
+  }
+}
+
+fn run() -> BarseResult {
+  let mut args = std::env::args().skip(1);
+  let input_path = args.next().ok_or_else(|| {
+    BarseError::new("usage: count_above_threshold <input> [threshold]".to_owned())
+  })?;
+  let threshold = args.next().unwrap_or_else(|| DEFAULT_THRESHOLD.to_owned());
+  let threshold = TemperatureReading::parse_checked(threshold.as_bytes(), DecimalSeparator::Dot)
+    .ok_or_else(|| BarseError::new(format!("invalid threshold \"{threshold}\"")))?;
+
+  let (stations, _) = build_aggregated(
+    &input_path,
+    CountAboveThreshold { threshold },
+    &Options::default(),
+  )?;
+  for (name, count) in stations {
+    println!("{name}: {}/{} above {threshold}", count.above, count.total);
+  }
+  Ok(())
+}
+
+fn main() -> ExitCode {
+  if let Err(err) = run() {
+    eprintln!("{err}");
+    ExitCode::FAILURE
+  } else {
+    ExitCode::SUCCESS
+  }
+}
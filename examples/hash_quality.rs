@@ -45,7 +45,7 @@ fn weather_stations(path: &str) -> BarseResult<Vec<String>> {
         let line = line?;
         line
           .split_once(';')
-          .ok_or_else(|| BarseError::new(format!("No ';' found in line \"{line}\"")).into())
+          .ok_or_else(|| BarseError::new(format!("No ';' found in line \"{line}\"")))
           .map(|(station, _)| station.to_owned())
       })
       .collect::<Result<Vec<_>, _>>()?
@@ -0,0 +1,74 @@
+//! Times workloads dominated by inserting new stations, where copying station
+//! names into the table shows up in profiles. Run with
+//! `cargo run --release --example insert_bench` on both sides of a change to
+//! compare them.
+
+use std::{fs, hint::black_box, process::ExitCode, time::Instant};
+
+#[cfg(not(feature = "multithreaded"))]
+use barse::inline_string::InlineString;
+#[cfg(feature = "multithreaded")]
+use barse::inline_string_mt::InlineString;
+use barse::{
+  barse::build_with_config,
+  error::BarseResult,
+  generate::{default_stations, Generator},
+  options::BuildConfig,
+};
+
+const UNIQUE_STATIONS: usize = 10_000;
+const ITERATIONS: usize = 100;
+
+/// Calls `f` `ITERATIONS` times, returning the mean time taken per name, in
+/// nanoseconds, if each call handles `names` names.
+fn time_per_name(names: usize, mut f: impl FnMut() -> BarseResult) -> BarseResult<f64> {
+  let start = Instant::now();
+  for _ in 0..ITERATIONS {
+    f()?;
+  }
+  Ok(start.elapsed().as_nanos() as f64 / (ITERATIONS * names) as f64)
+}
+
+fn run() -> BarseResult {
+  let stations = default_stations()?;
+  let generator = Generator::new(&stations, UNIQUE_STATIONS, 0x1a5e27)?;
+  let names = generator
+    .stations()
+    .iter()
+    .map(|station| station.name.as_str())
+    .collect::<Vec<_>>();
+
+  let construct = time_per_name(names.len(), || {
+    for name in &names {
+      black_box(<InlineString>::try_from(black_box(*name))?);
+    }
+    Ok(())
+  })?;
+  println!("InlineString::try_from: {construct:.2} ns/name");
+
+  // Every record of the input is the first reading of its station.
+  let input_path =
+    std::env::temp_dir().join(format!("barse_insert_bench_{}.txt", std::process::id()));
+  let input_path = input_path.to_string_lossy().into_owned();
+  generator.write_file(names.len() as u64, &input_path, 1)?;
+  let config = BuildConfig {
+    expected_stations: Some(names.len()),
+    ..BuildConfig::default()
+  };
+  let build = time_per_name(names.len(), || {
+    black_box(build_with_config(&input_path, &config)?);
+    Ok(())
+  });
+  fs::remove_file(&input_path)?;
+  println!("insert-only build: {:.2} ns/record", build?);
+  Ok(())
+}
+
+fn main() -> ExitCode {
+  if let Err(err) = run() {
+    println!("{err}");
+    ExitCode::FAILURE
+  } else {
+    ExitCode::SUCCESS
+  }
+}
@@ -0,0 +1,76 @@
+//! Prints the last reading of each station in the input, e.g.
+//!
+//! ```text
+//! cargo run --release --example last_reading -- measurements.txt
+//! ```
+
+use std::process::ExitCode;
+
+use barse::{
+  aggregator::{Aggregator, InPlaceInitializable, Merge},
+  barse::build_aggregated,
+  build_report::Strategy,
+  error::{BarseError, BarseResult},
+  Options, TemperatureReading,
+};
+
+#[derive(Clone, Copy)]
+struct LastReading {
+  reading: TemperatureReading,
+  seen: bool,
+}
+
+// SAFETY: Zeroed memory is a valid reading and `false`, an initialized
+// summary of no readings with `seen` unset.
+unsafe impl InPlaceInitializable for LastReading {
+  fn initialize(&mut self) {}
+}
+
+impl Merge for LastReading {
+  fn merge(&mut self, other: &Self) {
+    if other.seen {
+      *self = *other;
+    }
+  }
+}
+
+#[derive(Clone)]
+struct LastReadingWins;
+
+impl Aggregator for LastReadingWins {
+  type Summary = LastReading;
+
+  fn add(&self, summary: &mut LastReading, reading: TemperatureReading) {
+    *summary = LastReading {
+      reading,
+      seen: true,
+    };
+  }
+}
+
+fn run() -> BarseResult {
+  let input_path = std::env::args()
+    .nth(1)
+    .ok_or_else(|| BarseError::new("usage: last_reading <input>".to_owned()))?;
+
+  // Worker threads summarize chunks of the input in no particular order, and
+  // their tables are merged in thread order rather than input order, so the
+  // last reading is only well-defined when the whole input is folded on one
+  // thread.
+  let mut options = Options::default();
+  options.strategy = Some(Strategy::SingleThreaded);
+  let (stations, _) = build_aggregated(&input_path, LastReadingWins, &options)?;
+  for (name, last) in stations {
+    println!("{name}: {}", last.reading);
+  }
+  Ok(())
+}
+
+fn main() -> ExitCode {
+  if let Err(err) = run() {
+    eprintln!("{err}");
+    ExitCode::FAILURE
+  } else {
+    ExitCode::SUCCESS
+  }
+}
@@ -101,17 +101,17 @@ fn run() -> BarseResult {
     })
     .collect_vec();
 
-  for thread in threads {
+  for (worker, thread) in threads.into_iter().enumerate() {
     thread
       .join()
-      .map_err(|err| BarseError::new(format!("Failed to join thread: {err:?}")))?;
+      .map_err(|payload| BarseError::thread_panicked(worker, payload))?;
   }
 
   Ok(())
 }
 
 fn main() -> ExitCode {
-  #[cfg(feature = "profiled")]
+  #[cfg(all(feature = "profiled", unix))]
   let guard = pprof::ProfilerGuardBuilder::default()
     .frequency(1000)
     .blocklist(&["libc", "libgcc", "pthread", "vdso"])
@@ -120,7 +120,7 @@ fn main() -> ExitCode {
 
   let res = run();
 
-  #[cfg(feature = "profiled")]
+  #[cfg(all(feature = "profiled", unix))]
   if let Ok(report) = guard.report().build() {
     let file = std::fs::File::create("temp_parse_magic_search.svg").unwrap();
     report.flamegraph(file).unwrap();
@@ -0,0 +1,97 @@
+#ifndef BARSE_H
+#define BARSE_H
+
+/* Generated by cbindgen from src/capi.rs. Do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * The call succeeded.
+ */
+#define BARSE_OK 0
+
+/**
+ * A pointer argument was null, or the path was not UTF-8.
+ */
+#define BARSE_ERROR_INVALID_ARGUMENT 1
+
+/**
+ * The file could not be read or summarized.
+ */
+#define BARSE_ERROR_FAILED 2
+
+/**
+ * The library panicked.
+ */
+#define BARSE_ERROR_PANIC 3
+
+/**
+ * The summary of one weather station. Temperatures are in tenths of a
+ * degree.
+ */
+typedef struct BarseSummary {
+  /**
+   * The station's name, in UTF-8 and not nul-terminated.
+   */
+  const uint8_t *name;
+  /**
+   * The length of `name` in bytes.
+   */
+  size_t name_len;
+  int16_t min;
+  int16_t max;
+  /**
+   * The mean reading, rounded as the challenge's reference implementation
+   * does.
+   */
+  int16_t avg;
+  /**
+   * The number of readings.
+   */
+  uint32_t count;
+} BarseSummary;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Summarizes every weather station in the file at `path`, ordered by name,
+ * storing an array of the summaries in `*out` and its length in `*len`. The
+ * array must be freed with `barse_free_summary`. On failure, `*out` and
+ * `*len` are left unchanged.
+ *
+ * # Safety
+ *
+ * `path` must be a nul-terminated string, and `out` and `len` must be valid
+ * for writes.
+ */
+int32_t barse_summarize_file(const char *path, struct BarseSummary **out, size_t *len);
+
+/**
+ * Frees an array of `len` summaries returned by `barse_summarize_file`. Does
+ * nothing if `summaries` is null.
+ *
+ * # Safety
+ *
+ * `summaries` must be null, or an array returned by `barse_summarize_file`
+ * with its length `len`, which has not already been freed.
+ */
+void barse_free_summary(struct BarseSummary *summaries, size_t len);
+
+/**
+ * Returns the message of the error returned by the last call on this thread,
+ * or null if it succeeded. The message is valid until the next call on this
+ * thread.
+ */
+const char *barse_last_error(void);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* BARSE_H */
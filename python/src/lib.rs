@@ -0,0 +1,188 @@
+//! Python bindings, built into the `barse` module by maturin.
+
+use std::{collections::BTreeMap, ops::ControlFlow};
+
+use barse::{
+  error::RecordErrorKind,
+  generate::{default_stations, Generator},
+  scan_file_with_options, summarize_file, Options, TemperatureReading, TemperatureSummary,
+};
+use pyo3::{
+  create_exception,
+  exceptions::{PyException, PyOSError, PyValueError},
+  prelude::*,
+  types::{PyBool, PyDict},
+};
+
+/// The number of distinct stations `generate` picks by default, as the
+/// `barse generate` command does.
+const DEFAULT_UNIQUE_STATIONS: usize = 413;
+
+create_exception!(
+  barse,
+  BarseError,
+  PyException,
+  "Raised when an input file is malformed or can't be summarized."
+);
+
+/// Converts `err` to an `OSError` if the file couldn't be read, to a
+/// `ValueError` if a station name isn't UTF-8, and to a `BarseError`
+/// otherwise.
+fn to_py_err(err: barse::BarseError) -> PyErr {
+  let message = err.to_string();
+  let message = message
+    .strip_prefix("error: ")
+    .unwrap_or(&message)
+    .to_owned();
+  match err.without_context() {
+    barse::BarseError::Io(_) => PyOSError::new_err(message),
+    barse::BarseError::MalformedRecord {
+      kind: RecordErrorKind::InvalidUtf8,
+      ..
+    } => PyValueError::new_err(message),
+    _ => BarseError::new_err(message),
+  }
+}
+
+/// The options files are parsed with. Every record is checked, so that
+/// station names are known to be UTF-8 before they are read as `str`s.
+fn strict_options() -> Options {
+  let mut options = Options::default();
+  options.strict = true;
+  options
+}
+
+fn degrees(reading: TemperatureReading) -> f64 {
+  reading.reading() as f64 / 10.
+}
+
+/// Summarizes the file at `path` with the GIL released, keyed and ordered by
+/// station name.
+fn summaries(py: Python<'_>, path: &str) -> PyResult<BTreeMap<String, TemperatureSummary>> {
+  let stations = py
+    .detach(|| summarize_file(path, &strict_options()))
+    .map_err(to_py_err)?;
+  Ok(
+    stations
+      .into_iter()
+      .map(|station| (station.name, station.summary))
+      .collect(),
+  )
+}
+
+/// Returns a dict from each station in the file at `path` to its
+/// `(min, mean, max, count)`, in degrees, ordered by name. The mean is
+/// rounded to a tenth of a degree, as in the output of `barse`.
+#[pyfunction]
+fn summarize(py: Python<'_>, path: &str) -> PyResult<BTreeMap<String, (f64, f64, f64, u32)>> {
+  Ok(
+    summaries(py, path)?
+      .into_iter()
+      .map(|(name, summary)| {
+        let fields = (
+          degrees(summary.min()),
+          degrees(summary.avg()),
+          degrees(summary.max()),
+          summary.count,
+        );
+        (name, fields)
+      })
+      .collect(),
+  )
+}
+
+/// Returns a `pandas.DataFrame` with a row per station in the file at
+/// `path`, ordered by name, and `station`, `min`, `mean`, `max` and `count`
+/// columns. Requires pandas.
+#[pyfunction]
+fn to_pandas<'py>(py: Python<'py>, path: &str) -> PyResult<Bound<'py, PyAny>> {
+  let summaries = summaries(py, path)?;
+  let column =
+    |field: fn(&TemperatureSummary) -> f64| summaries.values().map(field).collect::<Vec<_>>();
+  let columns = PyDict::new(py);
+  columns.set_item("station", summaries.keys().collect::<Vec<_>>())?;
+  columns.set_item("min", column(|summary| degrees(summary.min())))?;
+  columns.set_item("mean", column(|summary| degrees(summary.avg())))?;
+  columns.set_item("max", column(|summary| degrees(summary.max())))?;
+  columns.set_item(
+    "count",
+    summaries
+      .values()
+      .map(|summary| summary.count)
+      .collect::<Vec<_>>(),
+  )?;
+  py.import("pandas")?.call_method1("DataFrame", (columns,))
+}
+
+/// Calls `callback(station, reading)` with each record of the file at
+/// `path` in order, with the reading in degrees. The scan stops early if
+/// `callback` returns `False` or raises, and `scan_records` returns whether
+/// it reached the end of the file. The GIL is held throughout, since the
+/// callback runs on every record.
+#[pyfunction]
+fn scan_records(path: &str, callback: &Bound<'_, PyAny>) -> PyResult<bool> {
+  let scanned = scan_file_with_options(path, &strict_options(), |station, reading| {
+    let stop = callback
+      .call1((station, degrees(reading)))
+      .and_then(|result| Ok(result.is_instance_of::<PyBool>() && !result.is_truthy()?));
+    match stop {
+      Ok(false) => ControlFlow::Continue(()),
+      Ok(true) => ControlFlow::Break(Ok(())),
+      Err(err) => ControlFlow::Break(Err(err)),
+    }
+  })
+  .map_err(to_py_err)?;
+  match scanned {
+    ControlFlow::Continue(()) => Ok(true),
+    ControlFlow::Break(result) => result.map(|()| false),
+  }
+}
+
+/// Writes `rows` measurements of `unique_stations` stations, picked from the
+/// bundled station list, to the file at `path`. The same seed, station count
+/// and row count always produce the same file. Raises `BarseError` if the
+/// list has fewer than `unique_stations` stations.
+#[pyfunction]
+#[pyo3(signature = (path, rows, seed = 0, unique_stations = DEFAULT_UNIQUE_STATIONS))]
+fn generate(
+  py: Python<'_>,
+  path: &str,
+  rows: u64,
+  seed: u64,
+  unique_stations: usize,
+) -> PyResult<()> {
+  py.detach(|| {
+    let threads = std::thread::available_parallelism().map_or(1, |threads| threads.get());
+    Generator::new(&default_stations()?, unique_stations, seed)?.write_file(rows, path, threads)
+  })
+  .map_err(to_py_err)
+}
+
+/// A fast parser for measurement files of the One Billion Row Challenge,
+/// with one record of `<station>;<temperature>` per line.
+///
+/// >>> import barse
+/// >>> barse.generate("measurements.txt", rows=1_000_000, seed=7)
+/// >>> for station, (min_temp, mean, max_temp, count) in barse.summarize(
+/// ...     "measurements.txt"
+/// ... ).items():
+/// ...     print(f"{station}: {min_temp}/{mean}/{max_temp} over {count} readings")
+/// >>> frame = barse.to_pandas("measurements.txt")
+///
+/// Temperatures are in degrees, and the files are parsed with the GIL
+/// released, except by `scan_records`, which calls back into Python.
+///
+/// Every record is checked as it is parsed, which is several times slower
+/// than the `barse` command's default, but malformed input raises instead of
+/// producing garbage. Station names must be UTF-8: a file with any other
+/// name raises `ValueError`, and other malformed records raise `BarseError`.
+#[pymodule]
+#[pyo3(name = "barse")]
+fn barse_module(module: &Bound<'_, PyModule>) -> PyResult<()> {
+  module.add_function(wrap_pyfunction!(summarize, module)?)?;
+  module.add_function(wrap_pyfunction!(to_pandas, module)?)?;
+  module.add_function(wrap_pyfunction!(scan_records, module)?)?;
+  module.add_function(wrap_pyfunction!(generate, module)?)?;
+  module.add("BarseError", module.py().get_type::<BarseError>())?;
+  Ok(())
+}
@@ -0,0 +1,102 @@
+"""Tests of the Python bindings. Run from `python/` with:
+
+    maturin develop --extras test
+    pytest
+"""
+
+import pytest
+
+import barse
+
+RECORDS = "Hamburg;12.0\nBulawayo;8.9\nSão Paulo;-3.4\nHamburg;34.2\nBulawayo;-0.1\n"
+
+
+@pytest.fixture
+def measurements(tmp_path):
+    path = tmp_path / "measurements.txt"
+    path.write_text(RECORDS, encoding="utf-8")
+    return str(path)
+
+
+def test_summarize(measurements):
+    assert barse.summarize(measurements) == {
+        "Bulawayo": (-0.1, 4.4, 8.9, 2),
+        "Hamburg": (12.0, 23.1, 34.2, 2),
+        "São Paulo": (-3.4, -3.4, -3.4, 1),
+    }
+
+
+def test_summarize_is_ordered_by_name(measurements):
+    assert list(barse.summarize(measurements)) == ["Bulawayo", "Hamburg", "São Paulo"]
+
+
+def test_invalid_utf8_names_are_rejected(tmp_path):
+    path = tmp_path / "measurements.txt"
+    path.write_bytes(b"Hamburg;1.0\nHam\xffburg;3.0\n")
+    with pytest.raises(ValueError, match="not valid UTF-8"):
+        barse.summarize(str(path))
+    with pytest.raises(ValueError):
+        barse.scan_records(str(path), lambda *record: None)
+
+
+def test_missing_file(tmp_path):
+    with pytest.raises(OSError):
+        barse.summarize(str(tmp_path / "missing.txt"))
+
+
+def test_scan_records(measurements):
+    records = []
+    assert barse.scan_records(measurements, lambda *record: records.append(record))
+    assert records == [
+        ("Hamburg", 12.0),
+        ("Bulawayo", 8.9),
+        ("São Paulo", -3.4),
+        ("Hamburg", 34.2),
+        ("Bulawayo", -0.1),
+    ]
+
+
+def test_scan_records_stops_on_false(measurements):
+    records = []
+
+    def callback(station, reading):
+        records.append(station)
+        return len(records) < 2
+
+    assert not barse.scan_records(measurements, callback)
+    assert records == ["Hamburg", "Bulawayo"]
+
+
+def test_scan_records_propagates_exceptions(measurements):
+    def callback(station, reading):
+        raise KeyError(station)
+
+    with pytest.raises(KeyError, match="Hamburg"):
+        barse.scan_records(measurements, callback)
+
+
+def test_generate(tmp_path):
+    path = str(tmp_path / "measurements.txt")
+    barse.generate(path, rows=10_000, seed=7, unique_stations=50)
+    stations = barse.summarize(path)
+    assert len(stations) == 50
+    assert sum(count for _, _, _, count in stations.values()) == 10_000
+
+    other = str(tmp_path / "other.txt")
+    barse.generate(other, rows=10_000, seed=7, unique_stations=50)
+    assert open(path, "rb").read() == open(other, "rb").read()
+
+
+def test_generate_rejects_too_many_stations(tmp_path):
+    path = str(tmp_path / "measurements.txt")
+    with pytest.raises(barse.BarseError, match="unique stations"):
+        barse.generate(path, rows=10, unique_stations=10_000_000)
+
+
+def test_to_pandas(measurements):
+    pytest.importorskip("pandas")
+    frame = barse.to_pandas(measurements)
+    assert list(frame.columns) == ["station", "min", "mean", "max", "count"]
+    assert frame["station"].tolist() == ["Bulawayo", "Hamburg", "São Paulo"]
+    assert frame["mean"].tolist() == [4.4, 23.1, -3.4]
+    assert frame["count"].tolist() == [2, 2, 1]
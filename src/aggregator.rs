@@ -0,0 +1,61 @@
+//! The statistic the table builders compute over each station's readings.
+//! By default, the builders keep a `TemperatureSummary` of each station, but
+//! with the `multithreaded` feature, `barse::build_aggregated` folds readings
+//! with any `Aggregator`.
+
+pub use crate::hugepage_backed_table::InPlaceInitializable;
+use crate::{temperature_reading::TemperatureReading, temperature_summary::TemperatureSummary};
+
+/// A summary which can absorb another summary of the same station.
+pub trait Merge {
+  /// Merges `other`, a summary of readings from a later part of the input
+  /// than those of `self`, into `self`. Only builds on one thread merge
+  /// summaries in input order, so statistics which depend on the order of
+  /// the readings are only exact when built single-threaded.
+  fn merge(&mut self, other: &Self);
+
+  /// Merges each of `src` into the summary at the same index of `dst`, which
+  /// is how worker threads' tables are combined.
+  fn merge_slices(dst: &mut [Self], src: &[Self])
+  where
+    Self: Sized,
+  {
+    debug_assert_eq!(dst.len(), src.len());
+    for (summary, other) in dst.iter_mut().zip(src) {
+      summary.merge(other);
+    }
+  }
+}
+
+/// Folds the readings of each station into a `Summary`. Summaries start out
+/// as zeroed memory, initialized with `InPlaceInitializable::initialize`.
+pub trait Aggregator {
+  type Summary: Merge + InPlaceInitializable;
+
+  fn add(&self, summary: &mut Self::Summary, reading: TemperatureReading);
+}
+
+/// The min, max, mean and count of each station's readings, as the challenge
+/// requires.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TemperatureAggregator;
+
+impl Aggregator for TemperatureAggregator {
+  type Summary = TemperatureSummary;
+
+  #[inline(always)]
+  fn add(&self, summary: &mut TemperatureSummary, reading: TemperatureReading) {
+    summary.add_reading(reading);
+  }
+}
+
+impl Merge for TemperatureSummary {
+  fn merge(&mut self, other: &Self) {
+    TemperatureSummary::merge(self, other);
+  }
+
+  #[cfg(all(feature = "multithreaded", target_feature = "avx2"))]
+  fn merge_slices(dst: &mut [Self], src: &[Self]) {
+    crate::temperature_summary_x86::merge_summaries(dst, src);
+  }
+}
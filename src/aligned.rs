@@ -0,0 +1,153 @@
+use std::{
+  alloc::{alloc, dealloc, handle_alloc_error, Layout},
+  ops::Deref,
+  ptr::NonNull,
+  slice,
+};
+
+use crate::scanner::SCANNER_CACHE_SIZE;
+
+/// The alignment of `AlignedInput`'s buffer, which is also the granularity
+/// its padded length is rounded to.
+pub const ALIGNMENT: usize = SCANNER_CACHE_SIZE;
+
+/// An input copied into a buffer aligned to `ALIGNMENT` bytes and padded to a
+/// multiple of `ALIGNMENT` bytes, which the scanner can read in whole batches.
+pub struct AlignedInput {
+  bytes: NonNull<u8>,
+  /// The length of the input.
+  len: usize,
+  /// The length of the input and its padding.
+  padded_len: usize,
+}
+
+impl AlignedInput {
+  /// Copies `src` into an aligned buffer, zeroing the space past the end of
+  /// `src` up to the alignment boundary.
+  pub fn new(src: impl AsRef<[u8]>) -> Self {
+    Self::with_padding(src, 0)
+  }
+
+  /// Like `new`, but pads `src` with at least `extra` zero bytes, e.g.
+  /// `BUFFER_OVERLAP` bytes for a scanner reading past the end of its slice.
+  pub fn with_padding(src: impl AsRef<[u8]>, extra: usize) -> Self {
+    Self::new_padded(src.as_ref(), extra, 0)
+  }
+
+  /// Copies `src` into an aligned buffer, filling the space past the end of
+  /// `src` up to the alignment boundary with `padding`.
+  pub fn new_with_padding_byte(src: impl AsRef<[u8]>, padding: u8) -> Self {
+    Self::new_padded(src.as_ref(), 0, padding)
+  }
+
+  fn new_padded(src: &[u8], extra: usize, padding: u8) -> Self {
+    let padded_len = (src.len() + extra).next_multiple_of(ALIGNMENT);
+    let layout = Self::layout(padded_len);
+    let bytes =
+      NonNull::new(unsafe { alloc(layout) }).unwrap_or_else(|| handle_alloc_error(layout));
+    // SAFETY: The allocation spans `layout.size()` bytes, which is at least
+    // `src.len()`. Every byte is initialized, so reads of the padding are
+    // defined.
+    unsafe {
+      bytes.as_ptr().write_bytes(padding, layout.size());
+      bytes.as_ptr().copy_from(src.as_ptr(), src.len());
+    }
+    Self {
+      bytes,
+      len: src.len(),
+      padded_len,
+    }
+  }
+
+  /// The layout of the buffer holding `padded_len` bytes, which is never
+  /// empty, as zero-sized allocations are undefined behavior. The layout is
+  /// derived from the padded length, not the length of the input, so that
+  /// the buffer is deallocated with the layout it was allocated with.
+  fn layout(padded_len: usize) -> Layout {
+    Layout::from_size_align(padded_len.max(ALIGNMENT), ALIGNMENT)
+      .expect("ALIGNMENT is a power of two, and inputs fit in memory")
+  }
+
+  pub fn len(&self) -> usize {
+    self.len
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len == 0
+  }
+
+  /// The length of the input and its padding, which is a multiple of
+  /// `ALIGNMENT`.
+  pub fn padded_len(&self) -> usize {
+    self.padded_len
+  }
+
+  pub fn exact_slice(&self) -> &[u8] {
+    unsafe { slice::from_raw_parts(self.bytes.as_ptr(), self.len) }
+  }
+
+  /// Returns the input and its padding, e.g. for
+  /// `Scanner::from_start_with_len`.
+  pub fn padded_slice(&self) -> &[u8] {
+    unsafe { slice::from_raw_parts(self.bytes.as_ptr(), self.padded_len) }
+  }
+}
+
+impl Deref for AlignedInput {
+  type Target = [u8];
+
+  fn deref(&self) -> &[u8] {
+    self.exact_slice()
+  }
+}
+
+impl Drop for AlignedInput {
+  fn drop(&mut self) {
+    unsafe {
+      dealloc(self.bytes.as_ptr(), Self::layout(self.padded_len));
+    }
+  }
+}
+
+// `AlignedInput` uniquely owns its bytes, like a `Box<[u8]>`.
+unsafe impl Send for AlignedInput {}
+unsafe impl Sync for AlignedInput {}
+
+#[cfg(test)]
+mod tests {
+  use googletest::prelude::*;
+
+  use crate::{
+    aligned::{AlignedInput, ALIGNMENT},
+    scanner::BUFFER_OVERLAP,
+  };
+
+  /// Small enough to run under Miri, which checks that each buffer is
+  /// deallocated with the layout it was allocated with.
+  #[gtest]
+  fn test_drop_with_unaligned_lengths() {
+    let text = "a".repeat(2 * ALIGNMENT + 1);
+    for len in 0..=text.len() {
+      let input = AlignedInput::new(&text[..len]);
+      expect_eq!(input.len(), len);
+      expect_eq!(&*input, &text.as_bytes()[..len]);
+      expect_eq!(input.padded_len(), len.next_multiple_of(ALIGNMENT));
+      expect_eq!(input.padded_slice().as_ptr().addr() % ALIGNMENT, 0);
+    }
+  }
+
+  #[gtest]
+  fn test_padded_slice_is_zeroed() {
+    let input = AlignedInput::with_padding("Oslo;1.0\n", BUFFER_OVERLAP);
+    expect_eq!(
+      input.padded_len(),
+      (9 + BUFFER_OVERLAP).next_multiple_of(ALIGNMENT)
+    );
+    expect_eq!(input.exact_slice(), b"Oslo;1.0\n");
+    expect_that!(&input.padded_slice()[input.len()..], each(eq(&0)));
+
+    let input = AlignedInput::new_with_padding_byte("Oslo;1.0\n", b';');
+    expect_eq!(input.padded_len(), ALIGNMENT);
+    expect_that!(&input.padded_slice()[input.len()..], each(eq(&b';')));
+  }
+}
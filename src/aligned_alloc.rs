@@ -0,0 +1,90 @@
+use std::{
+  alloc::{alloc_zeroed, dealloc, handle_alloc_error, Layout},
+  ops::{Deref, DerefMut},
+  ptr::NonNull,
+  slice,
+};
+
+use crate::error::{BarseError, BarseResult};
+
+/// A zero-initialized heap allocation with a chosen alignment, which stands in
+/// for anonymous and file mappings when the `mmap` feature is disabled, and
+/// holds in-memory inputs.
+pub struct AlignedAlloc {
+  bytes: NonNull<u8>,
+  layout: Layout,
+}
+
+impl AlignedAlloc {
+  /// Allocates `len` zeroed bytes aligned to `align`, which must be a power
+  /// of two. At least `align` bytes are allocated, so the allocation is never
+  /// empty.
+  pub fn zeroed(len: usize, align: usize) -> BarseResult<Self> {
+    let layout = Layout::from_size_align(len.max(align), align).map_err(|err| {
+      BarseError::new(format!(
+        "Can't allocate {len} bytes aligned to {align} bytes: {err}"
+      ))
+    })?;
+    let bytes =
+      NonNull::new(unsafe { alloc_zeroed(layout) }).unwrap_or_else(|| handle_alloc_error(layout));
+    Ok(Self { bytes, layout })
+  }
+}
+
+impl Deref for AlignedAlloc {
+  type Target = [u8];
+
+  fn deref(&self) -> &[u8] {
+    unsafe { slice::from_raw_parts(self.bytes.as_ptr(), self.layout.size()) }
+  }
+}
+
+impl DerefMut for AlignedAlloc {
+  fn deref_mut(&mut self) -> &mut [u8] {
+    unsafe { slice::from_raw_parts_mut(self.bytes.as_ptr(), self.layout.size()) }
+  }
+}
+
+impl Drop for AlignedAlloc {
+  fn drop(&mut self) {
+    unsafe { dealloc(self.bytes.as_ptr(), self.layout) };
+  }
+}
+
+// `AlignedAlloc` uniquely owns its bytes, like a `Box<[u8]>`.
+unsafe impl Send for AlignedAlloc {}
+unsafe impl Sync for AlignedAlloc {}
+
+#[cfg(test)]
+mod tests {
+  use googletest::prelude::*;
+
+  use crate::aligned_alloc::AlignedAlloc;
+
+  #[gtest]
+  fn test_zeroed_and_aligned() {
+    for (len, align) in [(100, 32), (4096, 4096), (3 << 20, 2 << 20)] {
+      let mut alloc = AlignedAlloc::zeroed(len, align).unwrap();
+      expect_eq!(alloc.len(), len);
+      expect_eq!(alloc.as_ptr().addr() % align, 0);
+      expect_that!(&alloc[..], each(eq(&0)));
+      alloc[len - 1] = 1;
+      expect_eq!(alloc[len - 1], 1);
+    }
+  }
+
+  #[gtest]
+  fn test_empty_allocation_rounds_up_to_alignment() {
+    expect_eq!(AlignedAlloc::zeroed(0, 64).unwrap().len(), 64);
+  }
+
+  #[gtest]
+  fn test_bad_alignment() {
+    expect_that!(
+      AlignedAlloc::zeroed(64, 3).map(|_| ()),
+      err(displays_as(contains_substring(
+        "Can't allocate 64 bytes aligned to 3 bytes"
+      )))
+    );
+  }
+}
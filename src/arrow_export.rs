@@ -0,0 +1,185 @@
+//! Conversion of station summaries to Arrow record batches, and Parquet files
+//! of them, for analytics tools which ingest columnar data.
+
+use std::{io::Write, sync::Arc};
+
+use arrow::{
+  array::{Float64Array, RecordBatch, StringArray, UInt64Array},
+  datatypes::{DataType, Field, Schema, SchemaRef},
+};
+use parquet::arrow::ArrowWriter;
+
+use crate::{
+  barse::{OwnedWeatherStation, Table},
+  error::{BarseError, BarseResult},
+  print_summary::write_atomically,
+  temperature_reading::TemperatureReading,
+  util::utf16_cmp,
+};
+
+/// The schema of the batches built by `to_record_batch`: a row per station
+/// with its name, its min, mean and max in degrees, and its number of
+/// readings.
+pub fn schema() -> SchemaRef {
+  Arc::new(Schema::new(vec![
+    Field::new("station", DataType::Utf8, false),
+    Field::new("min", DataType::Float64, false),
+    Field::new("mean", DataType::Float64, false),
+    Field::new("max", DataType::Float64, false),
+    Field::new("count", DataType::UInt64, false),
+  ]))
+}
+
+fn degrees(reading: TemperatureReading) -> f64 {
+  reading.reading() as f64 / 10.
+}
+
+/// Builds a batch of `stations`, in the order they are given. The mean is
+/// rounded to a tenth of a degree, as in the other output formats.
+pub fn stations_to_record_batch(stations: &[OwnedWeatherStation]) -> BarseResult<RecordBatch> {
+  let column = |field: fn(&OwnedWeatherStation) -> f64| {
+    Arc::new(Float64Array::from_iter_values(stations.iter().map(field)))
+  };
+  RecordBatch::try_new(
+    schema(),
+    vec![
+      Arc::new(StringArray::from_iter_values(
+        stations.iter().map(|station| station.name.as_str()),
+      )),
+      column(|station| degrees(station.summary.min())),
+      column(|station| degrees(station.summary.avg())),
+      column(|station| degrees(station.summary.max())),
+      Arc::new(UInt64Array::from_iter_values(
+        stations.iter().map(|station| station.summary.count as u64),
+      )),
+    ],
+  )
+  .map_err(|err| BarseError::new(format!("failed to build record batch: {err}")))
+}
+
+/// Builds a batch of every station in `table`, ordered by name.
+pub fn to_record_batch(table: &Table) -> BarseResult<RecordBatch> {
+  let mut stations = table
+    .to_owned_summary()
+    .into_iter()
+    .map(|(name, summary)| OwnedWeatherStation { name, summary })
+    .collect::<Vec<_>>();
+  stations.sort_unstable_by(|a, b| utf16_cmp(&a.name, &b.name));
+  stations_to_record_batch(&stations)
+}
+
+/// Writes `batch` to `out` as a Parquet file.
+pub fn write_record_batch<W: Write + Send>(batch: &RecordBatch, out: W) -> BarseResult {
+  let parquet_err = |err| BarseError::new(format!("failed to write parquet: {err}"));
+  let mut writer = ArrowWriter::try_new(out, batch.schema(), None).map_err(parquet_err)?;
+  writer.write(batch).map_err(parquet_err)?;
+  writer.close().map_err(parquet_err)?;
+  Ok(())
+}
+
+/// Writes every station in `table`, ordered by name, to a Parquet file at
+/// `path`. The file is replaced atomically once it is complete.
+pub fn write_parquet(table: &Table, path: &str) -> BarseResult {
+  let batch = to_record_batch(table)?;
+  write_atomically(path, |out| write_record_batch(&batch, out)).map_err(|err| err.in_file(path))
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{fs, path::PathBuf};
+
+  use arrow::{
+    array::{Array, AsArray, RecordBatch},
+    datatypes::{Float64Type, UInt64Type},
+  };
+  use googletest::prelude::*;
+  use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+  use crate::{
+    arrow_export::{schema, to_record_batch, write_parquet},
+    barse::build_with_config,
+    options::BuildConfig,
+    reference,
+    test_util::random_input_file,
+  };
+
+  fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("barse_arrow_{}_{name}", std::process::id()))
+  }
+
+  fn read_parquet(path: &PathBuf) -> Vec<RecordBatch> {
+    ParquetRecordBatchReaderBuilder::try_new(fs::File::open(path).unwrap())
+      .unwrap()
+      .build()
+      .unwrap()
+      .collect::<Result<_, _>>()
+      .unwrap()
+  }
+
+  /// The rows of `batch` as `(station, min, mean, max, count)`.
+  fn rows(batch: &RecordBatch) -> Vec<(String, f64, f64, f64, u64)> {
+    let stations = batch.column(0).as_string::<i32>();
+    let floats = |i: usize| batch.column(i).as_primitive::<Float64Type>().clone();
+    let (min, mean, max) = (floats(1), floats(2), floats(3));
+    let count = batch.column(4).as_primitive::<UInt64Type>();
+    (0..batch.num_rows())
+      .map(|i| {
+        (
+          stations.value(i).to_owned(),
+          min.value(i),
+          mean.value(i),
+          max.value(i),
+          count.value(i),
+        )
+      })
+      .collect()
+  }
+
+  #[gtest]
+  fn test_parquet_round_trip_matches_reference() {
+    let input = random_input_file(0x5a7c01, 100_000, 1_000).unwrap();
+    let (input_path, parquet_path) = (temp_path("in.txt"), temp_path("out.parquet"));
+    fs::write(&input_path, input.exact_slice()).unwrap();
+    let (table, _) =
+      build_with_config(input_path.to_str().unwrap(), &BuildConfig::default()).unwrap();
+    write_parquet(&table, parquet_path.to_str().unwrap()).unwrap();
+    let batches = read_parquet(&parquet_path);
+    fs::remove_file(&input_path).unwrap();
+    fs::remove_file(&parquet_path).unwrap();
+
+    let expected = reference::weather_stations(str::from_utf8(input.exact_slice()).unwrap(), false)
+      .unwrap()
+      .into_iter()
+      .map(|station| {
+        let summary = station.summary;
+        (
+          station.name,
+          summary.min as f64 / 10.,
+          summary.avg(),
+          summary.max as f64 / 10.,
+          summary.count as u64,
+        )
+      })
+      .collect::<Vec<_>>();
+    assert_that!(batches, len(eq(1)));
+    expect_eq!(batches[0].schema(), schema());
+    expect_eq!(rows(&batches[0]), expected);
+  }
+
+  #[gtest]
+  fn test_record_batch_of_empty_table() {
+    let input_path = temp_path("empty.txt");
+    fs::write(&input_path, "").unwrap();
+    let (table, _) =
+      build_with_config(input_path.to_str().unwrap(), &BuildConfig::default()).unwrap();
+    fs::remove_file(&input_path).unwrap();
+
+    let batch = to_record_batch(&table).unwrap();
+    expect_eq!(batch.schema(), schema());
+    expect_eq!(batch.num_rows(), 0);
+    expect_true!(batch
+      .columns()
+      .iter()
+      .all(|column| column.null_count() == 0));
+  }
+}
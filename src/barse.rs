@@ -1,25 +1,60 @@
-use std::{cmp::Ordering, fmt::Display, fs::File, slice};
+use std::{
+  cmp::Ordering,
+  collections::{BinaryHeap, HashMap},
+  fmt::Display,
+  fs::{self, File},
+  hash::BuildHasher,
+  ops::{ControlFlow, Deref},
+  sync::atomic::{AtomicUsize, Ordering as AtomicOrdering},
+  time::{Duration, Instant},
+};
+
+#[cfg(not(feature = "mmap"))]
+use std::io::Read;
+#[cfg(feature = "mmap")]
+use std::slice;
 
-use memmap2::{Advice, MmapOptions};
+#[cfg(all(feature = "mmap", unix))]
+use memmap2::Advice;
+#[cfg(feature = "mmap")]
+use memmap2::{Mmap, MmapOptions};
 
+#[cfg(feature = "multithreaded")]
+use crate::aggregator::Aggregator;
 #[cfg(not(feature = "multithreaded"))]
-use crate::build_table::build_temperature_reading_table_from_bytes;
+use crate::build_table::{
+  build_grouped_by_from_bytes, build_temperature_reading_table_from_bytes,
+  build_temperature_reading_table_from_bytes_with_options, build_with_normalizer_from_bytes,
+};
 #[cfg(feature = "multithreaded")]
-use crate::build_table_mt::build_temperature_reading_table_from_bytes;
+pub use crate::build_table_mt::PartialSummary;
+#[cfg(feature = "multithreaded")]
+use crate::build_table_mt::{
+  build_aggregated_table_from_bytes, build_grouped_by_from_bytes, build_streaming,
+  build_temperature_reading_table_from_bytes,
+  build_temperature_reading_table_from_bytes_with_options, build_with_normalizer_from_bytes,
+};
 
 use crate::{
-  error::BarseResult, scanner::SCANNER_CACHE_SIZE, temperature_summary::TemperatureSummary,
-  util::HasIter,
+  aligned_alloc::AlignedAlloc,
+  build_report::{BuildReport, Strategy},
+  error::{BarseError, BarseResult},
+  median::{ReadingHistogram, StationMedian},
+  options::{BuildConfig, Options},
+  scanner::{Scanner, SCANNER_CACHE_SIZE},
+  str_hash::BuildStringHash,
+  temperature_reading::TemperatureReading,
+  temperature_summary::TemperatureSummary,
+  util::{utf16_cmp, HasIter},
+  validating_scanner::ValidatingScanner,
 };
 
-unsafe fn round_up_to_cache_size_boundary(buffer: &[u8]) -> &[u8] {
-  unsafe {
-    slice::from_raw_parts(
-      buffer.as_ptr(),
-      buffer.len().next_multiple_of(SCANNER_CACHE_SIZE),
-    )
-  }
-}
+/// The table of weather station summaries produced by the builders.
+#[cfg(not(feature = "multithreaded"))]
+pub type Table = crate::table::WeatherStationTable;
+/// The table of weather station summaries produced by the builders.
+#[cfg(feature = "multithreaded")]
+pub type Table = crate::build_table_mt::SummaryTable;
 
 pub struct WeatherStation<'a> {
   name: &'a str,
@@ -30,6 +65,82 @@ impl<'a> WeatherStation<'a> {
   pub fn new(name: &'a str, summary: TemperatureSummary) -> Self {
     Self { name, summary }
   }
+
+  pub fn name(&self) -> &'a str {
+    self.name
+  }
+
+  pub fn summary(&self) -> &TemperatureSummary {
+    &self.summary
+  }
+}
+
+/// An owned copy of a `WeatherStation`, which can outlive the table it was
+/// read from.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OwnedWeatherStation {
+  pub name: String,
+  pub summary: TemperatureSummary,
+}
+
+/// The contents of a built table, which can be saved in any serde format and
+/// loaded back into a table by a later invocation, e.g. to fold new files
+/// into it with `append_file` as they arrive.
+#[cfg(feature = "serde")]
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct Snapshot {
+  /// The capacity of the table the snapshot was taken of.
+  pub capacity: usize,
+  pub stations: Vec<OwnedWeatherStation>,
+}
+
+#[cfg(feature = "serde")]
+impl Snapshot {
+  pub fn of(table: &Table) -> Self {
+    Self {
+      capacity: table.capacity(),
+      stations: table
+        .iter()
+        .map(|(name, summary)| OwnedWeatherStation {
+          name: name.to_owned(),
+          summary: *summary,
+        })
+        .collect(),
+    }
+  }
+
+  /// Loads the snapshot into a new table with the capacity of the table it
+  /// was taken of.
+  pub fn into_table(self) -> BarseResult<Table> {
+    if let Some(station) = self
+      .stations
+      .iter()
+      .find(|station| station.summary.count == 0)
+    {
+      return Err(BarseError::new(format!(
+        "Snapshot holds no readings for station \"{}\"",
+        station.name
+      )));
+    }
+    let mut table = Table::with_capacity(self.capacity)?;
+    table.merge_summaries(
+      self
+        .stations
+        .iter()
+        .map(|station| (station.name.as_str(), &station.summary)),
+    )?;
+    Ok(table)
+  }
+}
+
+impl<'a> From<WeatherStation<'a>> for OwnedWeatherStation {
+  fn from(station: WeatherStation<'a>) -> Self {
+    Self {
+      name: station.name.to_owned(),
+      summary: station.summary,
+    }
+  }
 }
 
 impl<'a> PartialEq for WeatherStation<'a> {
@@ -46,32 +157,1533 @@ impl<'a> PartialOrd for WeatherStation<'a> {
   }
 }
 
+/// Stations are ordered by name like the keys of a Java `TreeMap<String, _>`,
+/// as in the reference implementation of the challenge.
 impl<'a> Ord for WeatherStation<'a> {
   fn cmp(&self, other: &Self) -> Ordering {
-    self.name.cmp(other.name)
+    utf16_cmp(self.name, other.name)
   }
 }
 
 impl<'a> Display for WeatherStation<'a> {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-    write!(
-      f,
-      "{}={}/{}/{}",
-      self.name,
-      self.summary.min(),
-      self.summary.avg(),
-      self.summary.max()
-    )
+    write!(f, "{}={}", self.name, self.summary)
   }
 }
 
-pub fn build_temperature_reading_table(
-  input_path: &str,
-) -> BarseResult<impl for<'a> HasIter<'a, Item = (&'a str, &'a TemperatureSummary)>> {
+/// Returns the names and averages of the `n` stations with the highest
+/// average readings if `highest` is set, otherwise the lowest, most extreme
+/// first. Ties are broken by name. Only the `n` most extreme stations seen so
+/// far are kept while scanning `stations`, so they are never fully sorted.
+pub(crate) fn select_by_avg<'a>(
+  stations: impl IntoIterator<Item = (&'a str, &'a TemperatureSummary)>,
+  n: usize,
+  highest: bool,
+) -> Vec<(String, TemperatureReading)> {
+  if n == 0 {
+    return Vec::new();
+  }
+  // A max-heap of the selected stations, keyed so that the least extreme is
+  // on top, to be evicted by any more extreme station.
+  let mut selected = BinaryHeap::with_capacity(n + 1);
+  for (name, summary) in stations {
+    let avg = summary.avg().reading() as i32;
+    let key = if highest { -avg } else { avg };
+    selected.push((key, WeatherStation::new(name, *summary)));
+    if selected.len() > n {
+      selected.pop();
+    }
+  }
+  selected
+    .into_sorted_vec()
+    .into_iter()
+    .map(|(_, station)| (station.name().to_owned(), station.summary().avg()))
+    .collect()
+}
+
+/// Returns a fingerprint of the names and summaries of `stations`, which
+/// doesn't depend on the order they are visited in. The fingerprints of each
+/// station are summed, so tables holding the same stations with equal
+/// summaries share a fingerprint regardless of their capacity or bucket
+/// order.
+///
+/// Names are hashed with FNV-1a rather than `str_hash`, which only hashes a
+/// prefix of the name and differs between targets, so fingerprints may be
+/// compared across builds and machines.
+pub(crate) fn fingerprint<'a>(
+  stations: impl IntoIterator<Item = (&'a str, &'a TemperatureSummary)>,
+) -> u64 {
+  stations
+    .into_iter()
+    .fold(0, |fingerprint: u64, (name, summary)| {
+      fingerprint.wrapping_add(station_fingerprint(name, summary))
+    })
+}
+
+fn station_fingerprint(name: &str, summary: &TemperatureSummary) -> u64 {
+  const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+  const FNV_PRIME: u64 = 0x0100_0000_01b3;
+  let name_hash = name.bytes().fold(FNV_OFFSET_BASIS, |hash, byte| {
+    (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+  });
+  [
+    summary.min.reading() as u64,
+    summary.max.reading() as u64,
+    summary.count as u64,
+    summary.total as u64,
+  ]
+  .into_iter()
+  .fold(mix(name_hash), |hash, field| mix(hash ^ field))
+}
+
+/// The SplitMix64 finalizer, which spreads every bit of `x` across the whole
+/// result.
+fn mix(mut x: u64) -> u64 {
+  x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+  x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+  x ^ (x >> 31)
+}
+
+#[cfg(feature = "mmap")]
+fn map_input_file(input_path: &str) -> BarseResult<Mmap> {
   let file = File::open(input_path)?;
   let map = unsafe { MmapOptions::new().map(&file) }?;
+  // Only a hint, which isn't available on Windows.
+  #[cfg(unix)]
   map.advise(Advice::Sequential)?;
+  #[cfg(feature = "tracing")]
+  tracing::info!(path = input_path, bytes = map.len(), "mapped input file");
+  Ok(map)
+}
+
+/// Returns the mapped input file extended to a multiple of
+/// `SCANNER_CACHE_SIZE` bytes, which the scanner reads in whole batches.
+#[cfg(feature = "mmap")]
+fn padded_input(map: &Mmap) -> &[u8] {
+  // SAFETY: Mappings span whole pages, which are a multiple of
+  // `SCANNER_CACHE_SIZE` bytes, and the bytes of the last page past the end of
+  // the file read as zero.
+  unsafe { slice::from_raw_parts(map.as_ptr(), map.len().next_multiple_of(SCANNER_CACHE_SIZE)) }
+}
+
+/// An input file read into the heap, which is followed by zeroes up to the end
+/// of its last page, like a mapped file. Reads that don't cross a page
+/// boundary therefore stay within the allocation.
+#[cfg(not(feature = "mmap"))]
+struct InputFile {
+  bytes: AlignedAlloc,
+  len: usize,
+}
+
+#[cfg(not(feature = "mmap"))]
+impl Deref for InputFile {
+  type Target = [u8];
+
+  fn deref(&self) -> &[u8] {
+    &self.bytes[..self.len]
+  }
+}
+
+/// Reads the file at `input_path` in place of mapping it.
+#[cfg(not(feature = "mmap"))]
+fn map_input_file(input_path: &str) -> BarseResult<InputFile> {
+  // Aligned like a mapping, so that page boundaries fall between the
+  // scanner's batches, which its page crossing checks rely on.
+  const PAGE_ALIGNMENT: usize = 4096;
+  let mut file = File::open(input_path)?;
+  let file_len = file.metadata()?.len();
+  let len = usize::try_from(file_len)
+    .map_err(|_| BarseError::new(format!("Input of {file_len} bytes doesn't fit in memory")))?;
+  let mut bytes = AlignedAlloc::zeroed(len.next_multiple_of(PAGE_ALIGNMENT), PAGE_ALIGNMENT)?;
+  file.read_exact(&mut bytes[..len])?;
+  #[cfg(feature = "tracing")]
+  tracing::info!(path = input_path, bytes = len, "read input file");
+  Ok(InputFile { bytes, len })
+}
+
+/// Returns the input file extended to a multiple of `SCANNER_CACHE_SIZE`
+/// bytes, which the scanner reads in whole batches. Unlike a mapping, this
+/// doesn't need to reach past the end of the input's slice, since the
+/// allocation is padded to a whole page.
+#[cfg(not(feature = "mmap"))]
+fn padded_input(input: &InputFile) -> &[u8] {
+  &input.bytes[..input.len.next_multiple_of(SCANNER_CACHE_SIZE)]
+}
+
+/// An input held in memory, which is followed by zeroes up to the end of its
+/// last page like a mapped file, so that tables can be built from it without
+/// touching the file system, e.g. in benchmarks.
+pub struct PaddedInput {
+  bytes: AlignedAlloc,
+  len: usize,
+}
+
+impl PaddedInput {
+  /// Aligned like a mapping, so that page boundaries fall between the
+  /// scanner's batches, which its page crossing checks rely on.
+  const PAGE_ALIGNMENT: usize = 4096;
+
+  /// Copies `input` into a padded buffer.
+  pub fn new(input: &[u8]) -> BarseResult<Self> {
+    let mut bytes = AlignedAlloc::zeroed(
+      input.len().next_multiple_of(Self::PAGE_ALIGNMENT),
+      Self::PAGE_ALIGNMENT,
+    )?;
+    bytes[..input.len()].copy_from_slice(input);
+    Ok(Self {
+      bytes,
+      len: input.len(),
+    })
+  }
+
+  /// Returns the input extended to a multiple of `SCANNER_CACHE_SIZE` bytes,
+  /// which the scanner reads in whole batches, e.g. for
+  /// `Scanner::from_start_with_len`.
+  pub fn padded_slice(&self) -> &[u8] {
+    &self.bytes[..self.len.next_multiple_of(SCANNER_CACHE_SIZE)]
+  }
+
+  /// Builds the table from the input with `options`.
+  pub fn build(&self, options: &Options) -> BarseResult<(Table, BuildReport)> {
+    build_temperature_reading_table_from_bytes_with_options(self.padded_slice(), self.len, options)
+  }
+}
+
+impl Deref for PaddedInput {
+  type Target = [u8];
+
+  fn deref(&self) -> &[u8] {
+    &self.bytes[..self.len]
+  }
+}
+
+/// Maps the file at `input_path` and calls `build` with the padded buffer and
+/// the length of the input, attributing any error to the file.
+pub(crate) fn with_input_file<T>(
+  input_path: &str,
+  build: impl FnOnce(&[u8], usize) -> BarseResult<T>,
+) -> BarseResult<T> {
+  map_input_file(input_path)
+    .and_then(|map| {
+      let map_buffer = padded_input(&map);
+      build(map_buffer, map.len()).map_err(|err| err.with_line_number(&map))
+    })
+    .map_err(|err| err.in_file(input_path))
+}
+
+/// Builds the table with `options`, including the time taken to map the file
+/// in the report.
+pub(crate) fn build_with_options_and_map_time(
+  input_path: &str,
+  options: &Options,
+) -> BarseResult<(Table, BuildReport)> {
+  let start = Instant::now();
+  with_input_file(input_path, |input, len| {
+    let map_time = start.elapsed();
+    let (table, report) =
+      build_temperature_reading_table_from_bytes_with_options(input, len, options)?;
+    #[cfg(feature = "tracing")]
+    tracing::info!(
+      stations = report.unique_stations,
+      records = report.records,
+      map_time = ?map_time,
+      wall_time = ?report.wall_time,
+      "built table"
+    );
+    Ok((table, BuildReport { map_time, ..report }))
+  })
+}
+
+pub fn build_temperature_reading_table(
+  input_path: &str,
+) -> BarseResult<impl for<'a> HasIter<'a, Item = (&'a str, &'a TemperatureSummary)>> {
+  with_input_file(input_path, build_temperature_reading_table_from_bytes)
+}
+
+/// Like `build_temperature_reading_table`, but lets the caller tune how the
+/// table is built, and reports which strategy was chosen.
+pub fn build_temperature_reading_table_with_options(
+  input_path: &str,
+  options: &Options,
+) -> BarseResult<(
+  impl for<'a> HasIter<'a, Item = (&'a str, &'a TemperatureSummary)> + use<>,
+  BuildReport,
+)> {
+  build_with_options_and_map_time(input_path, options)
+}
+
+/// Like `build_temperature_reading_table`, but sizes the table and input
+/// chunks from the hints in `config`. Unlike the other builders, the concrete
+/// table type is returned, so its `stats()` can be inspected.
+pub fn build_with_config(
+  input_path: &str,
+  config: &BuildConfig,
+) -> BarseResult<(Table, BuildReport)> {
+  build_with_options_and_map_time(input_path, &config.options())
+}
+
+/// Returns the paths of the regular files in `dir` in sorted order, keeping
+/// only those named with `extension` if one is given.
+fn dir_input_paths(dir: &str, extension: Option<&str>) -> BarseResult<Vec<String>> {
+  let mut paths = Vec::new();
+  for entry in fs::read_dir(dir)? {
+    let path = entry?.path();
+    if !fs::metadata(&path)?.is_file()
+      || extension.is_some_and(|extension| path.extension().is_none_or(|ext| ext != extension))
+    {
+      continue;
+    }
+    let path = path
+      .to_str()
+      .ok_or_else(|| BarseError::new(format!("Path \"{}\" is not valid UTF-8", path.display())))?;
+    paths.push(path.to_owned());
+  }
+  paths.sort();
+  Ok(paths)
+}
+
+/// Folds the report of a build into `total`, the report of the builds it is
+/// being merged with.
+fn add_report(total: &mut BuildReport, report: &BuildReport) {
+  total.threads = total.threads.max(report.threads);
+  total.bytes += report.bytes;
+  total.records += report.records;
+  total.missing_records += report.missing_records;
+  total.chunks += report.chunks;
+  total.hugepages &= report.hugepages;
+  total.map_time += report.map_time;
+  total.merge_time += report.merge_time;
+}
+
+/// Builds a table from each of the files at `paths` claimed through `next`
+/// until every file has been claimed, merging them as they are built. On
+/// error, `next` is advanced past the last file so that other workers stop.
+fn build_dir_files(
+  paths: &[String],
+  next: &AtomicUsize,
+  options: &Options,
+) -> BarseResult<Option<(Table, BuildReport)>> {
+  let mut built: Option<(Table, BuildReport)> = None;
+  while let Some(path) = paths.get(next.fetch_add(1, AtomicOrdering::Relaxed)) {
+    let result = build_with_options_and_map_time(path, options).and_then(|(table, report)| {
+      match &mut built {
+        Some((merged, total)) => {
+          let start = Instant::now();
+          merged.merge(&table)?;
+          add_report(total, &report);
+          total.merge_time += start.elapsed();
+        }
+        None => built = Some((table, report)),
+      }
+      Ok(())
+    });
+    if let Err(err) = result {
+      next.store(paths.len(), AtomicOrdering::Relaxed);
+      return Err(err);
+    }
+  }
+  Ok(built)
+}
+
+/// Builds one table from all of the regular files in `dir`, as if they had
+/// been concatenated, skipping files not named with `extension` if one is
+/// given. With the `multithreaded` feature, files are built in parallel, each
+/// with a share of `options.threads`. An empty directory produces an empty
+/// table. The report's times are summed over the files, except `wall_time`,
+/// which is the time taken to build the whole directory.
+pub fn build_from_dir(
+  dir: &str,
+  extension: Option<&str>,
+  options: &Options,
+) -> BarseResult<(Table, BuildReport)> {
+  let start = Instant::now();
+  let paths = dir_input_paths(dir, extension).map_err(|err| err.in_file(dir))?;
+  let next = AtomicUsize::new(0);
+
+  #[cfg(feature = "multithreaded")]
+  let worker_tables = {
+    let threads = options.threads.unwrap_or_else(|| {
+      std::thread::available_parallelism()
+        .map(|nonzero| nonzero.get())
+        .unwrap_or(1)
+    });
+    let file_workers = threads.min(paths.len()).max(1);
+    let file_options = Options {
+      threads: Some((threads / file_workers).max(1)),
+      ..options.clone()
+    };
+    std::thread::scope(|scope| {
+      let workers = (0..file_workers)
+        .map(|_| scope.spawn(|| build_dir_files(&paths, &next, &file_options)))
+        .collect::<Vec<_>>();
+      workers
+        .into_iter()
+        .enumerate()
+        .map(|(worker, handle)| {
+          handle
+            .join()
+            .map_err(|payload| BarseError::thread_panicked(worker, payload))?
+        })
+        .collect::<BarseResult<Vec<_>>>()
+    })?
+  };
+  #[cfg(not(feature = "multithreaded"))]
+  let worker_tables = vec![build_dir_files(&paths, &next, options)?];
+
+  let mut worker_tables = worker_tables.into_iter().flatten();
+  let Some((mut table, mut report)) = worker_tables.next() else {
+    let report = BuildReport {
+      strategy: Strategy::SingleThreaded,
+      threads: 1,
+      bytes: 0,
+      records: 0,
+      missing_records: 0,
+      unique_stations: 0,
+      chunks: 0,
+      hugepages: true,
+      map_time: Duration::ZERO,
+      wall_time: start.elapsed(),
+      merge_time: Duration::ZERO,
+    };
+    return Ok((Table::with_capacity(options.table_capacity)?, report));
+  };
+  let mut file_workers = 1;
+  for (worker_table, worker_report) in worker_tables {
+    let merge_start = Instant::now();
+    table.merge(&worker_table)?;
+    add_report(&mut report, &worker_report);
+    report.merge_time += merge_start.elapsed();
+    file_workers += 1;
+  }
+  report.threads *= file_workers;
+  report.unique_stations = table.iter().count();
+  report.wall_time = start.elapsed();
+  Ok((table, report))
+}
+
+/// Folds the records of the file at `input_path` into `table`, as if they had
+/// been appended to the input it was built from, so that a summary can be
+/// updated when a new file arrives without rebuilding from every file. The
+/// new file is built with `options`, and the returned report describes it,
+/// except that `merge_time` includes merging it into `table`, and
+/// `unique_stations` counts the stations of the updated table.
+pub fn append_file(
+  table: &mut Table,
+  input_path: &str,
+  options: &Options,
+) -> BarseResult<BuildReport> {
+  let (file_table, mut report) = build_with_options_and_map_time(input_path, options)?;
+  let start = Instant::now();
+  table.merge(&file_table)?;
+  report.merge_time += start.elapsed();
+  report.unique_stations = table.iter().count();
+  Ok(report)
+}
+
+/// Like `build_temperature_reading_table`, but also returns statistics about
+/// the build.
+pub fn build_temperature_reading_table_with_report(
+  input_path: &str,
+) -> BarseResult<(
+  impl for<'a> HasIter<'a, Item = (&'a str, &'a TemperatureSummary)> + use<>,
+  BuildReport,
+)> {
+  build_temperature_reading_table_with_options(input_path, &Options::default())
+}
+
+/// Like `build_temperature_reading_table`, but calls `on_chunk` from the worker
+/// threads with a summary of each chunk of the input as soon as it has been
+/// processed, e.g. to report progress.
+#[cfg(feature = "multithreaded")]
+pub fn build_temperature_reading_table_streaming<F>(
+  input_path: &str,
+  on_chunk: F,
+) -> BarseResult<impl for<'a> HasIter<'a, Item = (&'a str, &'a TemperatureSummary)> + use<F>>
+where
+  F: Fn(PartialSummary<'_>) + Sync,
+{
+  with_input_file(input_path, |input, len| {
+    build_streaming(input, len, on_chunk)
+  })
+}
+
+/// The name and `Aggregator::Summary` of each station, sorted by name.
+#[cfg(feature = "multithreaded")]
+pub type AggregatedStations<S> = Vec<(String, S)>;
+
+/// Like `build_temperature_reading_table_with_options`, but folds the readings
+/// of each station with `aggregator` rather than computing a
+/// `TemperatureSummary`. Returns the name and summary of every station, sorted
+/// by name as the challenge output is.
+#[cfg(feature = "multithreaded")]
+pub fn build_aggregated<A>(
+  input_path: &str,
+  aggregator: A,
+  options: &Options,
+) -> BarseResult<(AggregatedStations<A::Summary>, BuildReport)>
+where
+  A: Aggregator + Clone + Send + 'static,
+  A::Summary: Clone + Send + 'static,
+{
+  with_input_file(input_path, |input, len| {
+    let (table, report) = build_aggregated_table_from_bytes(input, len, &aggregator, options)?;
+    let mut stations = table.to_owned_summary();
+    stations.sort_unstable_by(|(a, _), (b, _)| utf16_cmp(a, b));
+    Ok((stations, report))
+  })
+}
+
+/// Like `build_temperature_reading_table`, but aggregates readings under
+/// normalized station names. `normalizer` is called with each station name
+/// and an empty buffer, into which it writes the name to aggregate the
+/// reading under, e.g. `normalize::ascii_lowercase`. Normalized names must
+/// not be longer than 50 bytes.
+pub fn build_with_normalizer<F>(input_path: &str, normalizer: F) -> BarseResult<Table>
+where
+  F: Fn(&str, &mut String) + Sync,
+{
+  with_input_file(input_path, |input, len| {
+    build_with_normalizer_from_bytes(input, len, &normalizer)
+  })
+}
 
-  let map_buffer = unsafe { round_up_to_cache_size_boundary(&map) };
-  build_temperature_reading_table_from_bytes(map_buffer)
+/// Like `build_temperature_reading_table`, but aggregates readings under a
+/// key derived from each station name by `key_fn`, e.g.
+/// `normalize::country_prefix`. Stations with the same key are merged. The key is a sub-slice of
+/// the name, so unlike `build_with_normalizer`, no name is copied.
+pub fn build_grouped_by<K>(input_path: &str, key_fn: K) -> BarseResult<Table>
+where
+  K: Fn(&str) -> &str + Sync,
+{
+  with_input_file(input_path, |input, len| {
+    build_grouped_by_from_bytes(input, len, &key_fn)
+  })
+}
+
+/// Returns the summary and exact median of every station in the file at
+/// `input_path`, ordered by name, without storing the readings. The first
+/// pass builds the table of summaries as usual, which bounds each station's
+/// readings to the range between its min and max. The second pass counts the
+/// readings of each station into a histogram of that range on the calling
+/// thread, from which the median is read off.
+pub fn build_exact_medians(input_path: &str) -> BarseResult<Vec<StationMedian>> {
+  with_input_file(input_path, |input, len| {
+    let (table, _) =
+      build_temperature_reading_table_from_bytes_with_options(input, len, &Options::default())?;
+    let mut histograms = table
+      .iter()
+      .map(|(station, summary)| (station, (*summary, ReadingHistogram::for_summary(summary))))
+      .collect::<HashMap<_, _, BuildStringHash>>();
+    for (station, reading) in Scanner::from_start_with_len(input, len) {
+      let counted = histograms
+        .get_mut(station)
+        .is_some_and(|(_, histogram)| histogram.add_reading(reading));
+      if !counted {
+        return Err(BarseError::new(format!(
+          "reading {reading} of \"{station}\" wasn't seen in the first pass, the input \
+           may have changed"
+        )));
+      }
+    }
+
+    let mut stations = histograms
+      .into_iter()
+      .filter_map(|(name, (summary, histogram))| {
+        Some(StationMedian {
+          name: name.to_owned(),
+          summary,
+          median: histogram.median()?,
+        })
+      })
+      .collect::<Vec<_>>();
+    stations.sort_unstable_by(|a, b| utf16_cmp(&a.name, &b.name));
+    Ok(stations)
+  })
+}
+
+/// Like `build_temperature_reading_table`, but aggregates the readings into a
+/// standard `HashMap` on the calling thread. This is much slower than the
+/// other builders, but the result can be handed to code expecting a plain
+/// map.
+pub fn build_hashmap(input_path: &str) -> BarseResult<HashMap<String, TemperatureSummary>> {
+  build_hashmap_with_hasher(input_path)
+}
+
+/// Like `build_hashmap`, with a custom hasher for the map, e.g.
+/// `BuildStringHash`.
+pub fn build_hashmap_with_hasher<S>(
+  input_path: &str,
+) -> BarseResult<HashMap<String, TemperatureSummary, S>>
+where
+  S: BuildHasher + Default,
+{
+  with_input_file(input_path, |input, len| {
+    Ok(Scanner::from_start_with_len(input, len).fold(
+      HashMap::<String, TemperatureSummary, S>::default(),
+      |mut stations, (station, reading)| {
+        // Look the station up by `&str` first to avoid allocating a key for
+        // every record.
+        match stations.get_mut(station) {
+          Some(summary) => summary.add_reading(reading),
+          None => {
+            let mut summary = TemperatureSummary::default();
+            summary.add_reading(reading);
+            stations.insert(station.to_owned(), summary);
+          }
+        }
+        stations
+      },
+    ))
+  })
+}
+
+/// A station's summaries of each column of readings, for inputs with several
+/// readings per record.
+#[derive(Clone, Debug)]
+pub struct MultiColumnStation {
+  pub name: String,
+  pub summaries: Vec<TemperatureSummary>,
+}
+
+impl Display for MultiColumnStation {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}=", self.name)?;
+    for (column, summary) in self.summaries.iter().enumerate() {
+      if column != 0 {
+        write!(f, "/")?;
+      }
+      write!(f, "{summary}")?;
+    }
+    Ok(())
+  }
+}
+
+/// Summarizes an input whose records have `columns` readings each, e.g.
+/// `Station;12.3;-4.5` for two columns, keeping a summary of each column per
+/// station. The stations are returned sorted by name. The readings are
+/// aggregated into a standard `HashMap` on the calling thread.
+pub fn build_columns(input_path: &str, columns: usize) -> BarseResult<Vec<MultiColumnStation>> {
+  if columns == 0 {
+    return Err(BarseError::new(
+      "Records must have at least one column of readings".to_owned(),
+    ));
+  }
+  with_input_file(input_path, |input, len| {
+    let mut stations = HashMap::<String, Vec<TemperatureSummary>, BuildStringHash>::default();
+    let mut scanner = Scanner::from_start_with_len(input, len);
+    let mut readings = vec![TemperatureReading::new(0); columns];
+    while let Some(station) = scanner.next_record(&mut readings) {
+      // Look the station up by `&str` first to avoid allocating a key for
+      // every record.
+      let summaries = match stations.get_mut(station) {
+        Some(summaries) => summaries,
+        None => stations
+          .entry(station.to_owned())
+          .or_insert_with(|| vec![TemperatureSummary::default(); columns]),
+      };
+      for (summary, &reading) in summaries.iter_mut().zip(&readings) {
+        summary.add_reading(reading);
+      }
+    }
+
+    let mut stations = stations
+      .into_iter()
+      .map(|(name, summaries)| MultiColumnStation { name, summaries })
+      .collect::<Vec<_>>();
+    stations.sort_unstable_by(|a, b| utf16_cmp(&a.name, &b.name));
+    Ok(stations)
+  })
+}
+
+/// Calls `on_record` with each record of the input in order, on the calling
+/// thread, without aggregating them into a table. The scan stops early if
+/// `on_record` returns `ControlFlow::Break`, in which case the break value is
+/// returned.
+pub fn scan_records<B, F>(input_path: &str, on_record: F) -> BarseResult<ControlFlow<B>>
+where
+  F: FnMut(&str, TemperatureReading) -> ControlFlow<B>,
+{
+  scan_records_with_options(input_path, &Options::default(), on_record)
+}
+
+/// Like `scan_records`, but reads records as `options` describes. With
+/// `options.strict`, the scan fails at the first malformed record, e.g. one
+/// whose station name isn't valid UTF-8, before it is passed to `on_record`.
+/// The options for building tables are ignored.
+pub fn scan_records_with_options<B, F>(
+  input_path: &str,
+  options: &Options,
+  mut on_record: F,
+) -> BarseResult<ControlFlow<B>>
+where
+  F: FnMut(&str, TemperatureReading) -> ControlFlow<B>,
+{
+  with_input_file(input_path, |input, len| {
+    if options.strict {
+      let mut scanner = ValidatingScanner::from_start(&input[..len])
+        .with_decimal_separator(options.decimal_separator)
+        .with_extra_fields(options.extra_fields);
+      if options.skip_header {
+        scanner = scanner.skip_header();
+      }
+      for record in scanner {
+        let (station, reading) = record?;
+        if let ControlFlow::Break(value) = on_record(station, reading) {
+          return Ok(ControlFlow::Break(value));
+        }
+      }
+      return Ok(ControlFlow::Continue(()));
+    }
+
+    let mut scanner = Scanner::from_start_with_len(input, len)
+      .with_decimal_separator(options.decimal_separator)
+      .with_extra_fields(options.extra_fields);
+    if options.skip_header {
+      scanner = scanner.skip_header();
+    }
+    Ok(scanner.try_for_each(|(station, reading)| on_record(station, reading)))
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{collections::HashMap, fs, ops::ControlFlow};
+
+  use googletest::prelude::*;
+
+  #[cfg(feature = "serde")]
+  use crate::barse::Snapshot;
+  use crate::{
+    barse::{
+      append_file, build_columns, build_from_dir, build_grouped_by, build_hashmap,
+      build_hashmap_with_hasher, build_temperature_reading_table,
+      build_temperature_reading_table_with_options, build_with_config, build_with_normalizer,
+      scan_records, scan_records_with_options, PaddedInput, Table,
+    },
+    error::{BarseError, RecordErrorKind},
+    normalize::{ascii_lowercase, ascii_lowercase_key, country_prefix, NameBuf, Normalize},
+    options::{BuildConfig, Options},
+    scanner::{ExtraFields, BUFFER_OVERLAP},
+    station_filter::StationFilter,
+    str_hash::BuildStringHash,
+    temperature_reading::{DecimalSeparator, TemperatureReading},
+    temperature_summary::TemperatureSummary,
+    test_util::random_input_file,
+    util::HasIter,
+  };
+
+  fn summary_fields(summary: &TemperatureSummary) -> (i16, i16, i64, u32) {
+    (
+      summary.min.reading(),
+      summary.max.reading(),
+      summary.total,
+      summary.count,
+    )
+  }
+
+  #[gtest]
+  fn test_build_hashmap_matches_table() {
+    let input = random_input_file(0x1e2d3c, 100_000, 500).unwrap();
+    let input_path =
+      std::env::temp_dir().join(format!("barse_{}_build_hashmap.txt", std::process::id()));
+    fs::write(&input_path, input.exact_slice()).unwrap();
+    let input_path = input_path.to_str().unwrap();
+
+    let table = build_temperature_reading_table(input_path).unwrap();
+    let expected = table
+      .iter()
+      .map(|(station, summary)| (station.to_owned(), summary_fields(summary)))
+      .collect::<HashMap<_, _>>();
+
+    let map = build_hashmap(input_path).unwrap();
+    let fast_map = build_hashmap_with_hasher::<BuildStringHash>(input_path).unwrap();
+    fs::remove_file(input_path).unwrap();
+
+    expect_eq!(expected.len(), 500);
+    for map in [
+      map
+        .iter()
+        .map(|(station, summary)| (station.clone(), summary_fields(summary)))
+        .collect::<HashMap<_, _>>(),
+      fast_map
+        .iter()
+        .map(|(station, summary)| (station.clone(), summary_fields(summary)))
+        .collect(),
+    ] {
+      expect_eq!(map, expected);
+    }
+  }
+
+  #[gtest]
+  fn test_normalizer_merges_mixed_case_stations() {
+    let input_path =
+      std::env::temp_dir().join(format!("barse_{}_normalizer.txt", std::process::id()));
+    fs::write(
+      &input_path,
+      "Berlin;1.0\nberlin;2.0\nParis;-4.0\nBERLIN;3.0\nparis;4.0\nOslo;0.5\n",
+    )
+    .unwrap();
+    let input_path = input_path.to_str().unwrap();
+
+    let table = build_with_normalizer(input_path, ascii_lowercase).unwrap();
+    let too_long = build_with_normalizer(input_path, |name, out| {
+      out.extend(std::iter::repeat_n(name, 10))
+    });
+    fs::remove_file(input_path).unwrap();
+
+    let mut stations = table
+      .iter()
+      .map(|(station, summary)| (station.to_owned(), summary_fields(summary)))
+      .collect::<Vec<_>>();
+    stations.sort();
+    expect_that!(
+      stations,
+      elements_are![
+        eq(&("berlin".to_owned(), (10, 30, 60, 3))),
+        eq(&("oslo".to_owned(), (5, 5, 5, 1))),
+        eq(&("paris".to_owned(), (-40, 40, 0, 2))),
+      ]
+    );
+    expect_true!(matches!(
+      too_long.as_ref().map_err(BarseError::without_context),
+      Err(BarseError::Other(_))
+    ));
+  }
+
+  #[gtest]
+  fn test_grouped_by_country_prefix() {
+    let input_path =
+      std::env::temp_dir().join(format!("barse_{}_grouped_by.txt", std::process::id()));
+    fs::write(
+      &input_path,
+      "US/NYC;10.0\nDE/BER;-2.0\nUS/LA;20.0\nUS/NYC;0.0\nDE/BER;4.0\n",
+    )
+    .unwrap();
+    let input_path = input_path.to_str().unwrap();
+
+    let table = build_grouped_by(input_path, country_prefix).unwrap();
+    fs::remove_file(input_path).unwrap();
+
+    let mut stations = table
+      .iter()
+      .map(|(station, summary)| (station.to_owned(), summary_fields(summary)))
+      .collect::<Vec<_>>();
+    stations.sort();
+    expect_that!(
+      stations,
+      elements_are![
+        eq(&("DE".to_owned(), (-20, 40, 20, 2))),
+        eq(&("US".to_owned(), (0, 200, 300, 3))),
+      ]
+    );
+  }
+
+  #[gtest]
+  fn test_build_columns_two_readings() {
+    let input_path =
+      std::env::temp_dir().join(format!("barse_{}_build_columns.txt", std::process::id()));
+    fs::write(
+      &input_path,
+      "Oslo;-3.0;1.5\nAbha;20.0;31.2\nOslo;-1.0;4.5\n",
+    )
+    .unwrap();
+    let input_path = input_path.to_str().unwrap();
+
+    let stations = build_columns(input_path, 2).unwrap();
+    let no_columns = build_columns(input_path, 0);
+    fs::remove_file(input_path).unwrap();
+
+    let fields = stations
+      .iter()
+      .map(|station| {
+        (
+          station.name.clone(),
+          station
+            .summaries
+            .iter()
+            .map(summary_fields)
+            .collect::<Vec<_>>(),
+        )
+      })
+      .collect::<Vec<_>>();
+    expect_that!(
+      fields,
+      elements_are![
+        eq(&(
+          "Abha".to_owned(),
+          vec![(200, 200, 200, 1), (312, 312, 312, 1)]
+        )),
+        eq(&("Oslo".to_owned(), vec![(-30, -10, -40, 2), (15, 45, 60, 2)])),
+      ]
+    );
+    expect_eq!(stations[1].to_string(), "Oslo=-3.0/-2.0/-1.0/1.5/3.0/4.5");
+    expect_true!(no_columns.is_err());
+  }
+
+  #[gtest]
+  fn test_scan_records_counts_matching_records() {
+    let input_path =
+      std::env::temp_dir().join(format!("barse_{}_scan_records.txt", std::process::id()));
+    fs::write(
+      &input_path,
+      "Palembang;38.8\nHamburg;12.0\nAbha;30.0\nPalembang;30.1\nOslo;-31.4\nAbha;45.2\n",
+    )
+    .unwrap();
+    let input_path = input_path.to_str().unwrap();
+
+    let threshold = TemperatureReading::new(300);
+    let mut hot_records = 0;
+    let hot = scan_records(input_path, |_, reading| {
+      if reading > threshold {
+        hot_records += 1;
+      }
+      ControlFlow::<()>::Continue(())
+    })
+    .unwrap();
+
+    let mut stations_before_oslo = Vec::new();
+    let first_cold = scan_records(input_path, |station, reading| {
+      if reading < TemperatureReading::new(0) {
+        return ControlFlow::Break(station.to_owned());
+      }
+      stations_before_oslo.push(station.to_owned());
+      ControlFlow::Continue(())
+    })
+    .unwrap();
+    fs::remove_file(input_path).unwrap();
+
+    expect_eq!(hot, ControlFlow::Continue(()));
+    expect_eq!(hot_records, 3);
+    expect_eq!(first_cold, ControlFlow::Break("Oslo".to_owned()));
+    expect_that!(
+      stations_before_oslo,
+      elements_are![eq("Palembang"), eq("Hamburg"), eq("Abha"), eq("Palembang")]
+    );
+  }
+
+  #[gtest]
+  fn test_scan_records_with_options() {
+    let input_path =
+      std::env::temp_dir().join(format!("barse_{}_scan_strict.txt", std::process::id()));
+    fs::write(&input_path, "station;temp\nAbha;30,0;dry\nOslo;-31,4\n").unwrap();
+    let input_path = input_path.to_str().unwrap();
+
+    let mut options = Options {
+      skip_header: true,
+      decimal_separator: DecimalSeparator::Comma,
+      extra_fields: ExtraFields::Skip,
+      ..Options::default()
+    };
+    for strict in [false, true] {
+      options.strict = strict;
+      let mut records = Vec::new();
+      let scanned = scan_records_with_options(input_path, &options, |station, reading| {
+        records.push((station.to_owned(), reading));
+        ControlFlow::<()>::Continue(())
+      })
+      .unwrap();
+      expect_eq!(scanned, ControlFlow::Continue(()), "strict: {strict}");
+      expect_that!(
+        records,
+        elements_are![
+          eq(&("Abha".to_owned(), TemperatureReading::new(300))),
+          eq(&("Oslo".to_owned(), TemperatureReading::new(-314)))
+        ],
+        "strict: {strict}"
+      );
+    }
+
+    // Strict scans stop at an invalid name, without passing it on.
+    fs::write(input_path, b"station;temp\nAbha;30,0\nOs\xfflo;-31,4\n").unwrap();
+    let mut stations = Vec::new();
+    let result = scan_records_with_options(input_path, &options, |station, _| {
+      stations.push(station.to_owned());
+      ControlFlow::<()>::Continue(())
+    });
+    fs::remove_file(input_path).unwrap();
+    expect_that!(
+      result.as_ref().map_err(BarseError::without_context),
+      err(pat!(BarseError::MalformedRecord {
+        offset: eq(&23),
+        line: some(eq(&3)),
+        kind: eq(&RecordErrorKind::InvalidUtf8),
+      }))
+    );
+    expect_that!(stations, elements_are![eq("Abha")]);
+  }
+
+  #[gtest]
+  fn test_top_and_bottom_by_avg() {
+    let input_path = std::env::temp_dir().join(format!("barse_{}_by_avg.txt", std::process::id()));
+    fs::write(
+      &input_path,
+      "Abha;30.0\nOslo;-5.0\nBern;31.0\nCairo;35.5\nDakar;25.0\nAbha;32.0\nOslo;1.0\n\
+       Reykjavik;-2.0\nYakutsk;-30.0\nZurich;10.0\n",
+    )
+    .unwrap();
+    let (table, _) =
+      build_with_config(input_path.to_str().unwrap(), &BuildConfig::default()).unwrap();
+    fs::remove_file(&input_path).unwrap();
+
+    let averages = |stations: &[(&str, i16)]| {
+      stations
+        .iter()
+        .map(|&(station, avg)| (station.to_owned(), TemperatureReading::new(avg)))
+        .collect::<Vec<_>>()
+    };
+    // Abha and Bern tie at 31.0, and Oslo and Reykjavik at -2.0.
+    expect_eq!(
+      table.top_by_avg(3),
+      averages(&[("Cairo", 355), ("Abha", 310), ("Bern", 310)])
+    );
+    expect_eq!(
+      table.bottom_by_avg(3),
+      averages(&[("Yakutsk", -300), ("Oslo", -20), ("Reykjavik", -20)])
+    );
+    expect_eq!(table.top_by_avg(1), averages(&[("Cairo", 355)]));
+    expect_that!(table.bottom_by_avg(0), is_empty());
+    expect_that!(table.top_by_avg(100), len(eq(8)));
+  }
+
+  #[gtest]
+  fn test_fingerprint_is_independent_of_order() {
+    let input = random_input_file(0xf19e7, 20_000, 400).unwrap();
+    let records = str::from_utf8(input.exact_slice()).unwrap();
+    let reversed = records
+      .lines()
+      .rev()
+      .map(|line| format!("{line}\n"))
+      .collect::<String>();
+    let build = |records: &str, table_capacity| {
+      let options = Options {
+        table_capacity,
+        ..Options::default()
+      };
+      let (table, _) = PaddedInput::new(records.as_bytes())
+        .and_then(|input| input.build(&options))
+        .unwrap();
+      table
+    };
+
+    let table = build(records, 1024);
+    let fingerprint = table.fingerprint();
+    // A different capacity places the stations in different buckets.
+    expect_eq!(build(&reversed, 1024).fingerprint(), fingerprint);
+    expect_eq!(build(records, 4096).fingerprint(), fingerprint);
+    expect_eq!(
+      build(&format!("{records}{reversed}"), 1024).fingerprint(),
+      build(&format!("{reversed}{records}"), 2048).fingerprint()
+    );
+
+    let (station, temp) = records
+      .lines()
+      .next()
+      .and_then(|line| line.split_once(';'))
+      .unwrap();
+    let changed = format!("{station};{}\n", if temp == "1.0" { "2.0" } else { "1.0" });
+    let changed_records = format!("{changed}{}", records.split_once('\n').unwrap().1);
+    expect_ne!(build(&changed_records, 1024).fingerprint(), fingerprint);
+    expect_ne!(
+      build(&format!("{records}{changed}"), 1024).fingerprint(),
+      fingerprint
+    );
+    expect_ne!(
+      Table::with_capacity(1024).unwrap().fingerprint(),
+      fingerprint
+    );
+  }
+
+  #[gtest]
+  fn test_options_normalize_merges_spellings() {
+    fn fold_umlauts<'a>(name: &'a str, buf: &'a mut NameBuf) -> &'a str {
+      for c in name.chars().flat_map(char::to_lowercase) {
+        match c {
+          'ü' => buf.push_str("ue"),
+          c => buf.push(c),
+        }
+      }
+      buf.as_str()
+    }
+    fn constant<'a>(_: &'a str, _: &'a mut NameBuf) -> &'a str {
+      "Everywhere"
+    }
+    fn too_long<'a>(name: &'a str, _: &'a mut NameBuf) -> &'a str {
+      if name == "Oslo" {
+        "Llanfairpwllgwyngyllgogerychwyrndrobwllllantysiliogogogoch"
+      } else {
+        name
+      }
+    }
+
+    let records = "München;1.0\nMuenchen;3.0\nMÜNCHEN;-2.0\nmuenchen;4.0\nOslo;0.5\n".repeat(200);
+    let build = |normalize: Normalize, single_threaded_threshold, strict| {
+      let options = Options {
+        single_threaded_threshold,
+        min_chunk_size: 1,
+        chunk_size: 4 * BUFFER_OVERLAP,
+        strict,
+        normalize: Some(normalize),
+        ..Options::default()
+      };
+      PaddedInput::new(records.as_bytes())
+        .and_then(|input| input.build(&options))
+        .map(|(table, _)| {
+          let mut stations = table
+            .iter()
+            .map(|(station, summary)| (station.to_owned(), summary_fields(summary)))
+            .collect::<Vec<_>>();
+          stations.sort();
+          stations
+        })
+    };
+
+    for (single_threaded_threshold, strict) in [
+      (usize::MAX, false),
+      (usize::MAX, true),
+      (0, false),
+      (0, true),
+    ] {
+      expect_that!(
+        build(fold_umlauts, single_threaded_threshold, strict),
+        ok(elements_are![
+          eq(&("muenchen".to_owned(), (-20, 40, 12000, 800))),
+          eq(&("oslo".to_owned(), (5, 5, 1000, 200))),
+        ])
+      );
+      expect_that!(
+        build(ascii_lowercase_key, single_threaded_threshold, strict),
+        ok(elements_are![
+          eq(&("muenchen".to_owned(), (30, 40, 14000, 400))),
+          eq(&("mÜnchen".to_owned(), (-20, -20, -4000, 200))),
+          eq(&("münchen".to_owned(), (10, 10, 2000, 200))),
+          eq(&("oslo".to_owned(), (5, 5, 1000, 200))),
+        ])
+      );
+      expect_that!(
+        build(constant, single_threaded_threshold, strict),
+        ok(elements_are![eq(&(
+          "Everywhere".to_owned(),
+          (-20, 40, 13000, 1000)
+        ))])
+      );
+      expect_true!(matches!(
+        build(too_long, single_threaded_threshold, strict),
+        Err(BarseError::Other(_))
+      ));
+    }
+  }
+
+  #[gtest]
+  fn test_missing_readings_are_left_out() {
+    let records = "Oslo;-99.9\nOslo;1.0\nLima;-99.9\nOslo;-99.9\nRome;-3.0\n".repeat(200);
+    let build = |missing_reading, single_threaded_threshold, strict| {
+      let options = Options {
+        single_threaded_threshold,
+        min_chunk_size: 1,
+        chunk_size: 4 * BUFFER_OVERLAP,
+        strict,
+        missing_reading,
+        ..Options::default()
+      };
+      let (table, report) = PaddedInput::new(records.as_bytes())
+        .and_then(|input| input.build(&options))
+        .unwrap();
+      let mut stations = table
+        .iter()
+        .map(|(station, summary)| (station.to_owned(), summary_fields(summary)))
+        .collect::<Vec<_>>();
+      stations.sort();
+      (stations, report.records, report.missing_records)
+    };
+
+    for (single_threaded_threshold, strict) in [
+      (usize::MAX, false),
+      (usize::MAX, true),
+      (0, false),
+      (0, true),
+    ] {
+      let (stations, records, missing_records) = build(
+        Some(TemperatureReading::new(-999)),
+        single_threaded_threshold,
+        strict,
+      );
+      expect_that!(
+        stations,
+        elements_are![
+          eq(&("Oslo".to_owned(), (10, 10, 2000, 200))),
+          eq(&("Rome".to_owned(), (-30, -30, -6000, 200))),
+        ]
+      );
+      expect_eq!(records, 400);
+      expect_eq!(missing_records, 600);
+
+      let (stations, records, missing_records) = build(None, single_threaded_threshold, strict);
+      expect_that!(
+        stations,
+        elements_are![
+          eq(&("Lima".to_owned(), (-999, -999, -199800, 200))),
+          eq(&("Oslo".to_owned(), (-999, 10, -397600, 600))),
+          eq(&("Rome".to_owned(), (-30, -30, -6000, 200))),
+        ]
+      );
+      expect_eq!(records, 1000);
+      expect_eq!(missing_records, 0);
+    }
+  }
+
+  #[gtest]
+  fn test_station_filter_keeps_summaries_of_matching_stations() {
+    let input = random_input_file(0x5f11e, 20_000, 300).unwrap();
+    let build = |station_filter, single_threaded_threshold, strict| {
+      let options = Options {
+        single_threaded_threshold,
+        min_chunk_size: 1,
+        chunk_size: 16 * BUFFER_OVERLAP,
+        strict,
+        station_filter,
+        ..Options::default()
+      };
+      let (table, report) = PaddedInput::new(input.exact_slice())
+        .and_then(|input| input.build(&options))
+        .unwrap();
+      let mut stations = table
+        .iter()
+        .map(|(station, summary)| (station.to_owned(), summary_fields(summary)))
+        .collect::<Vec<_>>();
+      stations.sort();
+      (stations, report.records)
+    };
+
+    let (unfiltered, _) = build(None, usize::MAX, false);
+    let names = [&unfiltered[3].0, &unfiltered[150].0, &unfiltered[299].0];
+    let prefix = unfiltered[40].0.chars().take(2).collect::<String>();
+    let filter = StationFilter::new(names)
+      .unwrap()
+      .with_prefixes([prefix.as_str()]);
+    let expected = unfiltered
+      .iter()
+      .filter(|(station, _)| names.contains(&station) || station.starts_with(&prefix))
+      .cloned()
+      .collect::<Vec<_>>();
+    expect_that!(expected.len(), ge(4));
+
+    for (single_threaded_threshold, strict) in [
+      (usize::MAX, false),
+      (usize::MAX, true),
+      (0, false),
+      (0, true),
+    ] {
+      let (stations, records) = build(Some(filter.clone()), single_threaded_threshold, strict);
+      expect_eq!(stations, expected);
+      expect_eq!(
+        records,
+        expected
+          .iter()
+          .map(|(_, (_, _, _, count))| *count as u64)
+          .sum::<u64>()
+      );
+    }
+  }
+
+  #[gtest]
+  fn test_build_from_dir_matches_concatenated_input() {
+    let input = random_input_file(0xd1ec70, 50_000, 300).unwrap();
+    let contents = input.exact_slice();
+    let split = contents[..contents.len() / 3]
+      .iter()
+      .rposition(|&b| b == b'\n')
+      .unwrap()
+      + 1;
+
+    let dir = std::env::temp_dir().join(format!("barse_{}_input_dir", std::process::id()));
+    fs::create_dir_all(dir.join("nested")).unwrap();
+    fs::write(dir.join("shard0.txt"), &contents[..split]).unwrap();
+    fs::write(dir.join("shard1.txt"), &contents[split..]).unwrap();
+    // Neither of these should be read.
+    fs::write(dir.join("README.md"), "Not;a record\n").unwrap();
+    fs::write(dir.join("nested/shard2.txt"), "Abha;1.0\n").unwrap();
+    let concatenated = std::env::temp_dir().join(format!(
+      "barse_{}_input_dir_concatenated.txt",
+      std::process::id()
+    ));
+    fs::write(&concatenated, contents).unwrap();
+
+    let options = Options {
+      threads: Some(4),
+      min_chunk_size: 1,
+      chunk_size: 4 * BUFFER_OVERLAP,
+      single_threaded_threshold: 0,
+      ..Options::default()
+    };
+    let (table, report) = build_from_dir(dir.to_str().unwrap(), Some("txt"), &options).unwrap();
+    let (expected, _) =
+      build_with_config(concatenated.to_str().unwrap(), &BuildConfig::default()).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    fs::remove_file(&concatenated).unwrap();
+
+    let stations = |table: &Table| {
+      let mut stations = table
+        .iter()
+        .map(|(station, summary)| (station.to_owned(), summary_fields(summary)))
+        .collect::<Vec<_>>();
+      stations.sort();
+      stations
+    };
+    expect_eq!(stations(&table), stations(&expected));
+    expect_eq!(report.bytes, contents.len() as u64);
+    expect_eq!(report.records, 50_000);
+    expect_eq!(report.unique_stations, 300);
+  }
+
+  #[gtest]
+  fn test_build_from_empty_dir() {
+    let dir = std::env::temp_dir().join(format!("barse_{}_empty_input_dir", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (table, report) = build_from_dir(dir.to_str().unwrap(), None, &Options::default()).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    expect_eq!(table.iter().count(), 0);
+    expect_eq!(report.records, 0);
+  }
+
+  #[gtest]
+  fn test_append_file_matches_concatenated_input() {
+    let first = random_input_file(0xa99e4d, 20_000, 200).unwrap();
+    // Shares some stations with the first file, and adds new ones.
+    let second = random_input_file(0x5ec04d, 10_000, 400).unwrap();
+    let path = |name: &str| {
+      std::env::temp_dir().join(format!("barse_{}_append_{name}.txt", std::process::id()))
+    };
+    let (first_path, second_path, concatenated_path) =
+      (path("first"), path("second"), path("concatenated"));
+    fs::write(&first_path, first.exact_slice()).unwrap();
+    fs::write(&second_path, second.exact_slice()).unwrap();
+    fs::write(
+      &concatenated_path,
+      [first.exact_slice(), second.exact_slice()].concat(),
+    )
+    .unwrap();
+
+    let options = Options::default();
+    let (table, _) =
+      build_with_config(first_path.to_str().unwrap(), &BuildConfig::default()).unwrap();
+    // Save the table and load it back, as a later invocation would.
+    #[cfg(feature = "serde")]
+    let table = {
+      let json = serde_json::to_string(&Snapshot::of(&table)).unwrap();
+      serde_json::from_str::<Snapshot>(&json)
+        .unwrap()
+        .into_table()
+        .unwrap()
+    };
+    let mut table = table;
+    let report = append_file(&mut table, second_path.to_str().unwrap(), &options).unwrap();
+    let (expected, _) =
+      build_with_config(concatenated_path.to_str().unwrap(), &BuildConfig::default()).unwrap();
+    for path in [first_path, second_path, concatenated_path] {
+      fs::remove_file(path).unwrap();
+    }
+
+    let stations = |table: &Table| {
+      let mut stations = table
+        .iter()
+        .map(|(station, summary)| (station.to_owned(), summary_fields(summary)))
+        .collect::<Vec<_>>();
+      stations.sort();
+      stations
+    };
+    expect_eq!(stations(&table), stations(&expected));
+    expect_eq!(report.bytes, second.exact_slice().len() as u64);
+    expect_eq!(report.records, 10_000);
+    expect_eq!(report.unique_stations, expected.iter().count());
+  }
+
+  #[gtest]
+  fn test_records_at_end_of_16k_mapping() {
+    // A mapping of a 16 KiB file ends on a page boundary on every target, so
+    // reads of the final record must not run past its last byte.
+    let contents = format!("{}{}", "Abha;12.3\n".repeat(1633), "Oslo;1.0\n".repeat(6));
+    expect_eq!(contents.len(), 16 * 1024);
+    let input_path =
+      std::env::temp_dir().join(format!("barse_{}_16k_mapping.txt", std::process::id()));
+    fs::write(&input_path, &contents).unwrap();
+    let input_path = input_path.to_str().unwrap();
+
+    let mut last_record = None;
+    let scanned = scan_records(input_path, |station, reading| {
+      last_record = Some((station.to_owned(), reading));
+      ControlFlow::<()>::Continue(())
+    });
+    let table = build_temperature_reading_table(input_path);
+    fs::remove_file(input_path).unwrap();
+
+    expect_eq!(scanned.unwrap(), ControlFlow::Continue(()));
+    expect_eq!(
+      last_record,
+      Some(("Oslo".to_owned(), TemperatureReading::new(10)))
+    );
+    expect_eq!(
+      table
+        .unwrap()
+        .iter()
+        .map(|(station, summary)| (station.to_owned(), summary_fields(summary)))
+        .collect::<HashMap<_, _>>(),
+      HashMap::from([
+        ("Abha".to_owned(), (123, 123, 123 * 1633, 1633)),
+        ("Oslo".to_owned(), (10, 10, 10 * 6, 6)),
+      ])
+    );
+  }
+
+  #[gtest]
+  fn test_malformed_record_reports_path_and_offset() {
+    let input = random_input_file(0x0ff5e7, 20_000, 200).unwrap();
+    let mut contents = input.exact_slice().to_vec();
+    let newlines = contents
+      .iter()
+      .enumerate()
+      .filter_map(|(i, &b)| (b == b'\n').then_some(i))
+      .collect::<Vec<_>>();
+    // Corrupt the decimal point of a record two thirds of the way in.
+    let line = newlines.len() * 2 / 3;
+    let (start, end) = (newlines[line - 1] + 1, newlines[line]);
+    let record = &contents[start..end];
+    let semicolon = start + record.iter().position(|&b| b == b';').unwrap();
+    let decimal = start + record.iter().rposition(|&b| b == b'.').unwrap();
+    contents[decimal] = b'/';
+    let temperature = str::from_utf8(&contents[semicolon + 1..end])
+      .unwrap()
+      .to_owned();
+
+    let input_path =
+      std::env::temp_dir().join(format!("barse_{}_malformed_record.txt", std::process::id()));
+    fs::write(&input_path, &contents).unwrap();
+    let input_path = input_path.to_str().unwrap();
+
+    for single_threaded_threshold in [usize::MAX, 0] {
+      let result = build_temperature_reading_table_with_options(
+        input_path,
+        &Options {
+          single_threaded_threshold,
+          min_chunk_size: 1,
+          chunk_size: 4 * BUFFER_OVERLAP,
+          strict: true,
+          ..Options::default()
+        },
+      );
+      expect_eq!(
+        result.map(|_| ()).map_err(|err| err.to_string()),
+        Err(format!(
+          "error: {input_path} @ byte {start}, line {}: malformed temperature \"{temperature}\"",
+          line + 1
+        ))
+      );
+    }
+    fs::remove_file(input_path).unwrap();
+
+    let missing = build_temperature_reading_table("/nonexistent/measurements.txt");
+    match missing.map(|_| ()) {
+      Err(BarseError::InFile { path, error }) => {
+        expect_eq!(path, "/nonexistent/measurements.txt");
+        expect_true!(matches!(*error, BarseError::Io(_)));
+      }
+      result => fail!("expected an I/O error, got {result:?}").and_log_failure(),
+    }
+  }
+
+  #[cfg(feature = "multithreaded")]
+  #[gtest]
+  fn test_worker_panic_is_reported() {
+    let input_path =
+      std::env::temp_dir().join(format!("barse_{}_worker_panic.txt", std::process::id()));
+    fs::write(&input_path, "Berlin;1.0\nOslo;0.5\n").unwrap();
+    let input_path = input_path.to_str().unwrap();
+
+    let result = build_with_normalizer(input_path, |name, out| {
+      if name == "Oslo" {
+        panic!("cannot normalize {name}");
+      }
+      out.push_str(name);
+    });
+    fs::remove_file(input_path).unwrap();
+
+    match result.as_ref().map_err(BarseError::without_context) {
+      Err(BarseError::ThreadPanicked { message, .. }) => {
+        expect_eq!(message, "cannot normalize Oslo")
+      }
+      Err(err) => fail!("unexpected error {err}").and_log_failure(),
+      Ok(_) => fail!("expected the build to fail").and_log_failure(),
+    }
+  }
+
+  #[cfg(all(feature = "tracing", feature = "multithreaded"))]
+  #[gtest]
+  fn test_tracing_events() {
+    use crate::{build_report::Strategy, test_util::CapturingSubscriber};
+
+    let input = random_input_file(0x7ace5, 10_000, 100).unwrap();
+    let input_path = std::env::temp_dir().join(format!("barse_{}_tracing.txt", std::process::id()));
+    fs::write(&input_path, input.exact_slice()).unwrap();
+    let input_path = input_path.to_str().unwrap();
+
+    let subscriber = CapturingSubscriber::default();
+    tracing::subscriber::with_default(subscriber.clone(), || {
+      build_temperature_reading_table_with_options(
+        input_path,
+        &Options {
+          strategy: Some(Strategy::MultiThreaded),
+          threads: Some(4),
+          min_chunk_size: 1,
+          chunk_size: 4 * BUFFER_OVERLAP,
+          ..Options::default()
+        },
+      )
+      .unwrap();
+    });
+    fs::remove_file(input_path).unwrap();
+
+    let bytes = input.len().to_string();
+    #[cfg(feature = "mmap")]
+    const INPUT_EVENT: &str = "mapped input file";
+    #[cfg(not(feature = "mmap"))]
+    const INPUT_EVENT: &str = "read input file";
+    let mapped = subscriber.events(INPUT_EVENT);
+    assert_that!(mapped, len(eq(1)));
+    expect_eq!(mapped[0].fields["path"], input_path);
+    expect_eq!(mapped[0].fields["bytes"], bytes);
+
+    let build = subscriber.spans("build_table");
+    assert_that!(build, len(eq(1)));
+    expect_eq!(build[0].fields["bytes"], bytes);
+    let strategy = subscriber.events("chose strategy");
+    assert_that!(strategy, len(eq(1)));
+    expect_eq!(strategy[0].fields["strategy"], "multithreaded");
+    expect_eq!(strategy[0].fields["threads"], "4");
+
+    let scan = subscriber.spans("parallel_scan");
+    assert_that!(scan, len(eq(1)));
+    expect_eq!(scan[0].fields["threads"], "4");
+    expect_eq!(scan[0].fields["per_thread_tables"], "false");
+    let workers = subscriber.events("worker finished");
+    expect_that!(
+      workers
+        .iter()
+        .map(|event| event.fields["worker"].as_str())
+        .collect::<Vec<_>>(),
+      elements_are![eq(&"0"), eq(&"1"), eq(&"2"), eq(&"3")]
+    );
+    expect_eq!(
+      workers
+        .iter()
+        .map(|event| event.fields["records"].parse::<u64>().unwrap())
+        .sum::<u64>(),
+      10_000
+    );
+    expect_that!(subscriber.events("merged worker tables"), len(eq(1)));
+
+    let built = subscriber.events("built table");
+    assert_that!(built, len(eq(1)));
+    expect_eq!(built[0].fields["stations"], "100");
+    expect_eq!(built[0].fields["records"], "10000");
+  }
 }
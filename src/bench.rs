@@ -0,0 +1,321 @@
+use std::{
+  fmt::Display,
+  io::{self, Write},
+  str::FromStr,
+  time::{Duration, Instant},
+};
+
+use crate::{
+  barse::build_with_options_and_map_time,
+  build_report::{BuildReport, Strategy},
+  error::{BarseError, BarseResult},
+  options::Options,
+  print_summary::{write_table, SummaryOptions},
+};
+
+/// The format in which a `BenchReport` is written.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BenchFormat {
+  /// A table with one row per iteration, for reading in a terminal.
+  #[default]
+  Table,
+  /// A single JSON object, for collecting results in dashboards.
+  Json,
+}
+
+impl FromStr for BenchFormat {
+  type Err = BarseError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "table" => Ok(Self::Table),
+      "json" => Ok(Self::Json),
+      _ => Err(BarseError::new(format!(
+        "Unknown bench format \"{s}\", expected one of table, json"
+      ))),
+    }
+  }
+}
+
+/// Options controlling how a benchmark is run.
+#[derive(Clone, Debug)]
+pub struct BenchOptions {
+  /// The number of timed iterations, which must be at least 1.
+  pub iterations: usize,
+  /// The number of untimed iterations run first, e.g. to warm the page cache.
+  pub warmup: usize,
+  /// How the table of summaries is built in each iteration.
+  pub build: Options,
+}
+
+impl Default for BenchOptions {
+  fn default() -> Self {
+    Self {
+      iterations: 5,
+      warmup: 1,
+      build: Options::default(),
+    }
+  }
+}
+
+/// The timings of one iteration of a benchmark, broken down by phase.
+#[derive(Clone, Copy, Debug)]
+pub struct Iteration {
+  /// The time taken by the whole iteration.
+  pub wall_time: Duration,
+  /// The time taken to map the input file.
+  pub map_time: Duration,
+  /// The time taken to scan the input and aggregate its readings.
+  pub scan_time: Duration,
+  /// The time taken to merge the tables of the worker threads.
+  pub merge_time: Duration,
+  /// The time taken to sort and format the summary, which is discarded.
+  pub format_time: Duration,
+}
+
+/// The results of a benchmark over one input file.
+#[derive(Clone, Debug)]
+pub struct BenchReport {
+  pub strategy: Strategy,
+  pub threads: usize,
+  /// The size of the input in bytes.
+  pub bytes: u64,
+  /// The number of records in the input.
+  pub records: u64,
+  /// The number of distinct weather stations in the input.
+  pub unique_stations: usize,
+  /// The timed iterations, in the order they ran.
+  pub iterations: Vec<Iteration>,
+  /// The peak resident set size of the process in bytes, if known.
+  pub max_rss: Option<u64>,
+}
+
+impl BenchReport {
+  /// The number of records processed per second in `iteration`.
+  pub fn records_per_sec(&self, iteration: &Iteration) -> f64 {
+    self.records as f64 / iteration.wall_time.as_secs_f64()
+  }
+
+  /// The number of gigabytes (10^9 bytes) processed per second in
+  /// `iteration`.
+  pub fn gb_per_sec(&self, iteration: &Iteration) -> f64 {
+    self.bytes as f64 / 1e9 / iteration.wall_time.as_secs_f64()
+  }
+
+  /// The mean of the timings of every iteration.
+  pub fn mean(&self) -> Iteration {
+    let mean = |phase: fn(&Iteration) -> Duration| {
+      self.iterations.iter().map(phase).sum::<Duration>() / self.iterations.len().max(1) as u32
+    };
+    Iteration {
+      wall_time: mean(|iteration| iteration.wall_time),
+      map_time: mean(|iteration| iteration.map_time),
+      scan_time: mean(|iteration| iteration.scan_time),
+      merge_time: mean(|iteration| iteration.merge_time),
+      format_time: mean(|iteration| iteration.format_time),
+    }
+  }
+
+  pub fn write<W: Write>(&self, format: BenchFormat, mut out: W) -> io::Result<()> {
+    match format {
+      BenchFormat::Table => writeln!(out, "{self}"),
+      BenchFormat::Json => self.write_json(out),
+    }
+  }
+
+  fn write_json<W: Write>(&self, mut out: W) -> io::Result<()> {
+    let ms = |duration: Duration| duration.as_secs_f64() * 1e3;
+    write!(
+      out,
+      "{{\"strategy\":\"{}\",\"threads\":{},\"bytes\":{},\"records\":{},\"unique_stations\":{},",
+      self.strategy, self.threads, self.bytes, self.records, self.unique_stations
+    )?;
+    match self.max_rss {
+      Some(max_rss) => write!(out, "\"max_rss_bytes\":{max_rss},")?,
+      None => write!(out, "\"max_rss_bytes\":null,")?,
+    }
+    write!(out, "\"iterations\":[")?;
+    for (i, iteration) in self.iterations.iter().enumerate() {
+      if i != 0 {
+        write!(out, ",")?;
+      }
+      write!(
+        out,
+        "{{\"wall_ms\":{:.3},\"map_ms\":{:.3},\"scan_ms\":{:.3},\"merge_ms\":{:.3},\
+         \"format_ms\":{:.3},\"records_per_sec\":{:.0},\"gb_per_sec\":{:.3}}}",
+        ms(iteration.wall_time),
+        ms(iteration.map_time),
+        ms(iteration.scan_time),
+        ms(iteration.merge_time),
+        ms(iteration.format_time),
+        self.records_per_sec(iteration),
+        self.gb_per_sec(iteration)
+      )?;
+    }
+    writeln!(out, "]}}")
+  }
+}
+
+impl Display for BenchReport {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let ms = |duration: Duration| duration.as_secs_f64() * 1e3;
+    let write_row = |f: &mut std::fmt::Formatter<'_>, label: &str, iteration: &Iteration| {
+      writeln!(
+        f,
+        "{label:>9} {:>10.3} {:>10.3} {:>10.3} {:>10.3} {:>10.3} {:>12.0} {:>8.3}",
+        ms(iteration.wall_time),
+        ms(iteration.map_time),
+        ms(iteration.scan_time),
+        ms(iteration.merge_time),
+        ms(iteration.format_time),
+        self.records_per_sec(iteration),
+        self.gb_per_sec(iteration)
+      )
+    };
+
+    writeln!(
+      f,
+      "{} bytes, {} records, {} stations, {} with {} threads",
+      self.bytes, self.records, self.unique_stations, self.strategy, self.threads
+    )?;
+    writeln!(
+      f,
+      "{:>9} {:>10} {:>10} {:>10} {:>10} {:>10} {:>12} {:>8}",
+      "iteration", "wall ms", "map ms", "scan ms", "merge ms", "format ms", "records/s", "GB/s"
+    )?;
+    for (i, iteration) in self.iterations.iter().enumerate() {
+      write_row(f, &(i + 1).to_string(), iteration)?;
+    }
+    write_row(f, "mean", &self.mean())?;
+    match self.max_rss {
+      Some(max_rss) => write!(f, "max RSS: {} KiB", max_rss / 1024),
+      None => write!(f, "max RSS: unknown"),
+    }
+  }
+}
+
+/// Returns the peak resident set size of the process in bytes.
+#[cfg(target_os = "linux")]
+fn max_rss() -> Option<u64> {
+  let mut usage = unsafe { std::mem::zeroed::<libc::rusage>() };
+  if unsafe { libc::getrusage(libc::RUSAGE_SELF, &raw mut usage) } != 0 {
+    return None;
+  }
+  // Linux reports the maximum resident set size in KiB.
+  u64::try_from(usage.ru_maxrss).ok().map(|kib| kib * 1024)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn max_rss() -> Option<u64> {
+  None
+}
+
+/// Runs one iteration, building the table for the file at `input_path` and
+/// formatting its summary into a sink.
+fn run_iteration(
+  input_path: &str,
+  options: &BenchOptions,
+) -> BarseResult<(Iteration, BuildReport)> {
+  let start = Instant::now();
+  let (table, report) = build_with_options_and_map_time(input_path, &options.build)?;
+  let format_start = Instant::now();
+  write_table(&table, &SummaryOptions::default(), io::sink())?;
+  let format_time = format_start.elapsed();
+
+  let iteration = Iteration {
+    wall_time: start.elapsed(),
+    map_time: report.map_time,
+    scan_time: report.scan_time(),
+    merge_time: report.merge_time,
+    format_time,
+  };
+  Ok((iteration, report))
+}
+
+/// Summarizes the file at `input_path` `options.warmup + options.iterations`
+/// times, discarding the summaries, and reports the timings of the last
+/// `options.iterations` runs.
+pub fn run_bench(input_path: &str, options: &BenchOptions) -> BarseResult<BenchReport> {
+  if options.iterations == 0 {
+    return Err(BarseError::new(
+      "Benchmarks must run at least one iteration".to_owned(),
+    ));
+  }
+  for _ in 0..options.warmup {
+    run_iteration(input_path, options)?;
+  }
+
+  let (iteration, report) = run_iteration(input_path, options)?;
+  let mut iterations = vec![iteration];
+  for _ in 1..options.iterations {
+    iterations.push(run_iteration(input_path, options)?.0);
+  }
+  Ok(BenchReport {
+    strategy: report.strategy,
+    threads: report.threads,
+    bytes: report.bytes,
+    records: report.records,
+    unique_stations: report.unique_stations,
+    iterations,
+    max_rss: max_rss(),
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{fs, path::PathBuf};
+
+  use googletest::prelude::*;
+
+  use crate::bench::{run_bench, BenchFormat, BenchOptions};
+
+  const INPUT: &str = "Hamburg;12.0\nBulawayo;8.9\nPalembang;38.8\nHamburg;34.2\n";
+
+  fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("barse_{}_{name}", std::process::id()))
+  }
+
+  #[gtest]
+  fn test_bench_report() {
+    let input_path = temp_path("bench.txt");
+    fs::write(&input_path, INPUT).unwrap();
+    let options = BenchOptions {
+      iterations: 1,
+      warmup: 0,
+      ..BenchOptions::default()
+    };
+    let report = run_bench(input_path.to_str().unwrap(), &options).unwrap();
+    fs::remove_file(&input_path).unwrap();
+
+    expect_eq!(report.bytes, INPUT.len() as u64);
+    expect_eq!(report.records, 4);
+    expect_eq!(report.unique_stations, 3);
+    expect_eq!(report.iterations.len(), 1);
+    let iteration = &report.iterations[0];
+    expect_that!(
+      iteration.map_time + iteration.scan_time + iteration.merge_time + iteration.format_time,
+      le(iteration.wall_time)
+    );
+    expect_gt!(report.records_per_sec(iteration), 0.);
+    if cfg!(target_os = "linux") {
+      expect_that!(report.max_rss, some(gt(0)));
+    }
+
+    let mut json = Vec::new();
+    report.write(BenchFormat::Json, &mut json).unwrap();
+    let json = String::from_utf8(json).unwrap();
+    expect_that!(json, starts_with("{\"strategy\":\"single-threaded\""));
+    expect_that!(json, contains_substring("\"records\":4,"));
+    expect_that!(json, contains_substring("\"iterations\":[{\"wall_ms\":"));
+    expect_that!(json, ends_with("}]}\n"));
+  }
+
+  #[gtest]
+  fn test_zero_iterations_is_an_error() {
+    let options = BenchOptions {
+      iterations: 0,
+      ..BenchOptions::default()
+    };
+    expect_true!(run_bench("measurements.txt", &options).is_err());
+  }
+}
@@ -0,0 +1,97 @@
+use std::{fmt::Display, str::FromStr, time::Duration};
+
+use crate::error::BarseError;
+
+/// The strategy chosen for building the weather station table.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Strategy {
+  /// The whole input was folded into a single table on the calling thread.
+  SingleThreaded,
+  /// The input was split into chunks processed by a pool of worker threads,
+  /// which share one table of station names.
+  MultiThreaded,
+  /// The input was split into chunks processed by a pool of worker threads,
+  /// each with its own table, which were merged once every thread finished.
+  PerThreadTables,
+}
+
+impl Display for Strategy {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Self::SingleThreaded => write!(f, "single-threaded"),
+      Self::MultiThreaded => write!(f, "multithreaded"),
+      Self::PerThreadTables => write!(f, "multithreaded, per-thread tables"),
+    }
+  }
+}
+
+impl FromStr for Strategy {
+  type Err = BarseError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "single" => Ok(Self::SingleThreaded),
+      "shared-table" => Ok(Self::MultiThreaded),
+      "per-thread-table" => Ok(Self::PerThreadTables),
+      _ => Err(BarseError::new(format!(
+        "Unknown strategy \"{s}\", expected one of single, per-thread-table, shared-table"
+      ))),
+    }
+  }
+}
+
+/// Describes what a table build actually did.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct BuildReport {
+  pub strategy: Strategy,
+  pub threads: usize,
+  /// The number of input bytes processed, excluding padding.
+  pub bytes: u64,
+  /// The number of records parsed from the input and aggregated into the
+  /// table.
+  pub records: u64,
+  /// The number of records left out of the table for having
+  /// `Options::missing_reading` as their reading.
+  pub missing_records: u64,
+  /// The number of distinct weather stations in the table.
+  pub unique_stations: usize,
+  /// The number of chunks the input was split into. This is 1 for
+  /// single-threaded builds.
+  pub chunks: usize,
+  /// True if the kernel accepted the hugepage advice for every table, which
+  /// doesn't guarantee that they are backed by hugepages.
+  pub hugepages: bool,
+  /// The time taken to map the input file, which is zero for builds from an
+  /// in-memory buffer.
+  pub map_time: Duration,
+  /// The time taken to build the table, excluding mapping the input.
+  pub wall_time: Duration,
+  /// The part of `wall_time` spent merging the tables of the worker threads
+  /// once they finished.
+  pub merge_time: Duration,
+}
+
+impl BuildReport {
+  /// The part of `wall_time` spent scanning the input and aggregating its
+  /// readings.
+  pub fn scan_time(&self) -> Duration {
+    self.wall_time.saturating_sub(self.merge_time)
+  }
+}
+
+impl Display for BuildReport {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    writeln!(f, "strategy: {}", self.strategy)?;
+    writeln!(f, "threads: {}", self.threads)?;
+    writeln!(f, "bytes: {}", self.bytes)?;
+    writeln!(f, "records: {}", self.records)?;
+    writeln!(f, "missing records: {}", self.missing_records)?;
+    writeln!(f, "unique stations: {}", self.unique_stations)?;
+    writeln!(f, "chunks: {}", self.chunks)?;
+    writeln!(f, "hugepages: {}", self.hugepages)?;
+    writeln!(f, "map time: {:.3?}", self.map_time)?;
+    writeln!(f, "wall time: {:.3?}", self.wall_time)?;
+    write!(f, "merge time: {:.3?}", self.merge_time)
+  }
+}
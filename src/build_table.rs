@@ -1,14 +1,126 @@
+use std::time::{Duration, Instant};
+
 use crate::{
-  error::BarseResult, scanner::Scanner, str_hash::TABLE_SIZE, table::WeatherStationTable,
+  build_report::{BuildReport, Strategy},
+  error::BarseResult,
+  normalize::{add_checked_records, group_key, NameNormalizer, RecordChecks},
+  options::Options,
+  scanner::Scanner,
+  table::WeatherStationTable,
+  util::HasIter,
+  validating_scanner::ValidatingScanner,
 };
 
+/// Folds the input into a table, returning the table, the number of records
+/// aggregated into it and the number of records with missing readings.
+fn build_table(
+  input: &[u8],
+  len: usize,
+  options: &Options,
+) -> BarseResult<(WeatherStationTable, u64, u64)> {
+  let checks = RecordChecks::new(options);
+  let mut table = WeatherStationTable::with_capacity(options.table_capacity)?;
+  let mut records = 0;
+  let add_reading = |station: &str, temp| {
+    table.add_reading(station, temp);
+    records += 1;
+  };
+  if options.strict {
+    let mut scanner = ValidatingScanner::from_start(&input[..len])
+      .with_decimal_separator(options.decimal_separator)
+      .with_extra_fields(options.extra_fields);
+    if options.skip_header {
+      scanner = scanner.skip_header();
+    }
+    let missing = add_checked_records(scanner, &checks, add_reading)?;
+    return Ok((table, records, missing));
+  }
+
+  let mut scanner = Scanner::from_start_with_len(input, len)
+    .with_decimal_separator(options.decimal_separator)
+    .with_extra_fields(options.extra_fields);
+  if options.skip_header {
+    scanner = scanner.skip_header();
+  }
+  if !checks.is_empty() {
+    let missing = add_checked_records(scanner.map(Ok), &checks, add_reading)?;
+    return Ok((table, records, missing));
+  }
+  // The table is borrowed rather than threaded through the fold, so it isn't
+  // moved on every record.
+  let records = scanner.fold(0, |records, (station, temp)| {
+    table.add_reading(station, temp);
+    records + 1
+  });
+  Ok((table, records, 0))
+}
+
+/// Builds the table from the first `len` bytes of `input`, which is padded to
+/// a multiple of `SCANNER_CACHE_SIZE`.
 pub fn build_temperature_reading_table_from_bytes(
   input: &[u8],
-) -> BarseResult<WeatherStationTable<TABLE_SIZE>> {
-  Ok(
-    Scanner::from_start(input).fold(WeatherStationTable::new()?, |mut map, (station, temp)| {
-      map.add_reading(station, temp);
-      map
-    }),
-  )
+  len: usize,
+) -> BarseResult<WeatherStationTable> {
+  build_table(input, len, &Options::default()).map(|(table, _, _)| table)
+}
+
+/// Without the `multithreaded` feature, there is only one strategy to choose
+/// from, so only `options.table_capacity` has an effect on the build.
+pub fn build_temperature_reading_table_from_bytes_with_options(
+  input: &[u8],
+  len: usize,
+  options: &Options,
+) -> BarseResult<(WeatherStationTable, BuildReport)> {
+  options.validate()?;
+  let start = Instant::now();
+  let (table, records, missing_records) = build_table(input, len, options)?;
+  let report = BuildReport {
+    strategy: Strategy::SingleThreaded,
+    threads: 1,
+    bytes: len as u64,
+    records,
+    missing_records,
+    unique_stations: table.iter().count(),
+    chunks: 1,
+    hugepages: table.hugepages(),
+    map_time: Duration::ZERO,
+    wall_time: start.elapsed(),
+    merge_time: Duration::ZERO,
+  };
+  Ok((table, report))
+}
+
+/// Builds the table like `build_temperature_reading_table_from_bytes`, but
+/// aggregates readings under the names produced by `normalizer`.
+pub fn build_with_normalizer_from_bytes<F>(
+  input: &[u8],
+  len: usize,
+  normalizer: &F,
+) -> BarseResult<WeatherStationTable>
+where
+  F: Fn(&str, &mut String) + Sync,
+{
+  let mut table = WeatherStationTable::new()?;
+  let mut names = NameNormalizer::new(normalizer);
+  for (station, temp) in Scanner::from_start_with_len(input, len) {
+    table.add_reading(names.normalize(station)?, temp);
+  }
+  Ok(table)
+}
+
+/// Builds the table like `build_temperature_reading_table_from_bytes`, but
+/// aggregates readings under the keys `key_fn` slices from station names.
+pub fn build_grouped_by_from_bytes<K>(
+  input: &[u8],
+  len: usize,
+  key_fn: &K,
+) -> BarseResult<WeatherStationTable>
+where
+  K: Fn(&str) -> &str + Sync,
+{
+  let mut table = WeatherStationTable::new()?;
+  for (station, temp) in Scanner::from_start_with_len(input, len) {
+    table.add_reading(group_key(key_fn, station)?, temp);
+  }
+  Ok(table)
 }
@@ -1,73 +1,1012 @@
 use crate::{
+  aggregator::{Aggregator, InPlaceInitializable, Merge, TemperatureAggregator},
+  barse::{fingerprint, select_by_avg},
+  build_report::{BuildReport, Strategy},
   error::{BarseError, BarseResult},
-  str_hash::TABLE_SIZE,
+  inline_string_mt::InlineString,
+  normalize::{add_checked_records, group_key, NameNormalizer, RecordChecks},
+  options::Options,
+  scanner::Scanner,
+  sink::Sink,
+  slicer::Slicer,
   string_table::StringTable,
+  table_stats::{MemoryFootprint, TableStats},
+  temperature_reading::TemperatureReading,
   temperature_summary::TemperatureSummary,
   temperature_summary_table::TemperatureSummaryTable,
   util::HasIter,
+  validating_scanner::ValidatingScanner,
+};
+use itertools::Itertools;
+use std::{
+  collections::HashMap,
+  iter,
+  sync::Arc,
+  time::{Duration, Instant},
 };
-use std::sync::Arc;
 
-pub struct SummaryTable<const SIZE: usize> {
-  string_table: Arc<StringTable<SIZE>>,
-  temp_table: TemperatureSummaryTable<SIZE>,
+/// A table of the summary `S` of each station, which is the
+/// `TemperatureSummary` computed by the default builders, or the `Summary` of
+/// an `Aggregator`.
+pub struct SummaryTable<S = TemperatureSummary> {
+  string_table: Arc<StringTable>,
+  temp_table: TemperatureSummaryTable<S>,
 }
 
-impl<'a, const SIZE: usize> HasIter<'a> for SummaryTable<SIZE> {
-  type Item = (&'a str, &'a TemperatureSummary);
+impl<S: Merge + InPlaceInitializable> SummaryTable<S> {
+  /// Constructs an empty table with `capacity` buckets, which must be a power
+  /// of two.
+  pub fn with_capacity(capacity: usize) -> BarseResult<Self> {
+    Ok(Self {
+      string_table: Arc::new(StringTable::new(capacity)?),
+      temp_table: TemperatureSummaryTable::new(capacity)?,
+    })
+  }
+
+  pub fn capacity(&self) -> usize {
+    self.string_table.capacity()
+  }
+
+  /// Returns the name and summary of the station in bucket `index`, or
+  /// `None` if the bucket is empty.
+  pub fn station_at(&self, index: usize) -> Option<(&str, &S)> {
+    let station = self.string_table.entry_at(index);
+    station
+      .initialized()
+      .then(|| (station.value_str(), self.temp_table.entry_at(index)))
+  }
+
+  /// Faults in every page of the table, so that a build timed afterwards
+  /// doesn't pay for the page faults of first touching the table.
+  pub fn prefault(&self) {
+    self.string_table.prefault();
+    self.temp_table.prefault();
+  }
+
+  /// Removes every station from the table, keeping its allocations, so that
+  /// it can be reused without mapping and initializing a new table. Only the
+  /// buckets of stations in the table are written.
+  pub fn clear(&mut self) {
+    let string_table = Arc::get_mut(&mut self.string_table)
+      .expect("String tables are only shared with workers while they build the table");
+    for idx in 0..string_table.capacity() {
+      if string_table.entry_at(idx).initialized() {
+        self.temp_table.reset_at(idx);
+      }
+    }
+    string_table.clear();
+  }
+
+  /// Merges `tables`, which were filled with the indices of this table's
+  /// string table, into this table, leaving them empty.
+  pub(crate) fn drain_from<'a>(
+    &mut self,
+    tables: impl IntoIterator<Item = &'a mut TemperatureSummaryTable<S>>,
+  ) where
+    S: 'a,
+  {
+    let mut tables = tables.into_iter().collect::<Vec<_>>();
+    if tables.is_empty() {
+      return;
+    }
+    for idx in 0..self.capacity() {
+      if !self.string_table.entry_at(idx).initialized() {
+        continue;
+      }
+      for table in &mut tables {
+        self.temp_table.merge_at_index(table.entry_at(idx), idx);
+        table.reset_at(idx);
+      }
+    }
+  }
+
+  /// Folds the records of every chunk `slicer` hands out into the table, on
+  /// the calling thread. Returns the number of records aggregated and the
+  /// number with missing readings.
+  pub(crate) fn add_sliced_records<A>(
+    &mut self,
+    slicer: &Slicer,
+    aggregator: &A,
+    strict: bool,
+    checks: &RecordChecks,
+  ) -> BarseResult<(u64, u64)>
+  where
+    A: Aggregator<Summary = S>,
+  {
+    add_sliced_records(
+      slicer,
+      &self.string_table,
+      &mut self.temp_table,
+      aggregator,
+      strict,
+      checks,
+    )
+  }
+
+  /// True if the kernel accepted the hugepage advice for both tables.
+  pub(crate) fn hugepages(&self) -> bool {
+    self.string_table.hugepages() && self.temp_table.hugepages()
+  }
+
+  /// The table of station names, shared with the workers building the table.
+  pub(crate) fn string_table(&self) -> &Arc<StringTable> {
+    &self.string_table
+  }
+}
+
+impl<S: Merge + InPlaceInitializable + Clone> SummaryTable<S> {
+  /// Copies the name and summary of every station in the table, in table
+  /// order, so that the results outlive both the table and the input it was
+  /// built from.
+  pub fn to_owned_summary(&self) -> Vec<(String, S)> {
+    self
+      .iter()
+      .map(|(station, summary)| (station.to_owned(), summary.clone()))
+      .collect()
+  }
+}
+
+impl SummaryTable {
+  /// Returns the summary of every reading in the table, across all stations.
+  pub fn global_summary(&self) -> TemperatureSummary {
+    TemperatureSummary::merge_all(self.iter().map(|(_, summary)| summary))
+  }
+
+  /// Returns the memory `with_capacity(capacity)` reserves, without
+  /// allocating a table. Station names and summaries are stored in separate
+  /// tables, so there is no padding between them.
+  pub const fn memory_footprint_for_capacity(capacity: usize) -> MemoryFootprint {
+    let key_bytes = std::mem::size_of::<InlineString>();
+    let summary_bytes = std::mem::size_of::<TemperatureSummary>();
+    MemoryFootprint {
+      capacity,
+      entry_bytes: key_bytes + summary_bytes,
+      key_bytes,
+      summary_bytes,
+      mapped_bytes: StringTable::mapped_len_for_capacity(capacity)
+        + <TemperatureSummaryTable>::mapped_len_for_capacity(capacity),
+    }
+  }
+
+  /// Returns the memory reserved by this table.
+  pub fn memory_footprint(&self) -> MemoryFootprint {
+    MemoryFootprint {
+      mapped_bytes: self.string_table.mapped_len() + self.temp_table.mapped_len(),
+      ..Self::memory_footprint_for_capacity(self.capacity())
+    }
+  }
+
+  pub fn stats(&self) -> TableStats {
+    #[cfg(feature = "diagnostics")]
+    let lookups = self.iter().map(|(_, summary)| summary.count as u64).sum();
+    TableStats {
+      capacity: self.capacity(),
+      stations: self.iter().count(),
+      #[cfg(feature = "diagnostics")]
+      lookups,
+      // Every lookup examines its home bucket before scanning.
+      #[cfg(feature = "diagnostics")]
+      probes: lookups + self.string_table.scan_probes(),
+    }
+  }
+
+  /// Adds `reading` to the summary of `station`, which must be in a padded
+  /// buffer. Panics if `station` is longer than 50 bytes, or if the table is
+  /// full.
+  pub fn add_reading(&mut self, station: &str, reading: TemperatureReading) {
+    let idx = self.string_table.find_entry_index(station);
+    self.temp_table.add_reading_at_index(reading, idx);
+  }
+
+  /// Merges the summaries of every station in `other`, which must have been
+  /// built separately from this table, into this table.
+  pub fn merge(&mut self, other: &Self) -> BarseResult {
+    debug_assert!(!Arc::ptr_eq(&self.string_table, &other.string_table));
+    self.merge_summaries(other.iter())
+  }
+
+  /// Merges each summary into the summary of its station in this table.
+  pub fn merge_summaries<'a>(
+    &mut self,
+    summaries: impl IntoIterator<Item = (&'a str, &'a TemperatureSummary)>,
+  ) -> BarseResult {
+    // Names are copied into a padded buffer before being looked up in this
+    // table.
+    let mut names = NameNormalizer::new(&|name: &str, out: &mut String| out.push_str(name));
+    for (station, summary) in summaries {
+      let idx = self
+        .string_table
+        .find_entry_index(names.normalize(station)?);
+      self.temp_table.merge_at_index(summary, idx);
+    }
+    Ok(())
+  }
+
+  /// Returns a 64-bit fingerprint of the stations and summaries in the
+  /// table, which is equal for logically equal tables, regardless of their
+  /// capacities or where each station landed in the table, and almost surely
+  /// differs otherwise.
+  pub fn fingerprint(&self) -> u64 {
+    fingerprint(self.iter())
+  }
+
+  /// Hands the name and summary of every station in the table to `sink`, in
+  /// table order, stopping at the first error. Workers claim stations
+  /// concurrently, so unlike the single-threaded table, the order in which
+  /// stations first appeared in the input isn't kept. The table is left as it
+  /// is.
+  pub fn drain_into<K: Sink + ?Sized>(&self, sink: &mut K) -> BarseResult {
+    self
+      .iter()
+      .try_for_each(|(station, summary)| sink.on_station(station, summary))
+  }
+
+  /// Returns the names and averages of the `n` stations with the highest
+  /// average readings, hottest first, with ties broken by name.
+  pub fn top_by_avg(&self, n: usize) -> Vec<(String, TemperatureReading)> {
+    select_by_avg(self.iter(), n, true)
+  }
+
+  /// Returns the names and averages of the `n` stations with the lowest
+  /// average readings, coldest first, with ties broken by name.
+  pub fn bottom_by_avg(&self, n: usize) -> Vec<(String, TemperatureReading)> {
+    select_by_avg(self.iter(), n, false)
+  }
+}
+
+impl<'a, S: Merge + InPlaceInitializable + 'a> HasIter<'a> for SummaryTable<S> {
+  type Item = (&'a str, &'a S);
 
   fn iter(&'a self) -> impl Iterator<Item = Self::Item> {
-    (0..SIZE).filter_map(|i| {
-      let station = self.string_table.entry_at(i);
-      station
-        .initialized()
-        .then(|| (station.value_str(), self.temp_table.entry_at(i)))
-    })
+    (0..self.capacity()).filter_map(|i| self.station_at(i))
   }
 }
 
-pub fn build_temperature_reading_table_from_bytes(
+/// Returns the number of worker threads to use for an input of `input_len`
+/// bytes, which is capped so that each thread gets at least
+/// `options.min_chunk_size` bytes to process.
+pub(crate) fn thread_count(input_len: usize, options: &Options) -> usize {
+  let available = options.threads.unwrap_or_else(|| {
+    std::thread::available_parallelism()
+      .map(|nonzero| nonzero.get())
+      .unwrap_or(1)
+  });
+  available
+    .min(input_len / options.min_chunk_size.max(1))
+    .max(1)
+}
+
+/// Counters gathered while building a table, used to fill in a
+/// `BuildReport`.
+struct BuildCounters {
+  records: u64,
+  missing_records: u64,
+  chunks: usize,
+  hugepages: bool,
+  merge_time: Duration,
+}
+
+/// Folds the whole input into one table on the calling thread.
+fn build_single_threaded<A: Aggregator>(
   input: &[u8],
-) -> BarseResult<SummaryTable<TABLE_SIZE>> {
-  let thread_count = std::thread::available_parallelism()
-    .map(|nonzero| nonzero.get())
-    .unwrap_or(1);
+  len: usize,
+  aggregator: &A,
+  options: &Options,
+) -> BarseResult<(SummaryTable<A::Summary>, BuildCounters)> {
+  let string_table = StringTable::new(options.table_capacity)?;
+  let mut temp_table = TemperatureSummaryTable::<A::Summary>::new(options.table_capacity)?;
+  let checks = RecordChecks::new(options);
+  let mut records = 0;
+  let mut missing_records = 0;
+  let mut add_reading = |station: &str, temp| {
+    let idx = string_table.find_entry_index(station);
+    aggregator.add(temp_table.entry_at_mut(idx), temp);
+    records += 1;
+  };
+  if options.strict {
+    let mut scanner = ValidatingScanner::from_start(&input[..len])
+      .with_decimal_separator(options.decimal_separator)
+      .with_extra_fields(options.extra_fields);
+    if options.skip_header {
+      scanner = scanner.skip_header();
+    }
+    missing_records = add_checked_records(scanner, &checks, &mut add_reading)?;
+  } else {
+    let mut scanner = Scanner::from_start_with_len(input, len)
+      .with_decimal_separator(options.decimal_separator)
+      .with_extra_fields(options.extra_fields);
+    if options.skip_header {
+      scanner = scanner.skip_header();
+    }
+    if !checks.is_empty() {
+      missing_records = add_checked_records(scanner.map(Ok), &checks, &mut add_reading)?;
+    } else {
+      for (station, temp) in scanner {
+        add_reading(station, temp);
+      }
+    }
+  }
+
+  let counters = BuildCounters {
+    records,
+    missing_records,
+    chunks: 1,
+    hugepages: string_table.hugepages() && temp_table.hugepages(),
+    merge_time: Duration::ZERO,
+  };
+  Ok((
+    SummaryTable {
+      string_table: Arc::new(string_table),
+      temp_table,
+    },
+    counters,
+  ))
+}
+
+/// Returns the results of every worker, or the error of the worker which
+/// failed earliest in the input. Workers stop at the first malformed record in
+/// their chunk, and chunks are handed out in order, so the malformed record
+/// with the lowest offset is the first in the input. Errors without an
+/// offset, like panics, take precedence.
+pub(crate) fn collect_worker_results<T>(
+  results: impl IntoIterator<Item = BarseResult<T>>,
+) -> BarseResult<Vec<T>> {
+  let mut values = Vec::new();
+  let mut first_error: Option<BarseError> = None;
+  for result in results {
+    match result {
+      Ok(value) => values.push(value),
+      Err(err) => {
+        if first_error
+          .as_ref()
+          .is_none_or(|first| err.offset() < first.offset())
+        {
+          first_error = Some(err);
+        }
+      }
+    }
+  }
+  first_error.map_or(Ok(values), Err)
+}
+
+/// Feeds every record `slicer` hands out through `add_checked_records`,
+/// returning the number of records with missing readings. Kept out of line,
+/// behind `dyn`, so the workers' unchecked loop stays as tight as it was
+/// before normalization existed.
+#[inline(never)]
+fn add_sliced_records_checked(
+  slicer: &Slicer,
+  strict: bool,
+  checks: &RecordChecks,
+  add_reading: &mut dyn FnMut(&str, TemperatureReading),
+) -> BarseResult<u64> {
+  if strict {
+    let records = iter::from_fn(|| slicer.next_validating_slice()).flatten();
+    add_checked_records(records, checks, add_reading)
+  } else {
+    let records = iter::from_fn(|| slicer.try_next_chunk())
+      .map_ok(|(_, scanner)| scanner)
+      .flatten_ok();
+    add_checked_records(records, checks, add_reading)
+  }
+}
+
+/// Folds the records of every chunk `slicer` hands out into `summary_table`,
+/// with station names interned in `string_table`. Returns the number of
+/// records aggregated and the number with missing readings.
+pub(crate) fn add_sliced_records<A: Aggregator>(
+  slicer: &Slicer,
+  string_table: &StringTable,
+  summary_table: &mut TemperatureSummaryTable<A::Summary>,
+  aggregator: &A,
+  strict: bool,
+  checks: &RecordChecks,
+) -> BarseResult<(u64, u64)> {
+  let mut records = 0u64;
+  let mut missing_records = 0;
+  let mut add_reading = |station: &str, temp| {
+    let idx = string_table.find_entry_index(station);
+    aggregator.add(summary_table.entry_at_mut(idx), temp);
+    records += 1;
+  };
+  if strict || !checks.is_empty() {
+    missing_records = add_sliced_records_checked(slicer, strict, checks, &mut add_reading)?;
+  } else {
+    while let Some(chunk) = slicer.try_next_chunk() {
+      let (_, slice) = chunk?;
+      for (station, temp) in slice {
+        add_reading(station, temp);
+      }
+    }
+  }
+  Ok((records, missing_records))
+}
 
-  let slicer = Arc::new(unsafe { crate::slicer::Slicer::new(input) });
-  let string_table = Arc::new(StringTable::new()?);
+/// Splits the input across `thread_count` worker threads. If
+/// `per_thread_tables` is set, each worker interns station names in its own
+/// string table, which avoids contention between workers at the cost of
+/// merging the tables by name at the end. Otherwise the workers share one
+/// string table.
+fn build_multithreaded<A>(
+  input: &[u8],
+  len: usize,
+  thread_count: usize,
+  per_thread_tables: bool,
+  aggregator: &A,
+  options: &Options,
+) -> BarseResult<(SummaryTable<A::Summary>, BuildCounters)>
+where
+  A: Aggregator + Clone + Send + 'static,
+  A::Summary: Send + 'static,
+{
+  let slicer = Arc::new(
+    unsafe { crate::slicer::Slicer::new(input, len, options.chunk_size) }
+      .with_decimal_separator(options.decimal_separator)
+      .with_skip_header(options.skip_header)
+      .with_extra_fields(options.extra_fields),
+  );
+  let shared_string_table = Arc::new(StringTable::new(options.table_capacity)?);
+  #[cfg(feature = "tracing")]
+  let _span =
+    tracing::info_span!("parallel_scan", threads = thread_count, per_thread_tables).entered();
 
-  let mut threads = (0..thread_count)
-    .map(|_| -> BarseResult<_> {
+  let threads = (0..thread_count)
+    .map(|worker| -> BarseResult<_> {
       let slicer = slicer.clone();
-      let string_table = string_table.clone();
-      let mut summary_table = TemperatureSummaryTable::new()?;
-      Ok(std::thread::spawn(move || {
-        while let Some(slice) = slicer.next_slice() {
-          for (station, temp) in slice {
-            let idx = string_table.find_entry_index(station);
-            summary_table.add_reading_at_index(temp, idx);
-          }
-        }
-        summary_table
+      let string_table = if per_thread_tables && worker != 0 {
+        Arc::new(StringTable::new(options.table_capacity)?)
+      } else {
+        shared_string_table.clone()
+      };
+      let mut summary_table = TemperatureSummaryTable::<A::Summary>::new(options.table_capacity)?;
+      let aggregator = aggregator.clone();
+      let strict = options.strict;
+      // Every worker applies the same checks to the records it reads.
+      let checks = RecordChecks::new(options);
+      Ok(std::thread::spawn(move || -> BarseResult<_> {
+        let (records, missing_records) = add_sliced_records(
+          &slicer,
+          &string_table,
+          &mut summary_table,
+          &aggregator,
+          strict,
+          &checks,
+        )?;
+        Ok((summary_table, string_table, records, missing_records))
       }))
     })
     .collect::<Result<Vec<_>, _>>()?;
 
-  let mut temp_table = threads
-    .pop()
-    .expect("Thread list will not be empty")
-    .join()
-    .map_err(|err| BarseError::new(format!("Failed to join thread: {err:?}")))?;
-
-  for thread in threads {
-    let thread_map = thread
+  // Workers' events are emitted here rather than from the workers, which
+  // don't inherit the calling thread's subscriber.
+  let thread_results = threads.into_iter().enumerate().map(|(worker, thread)| {
+    let result = thread
       .join()
-      .map_err(|err| BarseError::new(format!("Failed to join thread: {err:?}")))?;
-    temp_table.merge(thread_map);
+      .map_err(|payload| BarseError::thread_panicked(worker, payload))?;
+    #[cfg(feature = "tracing")]
+    if let Ok((_, _, records, _)) = &result {
+      tracing::info!(worker, records, "worker finished");
+    }
+    result
+  });
+  let mut thread_tables = collect_worker_results(thread_results)?.into_iter();
+  let merge_start = Instant::now();
+  let (mut temp_table, string_table, mut records, mut missing_records) =
+    thread_tables.next().expect("Thread list will not be empty");
+  let mut hugepages = string_table.hugepages() && temp_table.hugepages();
+
+  // Names from other string tables are copied into a padded buffer before
+  // being looked up in `string_table`.
+  let mut names = NameNormalizer::new(&|name: &str, out: &mut String| out.push_str(name));
+  for (thread_map, thread_string_table, thread_records, thread_missing_records) in thread_tables {
+    hugepages &= thread_map.hugepages();
+    records += thread_records;
+    missing_records += thread_missing_records;
+    if Arc::ptr_eq(&thread_string_table, &string_table) {
+      temp_table.merge(thread_map);
+      continue;
+    }
+
+    hugepages &= thread_string_table.hugepages();
+    for idx in 0..thread_string_table.capacity() {
+      let station = thread_string_table.entry_at(idx);
+      if station.initialized() {
+        let merged_idx = string_table.find_entry_index(names.normalize(station.value_str())?);
+        temp_table.merge_at_index(thread_map.entry_at(idx), merged_idx);
+      }
+    }
+  }
+
+  let counters = BuildCounters {
+    records,
+    missing_records,
+    chunks: slicer.chunks_taken(),
+    hugepages,
+    merge_time: merge_start.elapsed(),
+  };
+  #[cfg(feature = "tracing")]
+  tracing::info!(merge_time = ?counters.merge_time, "merged worker tables");
+  Ok((
+    SummaryTable {
+      string_table,
+      temp_table,
+    },
+    counters,
+  ))
+}
+
+/// The summary of a single chunk of the input, handed to the `on_chunk`
+/// callback of `build_streaming` as soon as the chunk has been processed.
+pub struct PartialSummary<'a> {
+  /// The offset of the chunk in the input.
+  pub offset: u64,
+  /// The number of records read from the chunk.
+  pub records: u64,
+  /// The summary of each station seen in the chunk.
+  pub stations: Vec<(&'a str, TemperatureSummary)>,
+}
+
+/// Builds the table like `build_temperature_reading_table_from_bytes`, calling
+/// `on_chunk` from the worker threads with the summary of each chunk as it
+/// completes. Merging every `PartialSummary` yields the returned table.
+pub fn build_streaming<F>(input: &[u8], len: usize, on_chunk: F) -> BarseResult<SummaryTable>
+where
+  F: Fn(PartialSummary<'_>) + Sync,
+{
+  let options = Options::default();
+  let slicer = unsafe { crate::slicer::Slicer::new(input, len, options.chunk_size) };
+  let string_table = StringTable::new(options.table_capacity)?;
+  let thread_count = thread_count(len, &options);
+  let thread_tables = (0..thread_count)
+    .map(|_| TemperatureSummaryTable::new(options.table_capacity))
+    .collect::<Result<Vec<_>, _>>()?;
+
+  let thread_tables = std::thread::scope(|scope| {
+    let threads = thread_tables
+      .into_iter()
+      .map(|mut summary_table| {
+        let (slicer, string_table, on_chunk) = (&slicer, &string_table, &on_chunk);
+        scope.spawn(move || -> BarseResult<_> {
+          let mut chunk_summaries = HashMap::new();
+          while let Some(chunk) = slicer.try_next_chunk() {
+            let (offset, slice) = chunk?;
+            let mut records = 0;
+            for (station, temp) in slice {
+              let idx = string_table.find_entry_index(station);
+              chunk_summaries
+                .entry(idx)
+                .or_insert_with(TemperatureSummary::default)
+                .add_reading(temp);
+              records += 1;
+            }
+
+            let stations = chunk_summaries
+              .drain()
+              .map(|(idx, summary)| {
+                summary_table.merge_at_index(&summary, idx);
+                (string_table.entry_at(idx).value_str(), summary)
+              })
+              .collect();
+            on_chunk(PartialSummary {
+              offset,
+              records,
+              stations,
+            });
+          }
+          Ok(summary_table)
+        })
+      })
+      .collect::<Vec<_>>();
+
+    threads
+      .into_iter()
+      .enumerate()
+      .map(|(worker, thread)| {
+        thread
+          .join()
+          .map_err(|payload| BarseError::thread_panicked(worker, payload))?
+      })
+      .collect::<Result<Vec<_>, _>>()
+  })?;
+
+  let mut thread_tables = thread_tables.into_iter();
+  let mut temp_table = thread_tables.next().expect("Thread list will not be empty");
+  for thread_table in thread_tables {
+    temp_table.merge(thread_table);
+  }
+
+  Ok(SummaryTable {
+    string_table: Arc::new(string_table),
+    temp_table,
+  })
+}
+
+/// Builds the table like `build_temperature_reading_table_from_bytes`, but
+/// aggregates readings under the names produced by `normalizer`, which is
+/// called from the worker threads.
+pub fn build_with_normalizer_from_bytes<F>(
+  input: &[u8],
+  len: usize,
+  normalizer: &F,
+) -> BarseResult<SummaryTable>
+where
+  F: Fn(&str, &mut String) + Sync,
+{
+  let options = Options::default();
+  let slicer = unsafe { crate::slicer::Slicer::new(input, len, options.chunk_size) };
+  let string_table = StringTable::new(options.table_capacity)?;
+  let thread_count = thread_count(len, &options);
+  let thread_tables = (0..thread_count)
+    .map(|_| TemperatureSummaryTable::new(options.table_capacity))
+    .collect::<Result<Vec<_>, _>>()?;
+
+  let thread_tables = std::thread::scope(|scope| {
+    let threads = thread_tables
+      .into_iter()
+      .map(|mut summary_table| {
+        let (slicer, string_table) = (&slicer, &string_table);
+        scope.spawn(move || -> BarseResult<_> {
+          let mut names = NameNormalizer::new(normalizer);
+          while let Some(chunk) = slicer.try_next_chunk() {
+            let (_, slice) = chunk?;
+            for (station, temp) in slice {
+              let idx = string_table.find_entry_index(names.normalize(station)?);
+              summary_table.add_reading_at_index(temp, idx);
+            }
+          }
+          Ok(summary_table)
+        })
+      })
+      .collect::<Vec<_>>();
+
+    threads
+      .into_iter()
+      .enumerate()
+      .map(|(worker, thread)| {
+        thread
+          .join()
+          .map_err(|payload| BarseError::thread_panicked(worker, payload))?
+      })
+      .collect::<Result<Vec<_>, _>>()
+  })?;
+
+  let mut thread_tables = thread_tables.into_iter();
+  let mut temp_table = thread_tables.next().expect("Thread list will not be empty");
+  for thread_table in thread_tables {
+    temp_table.merge(thread_table);
+  }
+
+  Ok(SummaryTable {
+    string_table: Arc::new(string_table),
+    temp_table,
+  })
+}
+
+/// Builds the table like `build_temperature_reading_table_from_bytes`, but
+/// aggregates readings under the keys `key_fn` slices from station names,
+/// which is called from the worker threads.
+pub fn build_grouped_by_from_bytes<K>(
+  input: &[u8],
+  len: usize,
+  key_fn: &K,
+) -> BarseResult<SummaryTable>
+where
+  K: Fn(&str) -> &str + Sync,
+{
+  let options = Options::default();
+  let slicer = unsafe { crate::slicer::Slicer::new(input, len, options.chunk_size) };
+  let string_table = StringTable::new(options.table_capacity)?;
+  let thread_count = thread_count(len, &options);
+  let thread_tables = (0..thread_count)
+    .map(|_| TemperatureSummaryTable::new(options.table_capacity))
+    .collect::<Result<Vec<_>, _>>()?;
+
+  let thread_tables = std::thread::scope(|scope| {
+    let threads = thread_tables
+      .into_iter()
+      .map(|mut summary_table| {
+        let (slicer, string_table) = (&slicer, &string_table);
+        scope.spawn(move || -> BarseResult<_> {
+          while let Some(chunk) = slicer.try_next_chunk() {
+            let (_, slice) = chunk?;
+            for (station, temp) in slice {
+              let idx = string_table.find_entry_index(group_key(key_fn, station)?);
+              summary_table.add_reading_at_index(temp, idx);
+            }
+          }
+          Ok(summary_table)
+        })
+      })
+      .collect::<Vec<_>>();
+
+    threads
+      .into_iter()
+      .enumerate()
+      .map(|(worker, thread)| {
+        thread
+          .join()
+          .map_err(|payload| BarseError::thread_panicked(worker, payload))?
+      })
+      .collect::<Result<Vec<_>, _>>()
+  })?;
+
+  let mut thread_tables = thread_tables.into_iter();
+  let mut temp_table = thread_tables.next().expect("Thread list will not be empty");
+  for thread_table in thread_tables {
+    temp_table.merge(thread_table);
   }
 
   Ok(SummaryTable {
-    string_table,
+    string_table: Arc::new(string_table),
     temp_table,
   })
 }
+
+/// Builds the table from the first `len` bytes of `input`, which is padded to
+/// a multiple of `SCANNER_CACHE_SIZE`.
+pub fn build_temperature_reading_table_from_bytes(
+  input: &[u8],
+  len: usize,
+) -> BarseResult<SummaryTable> {
+  build_temperature_reading_table_from_bytes_with_options(input, len, &Options::default())
+    .map(|(table, _)| table)
+}
+
+/// Builds the table with `options.strategy`. If no strategy is given, builds
+/// the table on the calling thread if the input is smaller than
+/// `options.single_threaded_threshold`, otherwise splits the work across
+/// worker threads.
+pub fn build_temperature_reading_table_from_bytes_with_options(
+  input: &[u8],
+  len: usize,
+  options: &Options,
+) -> BarseResult<(SummaryTable, BuildReport)> {
+  build_aggregated_table_from_bytes(input, len, &TemperatureAggregator, options)
+}
+
+/// Builds the table like
+/// `build_temperature_reading_table_from_bytes_with_options`, but folds the
+/// readings of each station with `aggregator`. Each worker thread is handed a
+/// clone of `aggregator` and its own table of summaries, which are merged
+/// once every worker has finished.
+pub fn build_aggregated_table_from_bytes<A>(
+  input: &[u8],
+  len: usize,
+  aggregator: &A,
+  options: &Options,
+) -> BarseResult<(SummaryTable<A::Summary>, BuildReport)>
+where
+  A: Aggregator + Clone + Send + 'static,
+  A::Summary: Send + 'static,
+{
+  options.validate()?;
+  #[cfg(feature = "tracing")]
+  let _span = tracing::info_span!("build_table", bytes = len).entered();
+  let start = Instant::now();
+  let strategy = match options.strategy {
+    Some(strategy) => strategy,
+    None if len < options.single_threaded_threshold => Strategy::SingleThreaded,
+    None => Strategy::MultiThreaded,
+  };
+  let threads = match strategy {
+    Strategy::SingleThreaded => 1,
+    Strategy::MultiThreaded | Strategy::PerThreadTables => thread_count(len, options),
+  };
+  #[cfg(feature = "tracing")]
+  tracing::info!(strategy = %strategy, threads, "chose strategy");
+  let (table, counters) = match strategy {
+    Strategy::SingleThreaded => build_single_threaded(input, len, aggregator, options)?,
+    Strategy::MultiThreaded => {
+      build_multithreaded(input, len, threads, false, aggregator, options)?
+    }
+    Strategy::PerThreadTables => {
+      build_multithreaded(input, len, threads, true, aggregator, options)?
+    }
+  };
+
+  let report = BuildReport {
+    strategy,
+    threads,
+    bytes: len as u64,
+    records: counters.records,
+    missing_records: counters.missing_records,
+    unique_stations: table.iter().count(),
+    chunks: counters.chunks,
+    hugepages: counters.hugepages,
+    map_time: Duration::ZERO,
+    wall_time: start.elapsed(),
+    merge_time: counters.merge_time,
+  };
+  Ok((table, report))
+}
+
+#[cfg(test)]
+mod tests {
+  use googletest::prelude::*;
+
+  use itertools::Itertools;
+
+  use crate::{
+    aggregator::{Aggregator, InPlaceInitializable, Merge, TemperatureAggregator},
+    aligned::AlignedInput,
+    build_report::Strategy,
+    build_table_mt::{
+      add_sliced_records, build_aggregated_table_from_bytes,
+      build_temperature_reading_table_from_bytes_with_options, SummaryTable,
+    },
+    hugepage_backed_table::HUGEPAGE_SIZE,
+    normalize::RecordChecks,
+    options::Options,
+    scanner::{Scanner, BUFFER_OVERLAP},
+    slicer::Slicer,
+    string_table::StringTable,
+    temperature_reading::TemperatureReading,
+    temperature_summary_table::TemperatureSummaryTable,
+    test_util::random_input_file,
+  };
+
+  /// The count and total of each station's readings, which must agree with
+  /// the default `TemperatureSummary`.
+  #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+  struct CountAndTotal {
+    count: u32,
+    total: i64,
+  }
+
+  // SAFETY: `CountAndTotal` is made of integers, for which zero is valid.
+  unsafe impl InPlaceInitializable for CountAndTotal {
+    fn initialize(&mut self) {}
+  }
+
+  impl Merge for CountAndTotal {
+    fn merge(&mut self, other: &Self) {
+      self.count += other.count;
+      self.total += other.total;
+    }
+  }
+
+  #[derive(Clone)]
+  struct CountAndTotalAggregator;
+
+  impl Aggregator for CountAndTotalAggregator {
+    type Summary = CountAndTotal;
+
+    fn add(&self, summary: &mut CountAndTotal, reading: TemperatureReading) {
+      summary.count += 1;
+      summary.total += reading.reading() as i64;
+    }
+  }
+
+  #[gtest]
+  fn test_custom_aggregator_matches_default() {
+    let input = random_input_file(0x4a61, 200_000, 400).unwrap();
+    for strategy in [
+      Strategy::SingleThreaded,
+      Strategy::MultiThreaded,
+      Strategy::PerThreadTables,
+    ] {
+      let options = Options {
+        strategy: Some(strategy),
+        threads: Some(4),
+        chunk_size: 4096,
+        min_chunk_size: 4096,
+        ..Options::default()
+      };
+      let (table, _) = build_temperature_reading_table_from_bytes_with_options(
+        input.padded_slice(),
+        input.len(),
+        &options,
+      )
+      .unwrap();
+      let expected = table
+        .to_owned_summary()
+        .into_iter()
+        .map(|(station, summary)| {
+          let summary = CountAndTotal {
+            count: summary.count,
+            total: summary.total,
+          };
+          (station, summary)
+        })
+        .sorted_by(|(a, _), (b, _)| a.cmp(b))
+        .collect_vec();
+
+      let (table, report) = build_aggregated_table_from_bytes(
+        input.padded_slice(),
+        input.len(),
+        &CountAndTotalAggregator,
+        &options,
+      )
+      .unwrap();
+      expect_eq!(report.strategy, strategy);
+      expect_eq!(
+        table
+          .to_owned_summary()
+          .into_iter()
+          .sorted_by(|(a, _), (b, _)| a.cmp(b))
+          .collect_vec(),
+        expected,
+        "{strategy:?}"
+      );
+    }
+  }
+
+  #[gtest]
+  fn test_chunk_starting_in_overlong_record_is_an_error() {
+    // The second chunk's overlap lies entirely within the long name.
+    let text = format!(
+      "Oslo;1.0\n{};1.0\nBergen;2.0\n",
+      "a".repeat(2 * BUFFER_OVERLAP)
+    );
+    let input = AlignedInput::new(&text);
+    // With a missing reading to check for, records are added through
+    // `add_checked_records` rather than the unchecked loop.
+    for missing_reading in [None, Some(TemperatureReading::new(-999))] {
+      let checks = RecordChecks::new(&Options {
+        missing_reading,
+        ..Options::default()
+      });
+      let slicer = unsafe { Slicer::new(input.padded_slice(), input.len(), BUFFER_OVERLAP) };
+      // Take the first chunk, whose scanner would read the long name.
+      expect_true!(matches!(slicer.try_next_chunk(), Some(Ok(_))));
+      let string_table = StringTable::new(16).unwrap();
+      let mut summary_table = TemperatureSummaryTable::new(16).unwrap();
+      expect_that!(
+        add_sliced_records(
+          &slicer,
+          &string_table,
+          &mut summary_table,
+          &TemperatureAggregator,
+          false,
+          &checks,
+        ),
+        err(displays_as(contains_substring(format!(
+          "chunk at byte {BUFFER_OVERLAP} starts within a record"
+        ))))
+      );
+    }
+  }
+
+  #[gtest]
+  fn test_build_after_prefault() {
+    let input = random_input_file(0x9ef4, 10_000, 500).unwrap();
+    let mut table = SummaryTable::with_capacity(1024).unwrap();
+    let mut prefaulted = SummaryTable::with_capacity(1024).unwrap();
+    prefaulted.prefault();
+    for (station, temp) in Scanner::from_start_with_len(input.padded_slice(), input.len()) {
+      table.add_reading(station, temp);
+      prefaulted.add_reading(station, temp);
+    }
+
+    let sorted_summary = |table: &SummaryTable| {
+      table
+        .to_owned_summary()
+        .into_iter()
+        .sorted_by(|(a, _), (b, _)| a.cmp(b))
+        .collect_vec()
+    };
+    expect_eq!(table.to_owned_summary().len(), 500);
+    expect_eq!(sorted_summary(&prefaulted), sorted_summary(&table));
+  }
+
+  #[gtest]
+  fn test_memory_footprint_matches_mapping() {
+    for capacity in [16, 1 << 15, 1 << 20] {
+      let footprint = SummaryTable::with_capacity(capacity)
+        .unwrap()
+        .memory_footprint();
+      expect_eq!(
+        footprint,
+        SummaryTable::memory_footprint_for_capacity(capacity)
+      );
+      expect_eq!(footprint.capacity, capacity);
+      expect_eq!((footprint.key_bytes, footprint.summary_bytes), (56, 16));
+      expect_eq!(footprint.entry_bytes, 72);
+      expect_eq!(
+        footprint.mapped_bytes,
+        (capacity * 56).next_multiple_of(HUGEPAGE_SIZE)
+          + (capacity * 16).next_multiple_of(HUGEPAGE_SIZE)
+      );
+    }
+  }
+}
@@ -0,0 +1,221 @@
+//! A C API for summarizing files from other languages, declared in
+//! `include/barse.h`. Regenerate the header after changing this module with
+//! `cbindgen --config cbindgen.toml --output include/barse.h src/capi.rs`.
+//!
+//! Build the static library to link into C programs with
+//! `cargo rustc --lib --crate-type staticlib --features capi --profile capi`.
+//!
+//! Every function returns one of the `BARSE_*` status codes, and on failure
+//! records a message retrieved with `barse_last_error`. Panics are caught
+//! before they reach the caller and reported as `BARSE_ERROR_PANIC`, which
+//! requires the library to be built with `panic = "unwind"`, as the `capi`
+//! profile does, since the release profile aborts on panic.
+
+use std::{
+  cell::RefCell,
+  ffi::{c_char, CStr, CString},
+  panic::{self, AssertUnwindSafe},
+  ptr,
+};
+
+use crate::{
+  barse::OwnedWeatherStation, error::panic_message, options::Options, print_summary::summarize_file,
+};
+
+/// The call succeeded.
+pub const BARSE_OK: i32 = 0;
+/// A pointer argument was null, or the path was not UTF-8.
+pub const BARSE_ERROR_INVALID_ARGUMENT: i32 = 1;
+/// The file could not be read or summarized.
+pub const BARSE_ERROR_FAILED: i32 = 2;
+/// The library panicked.
+pub const BARSE_ERROR_PANIC: i32 = 3;
+
+/// The summary of one weather station. Temperatures are in tenths of a
+/// degree.
+#[repr(C)]
+pub struct BarseSummary {
+  /// The station's name, in UTF-8 and not nul-terminated.
+  pub name: *const u8,
+  /// The length of `name` in bytes.
+  pub name_len: usize,
+  pub min: i16,
+  pub max: i16,
+  /// The mean reading, rounded as the challenge's reference implementation
+  /// does.
+  pub avg: i16,
+  /// The number of readings.
+  pub count: u32,
+}
+
+impl BarseSummary {
+  /// Moves the name of `station` into the summary, to be freed with
+  /// `free_name`.
+  fn new(station: OwnedWeatherStation) -> Self {
+    let summary = station.summary;
+    let name_len = station.name.len();
+    let name = Box::into_raw(station.name.into_boxed_str());
+    Self {
+      name: name as *const u8,
+      name_len,
+      min: summary.min().reading(),
+      max: summary.max().reading(),
+      avg: summary.avg().reading(),
+      count: summary.count,
+    }
+  }
+
+  /// # Safety
+  ///
+  /// The summary must have been built by `new`, and its name not yet freed.
+  unsafe fn free_name(&self) {
+    let name = ptr::slice_from_raw_parts_mut(self.name as *mut u8, self.name_len);
+    // SAFETY: `name` was leaked from a `Box<str>` of `name_len` bytes by
+    // `new`.
+    drop(unsafe { Box::from_raw(name) });
+  }
+}
+
+thread_local! {
+  /// The message of the last error returned on this thread.
+  static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: Option<String>) {
+  let message = message
+    .map(|message| CString::new(message.replace('\0', "\\0")).expect("Nul bytes were escaped"));
+  LAST_ERROR.with(|last_error| *last_error.borrow_mut() = message);
+}
+
+/// Runs `f`, recording the message of any error or panic it raises, and
+/// returns its status code.
+fn ffi_boundary(f: impl FnOnce() -> Result<(), (i32, String)>) -> i32 {
+  let (status, message) = match panic::catch_unwind(AssertUnwindSafe(f)) {
+    Ok(Ok(())) => (BARSE_OK, None),
+    Ok(Err((status, message))) => (status, Some(message)),
+    Err(payload) => (
+      BARSE_ERROR_PANIC,
+      Some(format!("panicked: {}", panic_message(payload))),
+    ),
+  };
+  set_last_error(message);
+  status
+}
+
+/// Summarizes every weather station in the file at `path`, ordered by name,
+/// storing an array of the summaries in `*out` and its length in `*len`. The
+/// array must be freed with `barse_free_summary`. On failure, `*out` and
+/// `*len` are left unchanged.
+///
+/// # Safety
+///
+/// `path` must be a nul-terminated string, and `out` and `len` must be valid
+/// for writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn barse_summarize_file(
+  path: *const c_char,
+  out: *mut *mut BarseSummary,
+  len: *mut usize,
+) -> i32 {
+  ffi_boundary(|| {
+    if path.is_null() || out.is_null() || len.is_null() {
+      return Err((
+        BARSE_ERROR_INVALID_ARGUMENT,
+        "Null pointer passed to barse_summarize_file".to_owned(),
+      ));
+    }
+    // SAFETY: The caller guarantees `path` is nul-terminated.
+    let path = unsafe { CStr::from_ptr(path) }.to_str().map_err(|err| {
+      (
+        BARSE_ERROR_INVALID_ARGUMENT,
+        format!("Path is not UTF-8: {err}"),
+      )
+    })?;
+
+    let stations = summarize_file(path, &Options::default())
+      .map_err(|err| (BARSE_ERROR_FAILED, err.to_string()))?;
+    let summaries = stations
+      .into_iter()
+      .map(BarseSummary::new)
+      .collect::<Box<[_]>>();
+    // SAFETY: The caller guarantees `out` and `len` are valid for writes.
+    unsafe {
+      *len = summaries.len();
+      *out = Box::into_raw(summaries) as *mut BarseSummary;
+    }
+    Ok(())
+  })
+}
+
+/// Frees an array of `len` summaries returned by `barse_summarize_file`. Does
+/// nothing if `summaries` is null.
+///
+/// # Safety
+///
+/// `summaries` must be null, or an array returned by `barse_summarize_file`
+/// with its length `len`, which has not already been freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn barse_free_summary(summaries: *mut BarseSummary, len: usize) {
+  if summaries.is_null() {
+    return;
+  }
+  // SAFETY: The caller guarantees `summaries` was leaked from a boxed slice
+  // of `len` summaries by `barse_summarize_file`.
+  let summaries = unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(summaries, len)) };
+  for summary in &summaries {
+    // SAFETY: Each summary was built by `BarseSummary::new`, and is freed once.
+    unsafe { summary.free_name() };
+  }
+}
+
+/// Returns the message of the error returned by the last call on this thread,
+/// or null if it succeeded. The message is valid until the next call on this
+/// thread.
+#[unsafe(no_mangle)]
+pub extern "C" fn barse_last_error() -> *const c_char {
+  LAST_ERROR.with(|last_error| {
+    last_error
+      .borrow()
+      .as_ref()
+      .map_or(ptr::null(), |message| message.as_ptr())
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{ffi::CStr, ptr};
+
+  use googletest::prelude::*;
+
+  use crate::capi::{
+    barse_last_error, barse_summarize_file, ffi_boundary, BarseSummary,
+    BARSE_ERROR_INVALID_ARGUMENT, BARSE_ERROR_PANIC, BARSE_OK,
+  };
+
+  fn last_error() -> Option<String> {
+    let message = barse_last_error();
+    (!message.is_null()).then(|| {
+      unsafe { CStr::from_ptr(message) }
+        .to_string_lossy()
+        .into_owned()
+    })
+  }
+
+  #[gtest]
+  fn test_panic_is_converted_to_error_code() {
+    expect_eq!(ffi_boundary(|| panic!("Table is full")), BARSE_ERROR_PANIC);
+    expect_eq!(last_error(), Some("panicked: Table is full".to_owned()));
+
+    expect_eq!(ffi_boundary(|| Ok(())), BARSE_OK);
+    expect_eq!(last_error(), None);
+  }
+
+  #[gtest]
+  fn test_null_arguments() {
+    let mut out: *mut BarseSummary = ptr::null_mut();
+    let status = unsafe { barse_summarize_file(ptr::null(), &raw mut out, ptr::null_mut()) };
+    expect_eq!(status, BARSE_ERROR_INVALID_ARGUMENT);
+    expect_that!(last_error(), some(contains_substring("Null pointer")));
+    expect_true!(out.is_null());
+  }
+}
@@ -0,0 +1,372 @@
+use std::{collections::HashMap, fmt::Display, fs, io::BufRead};
+
+use crate::{
+  barse::{
+    build_temperature_reading_table, build_temperature_reading_table_with_options, WeatherStation,
+  },
+  error::{BarseError, BarseResult},
+  options::Options,
+  reference,
+  temperature_summary::TemperatureSummary,
+  util::HasIter,
+};
+
+/// The number of mismatches shown when a `CheckReport` is displayed.
+const MAX_DISPLAYED_MISMATCHES: usize = 10;
+
+/// A difference between the computed summary and the expected output.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Mismatch {
+  /// The station's `min/mean/max` differs from the expected value.
+  Value {
+    station: String,
+    expected: String,
+    actual: String,
+  },
+  /// The station is in the expected output, but not in the input.
+  Missing { station: String, expected: String },
+  /// The station is in the input, but not in the expected output.
+  Extra { station: String, actual: String },
+  /// The stations match, but are in a different order, starting at
+  /// `position`.
+  Order {
+    position: usize,
+    expected: String,
+    actual: String,
+  },
+}
+
+impl Display for Mismatch {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Self::Value {
+        station,
+        expected,
+        actual,
+      } => write!(f, "{station}: expected {expected}, got {actual}"),
+      Self::Missing { station, expected } => {
+        write!(f, "{station}: missing, expected {expected}")
+      }
+      Self::Extra { station, actual } => write!(f, "{station}: unexpected, got {actual}"),
+      Self::Order {
+        position,
+        expected,
+        actual,
+      } => write!(
+        f,
+        "station {position} out of order: expected {expected}, got {actual}"
+      ),
+    }
+  }
+}
+
+/// The result of comparing the summary of an input to an expected output.
+#[derive(Clone, Debug, Default)]
+pub struct CheckReport {
+  /// The number of stations whose values match the expected output.
+  pub matched: usize,
+  pub mismatches: Vec<Mismatch>,
+}
+
+impl CheckReport {
+  pub fn is_match(&self) -> bool {
+    self.mismatches.is_empty()
+  }
+}
+
+impl Display for CheckReport {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    if self.is_match() {
+      return write!(f, "OK: {} stations match", self.matched);
+    }
+    write!(
+      f,
+      "{} mismatches ({} stations match)",
+      self.mismatches.len(),
+      self.matched
+    )?;
+    for mismatch in self.mismatches.iter().take(MAX_DISPLAYED_MISMATCHES) {
+      write!(f, "\n  {mismatch}")?;
+    }
+    if self.mismatches.len() > MAX_DISPLAYED_MISMATCHES {
+      write!(
+        f,
+        "\n  ... and {} more",
+        self.mismatches.len() - MAX_DISPLAYED_MISMATCHES
+      )?;
+    }
+    Ok(())
+  }
+}
+
+/// Returns true if `value` looks like `min/mean/max`.
+fn is_summary_value(value: &str) -> bool {
+  let parts = value.split('/').collect::<Vec<_>>();
+  parts.len() == 3 && parts.iter().all(|part| part.parse::<f64>().is_ok())
+}
+
+/// Splits a `station=min/mean/max` entry into the station and its value.
+fn split_entry(entry: &str) -> Option<(&str, &str)> {
+  entry
+    .rsplit_once('=')
+    .filter(|(_, value)| is_summary_value(value))
+}
+
+/// Parses expected output in the brc format, `{a=min/mean/max, b=...}`, or
+/// with one `station=min/mean/max` entry per line. Station names may contain
+/// ", " and "=", since entries are only split where a complete value ends.
+fn parse_expected(expected: impl BufRead) -> BarseResult<Vec<(String, String)>> {
+  let mut entries = Vec::new();
+  for line in expected.lines() {
+    let line = line?;
+    let line = line.trim_end_matches('\r');
+    let line = line
+      .strip_prefix('{')
+      .and_then(|line| line.strip_suffix('}'))
+      .unwrap_or(line);
+    if line.is_empty() {
+      continue;
+    }
+
+    let mut entry = String::new();
+    for part in line.split(", ") {
+      if !entry.is_empty() {
+        entry.push_str(", ");
+      }
+      entry.push_str(part);
+      if let Some((station, value)) = split_entry(&entry) {
+        entries.push((station.to_owned(), value.to_owned()));
+        entry.clear();
+      }
+    }
+    if !entry.is_empty() {
+      return Err(BarseError::new(format!(
+        "Malformed expected entry \"{entry}\""
+      )));
+    }
+  }
+  Ok(entries)
+}
+
+/// Returns the name and `min/mean/max` of every station in `table`, in
+/// output order.
+fn sorted_summaries<T>(table: &T) -> Vec<(String, String)>
+where
+  T: for<'a> HasIter<'a, Item = (&'a str, &'a TemperatureSummary)>,
+{
+  let mut stations = table
+    .iter()
+    .map(|(station, summary)| WeatherStation::new(station, *summary))
+    .collect::<Vec<_>>();
+  stations.sort_unstable();
+  stations
+    .into_iter()
+    .map(|station| (station.name().to_owned(), station.summary().to_string()))
+    .collect()
+}
+
+/// Summarizes the file at `input_path` and compares the result, in the brc
+/// output format, against `expected`.
+pub fn verify_against(input_path: &str, expected: impl BufRead) -> BarseResult<CheckReport> {
+  let expected = parse_expected(expected)?;
+  let table = build_temperature_reading_table(input_path)?;
+  Ok(compare(&expected, &sorted_summaries(&table)))
+}
+
+/// Summarizes the file at `input_path` with `options`, then again with the
+/// simple parser of `reference`, and compares the two. The whole input is
+/// read into memory for the simple parser, which is far slower than the
+/// build.
+pub fn verify_with_reference(input_path: &str, options: &Options) -> BarseResult<CheckReport> {
+  let (table, _) = build_temperature_reading_table_with_options(input_path, options)?;
+  let input =
+    fs::read_to_string(input_path).map_err(|err| BarseError::from(err).in_file(input_path))?;
+  let expected = reference::weather_stations_with_extra_fields(
+    &input,
+    options.skip_header,
+    options.extra_fields,
+  )
+  .map_err(|err| err.in_file(input_path))?;
+  Ok(compare_to_reference(&table, &expected))
+}
+
+/// Compares the summaries in `table` against those of the simple parser.
+fn compare_to_reference<T>(table: &T, expected: &[reference::WeatherStation]) -> CheckReport
+where
+  T: for<'a> HasIter<'a, Item = (&'a str, &'a TemperatureSummary)>,
+{
+  let expected = expected
+    .iter()
+    .map(|station| (station.name.clone(), station.summary.to_string()))
+    .collect::<Vec<_>>();
+  compare(&expected, &sorted_summaries(table))
+}
+
+/// Compares the `(station, min/mean/max)` pairs of `actual` against
+/// `expected`, both in output order.
+fn compare(expected: &[(String, String)], actual: &[(String, String)]) -> CheckReport {
+  let mut report = CheckReport::default();
+  let actual_values = actual
+    .iter()
+    .map(|(station, value)| (station.as_str(), value.as_str()))
+    .collect::<HashMap<_, _>>();
+  let expected_values = expected
+    .iter()
+    .map(|(station, value)| (station.as_str(), value.as_str()))
+    .collect::<HashMap<_, _>>();
+  for (station, expected_value) in expected {
+    match actual_values.get(station.as_str()) {
+      Some(actual_value) if actual_value == expected_value => report.matched += 1,
+      Some(actual_value) => report.mismatches.push(Mismatch::Value {
+        station: station.clone(),
+        expected: expected_value.clone(),
+        actual: (*actual_value).to_owned(),
+      }),
+      None => report.mismatches.push(Mismatch::Missing {
+        station: station.clone(),
+        expected: expected_value.clone(),
+      }),
+    }
+  }
+  for (station, actual_value) in actual {
+    if !expected_values.contains_key(station.as_str()) {
+      report.mismatches.push(Mismatch::Extra {
+        station: station.clone(),
+        actual: actual_value.clone(),
+      });
+    }
+  }
+
+  // Only report ordering once the contents match, since any missing or extra
+  // station would also shift the order.
+  if report.is_match()
+    && let Some((position, ((expected, _), (actual, _)))) = expected
+      .iter()
+      .zip(actual)
+      .enumerate()
+      .find(|(_, ((expected, _), (actual, _)))| expected != actual)
+  {
+    report.mismatches.push(Mismatch::Order {
+      position,
+      expected: expected.clone(),
+      actual: actual.clone(),
+    });
+  }
+  report
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{fs, io::Cursor, path::PathBuf};
+
+  use googletest::prelude::*;
+
+  use crate::{
+    barse::build_temperature_reading_table,
+    check::{compare_to_reference, verify_against, verify_with_reference, CheckReport, Mismatch},
+    options::Options,
+    reference,
+  };
+
+  const INPUT: &str = "Hamburg;12.0\nBulawayo;8.9\nPalembang;38.8\nHamburg;34.2\n";
+
+  fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("barse_{}_{name}", std::process::id()))
+  }
+
+  fn check(name: &str, expected: &str) -> CheckReport {
+    let input_path = temp_path(name);
+    fs::write(&input_path, INPUT).unwrap();
+    let report = verify_against(input_path.to_str().unwrap(), Cursor::new(expected)).unwrap();
+    fs::remove_file(&input_path).unwrap();
+    report
+  }
+
+  #[gtest]
+  fn test_exact_match() {
+    let report = check(
+      "check_exact.txt",
+      "{Bulawayo=8.9/8.9/8.9, Hamburg=12.0/23.1/34.2, Palembang=38.8/38.8/38.8}\n",
+    );
+    expect_true!(report.is_match());
+    expect_eq!(report.matched, 3);
+  }
+
+  #[gtest]
+  fn test_value_mismatch() {
+    let report = check(
+      "check_value.txt",
+      "{Bulawayo=8.9/8.9/8.9, Hamburg=12.0/23.2/34.2, Palembang=38.8/38.8/38.8}\n",
+    );
+    expect_false!(report.is_match());
+    expect_eq!(report.matched, 2);
+    expect_that!(
+      report.mismatches,
+      elements_are![eq(&Mismatch::Value {
+        station: "Hamburg".to_owned(),
+        expected: "12.0/23.2/34.2".to_owned(),
+        actual: "12.0/23.1/34.2".to_owned(),
+      })]
+    );
+  }
+
+  #[gtest]
+  fn test_missing_station() {
+    let report = check(
+      "check_missing.txt",
+      "{Abha=5.0/5.0/5.0, Bulawayo=8.9/8.9/8.9, Hamburg=12.0/23.1/34.2, \
+       Palembang=38.8/38.8/38.8}\n",
+    );
+    expect_that!(
+      report.mismatches,
+      elements_are![eq(&Mismatch::Missing {
+        station: "Abha".to_owned(),
+        expected: "5.0/5.0/5.0".to_owned(),
+      })]
+    );
+  }
+
+  #[gtest]
+  fn test_extra_station() {
+    let report = check(
+      "check_extra.txt",
+      "{Bulawayo=8.9/8.9/8.9, Hamburg=12.0/23.1/34.2}\n",
+    );
+    expect_that!(
+      report.mismatches,
+      elements_are![eq(&Mismatch::Extra {
+        station: "Palembang".to_owned(),
+        actual: "38.8/38.8/38.8".to_owned(),
+      })]
+    );
+  }
+
+  #[gtest]
+  fn test_verify_with_reference() {
+    let input_path = temp_path("verify_reference.txt");
+    fs::write(&input_path, INPUT).unwrap();
+    let report = verify_with_reference(input_path.to_str().unwrap(), &Options::default()).unwrap();
+    fs::remove_file(&input_path).unwrap();
+    expect_true!(report.is_match());
+    expect_eq!(report.matched, 3);
+  }
+
+  #[gtest]
+  fn test_reference_catches_corrupted_build() {
+    // Simulates a fast path which misreads one of Hamburg's readings.
+    let input_path = temp_path("verify_corrupted.txt");
+    fs::write(&input_path, INPUT.replace("34.2", "43.2")).unwrap();
+    let table = build_temperature_reading_table(input_path.to_str().unwrap()).unwrap();
+    fs::remove_file(&input_path).unwrap();
+
+    let report = compare_to_reference(&table, &reference::weather_stations(INPUT, false).unwrap());
+    expect_that!(
+      report.mismatches,
+      elements_are![eq(&Mismatch::Value {
+        station: "Hamburg".to_owned(),
+        expected: "12.0/23.1/34.2".to_owned(),
+        actual: "12.0/27.6/43.2".to_owned(),
+      })]
+    );
+  }
+}
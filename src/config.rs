@@ -0,0 +1,581 @@
+use std::{fmt::Display, fs, str::FromStr, time::Duration};
+
+use toml::de::{DeTable, DeValue};
+
+use crate::{
+  build_report::Strategy,
+  error::{BarseError, BarseResult},
+  options::Options,
+  scanner::ExtraFields,
+  temperature_reading::{DecimalSeparator, TemperatureReading},
+};
+
+/// The environment variables the command line reads build options from.
+pub const ENV_CONFIG: &str = "BARSE_CONFIG";
+pub const ENV_THREADS: &str = "BARSE_THREADS";
+pub const ENV_CHUNK_SIZE: &str = "BARSE_CHUNK_SIZE";
+pub const ENV_STRATEGY: &str = "BARSE_STRATEGY";
+pub const ENV_STRICT: &str = "BARSE_STRICT";
+pub const ENV_SKIP_HEADER: &str = "BARSE_SKIP_HEADER";
+
+/// The keys accepted in config files, which are the fields of `Options`.
+const CONFIG_KEYS: [&str; 11] = [
+  "single_threaded_threshold",
+  "min_chunk_size",
+  "table_capacity",
+  "chunk_size",
+  "threads",
+  "decimal_separator",
+  "strict",
+  "skip_header",
+  "extra_fields",
+  "missing_reading",
+  "strategy",
+];
+
+/// Where a build option was set, in increasing order of precedence.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Source {
+  ConfigFile,
+  Environment,
+  CommandLine,
+}
+
+impl Display for Source {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Self::ConfigFile => write!(f, "the config file"),
+      Self::Environment => write!(f, "the environment"),
+      Self::CommandLine => write!(f, "the command line"),
+    }
+  }
+}
+
+/// The build options set by one source. Options left as `None` are taken
+/// from sources of lower precedence, or the defaults.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OptionOverrides {
+  pub single_threaded_threshold: Option<usize>,
+  pub min_chunk_size: Option<usize>,
+  pub table_capacity: Option<usize>,
+  pub chunk_size: Option<usize>,
+  /// The number of worker threads, where 0 selects the available
+  /// parallelism.
+  pub threads: Option<usize>,
+  pub decimal_separator: Option<DecimalSeparator>,
+  pub strict: Option<bool>,
+  pub skip_header: Option<bool>,
+  pub extra_fields: Option<ExtraFields>,
+  pub missing_reading: Option<TemperatureReading>,
+  pub strategy: Option<Strategy>,
+}
+
+impl OptionOverrides {
+  /// Overwrites the options set in `self`.
+  pub fn apply_to(&self, options: &mut Options) {
+    if let Some(single_threaded_threshold) = self.single_threaded_threshold {
+      options.single_threaded_threshold = single_threaded_threshold;
+    }
+    if let Some(min_chunk_size) = self.min_chunk_size {
+      options.min_chunk_size = min_chunk_size;
+    }
+    if let Some(table_capacity) = self.table_capacity {
+      options.table_capacity = table_capacity;
+    }
+    if let Some(chunk_size) = self.chunk_size {
+      options.chunk_size = chunk_size;
+    }
+    if let Some(threads) = self.threads {
+      options.threads = (threads != 0).then_some(threads);
+    }
+    if let Some(decimal_separator) = self.decimal_separator {
+      options.decimal_separator = decimal_separator;
+    }
+    if let Some(strict) = self.strict {
+      options.strict = strict;
+    }
+    if let Some(skip_header) = self.skip_header {
+      options.skip_header = skip_header;
+    }
+    if let Some(extra_fields) = self.extra_fields {
+      options.extra_fields = extra_fields;
+    }
+    if self.missing_reading.is_some() {
+      options.missing_reading = self.missing_reading;
+    }
+    if self.strategy.is_some() {
+      options.strategy = self.strategy;
+    }
+  }
+
+  /// Returns the names of the options set in `self`.
+  fn set_keys(&self) -> impl Iterator<Item = &'static str> {
+    let set = [
+      self.single_threaded_threshold.is_some(),
+      self.min_chunk_size.is_some(),
+      self.table_capacity.is_some(),
+      self.chunk_size.is_some(),
+      self.threads.is_some(),
+      self.decimal_separator.is_some(),
+      self.strict.is_some(),
+      self.skip_header.is_some(),
+      self.extra_fields.is_some(),
+      self.missing_reading.is_some(),
+      self.strategy.is_some(),
+    ];
+    CONFIG_KEYS
+      .into_iter()
+      .zip(set)
+      .filter_map(|(key, set)| set.then_some(key))
+  }
+
+  /// Sets the option `key` from its config file `value`, which is written
+  /// as `raw` in the file.
+  fn set(&mut self, key: &str, value: &DeValue<'_>, raw: &str) -> Result<(), String> {
+    if let DeValue::Table(_) = value {
+      return Err("tables are not supported, options must be set at the top level".to_owned());
+    }
+    match key {
+      "single_threaded_threshold" => {
+        self.single_threaded_threshold = Some(parse_size_value(value, raw)?)
+      }
+      "min_chunk_size" => self.min_chunk_size = Some(parse_size_value(value, raw)?),
+      "table_capacity" => self.table_capacity = Some(parse_integer(value, raw)?),
+      "chunk_size" => self.chunk_size = Some(parse_size_value(value, raw)?),
+      "threads" => self.threads = Some(parse_integer(value, raw)?),
+      "decimal_separator" => {
+        self.decimal_separator = Some(match parse_string(value, raw)? {
+          "." => DecimalSeparator::Dot,
+          "," => DecimalSeparator::Comma,
+          separator => {
+            return Err(format!(
+              "unknown decimal separator \"{separator}\", expected \".\" or \",\""
+            ))
+          }
+        })
+      }
+      "strict" => self.strict = Some(parse_bool(value, raw)?),
+      "skip_header" => self.skip_header = Some(parse_bool(value, raw)?),
+      "extra_fields" => {
+        self.extra_fields = Some(match parse_string(value, raw)? {
+          "forbid" => ExtraFields::Forbid,
+          "skip" => ExtraFields::Skip,
+          extra_fields => {
+            return Err(format!(
+              "unknown extra fields mode \"{extra_fields}\", expected \"forbid\" or \"skip\""
+            ))
+          }
+        })
+      }
+      "missing_reading" => {
+        self.missing_reading = Some(
+          match value {
+            DeValue::Float(reading) => {
+              TemperatureReading::parse_checked(reading.as_str().as_bytes(), DecimalSeparator::Dot)
+            }
+            _ => None,
+          }
+          .ok_or_else(|| format!("expected a temperature reading like -99.9, got {raw}"))?,
+        )
+      }
+      "strategy" => {
+        self.strategy = Some(Strategy::from_str(parse_string(value, raw)?).map_err(
+          |err| match err {
+            BarseError::Other(message) => message,
+            err => err.to_string(),
+          },
+        )?)
+      }
+      _ => {
+        return Err(format!(
+          "unknown option \"{key}\", expected one of {}",
+          CONFIG_KEYS.join(", ")
+        ))
+      }
+    }
+    Ok(())
+  }
+}
+
+/// Parses a number of bytes with an optional K, M or G suffix, e.g. `512K`.
+pub fn parse_size(arg: &str) -> Result<usize, String> {
+  let (digits, shift) = match arg.as_bytes().last() {
+    Some(b'K' | b'k') => (&arg[..arg.len() - 1], 10),
+    Some(b'M' | b'm') => (&arg[..arg.len() - 1], 20),
+    Some(b'G' | b'g') => (&arg[..arg.len() - 1], 30),
+    _ => (arg, 0),
+  };
+  let size = digits
+    .parse::<usize>()
+    .map_err(|_| format!("invalid size \"{arg}\""))?;
+  size
+    .checked_mul(1 << shift)
+    .ok_or_else(|| format!("size \"{arg}\" is too large"))
+}
+
+/// Parses a duration with an optional ms, s, m or h suffix, e.g. `500ms`. A
+/// bare number is a number of seconds.
+pub fn parse_duration(arg: &str) -> Result<Duration, String> {
+  let (digits, unit_ms) = if let Some(digits) = arg.strip_suffix("ms") {
+    (digits, 1)
+  } else if let Some(digits) = arg.strip_suffix('s') {
+    (digits, 1_000)
+  } else if let Some(digits) = arg.strip_suffix('m') {
+    (digits, 60_000)
+  } else if let Some(digits) = arg.strip_suffix('h') {
+    (digits, 3_600_000)
+  } else {
+    (arg, 1_000)
+  };
+  let count = digits
+    .parse::<u64>()
+    .map_err(|_| format!("invalid duration \"{arg}\""))?;
+  count
+    .checked_mul(unit_ms)
+    .map(Duration::from_millis)
+    .ok_or_else(|| format!("duration \"{arg}\" is too long"))
+}
+
+fn parse_integer(value: &DeValue<'_>, raw: &str) -> Result<usize, String> {
+  match value {
+    DeValue::Integer(integer) => usize::from_str_radix(integer.as_str(), integer.radix()).ok(),
+    _ => None,
+  }
+  .ok_or_else(|| format!("expected a non-negative integer, got {raw}"))
+}
+
+/// Parses a size given either as an integer, or as a string with a suffix
+/// like `parse_size` accepts.
+fn parse_size_value(value: &DeValue<'_>, raw: &str) -> Result<usize, String> {
+  match value {
+    DeValue::String(size) => parse_size(size),
+    _ => parse_integer(value, raw),
+  }
+}
+
+fn parse_bool(value: &DeValue<'_>, raw: &str) -> Result<bool, String> {
+  match value {
+    DeValue::Boolean(value) => Ok(*value),
+    _ => Err(format!("expected true or false, got {raw}")),
+  }
+}
+
+fn parse_string<'a>(value: &'a DeValue<'_>, raw: &str) -> Result<&'a str, String> {
+  match value {
+    DeValue::String(string) => Ok(string),
+    _ => Err(format!("expected a string, got {raw}")),
+  }
+}
+
+/// Returns the 1-based line number of the byte at `offset` in `text`.
+fn line_number(text: &str, offset: usize) -> usize {
+  text.as_bytes()[..offset.min(text.len())]
+    .iter()
+    .filter(|&&byte| byte == b'\n')
+    .count()
+    + 1
+}
+
+/// Parses a config file, which is a TOML document setting fields of
+/// `Options` at the top level, e.g.
+///
+/// ```toml
+/// threads = 8
+/// chunk_size = "512K"
+/// strategy = "per-thread-table"
+/// strict = true
+/// ```
+///
+/// Sizes may be integers, or strings with a K, M or G suffix. Errors name the
+/// line they were found on.
+pub fn parse_config(text: &str) -> BarseResult<OptionOverrides> {
+  let table = DeTable::parse(text).map_err(|err| match err.span() {
+    Some(span) => BarseError::new(format!(
+      "line {}: {}",
+      line_number(text, span.start),
+      err.message()
+    )),
+    None => BarseError::new(err.message().to_owned()),
+  })?;
+
+  // Set the options in the order they appear, so the first error in the file
+  // is reported.
+  let mut entries = table.get_ref().iter().collect::<Vec<_>>();
+  entries.sort_by_key(|(key, _)| key.span().start);
+
+  let mut overrides = OptionOverrides::default();
+  for (key, value) in entries {
+    overrides
+      .set(key.get_ref(), value.get_ref(), &text[value.span()])
+      .map_err(|message| {
+        BarseError::new(format!(
+          "line {}: {message}",
+          line_number(text, key.span().start)
+        ))
+      })?;
+  }
+  Ok(overrides)
+}
+
+/// Reads and parses the config file at `path`.
+pub fn read_config(path: &str) -> BarseResult<OptionOverrides> {
+  fs::read_to_string(path)
+    .map_err(BarseError::from)
+    .and_then(|text| parse_config(&text))
+    .map_err(|err| err.in_file(path))
+}
+
+/// Resolves the build options from `layers`, where options set by sources
+/// of higher precedence win, and unset options take their default. If the
+/// resulting options are invalid, the error names the source of every option
+/// that was set.
+pub fn resolve(layers: &[(Source, &OptionOverrides)]) -> BarseResult<Options> {
+  let mut layers = layers.to_vec();
+  layers.sort_by_key(|&(source, _)| source);
+  let mut options = Options::default();
+  for (_, overrides) in &layers {
+    overrides.apply_to(&mut options);
+  }
+
+  options.validate().map_err(|err| {
+    let mut sources = Vec::<(&str, Source)>::new();
+    for &(source, overrides) in layers.iter().rev() {
+      for key in overrides.set_keys() {
+        if sources.iter().all(|&(set_key, _)| set_key != key) {
+          sources.push((key, source));
+        }
+      }
+    }
+    let message = match err {
+      BarseError::Other(message) => message,
+      err => err.to_string(),
+    };
+    if sources.is_empty() {
+      return BarseError::new(message);
+    }
+    let sources = sources
+      .iter()
+      .map(|(key, source)| format!("{key} from {source}"))
+      .collect::<Vec<_>>()
+      .join(", ");
+    BarseError::new(format!("{message} (set {sources})"))
+  })?;
+  Ok(options)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::Duration;
+
+  use googletest::prelude::*;
+
+  use crate::{
+    build_report::Strategy,
+    config::{parse_config, parse_duration, parse_size, resolve, OptionOverrides, Source},
+    options::Options,
+    scanner::ExtraFields,
+    temperature_reading::{DecimalSeparator, TemperatureReading},
+  };
+
+  fn threads(threads: usize) -> OptionOverrides {
+    OptionOverrides {
+      threads: Some(threads),
+      ..OptionOverrides::default()
+    }
+  }
+
+  fn resolved_threads(layers: &[(Source, &OptionOverrides)]) -> Option<usize> {
+    resolve(layers).unwrap().threads
+  }
+
+  #[gtest]
+  fn test_precedence() {
+    let (cli, env, config) = (threads(1), threads(2), threads(3));
+    let unset = OptionOverrides::default();
+    expect_eq!(resolved_threads(&[]), Options::default().threads);
+    expect_eq!(resolved_threads(&[(Source::ConfigFile, &config)]), Some(3));
+    expect_eq!(
+      resolved_threads(&[(Source::Environment, &env), (Source::ConfigFile, &config)]),
+      Some(2)
+    );
+    expect_eq!(
+      resolved_threads(&[(Source::CommandLine, &cli), (Source::ConfigFile, &config)]),
+      Some(1)
+    );
+    expect_eq!(
+      resolved_threads(&[(Source::ConfigFile, &config), (Source::CommandLine, &cli)]),
+      Some(1)
+    );
+    expect_eq!(
+      resolved_threads(&[
+        (Source::CommandLine, &cli),
+        (Source::Environment, &env),
+        (Source::ConfigFile, &config)
+      ]),
+      Some(1)
+    );
+    expect_eq!(
+      resolved_threads(&[
+        (Source::CommandLine, &unset),
+        (Source::Environment, &env),
+        (Source::ConfigFile, &config)
+      ]),
+      Some(2)
+    );
+    // 0 selects the available parallelism, overriding lower sources.
+    expect_eq!(
+      resolved_threads(&[
+        (Source::CommandLine, &threads(0)),
+        (Source::ConfigFile, &config)
+      ]),
+      None
+    );
+  }
+
+  #[gtest]
+  fn test_options_merge_across_sources() {
+    let config = parse_config("chunk_size = \"512K\"\nstrict = true\n").unwrap();
+    let env = OptionOverrides {
+      strict: Some(false),
+      ..OptionOverrides::default()
+    };
+    let options = resolve(&[
+      (Source::CommandLine, &threads(4)),
+      (Source::Environment, &env),
+      (Source::ConfigFile, &config),
+    ])
+    .unwrap();
+    expect_eq!(options.threads, Some(4));
+    expect_eq!(options.chunk_size, 512 << 10);
+    expect_false!(options.strict);
+  }
+
+  #[gtest]
+  fn test_parse_config() {
+    let config = parse_config(
+      "# Per-host defaults.\n\
+       threads = 8\n\
+       \n\
+       chunk_size = 1_048_576 # 1M\n\
+       table_capacity = 4096\n\
+       strategy = \"per-thread-table\"\n\
+       decimal_separator = \",\"\n\
+       skip_header = true\n\
+       extra_fields = \"skip\"\n\
+       missing_reading = -99.9\n",
+    )
+    .unwrap();
+    expect_eq!(
+      config,
+      OptionOverrides {
+        threads: Some(8),
+        chunk_size: Some(1 << 20),
+        table_capacity: Some(4096),
+        strategy: Some(Strategy::PerThreadTables),
+        decimal_separator: Some(DecimalSeparator::Comma),
+        skip_header: Some(true),
+        extra_fields: Some(ExtraFields::Skip),
+        missing_reading: Some(TemperatureReading::new(-999)),
+        ..OptionOverrides::default()
+      }
+    );
+    expect_eq!(parse_config("").unwrap(), OptionOverrides::default());
+    expect_eq!(
+      parse_config(
+        "strategy = 'single' # Literal string.\n\
+         decimal_separator = \"\\u002C\"\n\
+         threads = 0x10\n",
+      )
+      .unwrap(),
+      OptionOverrides {
+        strategy: Some(Strategy::SingleThreaded),
+        decimal_separator: Some(DecimalSeparator::Comma),
+        threads: Some(16),
+        ..OptionOverrides::default()
+      }
+    );
+  }
+
+  #[gtest]
+  fn test_malformed_config() {
+    for (config, message) in [
+      ("threads 8", "line 1: key with no value"),
+      (
+        "\nthreads = \"8\"",
+        "line 2: expected a non-negative integer",
+      ),
+      ("threads = -1", "line 1: expected a non-negative integer"),
+      (
+        "threads = [8]",
+        "line 1: expected a non-negative integer, got [8]",
+      ),
+      ("strict = 1", "line 1: expected true or false, got 1"),
+      ("strategy = 1", "line 1: expected a string, got 1"),
+      ("strategy = single", "line 1: string values must be quoted"),
+      (
+        "strategy = \"fastest\"",
+        "line 1: Unknown strategy \"fastest\"",
+      ),
+      ("chunk_size = \"2X\"", "line 1: invalid size \"2X\""),
+      (
+        "extra_fields = \"ignore\"",
+        "line 1: unknown extra fields mode \"ignore\"",
+      ),
+      (
+        "missing_reading = -999",
+        "line 1: expected a temperature reading like -99.9",
+      ),
+      ("theads = 8", "line 1: unknown option \"theads\""),
+      ("threads = 8\nthreads = 4", "line 2: duplicate key"),
+      ("[build]\nthreads = 8", "line 1: tables are not supported"),
+      (
+        "strict = true\nbuild.threads = 8",
+        "line 2: tables are not supported",
+      ),
+    ] {
+      expect_that!(
+        parse_config(config),
+        err(displays_as(starts_with(format!("error: {message}")))),
+        "{config:?}"
+      );
+    }
+  }
+
+  #[gtest]
+  fn test_invalid_options_name_their_sources() {
+    let config = OptionOverrides {
+      chunk_size: Some(100),
+      ..OptionOverrides::default()
+    };
+    expect_that!(
+      resolve(&[
+        (Source::CommandLine, &threads(2)),
+        (Source::ConfigFile, &config)
+      ]),
+      err(displays_as(eq(
+        "error: Chunk size must be a nonzero multiple of 64, got 100 \
+         (set threads from the command line, chunk_size from the config file)"
+      )))
+    );
+  }
+
+  #[gtest]
+  fn test_parse_size() {
+    expect_eq!(parse_size("512"), Ok(512));
+    expect_eq!(parse_size("512K"), Ok(512 << 10));
+    expect_eq!(parse_size("2m"), Ok(2 << 20));
+    expect_eq!(parse_size("1G"), Ok(1 << 30));
+    expect_that!(parse_size("2X"), err(anything()));
+  }
+
+  #[gtest]
+  fn test_parse_duration() {
+    expect_eq!(parse_duration("5"), Ok(Duration::from_secs(5)));
+    expect_eq!(parse_duration("5s"), Ok(Duration::from_secs(5)));
+    expect_eq!(parse_duration("250ms"), Ok(Duration::from_millis(250)));
+    expect_eq!(parse_duration("2m"), Ok(Duration::from_secs(120)));
+    expect_eq!(parse_duration("1h"), Ok(Duration::from_secs(3600)));
+    expect_that!(parse_duration("1.5s"), err(anything()));
+    expect_that!(parse_duration("s"), err(anything()));
+  }
+}
@@ -1,22 +1,247 @@
-use std::{error::Error, fmt::Display};
+use std::{any::Any, error::Error, fmt::Display, io};
+
+/// The ways in which a record of the input can be malformed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RecordErrorKind {
+  /// The record has no ';' separating the station name from the reading.
+  MissingSemicolon,
+  /// The station name is longer than `MAX_STATION_NAME_LEN` bytes.
+  NameTooLong,
+  /// The station name is not valid UTF-8.
+  InvalidUtf8,
+  /// The temperature reading is not of the form `-?\d?\d\.\d`.
+  MalformedTemperature(String),
+  /// The last record of the input is not followed by a newline.
+  MissingNewline,
+  /// The fields following the temperature reading are longer than
+  /// `MAX_EXTRA_FIELDS_LEN` bytes.
+  ExtraFieldsTooLong,
+}
+
+impl Display for RecordErrorKind {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Self::MissingSemicolon => write!(f, "missing ';'"),
+      Self::NameTooLong => write!(f, "station name too long"),
+      Self::InvalidUtf8 => write!(f, "station name is not valid UTF-8"),
+      Self::MalformedTemperature(temperature) => {
+        write!(f, "malformed temperature \"{temperature}\"")
+      }
+      Self::MissingNewline => write!(f, "missing trailing newline"),
+      Self::ExtraFieldsTooLong => write!(f, "extra fields too long"),
+    }
+  }
+}
 
 #[derive(Debug)]
-pub struct BarseError {
-  message: String,
+pub enum BarseError {
+  Io(io::Error),
+  /// The record starting `offset` bytes into the input, on line `line` if
+  /// known, is malformed.
+  MalformedRecord {
+    offset: u64,
+    line: Option<u64>,
+    kind: RecordErrorKind,
+  },
+  /// Worker thread `worker` panicked with `message`.
+  ThreadPanicked {
+    worker: usize,
+    message: String,
+  },
+  Other(String),
+  /// `error` occurred while processing the input file at `path`.
+  InFile {
+    path: String,
+    error: Box<BarseError>,
+  },
 }
 
 impl BarseError {
   pub fn new(message: String) -> Self {
-    BarseError { message }
+    BarseError::Other(message)
+  }
+
+  /// Builds a `ThreadPanicked` error from the payload returned when joining a
+  /// panicked thread.
+  pub fn thread_panicked(worker: usize, payload: Box<dyn Any + Send>) -> Self {
+    BarseError::ThreadPanicked {
+      worker,
+      message: panic_message(payload),
+    }
+  }
+
+  /// Attributes this error to the input file at `path`.
+  pub fn in_file(self, path: &str) -> Self {
+    match self {
+      Self::InFile { .. } => self,
+      error => BarseError::InFile {
+        path: path.to_owned(),
+        error: Box::new(error),
+      },
+    }
+  }
+
+  /// Fills in the line number of a `MalformedRecord` from `input`, the whole
+  /// input its offset is relative to.
+  pub fn with_line_number(self, input: &[u8]) -> Self {
+    match self {
+      Self::MalformedRecord {
+        offset,
+        line: None,
+        kind,
+      } => {
+        let end = usize::try_from(offset).map_or(input.len(), |offset| offset.min(input.len()));
+        let preceding = &input[..end];
+        let line = preceding.iter().filter(|&&b| b == b'\n').count() as u64 + 1;
+        Self::MalformedRecord {
+          offset,
+          line: Some(line),
+          kind,
+        }
+      }
+      error => error,
+    }
+  }
+
+  /// Returns the underlying error, without the file it occurred in.
+  pub fn without_context(&self) -> &BarseError {
+    match self {
+      Self::InFile { error, .. } => error,
+      error => error,
+    }
+  }
+
+  /// Returns the offset of the malformed record this error is about, if any.
+  pub fn offset(&self) -> Option<u64> {
+    match self.without_context() {
+      Self::MalformedRecord { offset, .. } => Some(*offset),
+      _ => None,
+    }
+  }
+
+  /// Writes the error without the "error: " prefix.
+  fn describe(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Self::Io(err) => write!(f, "{err}"),
+      Self::MalformedRecord { offset, line, kind } => {
+        write!(f, "byte {offset}")?;
+        if let Some(line) = line {
+          write!(f, ", line {line}")?;
+        }
+        write!(f, ": {kind}")
+      }
+      Self::ThreadPanicked { worker, message } => {
+        write!(f, "worker thread {worker} panicked: {message}")
+      }
+      Self::Other(message) => write!(f, "{message}"),
+      Self::InFile { path, error } => {
+        let separator = match **error {
+          Self::MalformedRecord { .. } => " @ ",
+          _ => ": ",
+        };
+        write!(f, "{path}{separator}")?;
+        error.describe(f)
+      }
+    }
   }
 }
 
-impl Error for BarseError {}
+/// Returns the message a panic was raised with, given its payload.
+pub(crate) fn panic_message(payload: Box<dyn Any + Send>) -> String {
+  match payload.downcast::<String>() {
+    Ok(message) => *message,
+    Err(payload) => payload.downcast_ref::<&str>().map_or_else(
+      || "unknown panic".to_owned(),
+      |message| (*message).to_owned(),
+    ),
+  }
+}
+
+impl Error for BarseError {
+  fn source(&self) -> Option<&(dyn Error + 'static)> {
+    match self {
+      Self::Io(err) => Some(err),
+      Self::InFile { error, .. } => error.source(),
+      _ => None,
+    }
+  }
+}
 
 impl Display for BarseError {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-    write!(f, "error: {}", self.message)
+    write!(f, "error: ")?;
+    self.describe(f)
+  }
+}
+
+impl From<io::Error> for BarseError {
+  fn from(err: io::Error) -> Self {
+    BarseError::Io(err)
   }
 }
 
-pub type BarseResult<T = ()> = Result<T, Box<dyn Error + Send + Sync + 'static>>;
+pub type BarseResult<T = ()> = Result<T, BarseError>;
+
+#[cfg(test)]
+mod tests {
+  use std::{error::Error, io};
+
+  use googletest::prelude::*;
+
+  use crate::error::{BarseError, RecordErrorKind};
+
+  #[gtest]
+  fn test_io_error_is_source() {
+    let err = BarseError::from(io::Error::from(io::ErrorKind::NotFound));
+    expect_true!(matches!(err, BarseError::Io(_)));
+    expect_that!(
+      err
+        .source()
+        .and_then(|source| source.downcast_ref::<io::Error>())
+        .map(io::Error::kind),
+      some(eq(io::ErrorKind::NotFound))
+    );
+    expect_true!(BarseError::new("oops".to_owned()).source().is_none());
+  }
+
+  #[gtest]
+  fn test_display() {
+    expect_eq!(
+      BarseError::MalformedRecord {
+        offset: 1048641,
+        line: None,
+        kind: RecordErrorKind::MalformedTemperature("12..".to_owned()),
+      }
+      .to_string(),
+      "error: byte 1048641: malformed temperature \"12..\""
+    );
+    expect_eq!(
+      BarseError::MalformedRecord {
+        offset: 9,
+        line: None,
+        kind: RecordErrorKind::MalformedTemperature("12..".to_owned()),
+      }
+      .with_line_number(b"Oslo;1.0\nAbha;12..\n")
+      .in_file("measurements-03.txt")
+      .to_string(),
+      "error: measurements-03.txt @ byte 9, line 2: malformed temperature \"12..\""
+    );
+    expect_eq!(
+      BarseError::from(io::Error::from(io::ErrorKind::NotFound))
+        .in_file("measurements-03.txt")
+        .to_string(),
+      format!(
+        "error: measurements-03.txt: {}",
+        io::Error::from(io::ErrorKind::NotFound)
+      )
+    );
+    expect_eq!(
+      BarseError::thread_panicked(3, Box::new("boom")).to_string(),
+      "error: worker thread 3 panicked: boom"
+    );
+    expect_eq!(
+      BarseError::new("oops".to_owned()).to_string(),
+      "error: oops"
+    );
+  }
+}
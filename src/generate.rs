@@ -0,0 +1,369 @@
+use std::{
+  collections::HashSet,
+  f64::consts::TAU,
+  fs::{self, File},
+  io::{self, BufRead, BufReader, BufWriter, Write},
+  ops::Range,
+};
+
+use crate::{
+  error::{BarseError, BarseResult},
+  temperature_reading::TemperatureReading,
+};
+
+/// The list of weather stations shipped with the crate, in the format read by
+/// `read_stations`.
+pub const WEATHER_STATIONS_PATH: &str = "data/weather_stations.csv";
+/// A smaller list of stations embedded in the crate, which `default_stations`
+/// falls back to when `WEATHER_STATIONS_PATH` can't be found, e.g. when run
+/// from outside the crate's root.
+pub const FALLBACK_STATIONS: &str = include_str!("../data/fallback_stations.csv");
+
+/// The standard deviation of the readings of each station, in degrees, as in
+/// the official generator.
+const STANDARD_DEVIATION: f64 = 10.;
+/// Rows are generated in blocks of this many, each from its own random number
+/// generator, so that blocks can be generated in parallel without changing
+/// the output.
+const BLOCK_ROWS: u64 = 1 << 16;
+const WRITE_BUFFER_SIZE: usize = 1 << 20;
+
+/// A weather station, and the mean of the readings generated for it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Station {
+  pub name: String,
+  pub mean: f64,
+}
+
+/// Reads the stations listed in the file at `path`, with one `name;mean` per
+/// line. Empty lines and lines starting with '#' are skipped, and only the
+/// first of stations with the same name is kept.
+pub fn read_stations(path: &str) -> BarseResult<Vec<Station>> {
+  parse_stations(BufReader::new(File::open(path)?))
+}
+
+/// Reads the stations at `WEATHER_STATIONS_PATH`, or those in
+/// `FALLBACK_STATIONS` if that file doesn't exist.
+pub fn default_stations() -> BarseResult<Vec<Station>> {
+  match File::open(WEATHER_STATIONS_PATH) {
+    Ok(file) => parse_stations(BufReader::new(file)),
+    Err(err) if err.kind() == io::ErrorKind::NotFound => {
+      parse_stations(FALLBACK_STATIONS.as_bytes())
+    }
+    Err(err) => Err(err.into()),
+  }
+}
+
+/// Like `read_stations`, but reads the list from `reader`.
+pub fn parse_stations(reader: impl BufRead) -> BarseResult<Vec<Station>> {
+  let mut names = HashSet::new();
+  let mut stations = Vec::new();
+  for (line_number, line) in reader.lines().enumerate() {
+    let line = line?;
+    if line.is_empty() || line.starts_with('#') {
+      continue;
+    }
+    let station = line
+      .split_once(';')
+      .and_then(|(name, mean)| {
+        Some(Station {
+          name: name.to_owned(),
+          mean: mean.trim().parse().ok()?,
+        })
+      })
+      .ok_or_else(|| {
+        BarseError::new(format!(
+          "line {}: expected \"name;mean\", got \"{line}\"",
+          line_number + 1
+        ))
+      })?;
+    if names.insert(station.name.clone()) {
+      stations.push(station);
+    }
+  }
+  Ok(stations)
+}
+
+/// A small, fast random number generator (SplitMix64). This is used instead
+/// of `rand` so that the output for a given seed never changes.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+  fn next_u64(&mut self) -> u64 {
+    self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = self.0;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+  }
+
+  /// Returns a value uniformly distributed in `[0, 1)`.
+  fn next_f64(&mut self) -> f64 {
+    (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+  }
+
+  /// Returns a value uniformly distributed in `[0, n)`.
+  fn below(&mut self, n: usize) -> usize {
+    ((self.next_u64() as u128 * n as u128) >> 64) as usize
+  }
+
+  /// Returns a normally distributed value, using the Box-Muller transform.
+  fn next_normal(&mut self, mean: f64, standard_deviation: f64) -> f64 {
+    let radius = (-2. * (1. - self.next_f64()).ln()).sqrt();
+    mean + standard_deviation * radius * (TAU * self.next_f64()).cos()
+  }
+}
+
+/// Generates measurements files like the official generator of the challenge,
+/// drawing the readings of each station from a normal distribution around its
+/// mean. The output is fully determined by the stations, seed, and row count.
+pub struct Generator {
+  stations: Vec<Station>,
+  seed: u64,
+}
+
+impl Generator {
+  /// Constructs a generator over `unique_stations` stations picked at random
+  /// from `stations`. Fails if there are fewer than `unique_stations` to pick
+  /// from.
+  pub fn new(stations: &[Station], unique_stations: usize, seed: u64) -> BarseResult<Self> {
+    if unique_stations > stations.len() {
+      return Err(BarseError::new(format!(
+        "can't pick {unique_stations} unique stations from a list of {}",
+        stations.len()
+      )));
+    }
+    let mut rng = SplitMix64(seed);
+    let mut stations = stations.to_vec();
+    for i in 0..unique_stations {
+      let j = i + rng.below(stations.len() - i);
+      stations.swap(i, j);
+    }
+    stations.truncate(unique_stations);
+    Ok(Self { stations, seed })
+  }
+
+  /// The stations readings are generated for.
+  pub fn stations(&self) -> &[Station] {
+    &self.stations
+  }
+
+  /// Writes `rows` measurements to `out`. If there are at least as many rows
+  /// as stations, every station appears at least once.
+  pub fn write<W: Write>(&self, rows: u64, out: W) -> BarseResult {
+    self.check_stations(rows)?;
+    let mut out = BufWriter::with_capacity(WRITE_BUFFER_SIZE, out);
+    self.write_blocks(0..rows.div_ceil(BLOCK_ROWS), rows, &mut out)?;
+    out.flush()?;
+    Ok(())
+  }
+
+  /// Writes `rows` measurements to the file at `path`, splitting the work
+  /// across `threads` threads. Each thread writes its share of the rows to a
+  /// temporary file, and the temporary files are then concatenated, so the
+  /// output doesn't depend on the number of threads.
+  pub fn write_file(&self, rows: u64, path: &str, threads: usize) -> BarseResult {
+    self.check_stations(rows)?;
+    let blocks = rows.div_ceil(BLOCK_ROWS);
+    let threads = (threads as u64).clamp(1, blocks.max(1));
+    if threads == 1 {
+      return self.write(rows, File::create(path)?);
+    }
+
+    let parts = (0..threads)
+      .map(|part| {
+        let blocks = (blocks * part / threads)..(blocks * (part + 1) / threads);
+        (format!("{path}.{part}.tmp"), blocks)
+      })
+      .collect::<Vec<_>>();
+    let result = std::thread::scope(|scope| {
+      let threads = parts
+        .iter()
+        .map(|(part_path, blocks)| {
+          scope.spawn(move || -> BarseResult {
+            let mut out = BufWriter::with_capacity(WRITE_BUFFER_SIZE, File::create(part_path)?);
+            self.write_blocks(blocks.clone(), rows, &mut out)?;
+            out.flush()?;
+            Ok(())
+          })
+        })
+        .collect::<Vec<_>>();
+      threads
+        .into_iter()
+        .enumerate()
+        .try_for_each(|(worker, thread)| {
+          thread
+            .join()
+            .map_err(|payload| BarseError::thread_panicked(worker, payload))?
+        })
+    })
+    .and_then(|()| {
+      let mut out = File::create(path)?;
+      for (part_path, _) in &parts {
+        io::copy(&mut File::open(part_path)?, &mut out)?;
+      }
+      Ok(())
+    });
+    for (part_path, _) in &parts {
+      let _ = fs::remove_file(part_path);
+    }
+    result
+  }
+
+  fn check_stations(&self, rows: u64) -> BarseResult {
+    if rows > 0 && self.stations.is_empty() {
+      return Err(BarseError::new(
+        "no stations to generate readings for".to_owned(),
+      ));
+    }
+    Ok(())
+  }
+
+  /// Writes the rows in `blocks` of an output of `rows` rows.
+  fn write_blocks<W: Write>(&self, blocks: Range<u64>, rows: u64, out: &mut W) -> io::Result<()> {
+    for block in blocks {
+      let mut rng = SplitMix64(self.seed ^ block.wrapping_mul(0xd1b5_4a32_d192_ed03));
+      let block_rows = (block * BLOCK_ROWS)..((block + 1) * BLOCK_ROWS).min(rows);
+      for row in block_rows {
+        // The first rows cover every station once, so that small outputs
+        // still contain every station.
+        let station = match usize::try_from(row) {
+          Ok(row) if row < self.stations.len() => &self.stations[row],
+          _ => &self.stations[rng.below(self.stations.len())],
+        };
+        let reading = rng.next_normal(station.mean, STANDARD_DEVIATION);
+        let reading = (reading * 10.).round().clamp(-999., 999.) as i16;
+        writeln!(out, "{};{}", station.name, TemperatureReading::new(reading))?;
+      }
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::fs;
+
+  use googletest::prelude::*;
+
+  use crate::{
+    generate::{
+      default_stations, parse_stations, read_stations, Generator, Station, FALLBACK_STATIONS,
+    },
+    test_util::simple_scanner_iter,
+  };
+
+  fn generate(stations: &[Station], unique_stations: usize, seed: u64, rows: u64) -> Vec<u8> {
+    let mut out = Vec::new();
+    Generator::new(stations, unique_stations, seed)
+      .unwrap()
+      .write(rows, &mut out)
+      .unwrap();
+    out
+  }
+
+  #[gtest]
+  fn test_read_stations() {
+    let path = std::env::temp_dir().join(format!("barse_{}_stations.csv", std::process::id()));
+    fs::write(
+      &path,
+      "# A comment\nTokyo;35.6897\n\nOslo;59.9\nTokyo;1.0\n",
+    )
+    .unwrap();
+    let stations = read_stations(path.to_str().unwrap());
+    fs::remove_file(&path).unwrap();
+    expect_that!(
+      stations,
+      ok(elements_are![
+        eq(&Station {
+          name: "Tokyo".to_owned(),
+          mean: 35.6897,
+        }),
+        eq(&Station {
+          name: "Oslo".to_owned(),
+          mean: 59.9,
+        }),
+      ])
+    );
+    expect_that!(
+      parse_stations("Tokyo 35.6897\n".as_bytes()),
+      err(displays_as(contains_substring("line 1")))
+    );
+  }
+
+  #[gtest]
+  fn test_fallback_stations() {
+    let stations = parse_stations(FALLBACK_STATIONS.as_bytes()).unwrap();
+    expect_eq!(stations.len(), 1_000);
+    expect_that!(
+      stations.first(),
+      some(eq(&Station {
+        name: "Tokyo".to_owned(),
+        mean: 35.6897,
+      }))
+    );
+    // The fallback is the start of the full list, so small generators pick
+    // from the same names either way.
+    expect_eq!(default_stations().unwrap()[..1_000], stations[..]);
+  }
+
+  #[gtest]
+  fn test_deterministic_for_seed() {
+    let stations = default_stations().unwrap();
+    let output = generate(&stations, 100, 42, 200_000);
+    expect_eq!(output, generate(&stations, 100, 42, 200_000));
+    expect_ne!(output, generate(&stations, 100, 43, 200_000));
+    // A shorter output is a prefix of a longer one with the same seed.
+    expect_true!(output.starts_with(&generate(&stations, 100, 42, 70_000)));
+
+    let path = std::env::temp_dir().join(format!("barse_{}_generate.txt", std::process::id()));
+    let path = path.to_str().unwrap();
+    for threads in [1, 3, 8] {
+      Generator::new(&stations, 100, 42)
+        .unwrap()
+        .write_file(200_000, path, threads)
+        .unwrap();
+      expect_eq!(fs::read(path).unwrap(), output, "{threads} threads");
+    }
+    fs::remove_file(path).unwrap();
+  }
+
+  #[gtest]
+  fn test_output_covers_stations() {
+    let stations = default_stations().unwrap();
+    let generator = Generator::new(&stations, 500, 7).unwrap();
+    expect_eq!(generator.stations().len(), 500);
+    let output = generate(&stations, 500, 7, 10_000);
+
+    let records = simple_scanner_iter(&output).collect::<Vec<_>>();
+    expect_eq!(records.len(), 10_000);
+    let mut names = records
+      .iter()
+      .map(|(name, _)| name.to_owned())
+      .collect::<Vec<_>>();
+    names.sort_unstable();
+    names.dedup();
+    let mut expected_names = generator
+      .stations()
+      .iter()
+      .map(|station| station.name.as_str())
+      .collect::<Vec<_>>();
+    expected_names.sort_unstable();
+    expect_eq!(names, expected_names);
+  }
+
+  #[gtest]
+  fn test_too_many_unique_stations() {
+    let stations = parse_stations(FALLBACK_STATIONS.as_bytes()).unwrap();
+    expect_that!(
+      Generator::new(&stations, 1_001, 0).map(|_| ()),
+      err(displays_as(eq(
+        "error: can't pick 1001 unique stations from a list of 1000"
+      )))
+    );
+    expect_that!(
+      Generator::new(&stations, 1_000, 0).map(|generator| generator.stations().len()),
+      ok(eq(&1_000))
+    );
+  }
+}
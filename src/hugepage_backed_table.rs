@@ -1,65 +1,287 @@
-use std::marker::PhantomData;
+#[cfg(feature = "multithreaded")]
+use std::ptr;
+#[cfg(feature = "multithreaded")]
+use std::slice;
+use std::{marker::PhantomData, ptr::NonNull};
 
-use memmap2::{MmapMut, MmapOptions};
-
-use crate::error::BarseResult;
+use crate::{
+  error::{BarseError, BarseResult},
+  table_backend::{DefaultBackend, TableBackend},
+};
 
 pub const HUGEPAGE_SIZE: usize = 2 * 1024 * 1024;
 
 /// A trait for objects which can be initialized from zero-initialized memory.
 /// Implementers may assume `self` references zero-initialized memory.
-pub trait InPlaceInitializable {
+///
+/// # Safety
+///
+/// `size_of::<Self>()` zeroed bytes must be a valid `Self`, since tables hand
+/// out references to their zeroed elements, and those created with `new_lazy`
+/// do so before initializing them. Types holding references, `Box`es,
+/// `String`s, `NonZero*` integers or other types without a valid all-zero
+/// representation must not implement this trait.
+pub unsafe trait InPlaceInitializable {
   /// Initialize `self` from zero-initialized bytes spanning
   /// `std::mem::size_of::<Self>()` bytes.
   fn initialize(&mut self);
 }
 
-/// An array of `T`s with constant `SIZE` elements allocated from `mmap`,
-/// backed by hugepages on systems that support it.
-pub struct HugepageBackedTable<T, const SIZE: usize> {
-  /// The mmapped region of `SIZE` elements of type `T`.
-  elements: MmapMut,
+/// An array of `T`s with `size` elements allocated by a `TableBackend`. The
+/// default backend allocates from `mmap`, advising the kernel to back it with
+/// hugepages on systems that support them. Without the `mmap` feature, the elements are allocated from the
+/// heap instead.
+pub struct HugepageBackedTable<T, B: TableBackend = DefaultBackend> {
+  /// The region of `size` elements of type `T`, spanning `mapped_len` bytes.
+  elements: NonNull<T>,
+  size: usize,
+  mapped_len: usize,
+  backend: B,
   _phantom: PhantomData<T>,
 }
 
-impl<T: InPlaceInitializable, const SIZE: usize> HugepageBackedTable<T, SIZE> {
-  pub fn new() -> BarseResult<Self> {
-    let size = (SIZE * std::mem::size_of::<T>()).next_multiple_of(HUGEPAGE_SIZE);
-    let elements = MmapOptions::new().len(size).map_anon()?;
-    #[cfg(target_os = "linux")]
-    elements.advise(memmap2::Advice::HugePage)?;
+impl<T: InPlaceInitializable> HugepageBackedTable<T> {
+  pub fn new(size: usize) -> BarseResult<Self> {
+    Self::new_in(size, DefaultBackend::default())
+  }
 
-    let mut table = Self {
-      elements,
-      _phantom: PhantomData,
-    };
-    for i in 0..SIZE {
+  /// Like `new`, but leaves every element zeroed instead of initializing it,
+  /// so that each page of the table is only touched once one of its elements
+  /// is used. The caller must `initialize` each element before relying on
+  /// more than its zeroed bytes.
+  pub fn new_lazy(size: usize) -> BarseResult<Self> {
+    Self::new_lazy_in(size, DefaultBackend::default())
+  }
+}
+
+impl<T: InPlaceInitializable, B: TableBackend> HugepageBackedTable<T, B> {
+  /// Like `new`, but allocates the table from `backend`.
+  pub fn new_in(size: usize, backend: B) -> BarseResult<Self> {
+    let mut table = Self::new_lazy_in(size, backend)?;
+    for i in 0..size {
       table.entry_at_mut(i).initialize();
     }
     Ok(table)
   }
+
+  /// Like `new_lazy`, but allocates the table from `backend`.
+  pub fn new_lazy_in(size: usize, mut backend: B) -> BarseResult<Self> {
+    let mapped_len = HugepageBackedTable::<T>::checked_mapped_len_for_size(size)?;
+    let elements = backend
+      .allocate(mapped_len, std::mem::align_of::<T>())?
+      .cast();
+    Ok(Self {
+      elements,
+      size,
+      mapped_len,
+      backend,
+      _phantom: PhantomData,
+    })
+  }
+
+  /// Returns the element at `index` to the state `new` leaves it in.
+  #[cfg(feature = "multithreaded")]
+  pub fn reset_at(&mut self, index: usize) {
+    let entry = self.entry_at_mut(index);
+    // SAFETY: `InPlaceInitializable` types are valid when zeroed.
+    unsafe { ptr::write_bytes(ptr::from_mut(entry), 0, 1) };
+    entry.initialize();
+  }
 }
 
-impl<T, const SIZE: usize> HugepageBackedTable<T, SIZE> {
+impl<T> HugepageBackedTable<T> {
+  /// Returns the number of bytes mapped for a table of `size` elements, which
+  /// is rounded up to a multiple of `HUGEPAGE_SIZE`. Heap-backed tables
+  /// allocate the same number of bytes.
+  pub const fn mapped_len_for_size(size: usize) -> usize {
+    (size * std::mem::size_of::<T>()).next_multiple_of(HUGEPAGE_SIZE)
+  }
+
+  /// Like `mapped_len_for_size`, but fails if the mapping would span more
+  /// than half the address space, which on 32-bit targets is reachable with
+  /// realistic capacities.
+  fn checked_mapped_len_for_size(size: usize) -> BarseResult<usize> {
+    size
+      .checked_mul(std::mem::size_of::<T>())
+      .and_then(|len| len.checked_next_multiple_of(HUGEPAGE_SIZE))
+      .filter(|&len| len <= usize::MAX / 2)
+      .ok_or_else(|| {
+        BarseError::new(format!(
+          "A table of {size} elements of {} bytes is too large to map",
+          std::mem::size_of::<T>()
+        ))
+      })
+  }
+}
+
+impl<T, B: TableBackend> HugepageBackedTable<T, B> {
+  /// Returns the number of bytes mapped for the table.
+  pub fn mapped_len(&self) -> usize {
+    self.mapped_len
+  }
+
+  /// Returns the number of elements in the table.
+  pub fn size(&self) -> usize {
+    self.size
+  }
+
+  /// Returns true if the kernel accepted the advice to back the table with
+  /// hugepages. The kernel may still back it with regular pages, e.g. if no
+  /// hugepages are free, so this doesn't guarantee that it is.
+  pub fn hugepages(&self) -> bool {
+    self.backend.hugepages()
+  }
+
+  /// Faults in every page of the table, so that accesses to it don't pay for
+  /// page faults, e.g. to keep them out of a timed build.
+  pub fn prefault(&self) {
+    // SAFETY: The table owns the allocation of `mapped_len` bytes at
+    // `elements` from `backend`.
+    unsafe { self.backend.prefault(self.elements.cast(), self.mapped_len) };
+  }
+
   /// Returns a pointer to the start of the table.
   fn elements_ptr(&self) -> *const T {
-    self.elements.as_ptr() as *const T
+    self.elements.as_ptr()
   }
 
   /// Returns a mut pointer to the start of the table.
   fn mut_elements_ptr(&mut self) -> *mut T {
-    self.elements.as_mut_ptr() as *mut T
+    self.elements.as_ptr()
   }
 
   /// Returns a reference to the element at position `index` in the table.
   pub fn entry_at(&self, index: usize) -> &T {
-    debug_assert!(index < SIZE);
+    debug_assert!(index < self.size);
+    // SAFETY: Callers index the table with hashes masked to its size, and
+    // every element was either initialized by `new_in` or left zeroed by
+    // `new_lazy_in`, which `InPlaceInitializable` types accept.
     unsafe { &*self.elements_ptr().add(index) }
   }
 
   /// Returns a mutable reference to the element at position `index` in the table.
   pub fn entry_at_mut(&mut self, index: usize) -> &mut T {
-    debug_assert!(index < SIZE);
+    debug_assert!(index < self.size);
     unsafe { &mut *self.mut_elements_ptr().add(index) }
   }
+
+  /// Returns all `size` elements of the table.
+  #[cfg(feature = "multithreaded")]
+  pub fn elements(&self) -> &[T] {
+    unsafe { slice::from_raw_parts(self.elements_ptr(), self.size) }
+  }
+
+  /// Returns all `size` elements of the table mutably.
+  #[cfg(feature = "multithreaded")]
+  pub fn elements_mut(&mut self) -> &mut [T] {
+    unsafe { slice::from_raw_parts_mut(self.mut_elements_ptr(), self.size) }
+  }
+}
+
+impl<T, B: TableBackend> Drop for HugepageBackedTable<T, B> {
+  fn drop(&mut self) {
+    unsafe {
+      self
+        .backend
+        .deallocate(self.elements.cast(), self.mapped_len)
+    };
+  }
+}
+
+// The table uniquely owns its elements, like a `Box<[T]>`.
+unsafe impl<T: Send, B: TableBackend + Send> Send for HugepageBackedTable<T, B> {}
+unsafe impl<T: Sync, B: TableBackend + Sync> Sync for HugepageBackedTable<T, B> {}
+
+#[cfg(test)]
+mod tests {
+  use std::{cell::Cell, ptr::NonNull, rc::Rc};
+
+  use googletest::prelude::*;
+
+  use crate::{
+    error::BarseResult,
+    hugepage_backed_table::{HugepageBackedTable, InPlaceInitializable, HUGEPAGE_SIZE},
+    table_backend::TableBackend,
+    temperature_reading::TemperatureReading,
+    temperature_summary::TemperatureSummary,
+  };
+
+  /// Allocates from a `Vec`, recording the length of the allocation it frees.
+  struct VecBackend {
+    bytes: Vec<u8>,
+    freed_len: Rc<Cell<Option<usize>>>,
+  }
+
+  // SAFETY: The `len` bytes at `offset` are zeroed, aligned to `align` and
+  // owned by `self.bytes`.
+  unsafe impl TableBackend for VecBackend {
+    fn allocate(&mut self, len: usize, align: usize) -> BarseResult<NonNull<u8>> {
+      self.bytes = vec![0; len + align - 1];
+      let offset = self.bytes.as_ptr().align_offset(align);
+      Ok(NonNull::from(&mut self.bytes[offset..]).cast())
+    }
+
+    unsafe fn deallocate(&mut self, _ptr: NonNull<u8>, len: usize) {
+      self.bytes = Vec::new();
+      self.freed_len.set(Some(len));
+    }
+  }
+
+  #[gtest]
+  fn test_oversized_table_is_an_error() {
+    for size in [usize::MAX / 16, usize::MAX] {
+      expect_that!(
+        HugepageBackedTable::<TemperatureSummary>::new(size).map(|_| ()),
+        err(displays_as(contains_substring("is too large to map")))
+      );
+    }
+  }
+
+  #[gtest]
+  fn test_vec_backend() {
+    let freed_len = Rc::new(Cell::new(None));
+    let backend = VecBackend {
+      bytes: Vec::new(),
+      freed_len: freed_len.clone(),
+    };
+    let mut table = HugepageBackedTable::<TemperatureSummary, _>::new_in(100, backend).unwrap();
+    expect_eq!(table.size(), 100);
+    expect_eq!(table.mapped_len(), HUGEPAGE_SIZE);
+    expect_false!(table.hugepages());
+    for i in 0..100 {
+      expect_eq!(*table.entry_at(i), TemperatureSummary::default());
+    }
+
+    table
+      .entry_at_mut(99)
+      .add_reading(TemperatureReading::new(123));
+    expect_eq!(table.entry_at(99).max(), TemperatureReading::new(123));
+    expect_eq!(*table.entry_at(98), TemperatureSummary::default());
+
+    drop(table);
+    expect_eq!(freed_len.get(), Some(HUGEPAGE_SIZE));
+  }
+
+  #[gtest]
+  fn test_lazy_table_is_zeroed() {
+    let backend = VecBackend {
+      bytes: Vec::new(),
+      freed_len: Rc::new(Cell::new(None)),
+    };
+    let mut table =
+      HugepageBackedTable::<TemperatureSummary, _>::new_lazy_in(100, backend).unwrap();
+    let zeroed = TemperatureSummary {
+      min: TemperatureReading::new(0),
+      max: TemperatureReading::new(0),
+      count: 0,
+      total: 0,
+    };
+    for i in 0..100 {
+      expect_eq!(*table.entry_at(i), zeroed);
+    }
+
+    table.entry_at_mut(7).initialize();
+    expect_eq!(*table.entry_at(7), TemperatureSummary::default());
+    expect_eq!(*table.entry_at(8), zeroed);
+  }
 }
@@ -1,26 +1,68 @@
-use std::{borrow::Borrow, cmp::Ordering, fmt::Display};
-
+use std::{
+  borrow::Borrow,
+  cmp::Ordering,
+  fmt::{Debug, Display},
+  hash::{Hash, Hasher},
+  ops::Deref,
+};
+
+use crate::error::{BarseError, BarseResult};
 use crate::hugepage_backed_table::InPlaceInitializable;
+use crate::scanner::MAX_STATION_NAME_LEN;
 #[cfg(target_feature = "avx2")]
-use crate::str_cmp_x86::inline_str_eq_foreign_str;
-
-const MAX_STRING_LEN: usize = 50;
-const STRING_STORAGE_LEN: usize = 52;
-const INLINE_STRING_SIZE: usize = std::mem::size_of::<InlineString>();
+use crate::str_cmp_x86::{
+  inline_str_cmp_inline_str, inline_str_eq_foreign_str, inline_str_eq_inline_str, InlineStorage,
+};
+use crate::util::copy_no_libc;
+
+/// The capacity of the `InlineString`s used for station names in the weather
+/// station table, which is kept at 52 bytes so that its entries stay the same
+/// size.
+pub const DEFAULT_CAPACITY: usize = 52;
+
+fn too_long_error<const CAP: usize>(contents: &str) -> BarseError {
+  BarseError::new(format!(
+    "String \"{contents}\" is longer than the {CAP}-byte capacity of an InlineString"
+  ))
+}
 
+/// A string of at most `CAP` bytes, stored inline rather than on the heap.
+/// Strings which don't fit are rejected rather than truncated.
+///
+/// ```
+/// use barse::inline_string::InlineString;
+///
+/// let mut name = InlineString::<8>::try_from("Oslo")?;
+/// name.push_str(", NO")?;
+/// assert_eq!(name.as_str(), "Oslo, NO");
+///
+/// // The string is full, so it is left unchanged.
+/// assert!(name.push_str("!").is_err());
+/// assert_eq!(name, "Oslo, NO");
+/// # Ok::<(), barse::error::BarseError>(())
+/// ```
+///
+/// Strings dereference to `str`, and hash like their bytes, so they can be
+/// looked up by `&[u8]` in hash maps keyed by `InlineString`.
 #[repr(C, align(8))]
 #[derive(Clone)]
-pub struct InlineString {
-  bytes: [u8; STRING_STORAGE_LEN],
+pub struct InlineString<const CAP: usize = DEFAULT_CAPACITY> {
+  bytes: [u8; CAP],
   len: u32,
 }
 
-impl InlineString {
-  #[cfg(test)]
-  pub fn new(contents: &str) -> Self {
-    let mut s = Self::default();
-    s.initialize(contents);
-    s
+impl<const CAP: usize> InlineString<CAP> {
+  /// Returns an empty string.
+  pub const fn new() -> Self {
+    Self {
+      bytes: [0; CAP],
+      len: 0,
+    }
+  }
+
+  /// The maximum length of the string, in bytes.
+  pub const fn capacity(&self) -> usize {
+    CAP
   }
 
   pub fn is_empty(&self) -> bool {
@@ -31,96 +73,215 @@ impl InlineString {
     self.len as usize
   }
 
-  pub fn is_default(&self) -> bool {
-    self.is_empty()
+  /// The storage of the string for the AVX2 comparisons, or `None` if the
+  /// capacity isn't suited to them.
+  #[cfg(target_feature = "avx2")]
+  pub(crate) fn storage(&self) -> Option<InlineStorage<'_>> {
+    // `len()` is at most `CAP`.
+    unsafe { InlineStorage::new(&self.bytes, self.len()) }
   }
 
-  /// Performs a memcpy from contents to self.value() without calling
-  /// libc::memcpy.
-  fn memcpy_no_libc(bytes: &mut [u8], contents: &str) {
-    for i in 0..contents.len().min(MAX_STRING_LEN) {
-      unsafe {
-        *bytes.get_unchecked_mut(i) = std::hint::black_box(*contents.as_bytes().get_unchecked(i));
-      }
-    }
+  /// Performs a memcpy from contents to self.as_bytes() without calling
+  /// libc::memcpy. `contents` must be no longer than `CAP` bytes.
+  fn memcpy_no_libc(bytes: &mut [u8; CAP], contents: &str) {
+    debug_assert!(contents.len() <= CAP);
+    copy_no_libc(bytes, contents.as_bytes());
   }
 
-  pub fn value_str(&self) -> &str {
-    unsafe { str::from_utf8_unchecked(self.value()) }
+  pub fn as_str(&self) -> &str {
+    unsafe { str::from_utf8_unchecked(self.as_bytes()) }
   }
 
-  pub fn value(&self) -> &[u8] {
+  pub fn as_bytes(&self) -> &[u8] {
     unsafe { self.bytes.get_unchecked(..self.len()) }
   }
 
-  fn cmp_slice(&self) -> &[u8; INLINE_STRING_SIZE] {
-    unsafe { &*(self as *const Self as *const [u8; INLINE_STRING_SIZE]) }
+  /// Appends `contents` to the end of this string. Fails, leaving the string
+  /// unchanged, if the result would be longer than `CAP` bytes.
+  pub fn push_str(&mut self, contents: &str) -> BarseResult {
+    let len = self.len();
+    if contents.len() > CAP - len {
+      return Err(too_long_error::<CAP>(&format!("{self}{contents}")));
+    }
+    self.bytes[len..len + contents.len()].copy_from_slice(contents.as_bytes());
+    self.len = (len + contents.len()) as u32;
+    Ok(())
   }
 
-  pub fn initialize(&mut self, contents: &str) {
-    debug_assert!(
-      contents.len() <= MAX_STRING_LEN,
-      "{} > {}",
-      contents.len(),
-      MAX_STRING_LEN
-    );
+  /// Sets the contents of this string. Fails, leaving the string unchanged,
+  /// if `contents` is longer than `CAP` bytes, since it wouldn't fit.
+  pub(crate) fn initialize(&mut self, contents: &str) -> BarseResult {
+    if contents.len() > CAP {
+      return Err(too_long_error::<CAP>(contents));
+    }
     Self::memcpy_no_libc(&mut self.bytes, contents);
     self.len = contents.len() as u32;
+    Ok(())
   }
 
   #[cfg(target_feature = "avx2")]
-  pub fn eq_foreign_str(&self, other: &str) -> bool {
-    inline_str_eq_foreign_str(self, other)
+  fn eq_foreign_str(&self, other: &str) -> bool {
+    match self.storage() {
+      Some(storage) => inline_str_eq_foreign_str(storage, other),
+      None => self.as_str() == other,
+    }
   }
 
   #[cfg(not(target_feature = "avx2"))]
-  pub fn eq_foreign_str(&self, other: &str) -> bool {
-    self.value_str() == other
+  fn eq_foreign_str(&self, other: &str) -> bool {
+    self.as_str() == other
   }
 }
 
-impl Default for InlineString {
-  fn default() -> Self {
-    Self {
-      bytes: [0; STRING_STORAGE_LEN],
-      len: 0,
+impl InlineString {
+  /// Initializes the zeroed key of a table bucket to `station`.
+  ///
+  /// Panics without initializing the key if `station` is longer than
+  /// `MAX_STATION_NAME_LEN` bytes. This is deliberate: the scanner never finds
+  /// such names in valid input, and `Options::strict` rejects them with an
+  /// error before they reach a table, so they only come from malformed input
+  /// that wasn't validated, which produces garbage anyway. Returning an error
+  /// instead would put a `Result` on the path of every reading.
+  pub(crate) fn initialize_station(&mut self, station: &str) {
+    if station.len() > MAX_STATION_NAME_LEN {
+      panic!(
+        "{}",
+        BarseError::new(format!(
+          "Station name \"{station}\" is longer than {MAX_STATION_NAME_LEN} bytes"
+        ))
+      );
+    }
+    if let Err(err) = self.initialize(station) {
+      panic!("{err}");
     }
   }
 }
 
-impl PartialEq for InlineString {
+impl<const CAP: usize> Default for InlineString<CAP> {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl<const CAP: usize> Deref for InlineString<CAP> {
+  type Target = str;
+
+  fn deref(&self) -> &str {
+    self.as_str()
+  }
+}
+
+impl<const CAP: usize> AsRef<str> for InlineString<CAP> {
+  fn as_ref(&self) -> &str {
+    self.as_str()
+  }
+}
+
+/// Strings are equal if they have the same length and contents. The bytes
+/// stored past their lengths are ignored.
+impl<const CAP: usize> PartialEq for InlineString<CAP> {
+  #[cfg(target_feature = "avx2")]
   fn eq(&self, other: &Self) -> bool {
-    self.cmp_slice() == other.cmp_slice()
+    match (self.storage(), other.storage()) {
+      (Some(a), Some(b)) => inline_str_eq_inline_str(a, b),
+      _ => self.as_bytes() == other.as_bytes(),
+    }
+  }
+
+  #[cfg(not(target_feature = "avx2"))]
+  fn eq(&self, other: &Self) -> bool {
+    self.as_bytes() == other.as_bytes()
   }
 }
 
-impl Eq for InlineString {}
+impl<const CAP: usize> Eq for InlineString<CAP> {}
 
-impl PartialOrd for InlineString {
+impl<const CAP: usize> PartialOrd for InlineString<CAP> {
   fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
     Some(self.cmp(other))
   }
 }
 
-impl Ord for InlineString {
+/// Strings are ordered like their bytes.
+impl<const CAP: usize> Ord for InlineString<CAP> {
+  #[cfg(target_feature = "avx2")]
   fn cmp(&self, other: &Self) -> Ordering {
-    self.value().cmp(other.value())
+    match (self.storage(), other.storage()) {
+      (Some(a), Some(b)) => inline_str_cmp_inline_str(a, b),
+      _ => self.as_bytes().cmp(other.as_bytes()),
+    }
+  }
+
+  #[cfg(not(target_feature = "avx2"))]
+  fn cmp(&self, other: &Self) -> Ordering {
+    self.as_bytes().cmp(other.as_bytes())
   }
 }
 
-impl Borrow<[u8]> for InlineString {
+impl<const CAP: usize> Borrow<[u8]> for InlineString<CAP> {
   fn borrow(&self) -> &[u8] {
-    self.value()
+    self.as_bytes()
+  }
+}
+
+impl<const CAP: usize> Display for InlineString<CAP> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}", self.as_str())
   }
 }
 
-impl Display for InlineString {
+impl<const CAP: usize> Debug for InlineString<CAP> {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-    write!(f, "{}", self.value_str())
+    write!(f, "{:?}", self.as_str())
+  }
+}
+
+impl<const CAP: usize> PartialEq<str> for InlineString<CAP> {
+  fn eq(&self, other: &str) -> bool {
+    self.eq_foreign_str(other)
+  }
+}
+
+impl<const CAP: usize> PartialEq<&str> for InlineString<CAP> {
+  fn eq(&self, other: &&str) -> bool {
+    self == *other
+  }
+}
+
+impl<const CAP: usize> PartialEq<InlineString<CAP>> for str {
+  fn eq(&self, other: &InlineString<CAP>) -> bool {
+    other == self
+  }
+}
+
+impl<const CAP: usize> PartialEq<InlineString<CAP>> for &str {
+  fn eq(&self, other: &InlineString<CAP>) -> bool {
+    other == *self
+  }
+}
+
+/// Hashes the same as the `[u8]` that `InlineString` borrows as, so strings
+/// can be looked up by their bytes in hash maps.
+impl<const CAP: usize> Hash for InlineString<CAP> {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    self.as_bytes().hash(state);
+  }
+}
+
+/// Copies a string into an `InlineString`, failing if it is longer than `CAP`
+/// bytes rather than truncating it.
+impl<const CAP: usize> TryFrom<&str> for InlineString<CAP> {
+  type Error = BarseError;
+
+  fn try_from(contents: &str) -> BarseResult<Self> {
+    let mut s = Self::new();
+    s.initialize(contents)?;
+    Ok(s)
   }
 }
 
-impl InPlaceInitializable for InlineString {
+// SAFETY: A zeroed `InlineString` is the empty string.
+unsafe impl<const CAP: usize> InPlaceInitializable for InlineString<CAP> {
   fn initialize(&mut self) {
     // No need to do anything, a zero-initialized string is correctly initialized.
     debug_assert!(self.bytes.iter().all(|b| *b == 0));
@@ -128,31 +289,69 @@ impl InPlaceInitializable for InlineString {
   }
 }
 
-unsafe impl Sync for InlineString {}
+unsafe impl<const CAP: usize> Sync for InlineString<CAP> {}
+
+/// Serializes as a plain string.
+#[cfg(feature = "serde")]
+impl<const CAP: usize> serde::Serialize for InlineString<CAP> {
+  fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(self.as_str())
+  }
+}
+
+/// Deserializes from a string, failing if it is longer than `CAP` bytes.
+#[cfg(feature = "serde")]
+impl<'de, const CAP: usize> serde::Deserialize<'de> for InlineString<CAP> {
+  fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    struct InlineStringVisitor<const CAP: usize>;
+
+    impl<const CAP: usize> serde::de::Visitor<'_> for InlineStringVisitor<CAP> {
+      type Value = InlineString<CAP>;
+
+      fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "a string of at most {CAP} bytes")
+      }
+
+      fn visit_str<E: serde::de::Error>(self, contents: &str) -> Result<Self::Value, E> {
+        InlineString::try_from(contents).map_err(E::custom)
+      }
+    }
+
+    deserializer.deserialize_str(InlineStringVisitor)
+  }
+}
 
 #[cfg(test)]
 mod tests {
-  use std::cmp::Ordering;
+  use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    hash::{DefaultHasher, Hash, Hasher},
+  };
 
   use googletest::{expect_that, gtest, prelude::*};
 
-  use crate::str_hash::str_hash;
+  use crate::str_hash::{str_hash, BuildStringHash};
 
   use super::InlineString;
 
+  fn inline(contents: &str) -> InlineString {
+    <InlineString>::try_from(contents).unwrap()
+  }
+
   #[gtest]
   fn test_construction() {
     let str1 = "testabcd";
-    let i = InlineString::new(str::from_utf8(&str1.as_bytes()[..4]).unwrap());
-    expect_eq!(i.value_str(), "test");
+    let i = inline(str::from_utf8(&str1.as_bytes()[..4]).unwrap());
+    expect_eq!(i.as_str(), "test");
   }
 
   #[gtest]
   fn test_cmp() {
     let str1 = "testabcd";
     let str2 = "test1234";
-    let i1 = InlineString::new(str::from_utf8(&str1.as_bytes()[..4]).unwrap());
-    let i2 = InlineString::new(str::from_utf8(&str2.as_bytes()[..4]).unwrap());
+    let i1 = inline(str::from_utf8(&str1.as_bytes()[..4]).unwrap());
+    let i2 = inline(str::from_utf8(&str2.as_bytes()[..4]).unwrap());
     expect_true!(i1 == i2);
     expect_that!(i1.cmp(&i2), pat![Ordering::Equal]);
     expect_eq!(str_hash(&i1.bytes), str_hash(&i2.bytes));
@@ -161,8 +360,8 @@ mod tests {
   #[gtest]
   fn test_cmp_ne_diff_length() {
     let str1 = "testabcd";
-    let i1 = InlineString::new(str::from_utf8(&str1.as_bytes()[..4]).unwrap());
-    let i2 = InlineString::new(str::from_utf8(&str1.as_bytes()[..5]).unwrap());
+    let i1 = inline(str::from_utf8(&str1.as_bytes()[..4]).unwrap());
+    let i2 = inline(str::from_utf8(&str1.as_bytes()[..5]).unwrap());
     expect_true!(i1 != i2);
     expect_that!(i1.cmp(&i2), pat![Ordering::Less]);
     expect_ne!(str_hash(&i1.bytes), str_hash(&i2.bytes));
@@ -172,8 +371,8 @@ mod tests {
   fn test_cmp_ne_chars() {
     let str1 = "test";
     let str2 = "tesy";
-    let i1 = InlineString::new(str1);
-    let i2 = InlineString::new(str2);
+    let i1 = inline(str1);
+    let i2 = inline(str2);
     expect_true!(i1 != i2);
     expect_that!(i1.cmp(&i2), pat![Ordering::Less]);
     expect_ne!(str_hash(&i1.bytes), str_hash(&i2.bytes));
@@ -181,9 +380,244 @@ mod tests {
 
   #[gtest]
   fn test_eq_hash_with_u8_slice() {
+    expect_eq!(str_hash(&inline("word").bytes), str_hash("word".as_bytes()));
+  }
+
+  #[gtest]
+  fn test_eq_str() {
+    let i = <InlineString>::try_from("Berlin").unwrap();
+    expect_true!(i == "Berlin");
+    expect_true!("Berlin" == i);
+    expect_true!(*"Berlin" == i);
+    expect_true!(i != "Berli");
+    expect_true!("Berlin!" != i);
+  }
+
+  #[gtest]
+  fn test_hash_map_keys() {
+    let mut map = HashMap::<InlineString, u32, BuildStringHash>::default();
+    for station in ["Berlin", "Oslo", "Berlin", "Abha", "Berlin"] {
+      *map
+        .entry(<InlineString>::try_from(station).unwrap())
+        .or_default() += 1;
+    }
+    expect_eq!(map.len(), 3);
     expect_eq!(
-      str_hash(&InlineString::new("word").bytes),
-      str_hash("word".as_bytes())
+      map.get(&<InlineString>::try_from("Berlin").unwrap()),
+      Some(&3)
+    );
+    expect_eq!(map.get("Oslo".as_bytes()), Some(&1));
+    expect_eq!(map.get("Abha".as_bytes()), Some(&1));
+    expect_eq!(map.get("Bern".as_bytes()), None);
+  }
+
+  #[gtest]
+  fn test_try_from_rejects_long_names() {
+    let name = "a".repeat(53);
+    expect_that!(
+      <InlineString>::try_from(&name[..52]).map(|i| i.len()),
+      ok(eq(&52))
+    );
+    expect_that!(
+      <InlineString>::try_from(name.as_str()).map(|i| i.len()),
+      err(displays_as(eq(format!(
+        "error: String \"{name}\" is longer than the 52-byte capacity of an InlineString"
+      ))))
+    );
+  }
+
+  #[gtest]
+  fn test_over_length_names_are_rejected() {
+    let name = "a".repeat(100);
+    let mut s = inline(&name[..52]);
+    for len in [53, 54, 100] {
+      let long_name = &name[..len];
+      expect_that!(s.initialize(long_name), err(anything()));
+      expect_that!(
+        <InlineString>::try_from(long_name).map(|s| s.len()),
+        err(anything())
+      );
+      // The string is unchanged, and compares unequal to the longer name.
+      expect_eq!(s.as_str(), &name[..52]);
+      expect_true!(s != long_name);
+      expect_eq!(s.as_bytes().cmp(long_name.as_bytes()), Ordering::Less);
+    }
+
+    let map: HashMap<_, _, BuildStringHash> = [(s, 1)].into_iter().collect();
+    expect_eq!(map.get(&name.as_bytes()[..52]), Some(&1));
+    for len in [53, 54, 100] {
+      expect_eq!(map.get(&name.as_bytes()[..len]), None);
+    }
+  }
+
+  #[gtest]
+  fn test_initialize_every_length() {
+    const NAME: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ01";
+    for len in 0..=NAME.len() {
+      let name = &NAME[..len];
+      let mut s = InlineString::default();
+      if len > 52 {
+        expect_that!(s.initialize(name), err(anything()));
+        expect_true!(s.is_empty());
+        continue;
+      }
+      expect_that!(s.initialize(name), ok(anything()));
+      expect_eq!(s.as_str(), name);
+      // No bytes past the end of the name are written.
+      expect_true!(s == inline(name));
+    }
+  }
+
+  fn default_hash(s: &InlineString) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    s.hash(&mut hasher);
+    hasher.finish()
+  }
+
+  /// Returns a copy of `s` with every byte stored past its length set to
+  /// `dirt`.
+  fn with_dirty_tail(s: &InlineString, dirt: u8) -> InlineString {
+    let mut dirty = s.clone();
+    let len = dirty.len();
+    dirty.bytes[len..].fill(dirt);
+    dirty
+  }
+
+  #[gtest]
+  fn test_eq_ignores_bytes_past_len() {
+    const NAME: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+    for len in 0..=NAME.len() {
+      let clean = inline(&NAME[..len]);
+      let mut truncated = inline(NAME);
+      truncated.initialize(&NAME[..len]).unwrap();
+      let dirty_strings = [0x01, b'a', 0xff]
+        .map(|dirt| with_dirty_tail(&clean, dirt))
+        .into_iter()
+        .chain([truncated]);
+      for dirty in dirty_strings {
+        expect_true!(dirty == clean, "len {len}");
+        expect_true!(dirty == NAME[..len], "len {len}");
+        expect_eq!(dirty.cmp(&clean), Ordering::Equal, "len {len}");
+        expect_eq!(default_hash(&dirty), default_hash(&clean), "len {len}");
+
+        // Strings differing in any byte within their lengths are unequal.
+        for i in 0..len {
+          let mut other = dirty.clone();
+          other.bytes[i] ^= 1;
+          expect_false!(other == clean, "len {len}, differing byte {i}");
+        }
+        // Even if the next byte of a longer string is stored past the length.
+        if len < NAME.len() {
+          let longer = inline(&NAME[..len + 1]);
+          expect_false!(
+            with_dirty_tail(&dirty, NAME.as_bytes()[len]) == longer,
+            "len {len}"
+          );
+        }
+      }
+    }
+  }
+
+  /// Expects strings of every length up to `CAP` to be built with `try_from`
+  /// and `push_str`, and longer strings to be rejected.
+  fn expect_fills_capacity<const CAP: usize>() {
+    let name = "abcdefghij".repeat(11);
+    let mut pushed = InlineString::<CAP>::new();
+    for len in 0..=CAP {
+      let s = InlineString::<CAP>::try_from(&name[..len]).unwrap();
+      expect_eq!(s.as_str(), &name[..len]);
+      expect_eq!(s, pushed, "len {len}");
+      if len < CAP {
+        expect_that!(pushed.push_str(&name[len..len + 1]), ok(anything()));
+      }
+    }
+
+    expect_that!(
+      pushed.push_str("a"),
+      err(displays_as(eq(format!(
+        "error: String \"{}a\" is longer than the {CAP}-byte capacity of an InlineString",
+        &name[..CAP]
+      ))))
+    );
+    expect_eq!(pushed.as_str(), &name[..CAP]);
+    expect_that!(
+      InlineString::<CAP>::try_from(&name[..CAP + 1]),
+      err(anything())
+    );
+  }
+
+  /// Expects strings of capacity `CAP` to compare like `str`s, including
+  /// full strings differing only in their first, middle or last byte.
+  fn expect_cmp_matches_str_cmp<const CAP: usize>() {
+    let full = "x".repeat(CAP);
+    let mut names = [0, 1, CAP / 2, CAP - 1, CAP]
+      .map(|len| full[..len].to_owned())
+      .to_vec();
+    for idx in [0, CAP / 2, CAP - 1] {
+      let mut name = full.clone();
+      name.replace_range(idx..idx + 1, "y");
+      names.push(name);
+    }
+
+    for a in &names {
+      let inline_a = InlineString::<CAP>::try_from(a.as_str()).unwrap();
+      expect_true!(inline_a == a.as_str());
+      for b in &names {
+        let inline_b = InlineString::<CAP>::try_from(b.as_str()).unwrap();
+        expect_eq!(inline_a == inline_b, a == b, "{a:?} == {b:?}");
+        expect_eq!(inline_a == b.as_str(), a == b, "{a:?} == {b:?}");
+        expect_eq!(
+          inline_a.cmp(&inline_b),
+          a.cmp(b),
+          "Comparing {a:?} and {b:?}"
+        );
+      }
+    }
+  }
+
+  #[gtest]
+  fn test_capacity_8() {
+    expect_fills_capacity::<8>();
+    expect_cmp_matches_str_cmp::<8>();
+  }
+
+  #[gtest]
+  fn test_capacity_52() {
+    expect_fills_capacity::<52>();
+    expect_cmp_matches_str_cmp::<52>();
+  }
+
+  #[gtest]
+  fn test_capacity_104() {
+    expect_fills_capacity::<104>();
+    expect_cmp_matches_str_cmp::<104>();
+  }
+
+  #[gtest]
+  fn test_default_capacity_layout() {
+    let s: InlineString = InlineString::new();
+    expect_eq!(s.capacity(), 52);
+    expect_eq!(size_of::<InlineString>(), 56);
+  }
+
+  #[gtest]
+  fn test_deref_to_str() {
+    let s = InlineString::<8>::try_from("Oslo").unwrap();
+    expect_true!(s.starts_with("Os"));
+    expect_eq!(s.to_uppercase(), "OSLO");
+    expect_eq!(format!("{s:?}"), "\"Oslo\"");
+  }
+
+  #[cfg(feature = "serde")]
+  #[gtest]
+  fn test_serde_round_trip() {
+    let s = InlineString::<8>::try_from("Oslo").unwrap();
+    let json = serde_json::to_string(&s).unwrap();
+    expect_eq!(json, "\"Oslo\"");
+    expect_that!(serde_json::from_str::<InlineString<8>>(&json), ok(eq(&s)));
+    expect_that!(
+      serde_json::from_str::<InlineString<8>>("\"Stockholm\""),
+      err(anything())
     );
   }
 }
@@ -1,52 +1,81 @@
 use std::sync::atomic::{AtomicU32, Ordering as AtomicOrdering};
-use std::{borrow::Borrow, cell::UnsafeCell, cmp::Ordering, fmt::Display};
-
+use std::{
+  borrow::Borrow,
+  cell::UnsafeCell,
+  cmp::Ordering,
+  fmt::Display,
+  hash::{Hash, Hasher},
+  slice,
+};
+
+use crate::error::{BarseError, BarseResult};
 use crate::hugepage_backed_table::InPlaceInitializable;
 #[cfg(target_feature = "avx2")]
-use crate::str_cmp_x86::inline_str_eq_foreign_str;
-use crate::util::likely;
+use crate::str_cmp_x86::{
+  inline_str_cmp_inline_str, inline_str_eq_foreign_str, inline_str_eq_inline_str, InlineStorage,
+};
+use crate::util::{copy_no_libc, likely};
 
 const MAX_STRING_LEN: usize = 50;
 const STRING_STORAGE_LEN: usize = 52;
-const INLINE_STRING_SIZE: usize = std::mem::size_of::<InlineString>();
+
+fn too_long_error(contents: &str) -> BarseError {
+  BarseError::new(format!(
+    "Station name \"{contents}\" is longer than {MAX_STRING_LEN} bytes"
+  ))
+}
 
 #[repr(C, align(8))]
 pub struct InlineString {
   bytes: UnsafeCell<[u8; STRING_STORAGE_LEN]>,
+  /// The length of the string, or'ed with `INITIALIZED_BIT` once the string
+  /// has been initialized. Zero means the string is uninitialized, which is
+  /// distinct from an initialized empty string.
   len: AtomicU32,
 }
 
 impl InlineString {
   const INITIALIZING_RESERVED_LEN: u32 = u32::MAX;
+  const INITIALIZED_BIT: u32 = 1 << 31;
 
   #[cfg(test)]
   pub fn new(contents: &str) -> Self {
-    let s = Self::default();
-    Self::memcpy_no_libc(unsafe { &mut *s.bytes.get() }, contents);
-    s.len.store(contents.len() as u32, AtomicOrdering::Relaxed);
-    s
+    Self::try_from(contents).unwrap()
   }
 
-  fn bytes(&self) -> &[u8; STRING_STORAGE_LEN] {
+  /// The storage of the string, of which only the first `len()` bytes are
+  /// meaningful.
+  pub(crate) fn bytes(&self) -> &[u8; STRING_STORAGE_LEN] {
     unsafe { &*self.bytes.get() }
   }
 
+  /// The storage of the string for the AVX2 comparisons, which is always
+  /// long enough for them.
+  #[cfg(target_feature = "avx2")]
+  pub(crate) fn storage(&self) -> Option<InlineStorage<'_>> {
+    // `len()` is at most `MAX_STRING_LEN`.
+    unsafe { InlineStorage::new(self.bytes(), self.len()) }
+  }
+
   pub fn is_empty(&self) -> bool {
     self.len() == 0
   }
 
+  /// The length of the string, which is 0 until it has been initialized.
+  /// Loaded with `Acquire`, so that the first `len()` bytes may be read once
+  /// the length is seen.
   pub fn len(&self) -> usize {
-    self.len.load(AtomicOrdering::Relaxed) as usize
+    match self.len.load(AtomicOrdering::Acquire) {
+      Self::INITIALIZING_RESERVED_LEN => 0,
+      len => (len & !Self::INITIALIZED_BIT) as usize,
+    }
   }
 
   /// Performs a memcpy from contents to self.value() without calling
-  /// libc::memcpy.
-  fn memcpy_no_libc(bytes: &mut [u8], contents: &str) {
-    for i in 0..contents.len().min(MAX_STRING_LEN) {
-      unsafe {
-        *bytes.get_unchecked_mut(i) = std::hint::black_box(*contents.as_bytes().get_unchecked(i));
-      }
-    }
+  /// libc::memcpy. `contents` must be no longer than `MAX_STRING_LEN` bytes.
+  fn memcpy_no_libc(bytes: &mut [u8; STRING_STORAGE_LEN], contents: &str) {
+    debug_assert!(contents.len() <= MAX_STRING_LEN);
+    copy_no_libc(bytes, contents.as_bytes());
   }
 
   pub fn initialized(&self) -> bool {
@@ -59,27 +88,35 @@ impl InlineString {
   }
 
   pub fn value(&self) -> &[u8] {
-    unsafe { self.bytes().get_unchecked(..self.len()) }
-  }
-
-  fn cmp_slice(&self) -> &[u8; INLINE_STRING_SIZE] {
-    unsafe { &*(self as *const Self as *const [u8; INLINE_STRING_SIZE]) }
+    // SAFETY: Only the first `len()` bytes are borrowed, which are never
+    // written again once the length is published. In particular, while
+    // another thread initializes the string, `len()` is 0 and no bytes are
+    // borrowed, so the borrow doesn't alias the initializing write.
+    unsafe { slice::from_raw_parts(self.bytes.get().cast::<u8>(), self.len()) }
   }
 
   fn memcpy_no_libc_under_lock(&self, contents: &str) {
-    debug_assert_eq!(self.len() as u32, Self::INITIALIZING_RESERVED_LEN);
+    debug_assert_eq!(
+      self.len.load(AtomicOrdering::Relaxed),
+      Self::INITIALIZING_RESERVED_LEN
+    );
+    // SAFETY: The string is claimed by this thread, and no other thread
+    // borrows its bytes until the final length is published.
     Self::memcpy_no_libc(unsafe { &mut *self.bytes.get() }, contents);
   }
 
   fn initialize_contents_under_lock(&self, contents: &str) {
-    debug_assert!(contents.len() <= MAX_STRING_LEN,);
+    debug_assert!(contents.len() <= MAX_STRING_LEN);
     self.memcpy_no_libc_under_lock(contents);
   }
 
   #[cfg(target_feature = "avx2")]
   fn eq_foreign_str(&self, other: &str) -> bool {
     debug_assert!(self.initialized());
-    inline_str_eq_foreign_str(self, other)
+    match self.storage() {
+      Some(storage) => inline_str_eq_foreign_str(storage, other),
+      None => self.value_str() == other,
+    }
   }
 
   #[cfg(not(target_feature = "avx2"))]
@@ -88,9 +125,21 @@ impl InlineString {
     self.value_str() == other
   }
 
+  /// Waits for the thread which claimed this string to publish its contents.
+  /// Initialization only copies at most `MAX_STRING_LEN` bytes, so this
+  /// normally spins briefly, but yields to the scheduler after
+  /// `SPINS_BEFORE_YIELD` checks in case the initializing thread was
+  /// descheduled.
   fn wait_until_initialized(&self) {
+    const SPINS_BEFORE_YIELD: u32 = 64;
+    let mut spins = 0;
     while self.len.load(AtomicOrdering::Acquire) == Self::INITIALIZING_RESERVED_LEN {
-      std::hint::spin_loop();
+      if spins < SPINS_BEFORE_YIELD {
+        spins += 1;
+        std::hint::spin_loop();
+      } else {
+        std::thread::yield_now();
+      }
     }
   }
 
@@ -98,25 +147,49 @@ impl InlineString {
     if likely(self.initialized()) {
       return likely(self.eq_foreign_str(station));
     }
+    self.try_initialize(station)
+  }
 
-    let prev_len = self
-      .len
-      .swap(Self::INITIALIZING_RESERVED_LEN, AtomicOrdering::Acquire);
-    if prev_len == 0 {
-      self.initialize_contents_under_lock(station);
-      self
-        .len
-        .store(station.len() as u32, AtomicOrdering::Release);
-      return true;
-    } else if prev_len == Self::INITIALIZING_RESERVED_LEN {
-      self.wait_until_initialized();
-    } else {
-      // We accidentally overwrite the length with INITIALIZING_RESERVED_LEN,
-      // restore the length:
-      self.len.store(prev_len, AtomicOrdering::Relaxed);
+  /// Claims this string for `contents` if it is uninitialized. Returns true if
+  /// this thread initialized it, or if it was initialized (possibly by a
+  /// thread racing with this one) to a string equal to `contents`, and false
+  /// otherwise. Panics without claiming the string if `contents` is longer
+  /// than `MAX_STRING_LEN` bytes, rather than storing a truncated copy, which
+  /// is deliberate for the reasons given on `InlineString::initialize_station`
+  /// in the `inline_string` module.
+  ///
+  /// `len` only ever moves from 0 to `INITIALIZING_RESERVED_LEN`, by the one
+  /// thread whose `compare_exchange` succeeds, and from there to the final
+  /// length, so readers never modify a claimed string. The winner's contents
+  /// are published by its `Release` store of the final length, which every
+  /// other thread synchronizes with through an `Acquire` load of that length:
+  /// in the failed `compare_exchange`, in `wait_until_initialized`, or in
+  /// `initialized`. The bytes are therefore never read before they have been
+  /// written.
+  pub fn try_initialize(&self, contents: &str) -> bool {
+    if contents.len() > MAX_STRING_LEN {
+      panic!("{}", too_long_error(contents));
+    }
+    match self.len.compare_exchange(
+      0,
+      Self::INITIALIZING_RESERVED_LEN,
+      AtomicOrdering::AcqRel,
+      AtomicOrdering::Acquire,
+    ) {
+      Ok(_) => {
+        self.initialize_contents_under_lock(contents);
+        self.len.store(
+          contents.len() as u32 | Self::INITIALIZED_BIT,
+          AtomicOrdering::Release,
+        );
+        true
+      }
+      Err(Self::INITIALIZING_RESERVED_LEN) => {
+        self.wait_until_initialized();
+        self.eq_foreign_str(contents)
+      }
+      Err(_) => self.eq_foreign_str(contents),
     }
-
-    self.eq_foreign_str(station)
   }
 }
 
@@ -124,15 +197,25 @@ impl Default for InlineString {
   fn default() -> Self {
     Self {
       bytes: UnsafeCell::new([0; STRING_STORAGE_LEN]),
-      #[cfg(feature = "multithreaded")]
       len: AtomicU32::new(0),
     }
   }
 }
 
+/// Strings are equal if they have the same length and contents. The bytes
+/// stored past their lengths are ignored.
 impl PartialEq for InlineString {
+  #[cfg(target_feature = "avx2")]
   fn eq(&self, other: &Self) -> bool {
-    self.cmp_slice() == other.cmp_slice()
+    match (self.storage(), other.storage()) {
+      (Some(a), Some(b)) => inline_str_eq_inline_str(a, b),
+      _ => self.value() == other.value(),
+    }
+  }
+
+  #[cfg(not(target_feature = "avx2"))]
+  fn eq(&self, other: &Self) -> bool {
+    self.value() == other.value()
   }
 }
 
@@ -144,7 +227,17 @@ impl PartialOrd for InlineString {
   }
 }
 
+/// Strings are ordered like their bytes.
 impl Ord for InlineString {
+  #[cfg(target_feature = "avx2")]
+  fn cmp(&self, other: &Self) -> Ordering {
+    match (self.storage(), other.storage()) {
+      (Some(a), Some(b)) => inline_str_cmp_inline_str(a, b),
+      _ => self.value().cmp(other.value()),
+    }
+  }
+
+  #[cfg(not(target_feature = "avx2"))]
   fn cmp(&self, other: &Self) -> Ordering {
     self.value().cmp(other.value())
   }
@@ -162,7 +255,55 @@ impl Display for InlineString {
   }
 }
 
-impl InPlaceInitializable for InlineString {
+impl PartialEq<str> for InlineString {
+  fn eq(&self, other: &str) -> bool {
+    self.initialized() && self.eq_foreign_str(other)
+  }
+}
+
+impl PartialEq<&str> for InlineString {
+  fn eq(&self, other: &&str) -> bool {
+    self == *other
+  }
+}
+
+impl PartialEq<InlineString> for str {
+  fn eq(&self, other: &InlineString) -> bool {
+    other == self
+  }
+}
+
+impl PartialEq<InlineString> for &str {
+  fn eq(&self, other: &InlineString) -> bool {
+    other == *self
+  }
+}
+
+/// Hashes the same as the `[u8]` that `InlineString` borrows as, so strings
+/// can be looked up by their bytes in hash maps.
+impl Hash for InlineString {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    self.value().hash(state);
+  }
+}
+
+/// Copies a string into an `InlineString`, failing if it is longer than
+/// `MAX_STRING_LEN` bytes rather than truncating it.
+impl TryFrom<&str> for InlineString {
+  type Error = BarseError;
+
+  fn try_from(contents: &str) -> BarseResult<Self> {
+    if contents.len() > MAX_STRING_LEN {
+      return Err(too_long_error(contents));
+    }
+    let s = Self::default();
+    s.try_initialize(contents);
+    Ok(s)
+  }
+}
+
+// SAFETY: A zeroed `InlineString` is the empty, unclaimed string.
+unsafe impl InPlaceInitializable for InlineString {
   fn initialize(&mut self) {
     // No need to do anything, a zero-initialized string is correctly initialized.
     debug_assert!(self.bytes().iter().all(|b| *b == 0));
@@ -170,15 +311,28 @@ impl InPlaceInitializable for InlineString {
   }
 }
 
+// SAFETY: `bytes` is only written by the one thread which claims the string,
+// before it publishes the length, as described in `try_initialize`.
 unsafe impl Sync for InlineString {}
 
 #[cfg(test)]
 mod tests {
-  use std::cmp::Ordering;
+  use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    hash::{DefaultHasher, Hash, Hasher},
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{
+      atomic::{AtomicBool, Ordering as AtomicOrdering},
+      Barrier,
+    },
+    thread,
+    time::Duration,
+  };
 
   use googletest::{expect_that, gtest, prelude::*};
 
-  use crate::str_hash::str_hash;
+  use crate::str_hash::{str_hash, BuildStringHash};
 
   use super::InlineString;
 
@@ -228,4 +382,307 @@ mod tests {
       str_hash("word".as_bytes())
     );
   }
+
+  #[gtest]
+  fn test_default_is_uninitialized() {
+    expect_false!(InlineString::default().initialized());
+  }
+
+  #[gtest]
+  fn test_initialize_empty() {
+    let s = InlineString::default();
+    expect_true!(s.eq_or_initialize(""));
+    expect_true!(s.initialized());
+    expect_eq!(s.value_str(), "");
+    expect_true!(s.eq_or_initialize(""));
+    expect_false!(s.eq_or_initialize("station"));
+  }
+
+  /// Runs `try_initialize` on one string from `names.len()` threads at once,
+  /// returning the string and each thread's result.
+  fn race_to_initialize(names: &[String]) -> (InlineString, Vec<bool>) {
+    let s = InlineString::default();
+    let barrier = Barrier::new(names.len());
+    let results = thread::scope(|scope| {
+      let threads = names
+        .iter()
+        .map(|name| {
+          let (s, barrier) = (&s, &barrier);
+          scope.spawn(move || {
+            barrier.wait();
+            s.try_initialize(name)
+          })
+        })
+        .collect::<Vec<_>>();
+      threads
+        .into_iter()
+        .map(|thread| thread.join().unwrap())
+        .collect()
+    });
+    (s, results)
+  }
+
+  #[gtest]
+  fn test_try_initialize_race_same_name() {
+    for _ in 0..100 {
+      let names = vec!["station".to_owned(); 16];
+      let (s, results) = race_to_initialize(&names);
+      expect_that!(results, each(eq(&true)));
+      expect_eq!(s.value_str(), "station");
+    }
+  }
+
+  #[gtest]
+  fn test_try_initialize_race_different_names() {
+    for _ in 0..100 {
+      let names = (0..16).map(|i| format!("station{i}")).collect::<Vec<_>>();
+      let (s, results) = race_to_initialize(&names);
+      let winners = results
+        .iter()
+        .zip(&names)
+        .filter(|&(&won, _)| won)
+        .map(|(_, name)| name.as_str())
+        .collect::<Vec<_>>();
+      expect_that!(winners, elements_are![eq(&s.value_str())]);
+    }
+  }
+
+  #[gtest]
+  fn test_losers_wait_for_winner() {
+    let s = InlineString::default();
+    // Claim the string as the winner of a race would, without publishing its
+    // contents yet.
+    assert_that!(
+      s.len.compare_exchange(
+        0,
+        InlineString::INITIALIZING_RESERVED_LEN,
+        AtomicOrdering::AcqRel,
+        AtomicOrdering::Acquire,
+      ),
+      ok(anything())
+    );
+
+    thread::scope(|scope| {
+      let same = scope.spawn(|| s.try_initialize("station"));
+      let different = scope.spawn(|| s.try_initialize("other"));
+      thread::sleep(Duration::from_millis(10));
+      expect_false!(same.is_finished());
+      expect_false!(different.is_finished());
+
+      s.initialize_contents_under_lock("station");
+      s.len.store(
+        "station".len() as u32 | InlineString::INITIALIZED_BIT,
+        AtomicOrdering::Release,
+      );
+      expect_true!(same.join().unwrap());
+      expect_false!(different.join().unwrap());
+    });
+    expect_eq!(s.value_str(), "station");
+  }
+
+  #[gtest]
+  fn test_readers_never_modify_claimed_string() {
+    let s = InlineString::default();
+    expect_true!(s.try_initialize("station"));
+    let initialized_len = s.len.load(AtomicOrdering::Relaxed);
+
+    let done = AtomicBool::new(false);
+    thread::scope(|scope| {
+      let watcher = scope.spawn(|| {
+        let mut modified = false;
+        while !done.load(AtomicOrdering::Relaxed) {
+          modified |= s.len.load(AtomicOrdering::Relaxed) != initialized_len;
+        }
+        modified
+      });
+      let readers = (0..8)
+        .map(|i| {
+          let (s, name) = (&s, if i % 2 == 0 { "station" } else { "other" });
+          scope.spawn(move || (0..1000).all(|_| s.try_initialize(name) == (name == "station")))
+        })
+        .collect::<Vec<_>>();
+      for reader in readers {
+        expect_true!(reader.join().unwrap());
+      }
+      done.store(true, AtomicOrdering::Relaxed);
+      expect_false!(watcher.join().unwrap());
+    });
+    expect_eq!(s.value_str(), "station");
+  }
+
+  /// Readers borrow a string's bytes only once its length is published, so
+  /// they never race with the thread initializing it, which Miri checks.
+  #[gtest]
+  fn test_value_read_while_initializing() {
+    for _ in 0..20 {
+      let s = InlineString::default();
+      let barrier = Barrier::new(5);
+      thread::scope(|scope| {
+        let readers = (0..4)
+          .map(|_| {
+            let (s, barrier) = (&s, &barrier);
+            scope.spawn(move || {
+              barrier.wait();
+              (0..10).all(|_| matches!(s.value(), b"" | b"station"))
+            })
+          })
+          .collect::<Vec<_>>();
+        barrier.wait();
+        expect_true!(s.try_initialize("station"));
+        for reader in readers {
+          expect_true!(reader.join().unwrap());
+        }
+      });
+      expect_eq!(s.value_str(), "station");
+    }
+  }
+
+  #[gtest]
+  fn test_eq_str() {
+    let i = InlineString::try_from("Berlin").unwrap();
+    expect_true!(i == "Berlin");
+    expect_true!("Berlin" == i);
+    expect_true!(*"Berlin" == i);
+    expect_true!(i != "Berli");
+    expect_true!("Berlin!" != i);
+  }
+
+  // Keys are initialized before they are inserted, after which they are
+  // never modified.
+  #[allow(clippy::mutable_key_type)]
+  #[gtest]
+  fn test_hash_map_keys() {
+    let mut map = HashMap::<InlineString, u32, BuildStringHash>::default();
+    for station in ["Berlin", "Oslo", "Berlin", "Abha", "Berlin"] {
+      *map
+        .entry(InlineString::try_from(station).unwrap())
+        .or_default() += 1;
+    }
+    expect_eq!(map.len(), 3);
+    expect_eq!(
+      map.get(&InlineString::try_from("Berlin").unwrap()),
+      Some(&3)
+    );
+    expect_eq!(map.get("Oslo".as_bytes()), Some(&1));
+    expect_eq!(map.get("Abha".as_bytes()), Some(&1));
+    expect_eq!(map.get("Bern".as_bytes()), None);
+  }
+
+  #[gtest]
+  fn test_try_from_rejects_long_names() {
+    let name = "a".repeat(51);
+    expect_that!(
+      InlineString::try_from(&name[..50]).map(|i| i.len()),
+      ok(eq(&50))
+    );
+    expect_that!(
+      InlineString::try_from(name.as_str()).map(|i| i.len()),
+      err(displays_as(eq(format!(
+        "error: Station name \"{name}\" is longer than 50 bytes"
+      ))))
+    );
+  }
+
+  #[allow(clippy::mutable_key_type)]
+  #[gtest]
+  fn test_over_length_names_are_rejected() {
+    let name = "a".repeat(100);
+    for len in [51, 52, 100] {
+      let long_name = &name[..len];
+      expect_that!(
+        InlineString::try_from(long_name).map(|s| s.len()),
+        err(anything())
+      );
+
+      // Initializing fails without claiming the string, which can still be
+      // initialized to a shorter name.
+      let s = InlineString::default();
+      expect_true!(catch_unwind(AssertUnwindSafe(|| s.try_initialize(long_name))).is_err());
+      expect_false!(s.initialized());
+      expect_true!(s.try_initialize(&name[..50]));
+      expect_true!(s != long_name);
+      expect_false!(s.eq_or_initialize(long_name));
+      expect_eq!(s.value().cmp(long_name.as_bytes()), Ordering::Less);
+
+      let map: HashMap<_, _, BuildStringHash> = [(s, 1)].into_iter().collect();
+      expect_eq!(map.get(&name.as_bytes()[..50]), Some(&1));
+      expect_eq!(map.get(long_name.as_bytes()), None);
+    }
+  }
+
+  #[gtest]
+  fn test_initialize_every_length() {
+    const NAME: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+    for len in 0..=NAME.len() {
+      let name = &NAME[..len];
+      let s = InlineString::default();
+      if len > 50 {
+        expect_true!(catch_unwind(AssertUnwindSafe(|| s.try_initialize(name))).is_err());
+        expect_false!(s.initialized());
+        continue;
+      }
+      expect_true!(s.try_initialize(name));
+      expect_eq!(s.value_str(), name);
+      // No bytes past the end of the name are written.
+      expect_true!(s == InlineString::new(name));
+    }
+  }
+
+  fn default_hash(s: &InlineString) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    s.hash(&mut hasher);
+    hasher.finish()
+  }
+
+  /// Returns `contents` as an `InlineString` with every byte stored past its
+  /// length set to `dirt`.
+  fn with_dirty_tail(contents: &str, dirt: u8) -> InlineString {
+    let s = InlineString::new(contents);
+    let bytes = unsafe { &mut *s.bytes.get() };
+    bytes[contents.len()..].fill(dirt);
+    s
+  }
+
+  #[gtest]
+  fn test_eq_ignores_bytes_past_len() {
+    const NAME: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWX";
+    for len in 0..=NAME.len() {
+      let name = &NAME[..len];
+      let clean = InlineString::new(name);
+      for dirt in [0x01, b'a', 0xff] {
+        let dirty = with_dirty_tail(name, dirt);
+        expect_true!(dirty == clean, "len {len}");
+        expect_true!(dirty == name, "len {len}");
+        expect_eq!(dirty.cmp(&clean), Ordering::Equal, "len {len}");
+        expect_eq!(default_hash(&dirty), default_hash(&clean), "len {len}");
+
+        // Strings differing in any byte within their lengths are unequal.
+        for i in 0..len {
+          let other = with_dirty_tail(name, dirt);
+          unsafe { (&mut *other.bytes.get())[i] ^= 1 };
+          expect_false!(other == clean, "len {len}, differing byte {i}");
+        }
+        // Even if the next byte of a longer string is stored past the length.
+        if len < NAME.len() {
+          let longer = InlineString::new(&NAME[..len + 1]);
+          expect_false!(
+            with_dirty_tail(name, NAME.as_bytes()[len]) == longer,
+            "len {len}"
+          );
+        }
+      }
+    }
+  }
+
+  #[gtest]
+  fn test_string_being_initialized_is_empty() {
+    let s = with_dirty_tail("", b'a');
+    s.len.store(
+      InlineString::INITIALIZING_RESERVED_LEN,
+      AtomicOrdering::Relaxed,
+    );
+    expect_eq!(s.len(), 0);
+    expect_eq!(s.value(), b"");
+    expect_true!(s == InlineString::default());
+  }
 }
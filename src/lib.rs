@@ -1,42 +1,179 @@
+//! A fast solution to the One Billion Row Challenge.
+//!
+//! # Usage
+//!
+//! The entry points for summarizing a file of measurements are re-exported
+//! at the crate root. `summarize_file` builds the summary of every station,
+//! ordered by name:
+//!
+//! ```
+//! # fn main() -> barse::BarseResult {
+//! # let path = std::env::temp_dir().join(format!("barse_doc_{}.txt", std::process::id()));
+//! # std::fs::write(&path, "Hamburg;12.0\nBulawayo;8.9\nHamburg;34.2\n")?;
+//! # let path = path.to_str().expect("temp paths are UTF-8");
+//! use barse::{summarize_file, Options, TemperatureReading};
+//!
+//! let stations = summarize_file(path, &Options::default())?;
+//! let names = stations.iter().map(|station| station.name.as_str()).collect::<Vec<_>>();
+//! assert_eq!(names, ["Bulawayo", "Hamburg"]);
+//! assert_eq!(stations[1].summary.max(), TemperatureReading::new(342));
+//! assert_eq!(stations[1].summary.to_string(), "12.0/23.1/34.2");
+//! # std::fs::remove_file(path)?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! `scan_file` instead visits each record in order, without aggregating
+//! them, and can stop early:
+//!
+//! ```
+//! # fn main() -> barse::BarseResult {
+//! # let path = std::env::temp_dir().join(format!("barse_doc_scan_{}.txt", std::process::id()));
+//! # std::fs::write(&path, "Hamburg;12.0\nBulawayo;8.9\nHamburg;34.2\n")?;
+//! # let path = path.to_str().expect("temp paths are UTF-8");
+//! use std::ops::ControlFlow;
+//!
+//! use barse::{scan_file, TemperatureReading};
+//!
+//! let first_below_ten = scan_file(path, |station, reading| {
+//!   if reading < TemperatureReading::new(100) {
+//!     ControlFlow::Break(station.to_owned())
+//!   } else {
+//!     ControlFlow::Continue(())
+//!   }
+//! })?;
+//! assert_eq!(first_below_ten, ControlFlow::Break("Bulawayo".to_owned()));
+//! # std::fs::remove_file(path)?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The modules hidden from the documentation hold the building blocks of the
+//! tables, and may change without notice.
+//!
+//! # Supported targets
+//!
+//! | Target | Scanner, hasher and string comparison | Notes |
+//! |--------|---------------------------------------|-------|
+//! | x86_64 with AVX2 | AVX2 | Enable with `-C target-cpu=native`. |
+//! | x86_64 without AVX2, aarch64 | Portable SWAR | Apple silicon pages are 16 KiB. |
+//! | 32-bit (i686, armv7) | Portable SWAR | Requires 64-bit atomics. Inputs and tables must fit in the address space. |
+//!
+//! File offsets and input sizes are `u64` on every target. On 32-bit targets,
+//! input files which don't fit in memory are rejected with an error, as are
+//! tables which would span more than half the address space.
+//! Hugepages and the `perf-counters` feature are only used on Linux, and the
+//! `profiled` feature is only available on Unix. Without the `mmap` feature,
+//! inputs and tables are allocated from the heap.
+//!
+//! The portable paths can be tested on a 32-bit target with
+//! `cargo test --target i686-unknown-linux-gnu`.
+//!
+//! # Testing under Miri
+//!
+//! Under Miri, the scanner, hasher and temperature parser always use the
+//! bounds-checked reads of the `safe-fallback` feature, since their unchecked
+//! reads deliberately run past the ends of slices. Miri can't map files, so
+//! the portable, heap-backed configuration is tested, on the tests which are
+//! small enough to finish:
+//!
+//! ```text
+//! cargo +nightly miri test --lib --no-default-features --features multithreaded -- \
+//!   aligned_alloc:: hugepage_backed_table:: inline_string:: inline_string_mt:: \
+//!   scanner::tests::test_records_at_every_offset slicer:: str_hash:: \
+//!   temperature_reading:: temperature_summary:: util:: validating_scanner::
+//! ```
+
 #![cfg_attr(not(test), deny(clippy::unwrap_used))]
 #![deny(clippy::borrow_as_ptr)]
 
+pub use crate::{
+  barse::{
+    scan_records as scan_file, scan_records_with_options as scan_file_with_options,
+    OwnedWeatherStation as StationSummary,
+  },
+  error::{BarseError, BarseResult},
+  options::Options,
+  print_summary::summarize_file,
+  temperature_reading::TemperatureReading,
+  temperature_summary::TemperatureSummary,
+};
+
+pub mod aggregator;
+pub mod aligned;
+mod aligned_alloc;
+#[cfg(feature = "arrow")]
+pub mod arrow_export;
 pub mod barse;
+pub mod bench;
+pub mod build_report;
 #[cfg(not(feature = "multithreaded"))]
 mod build_table;
 #[cfg(feature = "multithreaded")]
 mod build_table_mt;
+#[cfg(feature = "capi")]
+pub mod capi;
+pub mod check;
+pub mod config;
 pub mod error;
+pub mod generate;
 mod hugepage_backed_table;
-#[cfg(not(feature = "multithreaded"))]
 pub mod inline_string;
-#[cfg(feature = "multithreaded")]
+#[doc(hidden)]
 pub mod inline_string_mt;
+pub mod median;
+pub mod normalize;
+pub mod options;
+pub mod output_format;
+#[cfg(feature = "multithreaded")]
+mod par_sort;
+#[cfg(all(feature = "perf-counters", target_os = "linux"))]
+pub mod perf_counters;
 pub mod print_summary;
+pub mod reference;
+#[doc(hidden)]
 pub mod scanner;
 #[cfg(not(target_feature = "avx2"))]
 mod scanner_cache;
 #[cfg(target_feature = "avx2")]
 mod scanner_cache_x86;
 #[cfg(feature = "multithreaded")]
+pub mod session;
+pub mod sink;
+#[cfg(feature = "multithreaded")]
 mod slicer;
+#[doc(hidden)]
+pub mod soa_table;
+pub mod station_filter;
 #[cfg(target_feature = "avx2")]
 mod str_cmp_x86;
+#[doc(hidden)]
 pub mod str_hash;
 #[cfg(target_feature = "avx2")]
+#[doc(hidden)]
 pub mod str_hash_x86;
-#[cfg(feature = "multithreaded")]
 mod string_table;
 #[cfg(not(feature = "multithreaded"))]
 pub mod table;
+mod table_backend;
 #[cfg(not(feature = "multithreaded"))]
 mod table_entry;
+pub mod table_stats;
+pub mod tdigest_summary;
 pub mod temperature_reading;
-mod temperature_summary;
+pub mod temperature_summary;
 #[cfg(feature = "multithreaded")]
 mod temperature_summary_table;
+#[cfg(all(feature = "multithreaded", target_feature = "avx2"))]
+mod temperature_summary_x86;
 #[cfg(test)]
 pub mod test_against_simple_parser;
 #[cfg(test)]
+mod test_differential;
+#[cfg(test)]
 pub mod test_util;
 mod util;
+pub mod validate;
+#[doc(hidden)]
+pub mod validating_scanner;
+pub mod watch;
@@ -1,38 +1,685 @@
-use std::process::ExitCode;
+use std::{
+  fs::File,
+  io::{self, BufReader, BufWriter, Write},
+  process::ExitCode,
+  thread,
+  time::Duration,
+};
 
-use barse::{error::BarseResult, print_summary::print_summary};
-use clap::Parser;
+#[cfg(all(feature = "perf-counters", target_os = "linux"))]
+use barse::perf_counters;
+use barse::{
+  bench::{self, BenchFormat, BenchOptions},
+  build_report::Strategy,
+  check::{verify_against, verify_with_reference},
+  config::{
+    parse_duration, parse_size, read_config, resolve, OptionOverrides, Source, ENV_CHUNK_SIZE,
+    ENV_CONFIG, ENV_SKIP_HEADER, ENV_STRATEGY, ENV_STRICT, ENV_THREADS,
+  },
+  error::{BarseError, BarseResult},
+  generate::{default_stations, read_stations, Generator},
+  options::Options,
+  output_format::OutputFormat,
+  print_summary::{
+    write_atomically, write_dir_summary_with_stats, write_summary_with_options,
+    write_summary_with_report, write_summary_with_stats, write_table_summary, SortKey,
+    SummaryOptions,
+  },
+  station_filter::StationFilter,
+  validate::{validate_file, validate_reader, ValidateOptions, DEFAULT_MAX_ERRORS},
+  watch::{IncrementalAggregator, Poll},
+};
+use clap::{
+  builder::BoolishValueParser, error::ErrorKind, parser::ValueSource, ArgMatches, CommandFactory,
+  FromArgMatches, Parser, Subcommand,
+};
+
+/// The exit code for invalid command line arguments.
+const EXIT_USAGE: u8 = 1;
+/// The exit code for failures to read the input or write the output.
+const EXIT_IO: u8 = 2;
+/// The exit code for malformed input.
+const EXIT_DATA: u8 = 3;
+/// The exit code for all other failures, e.g. a full table or a panicked
+/// worker thread.
+const EXIT_INTERNAL: u8 = 4;
+
+/// Where the flamegraph is written by default when built with the `profiled`
+/// feature on Unix.
+#[cfg(all(feature = "profiled", unix))]
+const DEFAULT_PROFILE_OUT: &str = "brc.svg";
 
 #[derive(Parser, Debug)]
 struct Args {
+  #[command(subcommand)]
+  command: Option<Command>,
+
   #[arg(long, default_value = "measurements.txt")]
   input: String,
+
+  /// Summarize every file in this directory as if they were concatenated,
+  /// instead of --input. Files are processed in parallel.
+  #[arg(long, value_name = "DIR", conflicts_with_all = ["input", "check"])]
+  input_dir: Option<String>,
+
+  /// With --input-dir, only read files with this extension, e.g. txt.
+  #[arg(long, value_name = "EXT", requires = "input_dir")]
+  input_ext: Option<String>,
+
+  /// Print statistics about the run to stderr after the summary.
+  #[arg(long)]
+  report: bool,
+
+  /// Like --report, also printing the occupancy of the station table.
+  #[arg(long)]
+  stats: bool,
+
+  /// Print nothing but the summary, e.g. no global roll-up or --check report
+  /// on stderr. Errors are still reported.
+  #[arg(short, long, conflicts_with_all = ["report", "stats"])]
+  quiet: bool,
+
+  /// Write the summary to this file instead of stdout. The file is replaced
+  /// atomically once the summary is complete.
+  #[arg(long)]
+  output: Option<String>,
+
+  /// The output format: brc, csv, tsv, or parquet. Parquet requires
+  /// --output, and building with the `arrow` feature.
+  #[arg(long, default_value = "brc")]
+  format: OutputFormat,
+
+  /// The field to sort stations by: name, min, max, avg, or count. With none,
+  /// stations are written unsorted, in the order they first appeared in the
+  /// input for single-threaded builds.
+  #[arg(long, default_value = "name")]
+  sort_by: SortKey,
+
+  /// Sort in descending order. Ties are broken by ascending station name.
+  #[arg(long)]
+  desc: bool,
+
+  /// Only print the first N stations in sorted order.
+  #[arg(long, value_name = "N")]
+  top: Option<usize>,
+
+  /// Leave out stations with fewer than N readings. Their readings still
+  /// count towards --global.
+  #[arg(long, value_name = "N", default_value_t = 0)]
+  min_count: u32,
+
+  /// Print the number of readings of each station, as `name=min/mean/max (n)`.
+  #[arg(long)]
+  with_count: bool,
+
+  /// Sort the stations by their index in the table, and format each one
+  /// straight from the table as it is written, instead of sorting copies of
+  /// them. This saves memory for inputs with very many stations.
+  #[arg(long)]
+  stream_sorted: bool,
+
+  /// Print a roll-up of all readings: to stderr for the brc format, or as a
+  /// final row for the csv and tsv formats.
+  #[arg(long)]
+  global: bool,
+
+  /// Compare the summary, in the brc format, against this expected output
+  /// instead of printing it, and fail if they differ.
+  #[arg(long, value_name = "EXPECTED")]
+  check: Option<String>,
+
+  /// Summarize the input, then re-read it with a simple reference parser
+  /// instead of printing the summary, and fail if they differ. This is much
+  /// slower, and reads the whole input into memory.
+  #[arg(long, conflicts_with_all = ["input_dir", "check"])]
+  verify: bool,
+
+  /// Write the flamegraph of the run to this file. Only available when built
+  /// with the `profiled` feature on Unix.
+  #[arg(long, value_name = "PATH")]
+  profile_out: Option<String>,
+
+  #[command(flatten)]
+  build: BuildArgs,
 }
 
-pub fn run_parser() -> BarseResult {
-  let args = Args::try_parse()?;
-  print_summary(&args.input)
+/// The options controlling how the table of summaries is built. Options not
+/// given on the command line are read from the environment, then the config
+/// file.
+#[derive(clap::Args, Debug)]
+struct BuildArgs {
+  /// Read default build options from this TOML file, which sets fields of
+  /// `Options` at the top level, e.g. `threads = 8`.
+  #[arg(long, value_name = "PATH", env = ENV_CONFIG)]
+  config: Option<String>,
+
+  /// Check the format of every record, failing on the first malformed one.
+  /// This is much slower, but malformed input otherwise produces garbage.
+  #[arg(
+    long,
+    env = ENV_STRICT,
+    value_name = "BOOL",
+    num_args = 0..=1,
+    require_equals = true,
+    default_missing_value = "true",
+    value_parser = BoolishValueParser::new()
+  )]
+  strict: Option<bool>,
+
+  /// Skip the first line of the input, e.g. a `station;temperature` header.
+  #[arg(
+    long,
+    env = ENV_SKIP_HEADER,
+    value_name = "BOOL",
+    num_args = 0..=1,
+    require_equals = true,
+    default_missing_value = "true",
+    value_parser = BoolishValueParser::new()
+  )]
+  skip_header: Option<bool>,
+
+  /// The number of worker threads to use, or 0 to use the available
+  /// parallelism.
+  #[arg(long, value_name = "N", env = ENV_THREADS)]
+  threads: Option<usize>,
+
+  /// The number of bytes worker threads claim from the input at a time, with
+  /// an optional K, M or G suffix, e.g. 512K.
+  #[arg(long, value_name = "BYTES", env = ENV_CHUNK_SIZE, value_parser = parse_size)]
+  chunk_size: Option<usize>,
+
+  /// How to build the table: single, per-thread-table, or shared-table. By
+  /// default, small inputs are processed on a single thread and larger ones
+  /// with a shared table.
+  #[arg(long, env = ENV_STRATEGY)]
+  strategy: Option<Strategy>,
+
+  /// Only summarize the station named NAME. May be given more than once.
+  #[arg(long = "station", value_name = "NAME")]
+  stations: Vec<String>,
+
+  /// Only summarize the stations whose names start with PREFIX. May be given
+  /// more than once, and combined with --station.
+  #[arg(long = "station-prefix", value_name = "PREFIX")]
+  station_prefixes: Vec<String>,
+
+  /// The options resolved from every source by `resolve`.
+  #[arg(skip)]
+  options: Options,
 }
 
-fn main() -> ExitCode {
-  #[cfg(feature = "profiled")]
-  let guard = pprof::ProfilerGuardBuilder::default()
-    .frequency(1000)
-    .build()
-    .unwrap();
+#[derive(Subcommand, Debug)]
+enum Command {
+  /// Generate a measurements file of random readings.
+  Generate(GenerateArgs),
+  /// Time repeated runs over the input, without printing its summary.
+  Bench(BenchArgs),
+  /// Check the format of every record of the input, without summarizing it.
+  Verify(VerifyArgs),
+  /// Follow an input which is being appended to, re-printing its summary
+  /// whenever it grows.
+  Watch(WatchArgs),
+}
+
+#[derive(clap::Args, Debug)]
+struct GenerateArgs {
+  /// The stations to generate readings for, with one `name;mean` per line.
+  /// Defaults to data/weather_stations.csv, or a smaller list embedded in the
+  /// binary if that file doesn't exist.
+  #[arg(long)]
+  stations: Option<String>,
+
+  /// The number of readings to generate.
+  #[arg(long, value_name = "N")]
+  rows: u64,
+
+  /// The file to write the readings to.
+  #[arg(long, default_value = "measurements.txt")]
+  out: String,
+
+  /// The seed of the random number generator. The same seed, stations and
+  /// row count always produce the same file.
+  #[arg(long, default_value_t = 0)]
+  seed: u64,
+
+  /// The number of distinct stations to pick from the stations file, which
+  /// must list at least this many.
+  #[arg(long, value_name = "N", default_value_t = 413)]
+  unique_stations: usize,
+
+  /// The number of threads to generate readings with, or 0 to use the
+  /// available parallelism.
+  #[arg(long, value_name = "N", default_value_t = 0)]
+  threads: usize,
+}
+
+#[derive(clap::Args, Debug)]
+struct BenchArgs {
+  #[arg(long, default_value = "measurements.txt")]
+  input: String,
+
+  /// The number of timed runs.
+  #[arg(
+    long,
+    value_name = "N",
+    default_value_t = 5,
+    value_parser = clap::value_parser!(u32).range(1..)
+  )]
+  iterations: u32,
+
+  /// The number of untimed runs before the timed ones.
+  #[arg(long, value_name = "N", default_value_t = 1)]
+  warmup: u32,
+
+  /// The report format: table or json.
+  #[arg(long, default_value = "table")]
+  format: BenchFormat,
+
+  #[command(flatten)]
+  build: BuildArgs,
+}
+
+#[derive(clap::Args, Debug)]
+struct VerifyArgs {
+  /// The input to check, or - to read it from stdin.
+  #[arg(long, default_value = "measurements.txt")]
+  input: String,
+
+  /// Stop after reporting this many malformed records, or report every one
+  /// if 0.
+  #[arg(long, value_name = "N", default_value_t = DEFAULT_MAX_ERRORS)]
+  max_errors: usize,
+}
+
+#[derive(clap::Args, Debug)]
+struct WatchArgs {
+  #[arg(long, default_value = "measurements.txt")]
+  input: String,
+
+  /// How long to wait between checks of the input for new records, with an
+  /// optional ms, s, m or h suffix, e.g. 500ms. Defaults to seconds.
+  #[arg(long, value_name = "DURATION", default_value = "5s", value_parser = parse_duration)]
+  interval: Duration,
+
+  /// Rewrite the summary to this file instead of printing it to stdout. The
+  /// file is replaced atomically each time, so readers never observe a
+  /// partial summary.
+  #[arg(long)]
+  output: Option<String>,
+
+  /// The output format: brc, csv, tsv, or parquet. Parquet requires
+  /// --output, and building with the `arrow` feature.
+  #[arg(long, default_value = "brc")]
+  format: OutputFormat,
 
-  let res = run_parser();
+  /// Stop after checking the input N times, instead of running until
+  /// interrupted.
+  #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+  count: Option<u64>,
 
-  #[cfg(feature = "profiled")]
-  if let Ok(report) = guard.report().build() {
-    let file = std::fs::File::create("brc.svg").unwrap();
-    report.flamegraph(file).unwrap();
+  #[command(flatten)]
+  build: BuildArgs,
+}
+
+impl Args {
+  /// Parses the command line, rejecting invalid combinations of options
+  /// before any file is opened.
+  fn parse_and_validate() -> Result<Self, clap::Error> {
+    let matches = Self::command().try_get_matches()?;
+    let mut args = Self::from_arg_matches(&matches)?;
+    if cfg!(not(all(feature = "profiled", unix))) && args.profile_out.is_some() {
+      return Err(Self::command().error(
+        ErrorKind::ArgumentConflict,
+        "--profile-out requires building with the `profiled` feature on Unix",
+      ));
+    }
+    let (format, output) = match &args.command {
+      Some(Command::Watch(watch_args)) => (watch_args.format, &watch_args.output),
+      _ => (args.format, &args.output),
+    };
+    if format.is_binary() && output.is_none() {
+      return Err(Self::command().error(
+        ErrorKind::MissingRequiredArgument,
+        "--format parquet requires --output",
+      ));
+    }
+    let (build, build_matches) = match (&mut args.command, matches.subcommand()) {
+      (Some(Command::Bench(bench_args)), Some((_, bench_matches))) => {
+        (&mut bench_args.build, bench_matches)
+      }
+      (Some(Command::Watch(watch_args)), Some((_, watch_matches))) => {
+        (&mut watch_args.build, watch_matches)
+      }
+      _ => (&mut args.build, &matches),
+    };
+    build.resolve(build_matches).map_err(|err| {
+      // clap prefixes the message with "error: " itself.
+      let message = err.to_string();
+      let message = message.strip_prefix("error: ").unwrap_or(&message);
+      Self::command().error(ErrorKind::ValueValidation, message)
+    })?;
+    Ok(args)
+  }
+}
+
+impl BuildArgs {
+  /// Resolves the build options from the command line, the environment and
+  /// the config file, in that order of precedence, and checks that they are
+  /// valid.
+  fn resolve(&mut self, matches: &ArgMatches) -> BarseResult {
+    let mut command_line = OptionOverrides {
+      threads: self.threads,
+      chunk_size: self.chunk_size,
+      strategy: self.strategy,
+      strict: self.strict,
+      skip_header: self.skip_header,
+      ..OptionOverrides::default()
+    };
+    // clap merges the environment into the command line, so separate them
+    // again to report where invalid options came from.
+    let mut environment = OptionOverrides::default();
+    let from_env = |id: &str| matches.value_source(id) == Some(ValueSource::EnvVariable);
+    if from_env("threads") {
+      environment.threads = command_line.threads.take();
+    }
+    if from_env("chunk_size") {
+      environment.chunk_size = command_line.chunk_size.take();
+    }
+    if from_env("strategy") {
+      environment.strategy = command_line.strategy.take();
+    }
+    if from_env("strict") {
+      environment.strict = command_line.strict.take();
+    }
+    if from_env("skip_header") {
+      environment.skip_header = command_line.skip_header.take();
+    }
+    let config_file = match &self.config {
+      Some(config_path) => read_config(config_path)?,
+      None => OptionOverrides::default(),
+    };
+
+    self.options = resolve(&[
+      (Source::CommandLine, &command_line),
+      (Source::Environment, &environment),
+      (Source::ConfigFile, &config_file),
+    ])?;
+    if !(self.stations.is_empty() && self.station_prefixes.is_empty()) {
+      self.options.station_filter =
+        Some(StationFilter::new(&self.stations)?.with_prefixes(&self.station_prefixes));
+    }
+    Ok(())
+  }
+
+  fn build_options(&self) -> Options {
+    self.options.clone()
+  }
+}
+
+fn write_output<W: Write>(args: &Args, out: W) -> BarseResult {
+  let options = SummaryOptions {
+    format: args.format,
+    sort_by: args.sort_by,
+    descending: args.desc,
+    limit: args.top,
+    min_count: args.min_count,
+    include_count: args.with_count,
+    global: args.global,
+    stream_sorted: args.stream_sorted,
+    quiet: args.quiet,
+    build: args.build.build_options(),
   };
+  if let Some(dir) = &args.input_dir {
+    let extension = args.input_ext.as_deref();
+    if !(args.stats || args.report) {
+      return write_dir_summary_with_stats(dir, extension, &options, out).map(|_| ());
+    }
+    let (report, stats) =
+      with_perf_counters(|| write_dir_summary_with_stats(dir, extension, &options, out))?;
+    eprintln!("{report}");
+    if args.stats {
+      eprintln!("{stats}");
+    }
+    Ok(())
+  } else if args.stats {
+    let (report, stats) =
+      with_perf_counters(|| write_summary_with_stats(&args.input, &options, out))?;
+    eprintln!("{report}");
+    eprintln!("{stats}");
+    Ok(())
+  } else if args.report {
+    let report = with_perf_counters(|| write_summary_with_report(&args.input, &options, out))?;
+    eprintln!("{report}");
+    Ok(())
+  } else {
+    write_summary_with_options(&args.input, &options, out)
+  }
+}
+
+#[cfg(not(all(feature = "perf-counters", target_os = "linux")))]
+fn with_perf_counters<T>(f: impl FnOnce() -> T) -> T {
+  f()
+}
+
+/// Calls `f`, printing the hardware counters of the call to stderr, or why
+/// they couldn't be read.
+#[cfg(all(feature = "perf-counters", target_os = "linux"))]
+fn with_perf_counters<T>(f: impl FnOnce() -> T) -> T {
+  let (result, counts) = perf_counters::measure(f);
+  match counts {
+    Ok(counts) => eprintln!("{counts}"),
+    Err(err) => eprintln!("{err}"),
+  }
+  result
+}
+
+fn check_output(args: &Args, expected_path: &str) -> BarseResult {
+  let expected = BufReader::new(File::open(expected_path)?);
+  let report = verify_against(&args.input, expected)?;
+  if !args.quiet {
+    eprintln!("{report}");
+  }
+  if report.is_match() {
+    Ok(())
+  } else {
+    Err(BarseError::new(format!(
+      "summary of {} does not match {expected_path}",
+      args.input
+    )))
+  }
+}
 
-  if let Err(err) = res {
-    println!("{err}");
-    ExitCode::FAILURE
+fn verify_output(args: &Args) -> BarseResult {
+  let report = verify_with_reference(&args.input, &args.build.build_options())?;
+  if !args.quiet {
+    eprintln!("{report}");
+  }
+  if report.is_match() {
+    Ok(())
   } else {
+    Err(BarseError::new(format!(
+      "summary of {} does not match the reference parser",
+      args.input
+    )))
+  }
+}
+
+fn run_parser(args: &Args) -> BarseResult {
+  if let Some(expected_path) = &args.check {
+    return check_output(args, expected_path);
+  }
+  if args.verify {
+    return verify_output(args);
+  }
+  match &args.output {
+    Some(output_path) => write_atomically(output_path, |out| write_output(args, out)),
+    None => write_output(args, BufWriter::new(io::stdout().lock())),
+  }
+}
+
+fn run_generator(args: &GenerateArgs) -> BarseResult {
+  let stations = match &args.stations {
+    Some(path) => read_stations(path).map_err(|err| err.in_file(path))?,
+    None => default_stations()?,
+  };
+  let threads = match args.threads {
+    0 => std::thread::available_parallelism().map_or(1, |threads| threads.get()),
+    threads => threads,
+  };
+  Generator::new(&stations, args.unique_stations, args.seed)?
+    .write_file(args.rows, &args.out, threads)
+    .map_err(|err| err.in_file(&args.out))
+}
+
+fn run_bench(args: &BenchArgs) -> BarseResult {
+  let options = BenchOptions {
+    iterations: args.iterations as usize,
+    warmup: args.warmup as usize,
+    build: args.build.build_options(),
+  };
+  let report = bench::run_bench(&args.input, &options)?;
+  report.write(args.format, io::stdout().lock())?;
+  Ok(())
+}
+
+/// Checks the input for new records every `args.interval`, and writes the
+/// summary of every record so far the first time and whenever it grew. Each
+/// summary printed to stdout is preceded by a `==> input (N bytes) <==`
+/// header, giving the bytes of complete records summarized. If the input
+/// shrinks, it is assumed to have been truncated or rotated, and is
+/// summarized again from its start.
+fn run_watch(args: &WatchArgs) -> BarseResult {
+  let options = SummaryOptions {
+    format: args.format,
+    build: args.build.build_options(),
+    ..SummaryOptions::default()
+  };
+  let mut aggregator = IncrementalAggregator::new(&options.build)?;
+  let mut checks = 0;
+  while args.count.is_none_or(|count| checks < count) {
+    if checks > 0 {
+      thread::sleep(args.interval);
+    }
+    checks += 1;
+    let poll = aggregator.poll_file(&args.input)?;
+    if poll == Poll::Reset {
+      eprintln!("{} shrank, summarizing it again from the start", args.input);
+    }
+    if poll == Poll::Unchanged && checks > 1 {
+      continue;
+    }
+
+    match &args.output {
+      Some(output_path) => write_atomically(output_path, |out| {
+        write_table_summary(aggregator.table(), &options, out)
+      })?,
+      None => {
+        let mut out = BufWriter::new(io::stdout().lock());
+        writeln!(
+          out,
+          "==> {} ({} bytes) <==",
+          args.input,
+          aggregator.parsed_offset()
+        )?;
+        write_table_summary(aggregator.table(), &options, &mut out)?;
+        out.flush()?;
+      }
+    }
+  }
+  Ok(())
+}
+
+/// Prints the malformed records of the input and a summary, exiting with
+/// `EXIT_DATA` if there are any.
+fn run_verify(args: &VerifyArgs) -> BarseResult<ExitCode> {
+  let options = ValidateOptions {
+    max_errors: args.max_errors,
+    ..ValidateOptions::default()
+  };
+  let report = if args.input == "-" {
+    validate_reader(io::stdin().lock(), &options)?
+  } else {
+    validate_file(&args.input, &options)?
+  };
+  println!("{report}");
+  Ok(if report.is_valid() {
     ExitCode::SUCCESS
+  } else {
+    ExitCode::from(EXIT_DATA)
+  })
+}
+
+#[cfg(all(feature = "profiled", unix))]
+fn write_flamegraph(guard: &pprof::ProfilerGuard<'_>, path: &str) -> BarseResult {
+  let report = guard
+    .report()
+    .build()
+    .map_err(|err| BarseError::new(format!("failed to build profile: {err}")))?;
+  let file = File::create(path).map_err(|err| BarseError::from(err).in_file(path))?;
+  report
+    .flamegraph(file)
+    .map_err(|err| BarseError::new(format!("failed to write flamegraph: {err}")).in_file(path))
+}
+
+fn exit_code(err: &BarseError) -> u8 {
+  match err.without_context() {
+    BarseError::Io(_) => EXIT_IO,
+    BarseError::MalformedRecord { .. } => EXIT_DATA,
+    BarseError::ThreadPanicked { .. } | BarseError::Other(_) | BarseError::InFile { .. } => {
+      EXIT_INTERNAL
+    }
   }
 }
+
+fn main() -> ExitCode {
+  let args = match Args::parse_and_validate() {
+    Ok(args) => args,
+    Err(err) => {
+      // `--help` is reported as an error too, but is printed to stdout.
+      let _ = err.print();
+      return if err.use_stderr() {
+        ExitCode::from(EXIT_USAGE)
+      } else {
+        ExitCode::SUCCESS
+      };
+    }
+  };
+
+  #[cfg(all(feature = "profiled", unix))]
+  let guard = match pprof::ProfilerGuardBuilder::default()
+    .frequency(1000)
+    .build()
+  {
+    Ok(guard) => guard,
+    Err(err) => {
+      eprintln!(
+        "{}",
+        BarseError::new(format!("failed to start profiler: {err}"))
+      );
+      return ExitCode::from(EXIT_INTERNAL);
+    }
+  };
+
+  let res = match &args.command {
+    Some(Command::Generate(generate_args)) => {
+      run_generator(generate_args).map(|()| ExitCode::SUCCESS)
+    }
+    Some(Command::Bench(bench_args)) => run_bench(bench_args).map(|()| ExitCode::SUCCESS),
+    Some(Command::Verify(verify_args)) => run_verify(verify_args),
+    Some(Command::Watch(watch_args)) => run_watch(watch_args).map(|()| ExitCode::SUCCESS),
+    None => run_parser(&args).map(|()| ExitCode::SUCCESS),
+  };
+
+  #[cfg(all(feature = "profiled", unix))]
+  let res = {
+    // The run is profiled even if it fails, but its error takes precedence.
+    let profile_path = args.profile_out.as_deref().unwrap_or(DEFAULT_PROFILE_OUT);
+    let profile_res = write_flamegraph(&guard, profile_path);
+    res.and_then(|exit_code| profile_res.map(|()| exit_code))
+  };
+
+  res.unwrap_or_else(|err| {
+    eprintln!("{err}");
+    ExitCode::from(exit_code(&err))
+  })
+}
@@ -0,0 +1,219 @@
+use std::fmt::Display;
+
+use crate::{temperature_reading::TemperatureReading, temperature_summary::TemperatureSummary};
+
+/// The exact median of a station's readings: the middle reading if there are
+/// an odd number of them, and otherwise the mean of the two middle readings.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Median {
+  /// The reading at rank `(count - 1) / 2` in sorted order.
+  pub lower: TemperatureReading,
+  /// The reading at rank `count / 2` in sorted order, which is `lower` if
+  /// there are an odd number of readings.
+  pub upper: TemperatureReading,
+}
+
+impl Median {
+  /// The median in degrees.
+  pub fn degrees(&self) -> f64 {
+    (self.lower.reading() as f64 + self.upper.reading() as f64) / 20.
+  }
+}
+
+impl Display for Median {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    if self.lower == self.upper {
+      write!(f, "{}", self.lower)
+    } else {
+      write!(f, "{:.2}", self.degrees())
+    }
+  }
+}
+
+/// Counts of every reading of a station, which are small integers between the
+/// station's min and max, so exact quantiles can be read off without storing
+/// the readings.
+#[derive(Clone, Debug)]
+pub struct ReadingHistogram {
+  min: TemperatureReading,
+  /// The number of readings of `min + i` tenths of a degree at index `i`.
+  counts: Vec<u32>,
+  count: u64,
+}
+
+impl ReadingHistogram {
+  /// Constructs an empty histogram of readings between `min` and `max`,
+  /// inclusive. An empty range, with `min` above `max`, holds no readings.
+  pub fn new(min: TemperatureReading, max: TemperatureReading) -> Self {
+    let len = (max.reading() as i32 - min.reading() as i32 + 1).max(0) as usize;
+    Self {
+      min,
+      counts: vec![0; len],
+      count: 0,
+    }
+  }
+
+  /// Constructs an empty histogram spanning the readings of `summary`.
+  pub fn for_summary(summary: &TemperatureSummary) -> Self {
+    Self::new(summary.min(), summary.max())
+  }
+
+  /// The number of readings added to the histogram.
+  pub fn count(&self) -> u64 {
+    self.count
+  }
+
+  /// Counts `temp`, returning false without counting it if it is outside of
+  /// the histogram's range.
+  pub fn add_reading(&mut self, temp: TemperatureReading) -> bool {
+    let index = temp.reading() as i32 - self.min.reading() as i32;
+    match usize::try_from(index)
+      .ok()
+      .and_then(|index| self.counts.get_mut(index))
+    {
+      Some(count) => {
+        *count += 1;
+        self.count += 1;
+        true
+      }
+      None => false,
+    }
+  }
+
+  /// Returns the reading at `rank` in sorted order, starting from 0, or
+  /// `None` if there are no more than `rank` readings.
+  pub fn nth(&self, rank: u64) -> Option<TemperatureReading> {
+    let mut remaining = rank;
+    for (offset, &count) in self.counts.iter().enumerate() {
+      if remaining < count as u64 {
+        return Some(TemperatureReading::new(self.min.reading() + offset as i16));
+      }
+      remaining -= count as u64;
+    }
+    None
+  }
+
+  /// Returns the exact median of the readings, or `None` if there are none.
+  pub fn median(&self) -> Option<Median> {
+    let count = self.count.checked_sub(1)?;
+    Some(Median {
+      lower: self.nth(count / 2)?,
+      upper: self.nth(count.div_ceil(2))?,
+    })
+  }
+}
+
+/// The summary and exact median of a station's readings.
+#[derive(Clone, Debug)]
+pub struct StationMedian {
+  pub name: String,
+  pub summary: TemperatureSummary,
+  pub median: Median,
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{collections::HashMap, fs};
+
+  use googletest::prelude::*;
+  use itertools::Itertools;
+
+  use crate::{
+    barse::build_exact_medians,
+    median::{Median, ReadingHistogram},
+    temperature_reading::TemperatureReading,
+    test_util::{random_input_file, simple_scanner_iter},
+  };
+
+  fn reading(reading: i16) -> TemperatureReading {
+    TemperatureReading::new(reading)
+  }
+
+  /// The median of `readings` by sorting them.
+  fn sorted_median(mut readings: Vec<i16>) -> Median {
+    readings.sort_unstable();
+    let count = readings.len() - 1;
+    Median {
+      lower: reading(readings[count / 2]),
+      upper: reading(readings[count.div_ceil(2)]),
+    }
+  }
+
+  #[gtest]
+  fn test_histogram_median() {
+    let mut histogram = ReadingHistogram::new(reading(-20), reading(35));
+    expect_eq!(histogram.median(), None);
+    for temp in [35, -20, 7, 7, 12] {
+      expect_true!(histogram.add_reading(reading(temp)));
+    }
+    expect_eq!(
+      histogram.median(),
+      Some(sorted_median(vec![35, -20, 7, 7, 12]))
+    );
+    expect_eq!(histogram.median().unwrap().to_string(), "0.7");
+
+    expect_true!(histogram.add_reading(reading(20)));
+    let median = histogram.median().unwrap();
+    expect_eq!(
+      median,
+      Median {
+        lower: reading(7),
+        upper: reading(12)
+      }
+    );
+    expect_eq!(median.degrees(), 0.95);
+    expect_eq!(median.to_string(), "0.95");
+
+    expect_eq!(histogram.nth(0), Some(reading(-20)));
+    expect_eq!(histogram.nth(5), Some(reading(35)));
+    expect_eq!(histogram.nth(6), None);
+  }
+
+  #[gtest]
+  fn test_histogram_rejects_readings_out_of_range() {
+    let mut histogram = ReadingHistogram::new(reading(-5), reading(5));
+    expect_false!(histogram.add_reading(reading(-6)));
+    expect_false!(histogram.add_reading(reading(6)));
+    expect_eq!(histogram.count(), 0);
+
+    let mut empty = ReadingHistogram::new(reading(1), reading(0));
+    expect_false!(empty.add_reading(reading(0)));
+    expect_eq!(empty.median(), None);
+  }
+
+  #[gtest]
+  fn test_exact_medians_match_sorted_medians() {
+    let input = random_input_file(0x3e61d4, 100_000, 50).unwrap();
+    let input_path =
+      std::env::temp_dir().join(format!("barse_exact_medians_{}.txt", std::process::id()));
+    fs::write(&input_path, input.exact_slice()).unwrap();
+    let stations = build_exact_medians(input_path.to_str().unwrap()).unwrap();
+    fs::remove_file(&input_path).unwrap();
+
+    let mut readings = HashMap::<_, Vec<_>>::new();
+    for (station, temp) in simple_scanner_iter(input.exact_slice()) {
+      readings.entry(station).or_default().push(temp.reading());
+    }
+    let expected = readings
+      .into_iter()
+      .map(|(station, readings)| (station.to_owned(), sorted_median(readings)))
+      .sorted_by(|(a, _), (b, _)| a.cmp(b))
+      .collect_vec();
+
+    expect_that!(
+      stations
+        .iter()
+        .map(|station| station.summary.count as usize)
+        .sum::<usize>(),
+      eq(100_000)
+    );
+    expect_eq!(
+      stations
+        .into_iter()
+        .map(|station| (station.name, station.median))
+        .sorted_by(|(a, _), (b, _)| a.cmp(b))
+        .collect_vec(),
+      expected
+    );
+  }
+}
@@ -0,0 +1,326 @@
+use crate::{
+  error::{BarseError, BarseResult},
+  options::Options,
+  scanner::MAX_STATION_NAME_LEN,
+  station_filter::StationFilter,
+  temperature_reading::TemperatureReading,
+};
+
+/// Normalizes station names by lowercasing ASCII letters, so that e.g.
+/// "Berlin" and "BERLIN" are aggregated as the same station.
+pub fn ascii_lowercase(name: &str, out: &mut String) {
+  out.extend(name.chars().map(|c| c.to_ascii_lowercase()));
+}
+
+/// A normalizer of station names for `Options::normalize`, which returns the
+/// key to aggregate the readings of `name` under. The key may be `name`
+/// itself or a slice of it, or be written to `buf`, which is empty when the
+/// normalizer is called, and returned with `NameBuf::as_str`.
+pub type Normalize = for<'a> fn(&'a str, &'a mut NameBuf) -> &'a str;
+
+/// `ascii_lowercase` as a `Normalize` function. Names without uppercase ASCII
+/// letters are returned as they are, without being copied.
+pub fn ascii_lowercase_key<'a>(name: &'a str, buf: &'a mut NameBuf) -> &'a str {
+  if !name.bytes().any(|b| b.is_ascii_uppercase()) {
+    return name;
+  }
+  for c in name.chars() {
+    buf.push(c.to_ascii_lowercase());
+  }
+  buf.as_str()
+}
+
+/// Groups station names by the prefix before their first '/', e.g. "US/NYC"
+/// and "US/LA" under "US". Names without a '/' are grouped by themselves.
+pub fn country_prefix(name: &str) -> &str {
+  name.split_once('/').map_or(name, |(prefix, _)| prefix)
+}
+
+/// Returns the key `key_fn` groups `station` under, failing if it is longer
+/// than `MAX_STATION_NAME_LEN` bytes, which keys sliced from the station name
+/// never are.
+pub(crate) fn group_key<'a, K>(key_fn: &K, station: &'a str) -> BarseResult<&'a str>
+where
+  K: Fn(&str) -> &str,
+{
+  let key = key_fn(station);
+  if key.len() > MAX_STATION_NAME_LEN {
+    return Err(BarseError::new(format!(
+      "Grouping key \"{key}\" is longer than {MAX_STATION_NAME_LEN} bytes"
+    )));
+  }
+  Ok(key)
+}
+
+/// The number of bytes a `NameBuf` holds, past which names are truncated.
+const NAME_BUF_CAPACITY: usize = 64;
+
+/// A station name stored inline, with enough padding after it for the
+/// hashing and comparison routines, which read past the end of station names
+/// under the assumption that they point into the padded input buffer. This
+/// lets normalized names be looked up in the tables without allocating.
+///
+/// Names longer than 64 bytes are truncated to the last whole character that
+/// fits. Keys are limited to `MAX_STATION_NAME_LEN` bytes, so truncated names
+/// are still rejected as too long.
+#[repr(C, align(64))]
+pub struct NameBuf {
+  bytes: [u8; 2 * NAME_BUF_CAPACITY],
+  len: usize,
+}
+
+impl NameBuf {
+  pub const fn new() -> Self {
+    Self {
+      bytes: [0; 2 * NAME_BUF_CAPACITY],
+      len: 0,
+    }
+  }
+
+  pub fn clear(&mut self) {
+    self.len = 0;
+  }
+
+  /// Appends `s`, or as many of its leading characters as fit.
+  pub fn push_str(&mut self, s: &str) {
+    let mut len = s.len().min(NAME_BUF_CAPACITY - self.len);
+    while !s.is_char_boundary(len) {
+      len -= 1;
+    }
+    self.bytes[self.len..self.len + len].copy_from_slice(&s.as_bytes()[..len]);
+    self.len += len;
+  }
+
+  pub fn push(&mut self, c: char) {
+    self.push_str(c.encode_utf8(&mut [0; 4]));
+  }
+
+  pub fn as_str(&self) -> &str {
+    // Only whole characters of `str`s are appended.
+    unsafe { str::from_utf8_unchecked(&self.bytes[..self.len]) }
+  }
+}
+
+impl Default for NameBuf {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+/// What `add_checked_records` does to each record besides aggregating its
+/// reading, as set in `Options`.
+#[derive(Clone)]
+pub(crate) struct RecordChecks {
+  normalize: Option<Normalize>,
+  missing_reading: Option<TemperatureReading>,
+  station_filter: Option<StationFilter>,
+}
+
+impl RecordChecks {
+  pub fn new(options: &Options) -> Self {
+    Self {
+      normalize: options.normalize,
+      missing_reading: options.missing_reading,
+      station_filter: options.station_filter.clone(),
+    }
+  }
+
+  /// True if records need only be aggregated, so builders may skip
+  /// `add_checked_records`.
+  pub fn is_empty(&self) -> bool {
+    self.normalize.is_none() && self.missing_reading.is_none() && self.station_filter.is_none()
+  }
+}
+
+/// Calls `add_reading` with the key `checks.normalize` returns for the station
+/// of each of `records`, or the station itself if there is no normalizer,
+/// stopping at the first error. Records of stations `checks.station_filter`
+/// doesn't match are dropped, and records with `checks.missing_reading` as
+/// their reading are skipped, and their number returned. The station names
+/// must point into the padded input buffer.
+///
+/// This is kept out of line, as the builders' loops over well-formed records
+/// are measurably slower when inlined alongside it, so builders only call it
+/// when `Options::strict` is set or `RecordChecks` are not empty.
+#[inline(never)]
+pub(crate) fn add_checked_records<'a>(
+  records: impl Iterator<Item = BarseResult<(&'a str, TemperatureReading)>>,
+  checks: &RecordChecks,
+  mut add_reading: impl FnMut(&str, TemperatureReading),
+) -> BarseResult<u64> {
+  let mut keys = KeyNormalizer::new(checks.normalize);
+  let mut missing = 0;
+  for record in records {
+    let (station, temp) = record?;
+    if checks
+      .station_filter
+      .as_ref()
+      .is_some_and(|filter| !filter.matches(station))
+    {
+      continue;
+    }
+    if checks.missing_reading == Some(temp) {
+      missing += 1;
+      continue;
+    }
+    add_reading(keys.key(station)?, temp);
+  }
+  Ok(missing)
+}
+
+/// Applies `Options::normalize` to the station names yielded by the scanners,
+/// which point into the padded input buffer.
+struct KeyNormalizer {
+  normalize: Option<Normalize>,
+  buf: NameBuf,
+  /// Holds keys which point neither into the station name nor `buf`, and so
+  /// may not be padded.
+  copied: NameBuf,
+}
+
+impl KeyNormalizer {
+  fn new(normalize: Option<Normalize>) -> Self {
+    Self {
+      normalize,
+      buf: NameBuf::new(),
+      copied: NameBuf::new(),
+    }
+  }
+
+  /// Returns the key to aggregate the readings of `station` under, which is
+  /// `station` itself if there is no normalizer. `station` must point into
+  /// the padded input buffer.
+  fn key<'a>(&'a mut self, station: &'a str) -> BarseResult<&'a str> {
+    let Some(normalize) = self.normalize else {
+      return Ok(station);
+    };
+    self.buf.clear();
+    let buf_range = self.buf.bytes.as_ptr_range();
+    let key = normalize(station, &mut self.buf);
+    if key.len() > MAX_STATION_NAME_LEN {
+      return Err(BarseError::new(format!(
+        "Normalized station name \"{key}\" is longer than {MAX_STATION_NAME_LEN} bytes"
+      )));
+    }
+    let station_range = station.as_bytes().as_ptr_range();
+    let in_station = station_range.contains(&key.as_ptr()) || key.as_ptr() == station_range.end;
+    if in_station || buf_range.contains(&key.as_ptr()) {
+      return Ok(key);
+    }
+    self.copied.clear();
+    self.copied.push_str(key);
+    Ok(self.copied.as_str())
+  }
+}
+
+/// Applies a normalizer to station names, keeping the result in a buffer
+/// which can be used as a key into the weather station tables.
+pub(crate) struct NameNormalizer<'a, F> {
+  normalizer: &'a F,
+  out: String,
+  name: NameBuf,
+}
+
+impl<'a, F> NameNormalizer<'a, F>
+where
+  F: Fn(&str, &mut String),
+{
+  pub fn new(normalizer: &'a F) -> Self {
+    Self {
+      normalizer,
+      out: String::with_capacity(MAX_STATION_NAME_LEN),
+      name: NameBuf::new(),
+    }
+  }
+
+  /// Returns the normalized form of `station`, which is valid until the next
+  /// call to `normalize`.
+  pub fn normalize(&mut self, station: &str) -> BarseResult<&str> {
+    self.out.clear();
+    (self.normalizer)(station, &mut self.out);
+    let len = self.out.len();
+    if len > MAX_STATION_NAME_LEN {
+      return Err(BarseError::new(format!(
+        "Normalized station name \"{}\" is longer than {MAX_STATION_NAME_LEN} bytes",
+        self.out
+      )));
+    }
+    self.name.clear();
+    self.name.push_str(&self.out);
+    Ok(self.name.as_str())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use googletest::prelude::*;
+
+  use crate::{
+    error::BarseError,
+    normalize::{
+      ascii_lowercase, ascii_lowercase_key, country_prefix, group_key, NameBuf, NameNormalizer,
+    },
+  };
+
+  #[gtest]
+  fn test_ascii_lowercase() {
+    let mut normalizer = NameNormalizer::new(&ascii_lowercase);
+    expect_that!(normalizer.normalize("BeRLiN"), ok(eq(&"berlin")));
+    expect_that!(normalizer.normalize("ZÜRICH"), ok(eq(&"zÜrich")));
+    expect_that!(normalizer.normalize(""), ok(eq(&"")));
+  }
+
+  #[gtest]
+  fn test_normalized_name_too_long() {
+    let mut normalizer = NameNormalizer::new(&|name: &str, out: &mut String| {
+      out.push_str(name);
+      out.push_str(name);
+    });
+    expect_that!(
+      normalizer.normalize(&"a".repeat(25)),
+      ok(eq(&"a".repeat(50)))
+    );
+    expect_true!(matches!(
+      normalizer.normalize(&"a".repeat(26)),
+      Err(BarseError::Other(_))
+    ));
+  }
+
+  #[gtest]
+  fn test_ascii_lowercase_key() {
+    let name = "berlin";
+    let mut buf = NameBuf::new();
+    expect_eq!(ascii_lowercase_key(name, &mut buf).as_ptr(), name.as_ptr());
+    let mut buf = NameBuf::new();
+    expect_eq!(ascii_lowercase_key("ZÜRICH", &mut buf), "zÜrich");
+  }
+
+  #[gtest]
+  fn test_name_buf_truncates_at_char_boundary() {
+    let mut buf = NameBuf::new();
+    buf.push_str(&"a".repeat(63));
+    buf.push('ü');
+    expect_eq!(buf.as_str(), "a".repeat(63));
+    buf.push('b');
+    expect_eq!(buf.as_str(), format!("{}b", "a".repeat(63)));
+    buf.push_str("c");
+    expect_eq!(buf.as_str().len(), 64);
+    buf.clear();
+    expect_eq!(buf.as_str(), "");
+  }
+
+  #[gtest]
+  fn test_group_key() {
+    expect_that!(group_key(&country_prefix, "US/NYC"), ok(eq(&"US")));
+    expect_that!(group_key(&country_prefix, "US/"), ok(eq(&"US")));
+    expect_that!(group_key(&country_prefix, "Oslo"), ok(eq(&"Oslo")));
+
+    fn too_long(_: &str) -> &str {
+      "Llanfairpwllgwyngyllgogerychwyrndrobwllllantysiliogogogoch"
+    }
+    expect_true!(matches!(
+      group_key(&too_long, "Llanfair"),
+      Err(BarseError::Other(_))
+    ));
+  }
+}
@@ -0,0 +1,272 @@
+use crate::{
+  build_report::Strategy,
+  error::{BarseError, BarseResult},
+  normalize::Normalize,
+  scanner::{ExtraFields, BUFFER_OVERLAP},
+  station_filter::StationFilter,
+  str_hash::TABLE_SIZE,
+  temperature_reading::{DecimalSeparator, TemperatureReading},
+};
+
+/// The size of inputs below which the single-threaded fold outperforms the
+/// multithreaded path, since thread spawning, per-thread table initialization
+/// and merging dominate for small files.
+pub const DEFAULT_SINGLE_THREADED_THRESHOLD: usize = 8 * 1024 * 1024;
+/// The smallest amount of input worth handing to a worker thread.
+pub const DEFAULT_MIN_CHUNK_SIZE: usize = 2 * 1024 * 1024;
+/// The number of bytes worker threads claim from the input at a time.
+pub const DEFAULT_CHUNK_SIZE: usize = 2 * 1024 * 1024;
+
+/// Tuning knobs for building the weather station table. More may be added,
+/// so construct them from `Options::default()`.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct Options {
+  /// Inputs shorter than this many bytes are processed on the calling
+  /// thread, even when the `multithreaded` feature is enabled.
+  pub single_threaded_threshold: usize,
+  /// The number of worker threads is capped at `input_len / min_chunk_size`.
+  pub min_chunk_size: usize,
+  /// The number of buckets in the weather station table. This must be a
+  /// power of two, and greater than the number of distinct stations in the
+  /// input.
+  pub table_capacity: usize,
+  /// The number of bytes worker threads claim from the input at a time. This
+  /// must be a nonzero multiple of `BUFFER_OVERLAP`.
+  pub chunk_size: usize,
+  /// The number of worker threads to use, or `None` to use the available
+  /// parallelism.
+  pub threads: Option<usize>,
+  /// The decimal separator used by temperature readings in the input.
+  pub decimal_separator: DecimalSeparator,
+  /// Check the format of every record, failing with the offset of the first
+  /// malformed record. This is much slower, but otherwise malformed input
+  /// produces undefined results. Trailing whitespace after readings is
+  /// tolerated in this mode only.
+  pub strict: bool,
+  /// Skip the first record of the input, e.g. a `station;temperature` header
+  /// line, which need not be well-formed.
+  pub skip_header: bool,
+  /// Whether records may have more fields after the temperature reading,
+  /// e.g. `station;temperature;humidity`, which are then ignored. With
+  /// `ExtraFields::Skip`, the fields after the reading may be up to
+  /// `MAX_EXTRA_FIELDS_LEN` bytes long.
+  pub extra_fields: ExtraFields,
+  /// Aggregates the readings of each station under the key `normalize`
+  /// returns for its name, e.g. `normalize::ascii_lowercase_key`, so that
+  /// spellings which normalize to the same key are summarized together, under
+  /// that key. Keys must be at most `MAX_STATION_NAME_LEN` bytes long.
+  ///
+  /// Keys are hashed rather than the names in the input, so tables, and any
+  /// summaries or fingerprints saved from them, are only comparable with
+  /// those built with the same normalizer.
+  pub normalize: Option<Normalize>,
+  /// A reading which stands for no data, e.g. `-99.9` from sensors which
+  /// report that when they fail. Records with this reading are left out of
+  /// the summaries, and counted in `BuildReport::missing_records` instead.
+  pub missing_reading: Option<TemperatureReading>,
+  /// Only aggregates the readings of the stations `station_filter` matches,
+  /// dropping other records before they touch the table.
+  pub station_filter: Option<StationFilter>,
+  /// The strategy to build the table with, or `None` to build it on the
+  /// calling thread if the input is shorter than `single_threaded_threshold`,
+  /// and with `Strategy::MultiThreaded` otherwise. Only
+  /// `Strategy::SingleThreaded` is available without the `multithreaded`
+  /// feature.
+  pub strategy: Option<Strategy>,
+}
+
+impl Options {
+  /// Checks that the options describe a valid build.
+  pub fn validate(&self) -> BarseResult {
+    if self.table_capacity < 2 || !self.table_capacity.is_power_of_two() {
+      return Err(BarseError::new(format!(
+        "Table capacity must be a power of two of at least 2, got {}",
+        self.table_capacity
+      )));
+    }
+    if self.chunk_size == 0 || !self.chunk_size.is_multiple_of(BUFFER_OVERLAP) {
+      return Err(BarseError::new(format!(
+        "Chunk size must be a nonzero multiple of {BUFFER_OVERLAP}, got {}",
+        self.chunk_size
+      )));
+    }
+    if self.threads == Some(0) {
+      return Err(BarseError::new("Thread count must be nonzero".to_owned()));
+    }
+    if cfg!(not(feature = "multithreaded"))
+      && self
+        .strategy
+        .is_some_and(|strategy| strategy != Strategy::SingleThreaded)
+    {
+      return Err(BarseError::new(
+        "Multithreaded strategies require the \"multithreaded\" feature".to_owned(),
+      ));
+    }
+    Ok(())
+  }
+}
+
+impl Default for Options {
+  fn default() -> Self {
+    Self {
+      single_threaded_threshold: DEFAULT_SINGLE_THREADED_THRESHOLD,
+      min_chunk_size: DEFAULT_MIN_CHUNK_SIZE,
+      table_capacity: TABLE_SIZE,
+      chunk_size: DEFAULT_CHUNK_SIZE,
+      threads: None,
+      decimal_separator: DecimalSeparator::Dot,
+      strict: false,
+      skip_header: false,
+      extra_fields: ExtraFields::Forbid,
+      normalize: None,
+      missing_reading: None,
+      station_filter: None,
+      strategy: None,
+    }
+  }
+}
+
+/// Hints about the input, from which `Options` sized for it are derived.
+/// Unset hints leave the corresponding defaults in place.
+#[derive(Clone, Debug, Default)]
+pub struct BuildConfig {
+  /// An upper bound on the number of distinct stations in the input.
+  pub expected_stations: Option<usize>,
+  /// The approximate size of the input in bytes. This is a `u64`, as inputs
+  /// may be larger than the address space of 32-bit targets.
+  pub expected_bytes: Option<u64>,
+  /// The number of worker threads to use.
+  pub threads: Option<usize>,
+}
+
+impl BuildConfig {
+  /// The highest fraction of table buckets that may be occupied by the
+  /// expected number of stations.
+  pub const MAX_LOAD_FACTOR: f64 = 0.5;
+  /// The number of chunks to split the input into per thread, so that threads
+  /// which fall behind don't hold up the build.
+  const CHUNKS_PER_THREAD: usize = 16;
+  /// Chunk sizes are rounded to a multiple of this many bytes.
+  const CHUNK_GRANULARITY: usize = 64 * 1024;
+  const MAX_CHUNK_SIZE: usize = 64 * 1024 * 1024;
+
+  /// Returns the table capacity for `expected_stations` distinct stations.
+  pub fn table_capacity(expected_stations: usize) -> usize {
+    ((expected_stations as f64 / Self::MAX_LOAD_FACTOR).ceil() as usize)
+      .next_power_of_two()
+      .max(2)
+  }
+
+  /// Returns the chunk size splitting `expected_bytes` of input into roughly
+  /// `CHUNKS_PER_THREAD` chunks per thread.
+  pub fn chunk_size(expected_bytes: u64, threads: usize) -> usize {
+    (expected_bytes / (threads.max(1) * Self::CHUNKS_PER_THREAD) as u64)
+      .next_multiple_of(Self::CHUNK_GRANULARITY as u64)
+      .clamp(Self::CHUNK_GRANULARITY as u64, Self::MAX_CHUNK_SIZE as u64) as usize
+  }
+
+  /// Derives build options from the hints in `self`.
+  pub fn options(&self) -> Options {
+    let mut options = Options {
+      threads: self.threads,
+      ..Options::default()
+    };
+    if let Some(expected_stations) = self.expected_stations {
+      options.table_capacity = Self::table_capacity(expected_stations);
+    }
+    if let Some(expected_bytes) = self.expected_bytes {
+      let threads = self.threads.unwrap_or_else(|| {
+        std::thread::available_parallelism()
+          .map(|nonzero| nonzero.get())
+          .unwrap_or(1)
+      });
+      options.chunk_size = Self::chunk_size(expected_bytes, threads);
+    }
+    options
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use googletest::prelude::*;
+
+  #[cfg(not(feature = "multithreaded"))]
+  use crate::build_report::Strategy;
+  use crate::{
+    error::BarseError,
+    options::{BuildConfig, Options, DEFAULT_CHUNK_SIZE},
+    str_hash::TABLE_SIZE,
+  };
+
+  #[gtest]
+  fn test_table_capacity() {
+    expect_eq!(BuildConfig::table_capacity(0), 2);
+    expect_eq!(BuildConfig::table_capacity(10_000), 32 * 1024);
+    expect_eq!(BuildConfig::table_capacity(16 * 1024), 32 * 1024);
+    expect_eq!(BuildConfig::table_capacity(16 * 1024 + 1), 64 * 1024);
+  }
+
+  #[gtest]
+  fn test_chunk_size() {
+    expect_eq!(BuildConfig::chunk_size(0, 8), 64 * 1024);
+    expect_eq!(
+      BuildConfig::chunk_size(16 * 1024 * 1024 * 1024, 32),
+      32 * 1024 * 1024
+    );
+    expect_eq!(BuildConfig::chunk_size(u64::MAX / 2, 1), 64 * 1024 * 1024);
+  }
+
+  #[gtest]
+  fn test_default_config_keeps_default_options() {
+    let options = BuildConfig::default().options();
+    expect_eq!(options.table_capacity, TABLE_SIZE);
+    expect_eq!(options.chunk_size, DEFAULT_CHUNK_SIZE);
+    expect_eq!(options.threads, None);
+    expect_that!(options.validate(), ok(anything()));
+  }
+
+  #[gtest]
+  fn test_derived_options_are_valid() {
+    let options = BuildConfig {
+      expected_stations: Some(100_000),
+      expected_bytes: Some(13_000_000_000),
+      threads: Some(32),
+    }
+    .options();
+    expect_that!(options.validate(), ok(anything()));
+  }
+
+  #[gtest]
+  fn test_validate() {
+    let invalid = [
+      Options {
+        table_capacity: 1000,
+        ..Options::default()
+      },
+      Options {
+        chunk_size: 0,
+        ..Options::default()
+      },
+      Options {
+        chunk_size: 100,
+        ..Options::default()
+      },
+      Options {
+        threads: Some(0),
+        ..Options::default()
+      },
+      #[cfg(not(feature = "multithreaded"))]
+      Options {
+        strategy: Some(Strategy::PerThreadTables),
+        ..Options::default()
+      },
+    ];
+    for options in invalid {
+      expect_true!(
+        matches!(options.validate(), Err(BarseError::Other(_))),
+        "{options:?}"
+      );
+    }
+  }
+}
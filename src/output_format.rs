@@ -0,0 +1,451 @@
+use std::{
+  fmt::Display,
+  io::{self, Write},
+  str::FromStr,
+};
+
+#[cfg(feature = "arrow")]
+use crate::{
+  arrow_export::{stations_to_record_batch, write_record_batch},
+  barse::OwnedWeatherStation,
+};
+use crate::{barse::WeatherStation, error::BarseError, temperature_summary::TemperatureSummary};
+
+/// The roll-up of every reading in the input, across all stations.
+#[derive(Clone, Copy, Debug)]
+pub struct GlobalSummary {
+  /// The number of distinct stations in the input.
+  pub stations: usize,
+  /// The merged summary of every station.
+  pub summary: TemperatureSummary,
+}
+
+impl GlobalSummary {
+  /// The station name of the footer row of the delimited formats.
+  pub const FOOTER_STATION: &str = "(global)";
+}
+
+impl Display for GlobalSummary {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "records={}, stations={}",
+      self.summary.count, self.stations
+    )?;
+    if self.summary.count != 0 {
+      write!(
+        f,
+        ", min={}, mean={}, max={}",
+        self.summary.min(),
+        self.summary.avg(),
+        self.summary.max()
+      )?;
+    }
+    Ok(())
+  }
+}
+
+/// The format in which station summaries are written.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+  /// `{station=min/mean/max, ...}` on a single line, byte-for-byte as
+  /// printed by the reference implementation of the original challenge.
+  #[default]
+  Brc,
+  /// Comma-separated values with a header row.
+  Csv,
+  /// Tab-separated values with a header row.
+  Tsv,
+  /// A Parquet file with the columns of the delimited formats. Only
+  /// available with the `arrow` feature.
+  #[cfg(feature = "arrow")]
+  Parquet,
+}
+
+impl OutputFormat {
+  /// Writes `stations` to `out` in the order they are given. If
+  /// `include_count` is set, the `Brc` format appends the number of readings
+  /// to each station as ` (n)`. The delimited formats always include a count
+  /// column.
+  pub(crate) fn write_stations<'a, W: Write>(
+    &self,
+    stations: impl Iterator<Item = WeatherStation<'a>>,
+    include_count: bool,
+    out: W,
+  ) -> io::Result<()> {
+    match self {
+      Self::Brc => write_brc(stations, include_count, out),
+      Self::Csv => write_delimited(stations, b',', out),
+      Self::Tsv => write_delimited(stations, b'\t', out),
+      #[cfg(feature = "arrow")]
+      Self::Parquet => write_parquet(stations, out),
+    }
+  }
+
+  /// Whether the format is binary, and so can't be written to a terminal.
+  pub fn is_binary(&self) -> bool {
+    match self {
+      Self::Brc | Self::Csv | Self::Tsv => false,
+      #[cfg(feature = "arrow")]
+      Self::Parquet => true,
+    }
+  }
+
+  /// Writes `global` after the stations. The delimited formats get a footer
+  /// row for station `GlobalSummary::FOOTER_STATION`, which is omitted if the
+  /// input is empty. The `Brc` and `Parquet` formats have no room for a
+  /// roll-up, so it is written to `diagnostics` instead.
+  pub(crate) fn write_global<W: Write, D: Write>(
+    &self,
+    global: &GlobalSummary,
+    out: W,
+    mut diagnostics: D,
+  ) -> io::Result<()> {
+    match self {
+      Self::Brc => writeln!(diagnostics, "{global}"),
+      #[cfg(feature = "arrow")]
+      Self::Parquet => writeln!(diagnostics, "{global}"),
+      Self::Csv | Self::Tsv if global.summary.count == 0 => Ok(()),
+      Self::Csv => write_delimited_row(
+        &WeatherStation::new(GlobalSummary::FOOTER_STATION, global.summary),
+        b',',
+        out,
+      ),
+      Self::Tsv => write_delimited_row(
+        &WeatherStation::new(GlobalSummary::FOOTER_STATION, global.summary),
+        b'\t',
+        out,
+      ),
+    }
+  }
+}
+
+impl FromStr for OutputFormat {
+  type Err = BarseError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "brc" => Ok(Self::Brc),
+      "csv" => Ok(Self::Csv),
+      "tsv" => Ok(Self::Tsv),
+      #[cfg(feature = "arrow")]
+      "parquet" => Ok(Self::Parquet),
+      #[cfg(not(feature = "arrow"))]
+      "parquet" => Err(BarseError::new(
+        "The parquet output format requires building with the `arrow` feature".to_owned(),
+      )),
+      _ => Err(BarseError::new(format!(
+        "Unknown output format \"{s}\", expected one of brc, csv, tsv, parquet"
+      ))),
+    }
+  }
+}
+
+/// Writes `stations` to `out` as a Parquet file, which is built in memory
+/// first, since the Parquet writer requires a `Send` writer.
+#[cfg(feature = "arrow")]
+fn write_parquet<'a, W: Write>(
+  stations: impl Iterator<Item = WeatherStation<'a>>,
+  mut out: W,
+) -> io::Result<()> {
+  let stations = stations.map(OwnedWeatherStation::from).collect::<Vec<_>>();
+  let mut parquet = Vec::new();
+  stations_to_record_batch(&stations)
+    .and_then(|batch| write_record_batch(&batch, &mut parquet))
+    .map_err(io::Error::other)?;
+  out.write_all(&parquet)
+}
+
+fn write_brc<'a, W: Write>(
+  stations: impl Iterator<Item = WeatherStation<'a>>,
+  include_count: bool,
+  mut out: W,
+) -> io::Result<()> {
+  let mut empty = true;
+  for station in stations {
+    write_brc_station(&station, empty, include_count, &mut out)?;
+    empty = false;
+  }
+  if empty {
+    write!(out, "{{")?;
+  }
+  writeln!(out, "}}")
+}
+
+/// Writes `station` in the `Brc` format, preceded by the opening brace if it
+/// is the `first` station, or by a separator otherwise.
+pub(crate) fn write_brc_station<W: Write>(
+  station: &WeatherStation<'_>,
+  first: bool,
+  include_count: bool,
+  out: &mut W,
+) -> io::Result<()> {
+  if first {
+    write!(out, "{{{station}")?;
+  } else {
+    write!(out, ", {station}")?;
+  }
+  if include_count {
+    write!(out, " ({})", station.summary().count)?;
+  }
+  Ok(())
+}
+
+/// Writes `field`, quoting it per RFC 4180 if it contains the delimiter, a
+/// quote, or a line break.
+fn write_delimited_field<W: Write>(field: &str, delimiter: u8, out: &mut W) -> io::Result<()> {
+  if field
+    .bytes()
+    .any(|b| b == delimiter || b == b'"' || b == b'\n' || b == b'\r')
+  {
+    write!(out, "\"{}\"", field.replace('"', "\"\""))
+  } else {
+    write!(out, "{field}")
+  }
+}
+
+fn write_delimited<'a, W: Write>(
+  stations: impl Iterator<Item = WeatherStation<'a>>,
+  delimiter: u8,
+  mut out: W,
+) -> io::Result<()> {
+  let d = delimiter as char;
+  writeln!(out, "station{d}min{d}mean{d}max{d}count")?;
+  for station in stations {
+    write_delimited_row(&station, delimiter, &mut out)?;
+  }
+  Ok(())
+}
+
+fn write_delimited_row<W: Write>(
+  station: &WeatherStation<'_>,
+  delimiter: u8,
+  mut out: W,
+) -> io::Result<()> {
+  let d = delimiter as char;
+  let summary = station.summary();
+  write_delimited_field(station.name(), delimiter, &mut out)?;
+  writeln!(
+    out,
+    "{d}{}{d}{}{d}{}{d}{}",
+    summary.min(),
+    summary.avg(),
+    summary.max(),
+    summary.count
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use googletest::prelude::*;
+  use itertools::Itertools;
+
+  use crate::{
+    barse::WeatherStation,
+    error::BarseError,
+    output_format::{GlobalSummary, OutputFormat},
+    temperature_reading::TemperatureReading,
+    temperature_summary::TemperatureSummary,
+  };
+
+  fn summary(min: i16, max: i16, total: i64, count: u32) -> TemperatureSummary {
+    TemperatureSummary {
+      min: TemperatureReading::new(min),
+      max: TemperatureReading::new(max),
+      total,
+      count,
+    }
+  }
+
+  fn stations() -> Vec<WeatherStation<'static>> {
+    vec![
+      WeatherStation::new("Abha", summary(-15, 203, 344, 3)),
+      WeatherStation::new("Foo, Bar", summary(10, 10, 10, 1)),
+      WeatherStation::new("Say \"hi\"", summary(-999, 999, 0, 2)),
+      WeatherStation::new("Tab\tCity", summary(5, 7, 12, 2)),
+    ]
+  }
+
+  fn write_to_string_with_count(format: OutputFormat, include_count: bool) -> String {
+    let mut out = Vec::new();
+    format
+      .write_stations(stations().into_iter(), include_count, &mut out)
+      .unwrap();
+    String::from_utf8(out).unwrap()
+  }
+
+  fn write_to_string(format: OutputFormat) -> String {
+    write_to_string_with_count(format, false)
+  }
+
+  /// Splits delimited text into records of fields, honoring RFC 4180 quoting.
+  fn split_delimited(text: &str, delimiter: char) -> Vec<Vec<String>> {
+    let mut records = vec![];
+    let mut record = vec![];
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+      match (c, in_quotes) {
+        ('"', true) if chars.peek() == Some(&'"') => {
+          chars.next();
+          field.push('"');
+        }
+        ('"', _) => in_quotes = !in_quotes,
+        ('\n', false) => {
+          record.push(std::mem::take(&mut field));
+          records.push(std::mem::take(&mut record));
+        }
+        (c, false) if c == delimiter => record.push(std::mem::take(&mut field)),
+        (c, _) => field.push(c),
+      }
+    }
+    records
+  }
+
+  fn expect_round_trip(format: OutputFormat, delimiter: char) {
+    let records = split_delimited(&write_to_string(format), delimiter);
+    let expected = stations()
+      .iter()
+      .map(|station| {
+        let summary = station.summary();
+        vec![
+          station.name().to_owned(),
+          summary.min().to_string(),
+          summary.avg().to_string(),
+          summary.max().to_string(),
+          summary.count.to_string(),
+        ]
+      })
+      .collect_vec();
+
+    expect_that!(
+      records.first(),
+      some(elements_are![
+        eq("station"),
+        eq("min"),
+        eq("mean"),
+        eq("max"),
+        eq("count")
+      ])
+    );
+    expect_eq!(&records[1..], expected.as_slice());
+  }
+
+  #[gtest]
+  fn test_brc() {
+    expect_eq!(
+      write_to_string(OutputFormat::Brc),
+      "{Abha=-1.5/11.5/20.3, Foo, Bar=1.0/1.0/1.0, Say \"hi\"=-99.9/0.0/99.9, \
+       Tab\tCity=0.5/0.6/0.7}\n"
+    );
+  }
+
+  #[gtest]
+  fn test_brc_with_count() {
+    expect_eq!(
+      write_to_string_with_count(OutputFormat::Brc, true),
+      "{Abha=-1.5/11.5/20.3 (3), Foo, Bar=1.0/1.0/1.0 (1), Say \"hi\"=-99.9/0.0/99.9 (2), \
+       Tab\tCity=0.5/0.6/0.7 (2)}\n"
+    );
+  }
+
+  #[gtest]
+  fn test_include_count_does_not_change_delimited_formats() {
+    expect_eq!(
+      write_to_string_with_count(OutputFormat::Csv, true),
+      write_to_string(OutputFormat::Csv)
+    );
+  }
+
+  #[gtest]
+  fn test_csv_round_trip() {
+    expect_round_trip(OutputFormat::Csv, ',');
+  }
+
+  #[gtest]
+  fn test_tsv_round_trip() {
+    expect_round_trip(OutputFormat::Tsv, '\t');
+  }
+
+  #[gtest]
+  fn test_csv_quoting() {
+    expect_eq!(
+      write_to_string(OutputFormat::Csv),
+      "station,min,mean,max,count\n\
+       Abha,-1.5,11.5,20.3,3\n\
+       \"Foo, Bar\",1.0,1.0,1.0,1\n\
+       \"Say \"\"hi\"\"\",-99.9,0.0,99.9,2\n\
+       Tab\tCity,0.5,0.6,0.7,2\n"
+    );
+  }
+
+  fn write_global_to_strings(format: OutputFormat, global: &GlobalSummary) -> (String, String) {
+    let (mut out, mut diagnostics) = (Vec::new(), Vec::new());
+    format
+      .write_global(global, &mut out, &mut diagnostics)
+      .unwrap();
+    (
+      String::from_utf8(out).unwrap(),
+      String::from_utf8(diagnostics).unwrap(),
+    )
+  }
+
+  #[gtest]
+  fn test_write_global() {
+    let global = GlobalSummary {
+      stations: 4,
+      summary: TemperatureSummary::merge_all(stations().iter().map(|station| station.summary())),
+    };
+    expect_eq!(
+      write_global_to_strings(OutputFormat::Brc, &global),
+      (
+        String::new(),
+        "records=8, stations=4, min=-99.9, mean=4.6, max=99.9\n".to_owned()
+      )
+    );
+    expect_eq!(
+      write_global_to_strings(OutputFormat::Tsv, &global),
+      ("(global)\t-99.9\t4.6\t99.9\t8\n".to_owned(), String::new())
+    );
+  }
+
+  #[gtest]
+  fn test_write_global_empty() {
+    let global = GlobalSummary {
+      stations: 0,
+      summary: TemperatureSummary::default(),
+    };
+    expect_eq!(
+      write_global_to_strings(OutputFormat::Brc, &global),
+      (String::new(), "records=0, stations=0\n".to_owned())
+    );
+    expect_eq!(
+      write_global_to_strings(OutputFormat::Csv, &global),
+      (String::new(), String::new())
+    );
+  }
+
+  #[gtest]
+  fn test_parse_format() {
+    expect_that!("brc".parse::<OutputFormat>(), ok(eq(&OutputFormat::Brc)));
+    expect_that!("csv".parse::<OutputFormat>(), ok(eq(&OutputFormat::Csv)));
+    expect_that!("tsv".parse::<OutputFormat>(), ok(eq(&OutputFormat::Tsv)));
+    expect_true!(matches!(
+      "json".parse::<OutputFormat>(),
+      Err(BarseError::Other(_))
+    ));
+    #[cfg(feature = "arrow")]
+    expect_that!(
+      "parquet".parse::<OutputFormat>(),
+      ok(eq(&OutputFormat::Parquet))
+    );
+    #[cfg(not(feature = "arrow"))]
+    expect_that!(
+      "parquet"
+        .parse::<OutputFormat>()
+        .map_err(|err| err.to_string()),
+      err(contains_substring("`arrow` feature"))
+    );
+  }
+}
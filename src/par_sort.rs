@@ -0,0 +1,149 @@
+use std::{cmp::Ordering, thread};
+
+/// Slices shorter than this are sorted on the calling thread, since spawning
+/// threads costs more than sorting them.
+pub const PARALLEL_SORT_THRESHOLD: usize = 1 << 16;
+/// The number of samples taken per bucket to choose the splitters between
+/// buckets. More samples give more evenly sized buckets.
+const OVERSAMPLING: usize = 32;
+
+/// Sorts `items` by `cmp` like `sort_unstable_by`, with a sample sort across
+/// `threads` threads. Samples of `items` are sorted to choose `threads - 1`
+/// splitters, each item is moved to the bucket between the splitters it falls
+/// between, and the buckets are sorted in parallel and concatenated.
+///
+/// If `cmp` is a total order, as when ties are broken by station name, the
+/// result is identical to `sort_unstable_by`.
+pub fn par_sort_unstable_by<T, F>(items: &mut Vec<T>, threads: usize, cmp: F)
+where
+  T: Send + Sync,
+  F: Fn(&T, &T) -> Ordering + Sync,
+{
+  let threads = threads.min(items.len() / PARALLEL_SORT_THRESHOLD);
+  if threads <= 1 {
+    items.sort_unstable_by(cmp);
+    return;
+  }
+
+  let bucket_ids = bucket_ids(items, threads, &cmp);
+  let mut buckets = (0..threads).map(|_| Vec::new()).collect::<Vec<_>>();
+  for (item, bucket_id) in items.drain(..).zip(bucket_ids) {
+    buckets[bucket_id as usize].push(item);
+  }
+
+  thread::scope(|scope| {
+    for bucket in &mut buckets {
+      let cmp = &cmp;
+      scope.spawn(move || bucket.sort_unstable_by(cmp));
+    }
+  });
+  for mut bucket in buckets {
+    items.append(&mut bucket);
+  }
+}
+
+/// Returns the index of the bucket each of `items` belongs in, out of
+/// `buckets` buckets. Every item in a bucket orders before every item in the
+/// buckets after it.
+fn bucket_ids<T, F>(items: &[T], buckets: usize, cmp: &F) -> Vec<u32>
+where
+  T: Sync,
+  F: Fn(&T, &T) -> Ordering + Sync,
+{
+  // Samples are spread evenly over `items`, which for a table are in hash
+  // order, so this is as good as sampling at random.
+  let samples = buckets * OVERSAMPLING;
+  let mut sample = (0..samples)
+    .map(|i| &items[i * items.len() / samples])
+    .collect::<Vec<_>>();
+  sample.sort_unstable_by(|a, b| cmp(a, b));
+  let splitters = (1..buckets)
+    .map(|bucket| sample[bucket * OVERSAMPLING])
+    .collect::<Vec<_>>();
+
+  let chunk_len = items.len().div_ceil(buckets);
+  thread::scope(|scope| {
+    let threads = items
+      .chunks(chunk_len)
+      .map(|chunk| {
+        let splitters = &splitters;
+        scope.spawn(move || {
+          chunk
+            .iter()
+            .map(|item| {
+              // Items equal to a splitter go in the bucket after it, so
+              // equal items always share a bucket.
+              splitters.partition_point(|splitter| cmp(splitter, item) != Ordering::Greater) as u32
+            })
+            .collect::<Vec<_>>()
+        })
+      })
+      .collect::<Vec<_>>();
+    threads
+      .into_iter()
+      .flat_map(|thread| thread.join().expect("Bucket classification panicked"))
+      .collect()
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use googletest::prelude::*;
+  use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+  use crate::{
+    par_sort::{par_sort_unstable_by, PARALLEL_SORT_THRESHOLD},
+    util::utf16_cmp,
+  };
+
+  /// Returns `len` distinct random names, including characters on either side
+  /// of the surrogate range, on which UTF-16 and byte order disagree, in
+  /// random order.
+  fn shuffled_names(len: usize) -> Vec<String> {
+    const CHARS: [char; 6] = ['a', 'b', 'é', '\u{e000}', '\u{ffee}', '\u{1f600}'];
+    let mut rng = StdRng::seed_from_u64(0x5a4b1e);
+    let mut names = (0..len)
+      .map(|i| {
+        let name_len = rng.random_range(1..8);
+        let mut name = (0..name_len)
+          .map(|_| CHARS[rng.random_range(0..CHARS.len())])
+          .collect::<String>();
+        name.push_str(&i.to_string());
+        name
+      })
+      .collect::<Vec<_>>();
+    names.shuffle(&mut rng);
+    names
+  }
+
+  #[gtest]
+  fn test_matches_std_sort() {
+    let names = shuffled_names(4 * PARALLEL_SORT_THRESHOLD + 123);
+    for threads in [1, 2, 7] {
+      let mut expected = names.clone();
+      expected.sort_unstable_by(|a, b| utf16_cmp(a, b));
+      let mut sorted = names.clone();
+      par_sort_unstable_by(&mut sorted, threads, |a, b| utf16_cmp(a, b));
+      expect_true!(sorted == expected, "{threads} threads");
+
+      expected.sort_unstable();
+      let mut sorted = names.clone();
+      par_sort_unstable_by(&mut sorted, threads, String::cmp);
+      expect_true!(sorted == expected, "{threads} threads");
+    }
+  }
+
+  #[gtest]
+  fn test_many_equal_keys() {
+    // Most splitters are equal, leaving most buckets empty.
+    let mut rng = StdRng::seed_from_u64(0x3e9a1);
+    let keys = (0..3 * PARALLEL_SORT_THRESHOLD)
+      .map(|_| rng.random_range(0..3u32))
+      .collect::<Vec<_>>();
+    let mut sorted = keys.clone();
+    par_sort_unstable_by(&mut sorted, 4, u32::cmp);
+    let mut expected = keys;
+    expected.sort_unstable();
+    expect_eq!(sorted, expected);
+  }
+}
@@ -0,0 +1,238 @@
+use std::{
+  fmt::Display,
+  io,
+  os::fd::{AsRawFd, FromRawFd, OwnedFd},
+};
+
+use crate::error::{BarseError, BarseResult};
+
+const PERF_TYPE_HARDWARE: u32 = 0;
+const PERF_COUNT_HW_CPU_CYCLES: u64 = 0;
+const PERF_COUNT_HW_INSTRUCTIONS: u64 = 1;
+/// Usually counts last-level cache misses.
+const PERF_COUNT_HW_CACHE_MISSES: u64 = 3;
+const PERF_COUNT_HW_BRANCH_MISSES: u64 = 5;
+
+const PERF_EVENT_IOC_ENABLE: u64 = 0x2400;
+const PERF_EVENT_IOC_DISABLE: u64 = 0x2401;
+const PERF_EVENT_IOC_RESET: u64 = 0x2403;
+
+/// The counter starts disabled.
+const FLAG_DISABLED: u64 = 1 << 0;
+/// Threads spawned after the counter is opened are counted too.
+const FLAG_INHERIT: u64 = 1 << 1;
+const FLAG_EXCLUDE_KERNEL: u64 = 1 << 5;
+const FLAG_EXCLUDE_HV: u64 = 1 << 6;
+
+/// The first version of `struct perf_event_attr`, which every kernel with
+/// `perf_event_open` accepts.
+#[repr(C)]
+#[derive(Default)]
+struct PerfEventAttr {
+  type_: u32,
+  size: u32,
+  config: u64,
+  sample_period: u64,
+  sample_type: u64,
+  read_format: u64,
+  flags: u64,
+  wakeup_events: u32,
+  bp_type: u32,
+  config1: u64,
+}
+
+/// Hardware event counts of the process over a measured region.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PerfCounts {
+  pub cycles: u64,
+  pub instructions: u64,
+  pub llc_misses: u64,
+  pub branch_misses: u64,
+}
+
+impl PerfCounts {
+  /// Instructions retired per cycle.
+  pub fn ipc(&self) -> f64 {
+    if self.cycles == 0 {
+      0.
+    } else {
+      self.instructions as f64 / self.cycles as f64
+    }
+  }
+}
+
+impl Display for PerfCounts {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    writeln!(f, "cycles: {}", self.cycles)?;
+    writeln!(
+      f,
+      "instructions: {} ({:.2} IPC)",
+      self.instructions,
+      self.ipc()
+    )?;
+    writeln!(f, "LLC misses: {}", self.llc_misses)?;
+    write!(f, "branch misses: {}", self.branch_misses)
+  }
+}
+
+/// Hardware performance counters of the calling thread and every thread it
+/// spawns while they are open. Only user-space events are counted, which
+/// the default `perf_event_paranoid` setting allows.
+pub struct PerfCounters {
+  cycles: OwnedFd,
+  instructions: OwnedFd,
+  llc_misses: OwnedFd,
+  branch_misses: OwnedFd,
+}
+
+impl PerfCounters {
+  /// Opens the counters, which fails if the kernel or the hardware doesn't
+  /// support them, or `perf_event_paranoid` forbids access.
+  pub fn open() -> BarseResult<Self> {
+    Ok(Self {
+      cycles: open_counter(PERF_COUNT_HW_CPU_CYCLES)?,
+      instructions: open_counter(PERF_COUNT_HW_INSTRUCTIONS)?,
+      llc_misses: open_counter(PERF_COUNT_HW_CACHE_MISSES)?,
+      branch_misses: open_counter(PERF_COUNT_HW_BRANCH_MISSES)?,
+    })
+  }
+
+  /// Resets and starts the counters.
+  pub fn start(&self) -> BarseResult {
+    for counter in self.counters() {
+      ioctl(counter, PERF_EVENT_IOC_RESET)?;
+      ioctl(counter, PERF_EVENT_IOC_ENABLE)?;
+    }
+    Ok(())
+  }
+
+  /// Stops the counters, returning the events counted since `start`. Events
+  /// of spawned threads are only included once the threads have exited.
+  pub fn stop(&self) -> BarseResult<PerfCounts> {
+    for counter in self.counters() {
+      ioctl(counter, PERF_EVENT_IOC_DISABLE)?;
+    }
+    Ok(PerfCounts {
+      cycles: read_counter(&self.cycles)?,
+      instructions: read_counter(&self.instructions)?,
+      llc_misses: read_counter(&self.llc_misses)?,
+      branch_misses: read_counter(&self.branch_misses)?,
+    })
+  }
+
+  fn counters(&self) -> [&OwnedFd; 4] {
+    [
+      &self.cycles,
+      &self.instructions,
+      &self.llc_misses,
+      &self.branch_misses,
+    ]
+  }
+}
+
+/// Calls `f`, returning its result and the events counted while it ran. `f`
+/// is called even if the counters are unavailable, in which case the reason
+/// is returned in place of the counts.
+pub fn measure<T>(f: impl FnOnce() -> T) -> (T, BarseResult<PerfCounts>) {
+  let counters = PerfCounters::open().and_then(|counters| {
+    counters.start()?;
+    Ok(counters)
+  });
+  let result = f();
+  (result, counters.and_then(|counters| counters.stop()))
+}
+
+fn open_counter(config: u64) -> BarseResult<OwnedFd> {
+  let attr = PerfEventAttr {
+    type_: PERF_TYPE_HARDWARE,
+    size: size_of::<PerfEventAttr>() as u32,
+    config,
+    flags: FLAG_DISABLED | FLAG_INHERIT | FLAG_EXCLUDE_KERNEL | FLAG_EXCLUDE_HV,
+    ..PerfEventAttr::default()
+  };
+  // Measure the calling process on any CPU.
+  let (pid, cpu, group_fd, flags) = (0, -1, -1, 0);
+  let fd = unsafe {
+    libc::syscall(
+      libc::SYS_perf_event_open,
+      std::ptr::from_ref(&attr),
+      pid as libc::pid_t,
+      cpu as libc::c_int,
+      group_fd as libc::c_int,
+      flags as libc::c_ulong,
+    )
+  };
+  if fd < 0 {
+    return Err(open_error(io::Error::last_os_error()));
+  }
+  Ok(unsafe { OwnedFd::from_raw_fd(fd as libc::c_int) })
+}
+
+/// Explains why a counter couldn't be opened.
+fn open_error(err: io::Error) -> BarseError {
+  match err.raw_os_error() {
+    Some(libc::EACCES | libc::EPERM) => BarseError::new(format!(
+      "perf counters are unavailable ({err}), access may be restricted by \
+       /proc/sys/kernel/perf_event_paranoid"
+    )),
+    _ => BarseError::new(format!("perf counters are unavailable ({err})")),
+  }
+}
+
+fn ioctl(counter: &OwnedFd, request: u64) -> BarseResult {
+  if unsafe { libc::ioctl(counter.as_raw_fd(), request as _, 0) } < 0 {
+    return Err(io::Error::last_os_error().into());
+  }
+  Ok(())
+}
+
+fn read_counter(counter: &OwnedFd) -> BarseResult<u64> {
+  let mut value = 0u64;
+  let len = unsafe {
+    libc::read(
+      counter.as_raw_fd(),
+      std::ptr::from_mut(&mut value).cast(),
+      size_of::<u64>(),
+    )
+  };
+  if len != size_of::<u64>() as isize {
+    return Err(io::Error::last_os_error().into());
+  }
+  Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io;
+
+  use googletest::prelude::*;
+
+  use crate::perf_counters::{measure, open_error};
+
+  #[gtest]
+  fn test_measure() {
+    let (result, counts) = measure(|| (0..1_000_000u64).map(std::hint::black_box).sum::<u64>());
+    expect_eq!(result, 499_999_500_000);
+    // Counters are often unavailable in containers and VMs, in which case the
+    // work still runs.
+    match counts {
+      Ok(counts) => expect_gt!(counts.instructions, 1_000_000),
+      Err(err) => expect_that!(
+        err.to_string(),
+        contains_substring("perf counters are unavailable")
+      ),
+    }
+  }
+
+  #[gtest]
+  fn test_open_error_mentions_paranoid() {
+    expect_that!(
+      open_error(io::Error::from_raw_os_error(libc::EACCES)).to_string(),
+      contains_substring("perf_event_paranoid")
+    );
+    expect_that!(
+      open_error(io::Error::from_raw_os_error(libc::ENOENT)).to_string(),
+      not(contains_substring("perf_event_paranoid"))
+    );
+  }
+}
@@ -1,20 +1,906 @@
-use itertools::Itertools;
+use std::{
+  cmp::Ordering,
+  fs::{self, File},
+  io::{self, BufWriter, Write},
+  str::FromStr,
+};
 
+#[cfg(feature = "multithreaded")]
+use crate::par_sort::par_sort_unstable_by;
 use crate::{
-  barse::{build_temperature_reading_table, WeatherStation},
-  error::BarseResult,
+  barse::{
+    build_from_dir, build_with_options_and_map_time, OwnedWeatherStation, Table, WeatherStation,
+  },
+  build_report::BuildReport,
+  error::{BarseError, BarseResult},
+  options::Options,
+  output_format::{GlobalSummary, OutputFormat},
+  sink::{BrcSink, Sink},
+  table_stats::TableStats,
+  temperature_summary::TemperatureSummary,
   util::HasIter,
 };
 
-pub fn print_summary(input_path: &str) -> BarseResult {
-  println!(
-    "{{{}}}",
-    build_temperature_reading_table(input_path)?
-      .iter()
-      .map(|(station, summary)| WeatherStation::new(station, *summary))
-      .sorted_unstable()
-      .map(|station| format!("{station}"))
-      .join(", ")
-  );
+/// The field stations are ordered by in the summary.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SortKey {
+  #[default]
+  Name,
+  Min,
+  Max,
+  Avg,
+  Count,
+  /// Stations are written as the table visits them, without being collected
+  /// and sorted first: in the order they first appeared in the input for
+  /// single-threaded builds, and in no particular order otherwise.
+  /// `descending` has no effect.
+  Unsorted,
+}
+
+impl SortKey {
+  /// Compares `a` and `b` by this key alone.
+  fn cmp(&self, a: &WeatherStation<'_>, b: &WeatherStation<'_>) -> Ordering {
+    let (a_summary, b_summary) = (a.summary(), b.summary());
+    match self {
+      Self::Name => a.cmp(b),
+      Self::Min => a_summary.min().cmp(&b_summary.min()),
+      Self::Max => a_summary.max().cmp(&b_summary.max()),
+      Self::Avg => a_summary.avg().cmp(&b_summary.avg()),
+      Self::Count => a_summary.count.cmp(&b_summary.count),
+      Self::Unsorted => Ordering::Equal,
+    }
+  }
+}
+
+impl FromStr for SortKey {
+  type Err = BarseError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "name" => Ok(Self::Name),
+      "min" => Ok(Self::Min),
+      "max" => Ok(Self::Max),
+      "avg" => Ok(Self::Avg),
+      "count" => Ok(Self::Count),
+      "none" => Ok(Self::Unsorted),
+      _ => Err(BarseError::new(format!(
+        "Unknown sort key \"{s}\", expected one of name, min, max, avg, count, none"
+      ))),
+    }
+  }
+}
+
+/// Options controlling how the summary is written.
+#[derive(Clone, Debug, Default)]
+pub struct SummaryOptions {
+  pub format: OutputFormat,
+  pub sort_by: SortKey,
+  /// Reverses the order of `sort_by`. Ties are still broken by ascending
+  /// name, unless sorting by name.
+  pub descending: bool,
+  /// If set, only the first `limit` stations in sorted order are included.
+  pub limit: Option<usize>,
+  /// Stations with fewer than `min_count` readings are left out, after the
+  /// table has been built, so their readings still count towards `global`.
+  pub min_count: u32,
+  /// Appends the number of readings to each station in the `Brc` format, as
+  /// `name=min/mean/max (n)`.
+  pub include_count: bool,
+  /// Rolls up the readings of every station (regardless of `limit`) into a
+  /// global summary, which is written to stderr for the `Brc` format, or as a
+  /// footer row for the delimited formats.
+  pub global: bool,
+  /// Suppresses the global summary written to stderr for the `Brc` format,
+  /// so that nothing but the summary is written.
+  pub quiet: bool,
+  /// Sorts the bucket indices of the stations instead of copies of the
+  /// stations, and formats each station straight from the table as it is
+  /// written. The summary then takes 4 bytes per station beyond the table,
+  /// rather than 32, at the cost of a table lookup per comparison.
+  pub stream_sorted: bool,
+  /// How the table of summaries is built.
+  pub build: Options,
+}
+
+impl SummaryOptions {
+  fn cmp(&self, a: &WeatherStation<'_>, b: &WeatherStation<'_>) -> Ordering {
+    let ordering = self.sort_by.cmp(a, b);
+    if self.descending {
+      ordering.reverse()
+    } else {
+      ordering
+    }
+    .then_with(|| a.cmp(b))
+  }
+}
+
+/// Returns the stations in `table` in the order `drain_into` visits them,
+/// filtered and limited according to `options`.
+fn unsorted_stations<'a>(
+  table: &'a Table,
+  options: &SummaryOptions,
+) -> impl Iterator<Item = WeatherStation<'a>> {
+  #[cfg(not(feature = "multithreaded"))]
+  let stations = table.iter_by_appearance();
+  #[cfg(feature = "multithreaded")]
+  let stations = table.iter();
+  stations
+    .filter(|(_, summary)| summary.count >= options.min_count)
+    .take(options.limit.unwrap_or(usize::MAX))
+    .map(|(station, summary)| WeatherStation::new(station, *summary))
+}
+
+/// Returns the stations in `table` filtered, ordered and limited according
+/// to `options`.
+fn sorted_stations<'a>(table: &'a Table, options: &SummaryOptions) -> Vec<WeatherStation<'a>> {
+  if options.sort_by == SortKey::Unsorted {
+    return unsorted_stations(table, options).collect();
+  }
+  let mut stations = table
+    .iter()
+    .filter(|(_, summary)| summary.count >= options.min_count)
+    .map(|(station, summary)| WeatherStation::new(station, *summary))
+    .collect::<Vec<_>>();
+  if let Some(limit) = options.limit.filter(|&limit| limit < stations.len()) {
+    // Only the first `limit` stations need to be sorted.
+    if limit != 0 {
+      stations.select_nth_unstable_by(limit - 1, |a, b| options.cmp(a, b));
+    }
+    stations.truncate(limit);
+  }
+  sort_by(&mut stations, options, |a, b| options.cmp(a, b));
+  stations
+}
+
+/// Returns the bucket indices of the stations in `table`, filtered, ordered
+/// and limited like `sorted_stations`.
+fn sorted_station_indices(table: &Table, options: &SummaryOptions) -> BarseResult<Vec<u32>> {
+  if u32::try_from(table.capacity() - 1).is_err() {
+    return Err(BarseError::new(format!(
+      "Can't stream the summary of a table of capacity {}, whose bucket indices don't fit in 32 \
+       bits",
+      table.capacity()
+    )));
+  }
+  let station = |index: &u32| {
+    let (name, summary) = table
+      .station_at(*index as usize)
+      .expect("Only the indices of occupied buckets are sorted");
+    WeatherStation::new(name, *summary)
+  };
+  let cmp = |a: &u32, b: &u32| options.cmp(&station(a), &station(b));
+
+  let mut indices = (0..table.capacity() as u32)
+    .filter(|&index| {
+      table
+        .station_at(index as usize)
+        .is_some_and(|(_, summary)| summary.count >= options.min_count)
+    })
+    .collect::<Vec<_>>();
+  if let Some(limit) = options.limit.filter(|&limit| limit < indices.len()) {
+    if limit != 0 {
+      indices.select_nth_unstable_by(limit - 1, cmp);
+    }
+    indices.truncate(limit);
+  }
+  sort_by(&mut indices, options, cmp);
+  Ok(indices)
+}
+
+/// Sorts `items` by `cmp`, across threads if there are enough of them.
+#[cfg(feature = "multithreaded")]
+fn sort_by<T: Send + Sync>(
+  items: &mut Vec<T>,
+  options: &SummaryOptions,
+  cmp: impl Fn(&T, &T) -> Ordering + Sync,
+) {
+  let threads = options.build.threads.unwrap_or_else(|| {
+    std::thread::available_parallelism()
+      .map(|nonzero| nonzero.get())
+      .unwrap_or(1)
+  });
+  par_sort_unstable_by(items, threads, cmp);
+}
+
+#[cfg(not(feature = "multithreaded"))]
+fn sort_by<T>(items: &mut [T], _options: &SummaryOptions, cmp: impl Fn(&T, &T) -> Ordering) {
+  items.sort_unstable_by(cmp);
+}
+
+pub(crate) fn write_table<W: Write>(
+  table: &Table,
+  options: &SummaryOptions,
+  mut out: W,
+) -> BarseResult {
+  if options.sort_by == SortKey::Unsorted && options.format == OutputFormat::Brc {
+    // Each station is formatted straight from the table into `out`.
+    let mut sink = BrcSink::new(&mut out, options.include_count);
+    let mut remaining = options.limit.unwrap_or(usize::MAX);
+    table.drain_into(&mut |station: &str, summary: &TemperatureSummary| {
+      if remaining == 0 || summary.count < options.min_count {
+        return Ok(());
+      }
+      remaining -= 1;
+      sink.on_station(station, summary)
+    })?;
+    sink.finish()?;
+  } else if options.sort_by == SortKey::Unsorted {
+    options.format.write_stations(
+      unsorted_stations(table, options),
+      options.include_count,
+      &mut out,
+    )?;
+  } else if options.stream_sorted {
+    let indices = sorted_station_indices(table, options)?;
+    let stations = indices.iter().filter_map(|&index| {
+      let (name, summary) = table.station_at(index as usize)?;
+      Some(WeatherStation::new(name, *summary))
+    });
+    options
+      .format
+      .write_stations(stations, options.include_count, &mut out)?;
+  } else {
+    let stations = sorted_stations(table, options);
+    options
+      .format
+      .write_stations(stations.into_iter(), options.include_count, &mut out)?;
+  }
+  if options.global {
+    let global = GlobalSummary {
+      stations: table.iter().count(),
+      summary: TemperatureSummary::merge_all(table.iter().map(|(_, summary)| summary)),
+    };
+    if options.quiet {
+      options.format.write_global(&global, &mut out, io::sink())?;
+    } else {
+      options
+        .format
+        .write_global(&global, &mut out, io::stderr().lock())?;
+    }
+  }
+  out.flush()?;
   Ok(())
 }
+
+/// Returns owned copies of the weather station summaries in the file at
+/// `input_path`, filtered, ordered and limited according to `options`. Only
+/// the filtering, ordering and build options are used; the summaries always
+/// carry their count.
+pub fn summarize(
+  input_path: &str,
+  options: &SummaryOptions,
+) -> BarseResult<Vec<OwnedWeatherStation>> {
+  let (table, _) = build_with_options_and_map_time(input_path, &options.build)?;
+  Ok(
+    sorted_stations(&table, options)
+      .into_iter()
+      .map(OwnedWeatherStation::from)
+      .collect(),
+  )
+}
+
+/// Returns owned copies of the summaries of every weather station in the file
+/// at `input_path`, ordered by name, building the table with `options`.
+pub fn summarize_file(
+  input_path: &str,
+  options: &Options,
+) -> BarseResult<Vec<OwnedWeatherStation>> {
+  summarize(
+    input_path,
+    &SummaryOptions {
+      build: options.clone(),
+      ..SummaryOptions::default()
+    },
+  )
+}
+
+/// Writes the summary of the weather stations in the file at `input_path` to
+/// `out`.
+pub fn write_summary<W: Write>(input_path: &str, out: W) -> BarseResult {
+  write_summary_with_options(input_path, &SummaryOptions::default(), out)
+}
+
+/// Like `write_summary`, but lets the caller choose how the summary is
+/// written.
+pub fn write_summary_with_options<W: Write>(
+  input_path: &str,
+  options: &SummaryOptions,
+  out: W,
+) -> BarseResult {
+  let (table, _) = build_with_options_and_map_time(input_path, &options.build)?;
+  write_table(&table, options, out)
+}
+
+/// Writes the summary like `write_summary_with_options`, and returns
+/// statistics about how the table was built.
+pub fn write_summary_with_report<W: Write>(
+  input_path: &str,
+  options: &SummaryOptions,
+  out: W,
+) -> BarseResult<BuildReport> {
+  let (table, report) = build_with_options_and_map_time(input_path, &options.build)?;
+  write_table(&table, options, out)?;
+  Ok(report)
+}
+
+/// Like `write_summary_with_report`, also returning the occupancy of the
+/// table the summary was built from.
+pub fn write_summary_with_stats<W: Write>(
+  input_path: &str,
+  options: &SummaryOptions,
+  out: W,
+) -> BarseResult<(BuildReport, TableStats)> {
+  let (table, report) = build_with_options_and_map_time(input_path, &options.build)?;
+  write_table(&table, options, out)?;
+  Ok((report, table.stats()))
+}
+
+/// Like `write_summary_with_stats`, but summarizes every file in `dir` named
+/// with `extension`, or every file if no extension is given.
+pub fn write_dir_summary_with_stats<W: Write>(
+  dir: &str,
+  extension: Option<&str>,
+  options: &SummaryOptions,
+  out: W,
+) -> BarseResult<(BuildReport, TableStats)> {
+  let (table, report) = build_from_dir(dir, extension, &options.build)?;
+  write_table(&table, options, out)?;
+  Ok((report, table.stats()))
+}
+
+/// Writes the summary of an already built `table`, such as one that files
+/// have been folded into with `append_file`.
+pub fn write_table_summary<W: Write>(
+  table: &Table,
+  options: &SummaryOptions,
+  out: W,
+) -> BarseResult {
+  write_table(table, options, out)
+}
+
+pub fn print_summary(input_path: &str) -> BarseResult {
+  write_summary(input_path, BufWriter::new(io::stdout().lock()))
+}
+
+/// Calls `write` with a writer to a temporary file next to `output_path`, then
+/// renames the temporary file to `output_path`, so readers of `output_path`
+/// never observe partial output. The temporary file is removed if `write`
+/// fails.
+pub fn write_atomically<T>(
+  output_path: &str,
+  write: impl FnOnce(&mut BufWriter<File>) -> BarseResult<T>,
+) -> BarseResult<T> {
+  let tmp_path = format!("{output_path}.{}.tmp", std::process::id());
+  let result = File::create(&tmp_path)
+    .map_err(Into::into)
+    .and_then(|file| {
+      let mut out = BufWriter::new(file);
+      let result = write(&mut out)?;
+      out
+        .into_inner()
+        .map_err(io::IntoInnerError::into_error)?
+        .sync_all()?;
+      Ok(result)
+    })
+    .and_then(|result| {
+      fs::rename(&tmp_path, output_path)?;
+      Ok(result)
+    });
+  if result.is_err() {
+    let _ = fs::remove_file(&tmp_path);
+  }
+  result
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{fs, path::PathBuf};
+
+  use googletest::prelude::*;
+
+  use crate::{
+    barse::{append_file, build_with_config, PaddedInput},
+    error::BarseError,
+    options::{BuildConfig, Options},
+    output_format::OutputFormat,
+    print_summary::{
+      summarize, write_atomically, write_summary, write_summary_with_options,
+      write_summary_with_stats, write_table_summary, SortKey, SummaryOptions,
+    },
+    test_util::random_input_file,
+  };
+
+  const INPUT: &str = "Hamburg;12.0\nBulawayo;8.9\nPalembang;38.8\nHamburg;34.2\n";
+  const EXPECTED: &str =
+    "{Bulawayo=8.9/8.9/8.9, Hamburg=12.0/23.1/34.2, Palembang=38.8/38.8/38.8}\n";
+
+  fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("barse_{}_{name}", std::process::id()))
+  }
+
+  #[gtest]
+  fn test_write_summary() {
+    let input_path = temp_path("write_summary.txt");
+    fs::write(&input_path, INPUT).unwrap();
+
+    let mut out = Vec::new();
+    write_summary(input_path.to_str().unwrap(), &mut out).unwrap();
+    fs::remove_file(&input_path).unwrap();
+
+    expect_eq!(str::from_utf8(&out).unwrap(), EXPECTED);
+  }
+
+  #[gtest]
+  fn test_write_table_summary_after_append() {
+    let (input_path, appended_path) = (
+      temp_path("write_table_summary.txt"),
+      temp_path("write_table_summary_appended.txt"),
+    );
+    fs::write(&input_path, INPUT).unwrap();
+    fs::write(&appended_path, "Bulawayo;10.9\nOslo;-3.0\n").unwrap();
+
+    let (mut table, _) =
+      build_with_config(input_path.to_str().unwrap(), &BuildConfig::default()).unwrap();
+    let mut out = Vec::new();
+    write_table_summary(&table, &SummaryOptions::default(), &mut out).unwrap();
+    append_file(
+      &mut table,
+      appended_path.to_str().unwrap(),
+      &Options::default(),
+    )
+    .unwrap();
+    let mut appended_out = Vec::new();
+    write_table_summary(&table, &SummaryOptions::default(), &mut appended_out).unwrap();
+    fs::remove_file(&input_path).unwrap();
+    fs::remove_file(&appended_path).unwrap();
+
+    expect_eq!(str::from_utf8(&out).unwrap(), EXPECTED);
+    expect_eq!(
+      str::from_utf8(&appended_out).unwrap(),
+      "{Bulawayo=8.9/9.9/10.9, Hamburg=12.0/23.1/34.2, Oslo=-3.0/-3.0/-3.0, \
+       Palembang=38.8/38.8/38.8}\n"
+    );
+  }
+
+  #[gtest]
+  fn test_write_summary_with_stats() {
+    let input_path = temp_path("write_summary_with_stats.txt");
+    fs::write(&input_path, INPUT).unwrap();
+
+    let mut out = Vec::new();
+    let (report, stats) = write_summary_with_stats(
+      input_path.to_str().unwrap(),
+      &SummaryOptions::default(),
+      &mut out,
+    )
+    .unwrap();
+    fs::remove_file(&input_path).unwrap();
+
+    expect_eq!(str::from_utf8(&out).unwrap(), EXPECTED);
+    expect_eq!(report.records, 4);
+    expect_eq!(stats.stations, 3);
+    expect_that!(
+      stats.to_string(),
+      starts_with(format!(
+        "table capacity: {}\ntable stations: 3",
+        stats.capacity
+      ))
+    );
+  }
+
+  /// Checks the output against the sample inputs in `data/samples`, and the
+  /// outputs the 1BRC baseline in `data/reference` prints for them.
+  #[gtest]
+  fn test_write_summary_matches_reference_samples() {
+    let samples_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("data/samples");
+    let mut samples = fs::read_dir(&samples_dir)
+      .unwrap()
+      .map(|entry| entry.unwrap().path())
+      .filter(|path| path.extension().is_some_and(|extension| extension == "txt"))
+      .collect::<Vec<_>>();
+    samples.sort();
+    assert_that!(samples, not(is_empty()));
+
+    for input_path in samples {
+      let expected = fs::read_to_string(input_path.with_extension("out")).unwrap();
+      let mut out = Vec::new();
+      write_summary(input_path.to_str().unwrap(), &mut out).unwrap();
+      expect_eq!(
+        str::from_utf8(&out).unwrap(),
+        expected,
+        "Sample {}",
+        input_path.display()
+      );
+    }
+  }
+
+  #[gtest]
+  fn test_write_summary_with_count() {
+    let input_path = temp_path("write_summary_with_count.txt");
+    fs::write(&input_path, INPUT).unwrap();
+
+    let write = |include_count| {
+      let mut out = Vec::new();
+      write_summary_with_options(
+        input_path.to_str().unwrap(),
+        &SummaryOptions {
+          include_count,
+          ..SummaryOptions::default()
+        },
+        &mut out,
+      )
+      .unwrap();
+      String::from_utf8(out).unwrap()
+    };
+    let without_count = write(false);
+    let with_count = write(true);
+    fs::remove_file(&input_path).unwrap();
+
+    expect_eq!(without_count, EXPECTED);
+    expect_eq!(
+      with_count,
+      "{Bulawayo=8.9/8.9/8.9 (1), Hamburg=12.0/23.1/34.2 (2), Palembang=38.8/38.8/38.8 (1)}\n"
+    );
+  }
+
+  #[gtest]
+  fn test_write_summary_csv_with_global_footer() {
+    let input_path = temp_path("write_summary_csv_with_global_footer.txt");
+    fs::write(&input_path, INPUT).unwrap();
+
+    let mut out = Vec::new();
+    write_summary_with_options(
+      input_path.to_str().unwrap(),
+      &SummaryOptions {
+        format: OutputFormat::Csv,
+        limit: Some(1),
+        global: true,
+        ..SummaryOptions::default()
+      },
+      &mut out,
+    )
+    .unwrap();
+    fs::remove_file(&input_path).unwrap();
+
+    expect_eq!(
+      str::from_utf8(&out).unwrap(),
+      "station,min,mean,max,count\n\
+       Bulawayo,8.9,8.9,8.9,1\n\
+       (global),8.9,23.5,38.8,4\n"
+    );
+  }
+
+  #[gtest]
+  fn test_summarize_includes_count() {
+    let input_path = temp_path("summarize_includes_count.txt");
+    fs::write(&input_path, INPUT).unwrap();
+    let stations = summarize(input_path.to_str().unwrap(), &SummaryOptions::default()).unwrap();
+    fs::remove_file(&input_path).unwrap();
+
+    expect_that!(
+      stations
+        .iter()
+        .map(|station| (station.name.as_str(), station.summary.count))
+        .collect::<Vec<_>>(),
+      elements_are![
+        eq(&("Bulawayo", 1)),
+        eq(&("Hamburg", 2)),
+        eq(&("Palembang", 1))
+      ]
+    );
+  }
+
+  #[gtest]
+  fn test_write_summary_csv() {
+    let input_path = temp_path("write_summary_csv.txt");
+    fs::write(&input_path, "Foo, Bar;1.0\nSay \"hi\";-2.5\nFoo, Bar;3.0\n").unwrap();
+
+    let mut out = Vec::new();
+    write_summary_with_options(
+      input_path.to_str().unwrap(),
+      &SummaryOptions {
+        format: OutputFormat::Csv,
+        ..SummaryOptions::default()
+      },
+      &mut out,
+    )
+    .unwrap();
+    fs::remove_file(&input_path).unwrap();
+
+    expect_eq!(
+      str::from_utf8(&out).unwrap(),
+      "station,min,mean,max,count\n\
+       \"Foo, Bar\",1.0,2.0,3.0,2\n\
+       \"Say \"\"hi\"\"\",-2.5,-2.5,-2.5,1\n"
+    );
+  }
+
+  const SORT_INPUT: &str = "A;10.0\nB;30.0\nC;10.0\nD;-5.0\nD;35.0\nE;30.0\nB;0.0\n";
+
+  fn sorted_names(options: &SummaryOptions) -> Vec<String> {
+    let input_path = temp_path(&format!(
+      "sorted_names_{:?}_{}_{:?}_{}.txt",
+      options.sort_by, options.descending, options.limit, options.min_count
+    ));
+    fs::write(&input_path, SORT_INPUT).unwrap();
+    let stations = summarize(input_path.to_str().unwrap(), options).unwrap();
+    fs::remove_file(&input_path).unwrap();
+    stations.into_iter().map(|station| station.name).collect()
+  }
+
+  fn sorted_by(sort_by: SortKey, descending: bool) -> Vec<String> {
+    sorted_names(&SummaryOptions {
+      sort_by,
+      descending,
+      ..SummaryOptions::default()
+    })
+  }
+
+  #[gtest]
+  fn test_sort_ascending() {
+    expect_that!(
+      sorted_by(SortKey::Name, false),
+      elements_are!["A", "B", "C", "D", "E"]
+    );
+    expect_that!(
+      sorted_by(SortKey::Min, false),
+      elements_are!["D", "B", "A", "C", "E"]
+    );
+    expect_that!(
+      sorted_by(SortKey::Max, false),
+      elements_are!["A", "C", "B", "E", "D"]
+    );
+    expect_that!(
+      sorted_by(SortKey::Avg, false),
+      elements_are!["A", "C", "B", "D", "E"]
+    );
+    expect_that!(
+      sorted_by(SortKey::Count, false),
+      elements_are!["A", "C", "E", "B", "D"]
+    );
+  }
+
+  #[gtest]
+  fn test_sort_descending_breaks_ties_by_ascending_name() {
+    expect_that!(
+      sorted_by(SortKey::Name, true),
+      elements_are!["E", "D", "C", "B", "A"]
+    );
+    expect_that!(
+      sorted_by(SortKey::Min, true),
+      elements_are!["E", "A", "C", "B", "D"]
+    );
+    expect_that!(
+      sorted_by(SortKey::Max, true),
+      elements_are!["D", "B", "E", "A", "C"]
+    );
+    expect_that!(
+      sorted_by(SortKey::Avg, true),
+      elements_are!["E", "B", "D", "A", "C"]
+    );
+    expect_that!(
+      sorted_by(SortKey::Count, true),
+      elements_are!["B", "D", "A", "C", "E"]
+    );
+  }
+
+  #[gtest]
+  fn test_limit() {
+    let top = |limit| {
+      sorted_names(&SummaryOptions {
+        sort_by: SortKey::Max,
+        descending: true,
+        limit: Some(limit),
+        ..SummaryOptions::default()
+      })
+    };
+    expect_that!(top(0), is_empty());
+    expect_that!(top(2), elements_are!["D", "B"]);
+    expect_that!(top(3), elements_are!["D", "B", "E"]);
+    expect_that!(top(10), elements_are!["D", "B", "E", "A", "C"]);
+  }
+
+  #[gtest]
+  fn test_min_count() {
+    let with_min_count = |min_count| {
+      sorted_names(&SummaryOptions {
+        min_count,
+        ..SummaryOptions::default()
+      })
+    };
+    expect_that!(with_min_count(0), elements_are!["A", "B", "C", "D", "E"]);
+    expect_that!(with_min_count(1), elements_are!["A", "B", "C", "D", "E"]);
+    expect_that!(with_min_count(2), elements_are!["B", "D"]);
+    expect_that!(with_min_count(3), is_empty());
+
+    // The limit applies to the stations that pass the filter.
+    expect_that!(
+      sorted_names(&SummaryOptions {
+        sort_by: SortKey::Avg,
+        limit: Some(1),
+        min_count: 2,
+        ..SummaryOptions::default()
+      }),
+      elements_are!["B"]
+    );
+  }
+
+  #[gtest]
+  fn test_write_summary_top_n() {
+    let input_path = temp_path("write_summary_top_n.txt");
+    fs::write(&input_path, SORT_INPUT).unwrap();
+
+    let mut out = Vec::new();
+    write_summary_with_options(
+      input_path.to_str().unwrap(),
+      &SummaryOptions {
+        sort_by: SortKey::Count,
+        descending: true,
+        limit: Some(2),
+        ..SummaryOptions::default()
+      },
+      &mut out,
+    )
+    .unwrap();
+    fs::remove_file(&input_path).unwrap();
+
+    expect_eq!(
+      str::from_utf8(&out).unwrap(),
+      "{B=0.0/15.0/30.0, D=-5.0/15.0/35.0}\n"
+    );
+  }
+
+  #[gtest]
+  fn test_stream_sorted_matches_collected_output() {
+    let input = random_input_file(0x51a7ed, 200_000, 10_000).unwrap();
+    let (table, _) = PaddedInput::new(input.exact_slice())
+      .unwrap()
+      .build(&Options::default())
+      .unwrap();
+    let write = |options: &SummaryOptions| {
+      let mut out = Vec::new();
+      write_table_summary(&table, options, &mut out).unwrap();
+      out
+    };
+
+    for (sort_by, descending, limit, min_count, format) in [
+      (SortKey::Name, false, None, 0, OutputFormat::Brc),
+      (SortKey::Name, true, None, 0, OutputFormat::Csv),
+      (SortKey::Min, false, None, 0, OutputFormat::Tsv),
+      (SortKey::Max, true, Some(100), 0, OutputFormat::Brc),
+      (SortKey::Avg, false, Some(0), 0, OutputFormat::Brc),
+      (SortKey::Count, true, Some(5000), 20, OutputFormat::Csv),
+    ] {
+      let options = SummaryOptions {
+        format,
+        sort_by,
+        descending,
+        limit,
+        min_count,
+        include_count: true,
+        ..SummaryOptions::default()
+      };
+      let streamed = write(&SummaryOptions {
+        stream_sorted: true,
+        ..options.clone()
+      });
+      expect_eq!(streamed, write(&options), "{options:?}");
+    }
+  }
+
+  #[gtest]
+  fn test_write_summary_unsorted() {
+    let input_path = temp_path("write_summary_unsorted.txt");
+    fs::write(
+      &input_path,
+      "Oslo;1.0\nBerlin;2.0\nAthens;3.0\nOslo;3.0\nLima;4.0\n",
+    )
+    .unwrap();
+    let write = |format, limit, min_count| {
+      let mut out = Vec::new();
+      write_summary_with_options(
+        input_path.to_str().unwrap(),
+        &SummaryOptions {
+          format,
+          sort_by: SortKey::Unsorted,
+          limit,
+          min_count,
+          ..SummaryOptions::default()
+        },
+        &mut out,
+      )
+      .unwrap();
+      String::from_utf8(out).unwrap()
+    };
+    let brc = write(OutputFormat::Brc, None, 0);
+    let csv = write(OutputFormat::Csv, None, 0);
+    let limited = write(OutputFormat::Brc, Some(2), 0);
+    let repeated = write(OutputFormat::Brc, None, 2);
+    let empty = write(OutputFormat::Brc, Some(0), 0);
+    fs::remove_file(&input_path).unwrap();
+
+    // Without the `multithreaded` feature, stations are written in the order
+    // they first appeared.
+    #[cfg(not(feature = "multithreaded"))]
+    {
+      expect_eq!(
+        brc,
+        "{Oslo=1.0/2.0/3.0, Berlin=2.0/2.0/2.0, Athens=3.0/3.0/3.0, Lima=4.0/4.0/4.0}\n"
+      );
+      expect_eq!(
+        csv,
+        "station,min,mean,max,count\nOslo,1.0,2.0,3.0,2\nBerlin,2.0,2.0,2.0,1\n\
+         Athens,3.0,3.0,3.0,1\nLima,4.0,4.0,4.0,1\n"
+      );
+      expect_eq!(limited, "{Oslo=1.0/2.0/3.0, Berlin=2.0/2.0/2.0}\n");
+    }
+    #[cfg(feature = "multithreaded")]
+    {
+      let mut stations = brc
+        .trim_matches(['{', '}', '\n'])
+        .split(", ")
+        .collect::<Vec<_>>();
+      stations.sort_unstable();
+      expect_that!(
+        stations,
+        elements_are![
+          eq(&"Athens=3.0/3.0/3.0"),
+          eq(&"Berlin=2.0/2.0/2.0"),
+          eq(&"Lima=4.0/4.0/4.0"),
+          eq(&"Oslo=1.0/2.0/3.0"),
+        ]
+      );
+      expect_eq!(csv.lines().count(), 5);
+      expect_eq!(limited.split(", ").count(), 2);
+    }
+    expect_eq!(repeated, "{Oslo=1.0/2.0/3.0}\n");
+    expect_eq!(empty, "{}\n");
+  }
+
+  #[gtest]
+  fn test_parse_sort_key() {
+    expect_that!("count".parse::<SortKey>(), ok(eq(&SortKey::Count)));
+    expect_that!("none".parse::<SortKey>(), ok(eq(&SortKey::Unsorted)));
+    expect_true!(matches!(
+      "hottest".parse::<SortKey>(),
+      Err(BarseError::Other(_))
+    ));
+  }
+
+  #[gtest]
+  fn test_write_atomically() {
+    let input_path = temp_path("write_atomically_in.txt");
+    let output_path = temp_path("write_atomically_out.txt");
+    fs::write(&input_path, INPUT).unwrap();
+
+    write_atomically(output_path.to_str().unwrap(), |out| {
+      write_summary(input_path.to_str().unwrap(), out)
+    })
+    .unwrap();
+    let output = fs::read_to_string(&output_path).unwrap();
+    fs::remove_file(&input_path).unwrap();
+    fs::remove_file(&output_path).unwrap();
+
+    expect_eq!(output, EXPECTED);
+  }
+
+  #[gtest]
+  fn test_write_atomically_failure_leaves_no_output() {
+    let output_path = temp_path("write_atomically_fail.txt");
+    let result = write_atomically(output_path.to_str().unwrap(), |out| {
+      write_summary("/nonexistent/measurements.txt", out)
+    });
+
+    expect_true!(matches!(
+      result.as_ref().map_err(BarseError::without_context),
+      Err(BarseError::Io(_))
+    ));
+    expect_false!(output_path.exists());
+    expect_false!(PathBuf::from(format!(
+      "{}.{}.tmp",
+      output_path.display(),
+      std::process::id()
+    ))
+    .exists());
+  }
+}
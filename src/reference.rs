@@ -0,0 +1,147 @@
+use std::{cmp::Ordering, collections::HashMap, fmt::Display};
+
+use crate::{
+  error::{BarseError, BarseResult, RecordErrorKind},
+  scanner::ExtraFields,
+  temperature_summary::round_half_up,
+};
+
+/// The summary of a station's readings, in tenths of a degree.
+#[derive(Clone, Copy, Debug)]
+pub struct TemperatureSummary {
+  pub min: i32,
+  pub max: i32,
+  pub total: i64,
+  pub count: u32,
+}
+
+impl TemperatureSummary {
+  pub fn min(&self) -> f32 {
+    self.min as f32 / 10.0
+  }
+
+  pub fn max(&self) -> f32 {
+    self.max as f32 / 10.0
+  }
+
+  /// Mirrors the reference Java implementation, which computes the mean in
+  /// double precision and rounds it with `Math.round`. The rounding itself
+  /// is shared with the crate's `TemperatureSummary::avg`, so the two can only
+  /// disagree on the mean being rounded.
+  pub fn avg(&self) -> f64 {
+    let mean = (self.total as f64 / 10.0) / self.count as f64;
+    round_half_up(mean * 10.0) / 10.0
+  }
+
+  pub fn add_reading(&mut self, temp: f32) {
+    let temp = (temp * 10.0).round() as i32;
+    self.min = self.min.min(temp);
+    self.max = self.max.max(temp);
+    self.total += temp as i64;
+    self.count += 1;
+  }
+}
+
+impl Default for TemperatureSummary {
+  fn default() -> Self {
+    Self {
+      min: i32::MAX,
+      max: i32::MIN,
+      total: 0,
+      count: 0,
+    }
+  }
+}
+
+impl Display for TemperatureSummary {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{:.1}/{:.1}/{:.1}", self.min(), self.avg(), self.max())
+  }
+}
+
+#[derive(Clone, Debug)]
+pub struct WeatherStation {
+  pub name: String,
+  pub summary: TemperatureSummary,
+}
+
+impl PartialEq for WeatherStation {
+  fn eq(&self, other: &Self) -> bool {
+    self.name.eq(&other.name)
+  }
+}
+
+impl Eq for WeatherStation {}
+
+impl PartialOrd for WeatherStation {
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl Ord for WeatherStation {
+  fn cmp(&self, other: &Self) -> Ordering {
+    self.name.encode_utf16().cmp(other.name.encode_utf16())
+  }
+}
+
+impl Display for WeatherStation {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}={}", self.name, self.summary)
+  }
+}
+
+/// Summarizes the readings in `input`, returning the stations sorted by name.
+/// The first line is skipped if `skip_header` is set. Fails on the first line
+/// without a ';', or whose reading isn't a number.
+///
+/// This is deliberately simple, splitting lines with `str` methods and
+/// parsing readings as floats. It is far slower than the builders, but shares
+/// none of their code, so it is used to check them.
+pub fn weather_stations(input: &str, skip_header: bool) -> BarseResult<Vec<WeatherStation>> {
+  weather_stations_with_extra_fields(input, skip_header, ExtraFields::Forbid)
+}
+
+/// Like `weather_stations`, but with `ExtraFields::Skip`, only the first two
+/// fields of each line are read, and anything after a second ';' is ignored.
+pub fn weather_stations_with_extra_fields(
+  input: &str,
+  skip_header: bool,
+  extra_fields: ExtraFields,
+) -> BarseResult<Vec<WeatherStation>> {
+  let mut stations = HashMap::<&str, TemperatureSummary>::new();
+  let mut offset = 0;
+  for (line_number, line) in input.split('\n').enumerate() {
+    let line_offset = offset;
+    offset += line.len() + 1;
+    if line.is_empty() || (skip_header && line_number == 0) {
+      continue;
+    }
+    let malformed = |kind| BarseError::MalformedRecord {
+      offset: line_offset as u64,
+      line: Some(line_number as u64 + 1),
+      kind,
+    };
+    let (station, temp) = line
+      .split_once(';')
+      .ok_or_else(|| malformed(RecordErrorKind::MissingSemicolon))?;
+    let temp = match extra_fields {
+      ExtraFields::Forbid => temp,
+      ExtraFields::Skip => temp.split(';').next().unwrap_or(temp),
+    };
+    let temp = temp
+      .parse()
+      .map_err(|_| malformed(RecordErrorKind::MalformedTemperature(temp.to_owned())))?;
+    stations.entry(station).or_default().add_reading(temp);
+  }
+
+  let mut stations = stations
+    .into_iter()
+    .map(|(name, summary)| WeatherStation {
+      name: name.to_owned(),
+      summary,
+    })
+    .collect::<Vec<_>>();
+  stations.sort_unstable();
+  Ok(stations)
+}
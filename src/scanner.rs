@@ -1,8 +1,12 @@
-use std::{hint::unreachable_unchecked, slice};
+#[cfg(not(any(feature = "safe-fallback", miri)))]
+use std::slice;
 
+#[cfg(not(any(feature = "safe-fallback", miri)))]
+use crate::util::{unaligned_read_would_cross_page_boundary, unlikely};
 use crate::{
-  temperature_reading::{TemperatureReading, MAX_TEMP_READING_LEN},
-  util::{unaligned_read_would_cross_page_boundary, unlikely, BitVector},
+  error::{BarseError, BarseResult, RecordErrorKind},
+  temperature_reading::{DecimalSeparator, TemperatureReading, MAX_TEMP_READING_LEN},
+  util::BitVector,
 };
 
 #[cfg(not(target_feature = "avx2"))]
@@ -10,7 +14,7 @@ use crate::scanner_cache::{read_next_from_buffer, BYTES_PER_BATCH};
 #[cfg(target_feature = "avx2")]
 use crate::scanner_cache_x86::{read_next_from_buffer, BYTES_PER_BATCH};
 
-const MAX_STATION_NAME_LEN: usize = 50;
+pub(crate) const MAX_STATION_NAME_LEN: usize = 50;
 /// The amount of overlapping bytes between consecutive buffers in
 /// multithreaded mode.
 pub const BUFFER_OVERLAP: usize = (MAX_STATION_NAME_LEN
@@ -19,68 +23,194 @@ pub const BUFFER_OVERLAP: usize = (MAX_STATION_NAME_LEN
   + std::mem::size_of_val(&b'\n'))
 .next_multiple_of(BYTES_PER_BATCH);
 
+/// The longest the fields following the temperature reading of a record may
+/// be with `ExtraFields::Skip`, including their leading semicolons.
+pub const MAX_EXTRA_FIELDS_LEN: usize = 64;
+/// Like `BUFFER_OVERLAP`, for records with extra fields of up to
+/// `MAX_EXTRA_FIELDS_LEN` bytes.
+pub const BUFFER_OVERLAP_WITH_EXTRA_FIELDS: usize = (MAX_STATION_NAME_LEN
+  + std::mem::size_of_val(&b';')
+  + MAX_TEMP_READING_LEN
+  + MAX_EXTRA_FIELDS_LEN
+  + std::mem::size_of_val(&b'\n'))
+.next_multiple_of(BYTES_PER_BATCH);
+
 pub(crate) const SCANNER_CACHE_SIZE: usize = BYTES_PER_BATCH;
 
+/// Whether records may have more `;`-separated fields after the temperature
+/// reading, e.g. `station;temperature;humidity`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExtraFields {
+  /// Records have exactly two fields, and extra fields produce garbage.
+  #[default]
+  Forbid,
+  /// Only the first two fields of records are read, and up to
+  /// `MAX_EXTRA_FIELDS_LEN` bytes of fields after them are skipped.
+  Skip,
+}
+
+impl ExtraFields {
+  /// The number of bytes each chunk of the input must overlap with the
+  /// previous chunk by, so that every record fits in the overlap.
+  pub const fn buffer_overlap(self) -> usize {
+    match self {
+      Self::Forbid => BUFFER_OVERLAP,
+      Self::Skip => BUFFER_OVERLAP_WITH_EXTRA_FIELDS,
+    }
+  }
+}
+
+/// The alignment required of buffers passed to `Scanner::from_start`.
+pub const BUFFER_ALIGNMENT: usize = 32;
+
+/// A batch with no records in it, which exhausted scanners point to.
+#[repr(align(64))]
+struct EmptyBatch([u8; BYTES_PER_BATCH]);
+
+static EMPTY_BATCH: EmptyBatch = EmptyBatch([0; BYTES_PER_BATCH]);
+
 /// Scans for alternating semicolons and newlines.
 pub struct Scanner<'a> {
   buffer: &'a [u8],
   semicolon_mask: u64,
   newline_mask: u64,
+  /// A mask of the bytes in the last batch of `buffer` which are part of the
+  /// input. The bytes past the end of the input are padding, which may
+  /// contain arbitrary values.
+  last_batch_mask: u64,
 
   /// The offset of the previously-read newline character + 1, e.g. the
   /// starting point of the expected next weather station name.
   batch_offset: u32,
+
+  /// The input this scanner was constructed over, excluding padding. Only
+  /// used for progress tracking.
+  input: &'a [u8],
+  /// Set once iteration has returned `None`.
+  finished: bool,
+  /// The decimal separator used by temperature readings in the input.
+  decimal_separator: DecimalSeparator,
+  /// Whether fields after the temperature reading are skipped.
+  extra_fields: ExtraFields,
 }
 
 impl<'a> Scanner<'a> {
   /// Constructs a Scanner over a buffer, which must be aligned to 32 bytes.
   pub fn from_start<'b: 'a>(buffer: &'b [u8]) -> Self {
-    debug_assert!(buffer.len().is_multiple_of(BYTES_PER_BATCH));
-    let (semicolon_mask, newline_mask) = read_next_from_buffer(buffer);
+    Self::from_start_with_len(buffer, buffer.len())
+  }
+
+  /// Like `from_start`, but returns an error instead of proceeding with
+  /// undefined behavior if `buffer` is not aligned to `BUFFER_ALIGNMENT`
+  /// bytes, or its length is not a multiple of the scanner's batch size.
+  pub fn try_from_start<'b: 'a>(buffer: &'b [u8]) -> BarseResult<Self> {
+    if !buffer.is_empty() && !buffer.as_ptr().addr().is_multiple_of(BUFFER_ALIGNMENT) {
+      return Err(BarseError::new(format!(
+        "Scanner buffer at {:p} is not aligned to {BUFFER_ALIGNMENT} bytes",
+        buffer.as_ptr()
+      )));
+    }
+    if !buffer.len().is_multiple_of(BYTES_PER_BATCH) {
+      return Err(BarseError::new(format!(
+        "Scanner buffer length {} is not a multiple of {BYTES_PER_BATCH} bytes",
+        buffer.len()
+      )));
+    }
+    Ok(Self::from_start(buffer))
+  }
+
+  /// Constructs a Scanner over the first `len` bytes of a buffer, which must
+  /// be aligned to 32 bytes. The buffer must extend at least to the next
+  /// multiple of `BYTES_PER_BATCH` past `len`, but the contents of the buffer
+  /// past `len` are ignored.
+  pub fn from_start_with_len<'b: 'a>(buffer: &'b [u8], len: usize) -> Self {
+    if len == 0 {
+      return Self::exhausted();
+    }
+
+    let input = &buffer[..len];
+    let (buffer, last_batch_mask) = Self::truncate_to_len(buffer, len);
+    let (semicolon_mask, newline_mask) = Self::read_batch(buffer, last_batch_mask);
     Self {
       buffer,
       semicolon_mask,
       newline_mask,
+      last_batch_mask,
       batch_offset: 0,
+      input,
+      finished: false,
+      decimal_separator: DecimalSeparator::Dot,
+      extra_fields: ExtraFields::Forbid,
+    }
+  }
+
+  /// Truncates `buffer` to the batch containing the last byte of input,
+  /// returning the truncated buffer and a mask of the bytes in the final
+  /// batch which are part of the input.
+  fn truncate_to_len(buffer: &[u8], len: usize) -> (&[u8], u64) {
+    debug_assert!(buffer.len().is_multiple_of(BYTES_PER_BATCH));
+    let padded_len = len.next_multiple_of(BYTES_PER_BATCH).max(BYTES_PER_BATCH);
+    debug_assert!(padded_len <= buffer.len());
+    let last_batch_len = len.saturating_sub(padded_len - BYTES_PER_BATCH);
+    let last_batch_mask = 1u64.unbounded_shl(last_batch_len as u32).wrapping_sub(1);
+    (&buffer[..padded_len], last_batch_mask)
+  }
+
+  /// Reads the semicolon/newline masks of the batch at the start of `buffer`,
+  /// masking off the padding bytes if this is the last batch.
+  fn read_batch(buffer: &[u8], last_batch_mask: u64) -> (u64, u64) {
+    let (semicolon_mask, newline_mask) = read_next_from_buffer(buffer);
+    if buffer.len() == BYTES_PER_BATCH {
+      (
+        semicolon_mask & last_batch_mask,
+        newline_mask & last_batch_mask,
+      )
+    } else {
+      (semicolon_mask, newline_mask)
     }
   }
 
   /// Finds the point we should start iterating from, assuming the first
-  /// `BUFFER_OVERLAP` bytes are overlapping with the previous batch. We
+  /// `overlap` bytes are overlapping with the previous batch. We
   /// choose to start iterating after the last newline character found in the
   /// overlap region, since this is naturally where the scanner iterating over
-  /// the previous slice would stop.
-  fn find_starting_point_in_overlap(buffer: &[u8]) -> (&[u8], u64, u64, u32) {
-    let (mut semicolon_mask, mut newline_mask) = read_next_from_buffer(buffer);
+  /// the previous slice would stop. Returns `None` if there is no newline in
+  /// the overlap region.
+  fn find_starting_point_in_overlap(
+    buffer: &[u8],
+    last_batch_mask: u64,
+    overlap: usize,
+  ) -> Option<(&[u8], u64, u64, u32)> {
+    let (mut semicolon_mask, mut newline_mask) = Self::read_batch(buffer, last_batch_mask);
     let mut buffer_offset = 0;
     #[allow(clippy::reversed_empty_ranges)]
-    for offset in (BYTES_PER_BATCH..BUFFER_OVERLAP).step_by(BYTES_PER_BATCH) {
-      let (next_semicolon_mask, next_newline_mask) = read_next_from_buffer(&buffer[offset..]);
+    for offset in (BYTES_PER_BATCH..overlap).step_by(BYTES_PER_BATCH) {
+      let (next_semicolon_mask, next_newline_mask) =
+        Self::read_batch(&buffer[offset..], last_batch_mask);
       if next_newline_mask != 0 {
         buffer_offset = offset;
         semicolon_mask = next_semicolon_mask;
         newline_mask = next_newline_mask;
       }
     }
-    let buffer = &buffer[buffer_offset..];
-    debug_assert_ne!(newline_mask, 0);
     if newline_mask == 0 {
-      unsafe { unreachable_unchecked() };
+      return None;
     }
+    let buffer = &buffer[buffer_offset..];
 
     let batch_offset = newline_mask.ilog2();
     if batch_offset == BYTES_PER_BATCH as u32 - 1 {
       let buffer = &buffer[BYTES_PER_BATCH..];
-      let (semicolon_mask, newline_mask) = read_next_from_buffer(buffer);
-      (buffer, semicolon_mask, newline_mask, 0)
+      let (semicolon_mask, newline_mask) = Self::read_batch(buffer, last_batch_mask);
+      Some((buffer, semicolon_mask, newline_mask, 0))
     } else {
       let remove_mask = !((2 << batch_offset) - 1);
-      (
+      Some((
         buffer,
         semicolon_mask & remove_mask,
         newline_mask & remove_mask,
         batch_offset + 1,
-      )
+      ))
     }
   }
 
@@ -89,15 +219,232 @@ impl<'a> Scanner<'a> {
   /// assuming the first `BUFFER_OVERLAP` bytes are overlapping with the
   /// previous slice.
   pub fn from_midpoint<'b: 'a>(buffer: &'b [u8]) -> Self {
-    debug_assert!(buffer.len() >= BUFFER_OVERLAP);
+    Self::from_midpoint_with_len(buffer, buffer.len())
+  }
+
+  /// Like `from_midpoint`, but only the first `len` bytes of `buffer` are
+  /// scanned, and the contents of the buffer past `len` are ignored.
+  ///
+  /// Unless the input ends within them, the first `BUFFER_OVERLAP` bytes
+  /// must contain a newline, which they do if no record is longer than
+  /// `BUFFER_OVERLAP` bytes. Panics otherwise.
+  pub fn from_midpoint_with_len<'b: 'a>(buffer: &'b [u8], len: usize) -> Self {
+    match Self::try_from_midpoint_with_len(buffer, len) {
+      Ok(scanner) => scanner,
+      Err(err) => panic!("{err}"),
+    }
+  }
+
+  /// Like `from_midpoint_with_len`, but returns an error if there is no
+  /// newline in the first `BUFFER_OVERLAP` bytes of `buffer`, in which case
+  /// there is no record boundary to start from.
+  pub fn try_from_midpoint_with_len<'b: 'a>(buffer: &'b [u8], len: usize) -> BarseResult<Self> {
+    Self::try_from_midpoint_with_overlap(buffer, len, BUFFER_OVERLAP)
+  }
+
+  /// Like `try_from_midpoint_with_len`, but the first `overlap` bytes of
+  /// `buffer` overlap with the previous slice instead of `BUFFER_OVERLAP`,
+  /// e.g. `ExtraFields::buffer_overlap`. `overlap` must be a multiple of the
+  /// scanner's batch size.
+  pub fn try_from_midpoint_with_overlap<'b: 'a>(
+    buffer: &'b [u8],
+    len: usize,
+    overlap: usize,
+  ) -> BarseResult<Self> {
     debug_assert!(buffer.len().is_multiple_of(BYTES_PER_BATCH));
+    debug_assert!(overlap.is_multiple_of(BYTES_PER_BATCH));
+    if len <= overlap {
+      // The input ends within the overlap region, meaning the scanner over
+      // the previous slice will process every remaining record. The buffer
+      // itself may be shorter than the overlap if it is the tail of the
+      // input.
+      return Ok(Self::exhausted());
+    }
+    debug_assert!(buffer.len() >= overlap);
+
+    let input = &buffer[..len];
+    let (buffer, last_batch_mask) = Self::truncate_to_len(buffer, len);
     let (buffer, semicolon_mask, newline_mask, batch_offset) =
-      Self::find_starting_point_in_overlap(buffer);
-    Self {
+      Self::find_starting_point_in_overlap(buffer, last_batch_mask, overlap).ok_or_else(|| {
+        BarseError::new(format!(
+          "No newline in the first {overlap} bytes of the slice, so it starts within a record \
+           longer than {overlap} bytes"
+        ))
+      })?;
+    Ok(Self {
       buffer,
       semicolon_mask,
       newline_mask,
+      last_batch_mask,
       batch_offset,
+      input,
+      finished: false,
+      decimal_separator: DecimalSeparator::Dot,
+      extra_fields: ExtraFields::Forbid,
+    })
+  }
+
+  /// Constructs a scanner which yields no records.
+  fn exhausted() -> Self {
+    Self {
+      buffer: &EMPTY_BATCH.0,
+      semicolon_mask: 0,
+      newline_mask: 0,
+      last_batch_mask: 0,
+      batch_offset: 0,
+      input: &[],
+      finished: true,
+      decimal_separator: DecimalSeparator::Dot,
+      extra_fields: ExtraFields::Forbid,
+    }
+  }
+
+  /// Skips the first record, up to and including the first newline, e.g. to
+  /// discard a header line. This must be called before iterating, and the
+  /// skipped record need not be well-formed.
+  pub fn skip_header(mut self) -> Self {
+    if self.finished {
+      return self;
+    }
+    while self.newline_mask == 0 {
+      if !self.read_next() {
+        return self.finish();
+      }
+    }
+
+    let newline_offset = self.newline_mask.trailing_zeros();
+    if newline_offset == BYTES_PER_BATCH as u32 - 1 {
+      if !self.read_next() {
+        return self.finish();
+      }
+      self.batch_offset = 0;
+    } else {
+      let remove_mask = !((2 << newline_offset) - 1);
+      self.semicolon_mask &= remove_mask;
+      self.newline_mask &= remove_mask;
+      self.batch_offset = newline_offset + 1;
+    }
+    self
+  }
+
+  /// Marks the scanner as having consumed all of its input.
+  fn finish(self) -> Self {
+    Self {
+      semicolon_mask: 0,
+      newline_mask: 0,
+      finished: true,
+      ..self
+    }
+  }
+
+  /// Parses temperature readings using `decimal_separator`, e.g. `12,3` for
+  /// `DecimalSeparator::Comma`.
+  pub fn with_decimal_separator(self, decimal_separator: DecimalSeparator) -> Self {
+    Self {
+      decimal_separator,
+      ..self
+    }
+  }
+
+  /// Skips any fields following the temperature reading of each record if
+  /// `extra_fields` is `ExtraFields::Skip`. Scanners from the midpoint of the
+  /// input must also be constructed with an overlap of
+  /// `extra_fields.buffer_overlap()`.
+  pub fn with_extra_fields(self, extra_fields: ExtraFields) -> Self {
+    Self {
+      extra_fields,
+      ..self
+    }
+  }
+
+  /// Returns the number of bytes of the input this scanner has advanced past,
+  /// i.e. the offset of the start of the next record to be returned. This
+  /// reaches the length of the input once iteration has finished.
+  pub fn bytes_consumed(&self) -> usize {
+    if self.finished {
+      return self.input.len();
+    }
+    let next_record = self.offset_to_ptr(self.batch_offset);
+    (next_record.addr() - self.input.as_ptr().addr()).min(self.input.len())
+  }
+
+  /// Returns the number of bytes of the input left to scan.
+  pub fn remaining(&self) -> usize {
+    self.input.len() - self.bytes_consumed()
+  }
+
+  /// Panics if the bookkeeping of the scanner is inconsistent with its
+  /// buffer. This holds between records, i.e. after construction and after
+  /// every call to `next`.
+  #[cfg(any(test, debug_assertions))]
+  pub fn check_invariants(&self) {
+    let buffer_len = self.buffer.len();
+    assert!(
+      buffer_len != 0 && buffer_len.is_multiple_of(BYTES_PER_BATCH),
+      "Buffer length {buffer_len} is not a nonzero multiple of {BYTES_PER_BATCH}"
+    );
+    assert!(
+      self.batch_offset <= BYTES_PER_BATCH as u32,
+      "Batch offset {} is past the end of the batch",
+      self.batch_offset
+    );
+    let batch_mask = 1u64.unbounded_shl(BYTES_PER_BATCH as u32).wrapping_sub(1);
+    assert!(
+      self.last_batch_mask & !batch_mask == 0
+        && self.last_batch_mask & self.last_batch_mask.wrapping_add(1) == 0,
+      "Last batch mask {:#x} is not a mask of the start of a batch",
+      self.last_batch_mask
+    );
+    if self.input.is_empty() {
+      assert!(self.finished, "Scanners over empty input must be finished");
+    }
+    if self.finished {
+      if self.input.is_empty() {
+        assert_eq!(self.semicolon_mask | self.newline_mask, 0);
+      }
+      return;
+    }
+
+    // The buffer is a suffix of the input padded to a whole number of batches,
+    // starting on a batch boundary of the input.
+    let input_start = self.input.as_ptr().addr();
+    let buffer_start = self.buffer.as_ptr().addr();
+    let padded_len = self.input.len().next_multiple_of(BYTES_PER_BATCH);
+    assert!(
+      buffer_start >= input_start && (buffer_start - input_start).is_multiple_of(BYTES_PER_BATCH),
+      "Buffer at {buffer_start:#x} is not at a batch boundary of the input at {input_start:#x}"
+    );
+    assert_eq!(
+      buffer_start + buffer_len,
+      input_start + padded_len,
+      "Buffer does not end at the end of the padded input"
+    );
+    assert_eq!(
+      self.last_batch_mask.count_ones() as usize,
+      self.input.len() - (padded_len - BYTES_PER_BATCH),
+      "Last batch mask does not cover the input in the last batch"
+    );
+
+    // Delimiters are only ever removed from the masks once consumed, so the
+    // masks are the delimiters of the batch past `batch_offset`, plus any
+    // left behind by malformed records before it.
+    let (semicolon_mask, newline_mask) = Self::read_batch(self.buffer, self.last_batch_mask);
+    let unconsumed_mask = u64::MAX.unbounded_shl(self.batch_offset);
+    for (name, mask, batch_mask) in [
+      ("Semicolon", self.semicolon_mask, semicolon_mask),
+      ("Newline", self.newline_mask, newline_mask),
+    ] {
+      assert_eq!(
+        mask & !batch_mask,
+        0,
+        "{name} mask {mask:#x} has bits not in the batch {batch_mask:#x}"
+      );
+      assert_eq!(
+        mask & unconsumed_mask,
+        batch_mask & unconsumed_mask,
+        "{name} mask {mask:#x} is missing delimiters of the batch {batch_mask:#x} past offset {}",
+        self.batch_offset
+      );
     }
   }
 
@@ -105,8 +452,12 @@ impl<'a> Scanner<'a> {
   /// bitmasks. This method assumes that we are not at the end of the file.
   fn read_next_assuming_available(&mut self) {
     debug_assert!(self.buffer.len() > BYTES_PER_BATCH);
-    self.buffer = unsafe { self.buffer.get_unchecked(BYTES_PER_BATCH..) };
-    let (semicolon_mask, newline_mask) = read_next_from_buffer(self.buffer);
+    self.buffer = if cfg!(any(feature = "safe-fallback", miri)) {
+      &self.buffer[BYTES_PER_BATCH..]
+    } else {
+      unsafe { self.buffer.get_unchecked(BYTES_PER_BATCH..) }
+    };
+    let (semicolon_mask, newline_mask) = Self::read_batch(self.buffer, self.last_batch_mask);
     self.semicolon_mask = semicolon_mask;
     self.newline_mask = newline_mask;
   }
@@ -148,7 +499,18 @@ impl<'a> Scanner<'a> {
   /// Translates a byte offset from the start of `buffer` to a pointer.
   fn offset_to_ptr(&self, offset: u32) -> *const u8 {
     debug_assert!(offset <= BYTES_PER_BATCH as u32);
-    unsafe { self.buffer.get_unchecked(offset as usize..) }.as_ptr()
+    if cfg!(any(feature = "safe-fallback", miri)) {
+      self.buffer[offset as usize..].as_ptr()
+    } else {
+      unsafe { self.buffer.get_unchecked(offset as usize..) }.as_ptr()
+    }
+  }
+
+  /// Returns the rest of the buffer starting from `offset` bytes into the
+  /// current batch.
+  #[cfg(any(feature = "safe-fallback", miri))]
+  fn offset_to_slice(&self, offset: u32) -> &'a [u8] {
+    &self.buffer[offset as usize..]
   }
 
   /// Reads batches from the buffer into the cache while no newline characters
@@ -188,6 +550,8 @@ impl<'a> Scanner<'a> {
   fn find_next_station_name(&mut self) -> Option<&'a str> {
     // Pointer to the start of the next station name.
     let station_start = self.offset_to_ptr(self.batch_offset);
+    #[cfg(any(feature = "safe-fallback", miri))]
+    let station_start_slice = self.offset_to_slice(self.batch_offset);
     if !self.read_until_next_semicolon() {
       return None;
     }
@@ -199,26 +563,27 @@ impl<'a> Scanner<'a> {
     let semicolon_offset = self.semicolon_mask.pop_lsb();
 
     let station_end = self.offset_to_ptr(semicolon_offset);
-    let station_name_slice = unsafe {
-      slice::from_raw_parts::<'a>(
-        station_start,
-        station_end.byte_offset_from_unsigned(station_start),
-      )
+    #[cfg(not(any(feature = "safe-fallback", miri)))]
+    let station_name = {
+      let station_name_slice = unsafe {
+        slice::from_raw_parts::<'a>(
+          station_start,
+          station_end.byte_offset_from_unsigned(station_start),
+        )
+      };
+      unsafe { str::from_utf8_unchecked(station_name_slice) }
     };
-    let station_name = unsafe { str::from_utf8_unchecked(station_name_slice) };
+    #[cfg(any(feature = "safe-fallback", miri))]
+    let station_name =
+      str::from_utf8(&station_start_slice[..station_end.addr() - station_start.addr()])
+        .expect("Station names must be valid UTF-8");
 
     // Temporarily set batch_offset to the character past the semicolon - where
     // we expect to find the temperature reading for this weather station. It
     // will be used in `find_next_temp_reading` and later set to the start of
     // the next line.
-    self.batch_offset = semicolon_offset + 1;
-    // If the semicolon character is the last character of this batch,
-    // preemptively fetch the next batch of `BYTES_PER_BATCH` bytes.
-    if semicolon_offset == BYTES_PER_BATCH as u32 - 1 {
-      if !self.read_next_assuming_available_if_single_thread() {
-        return None;
-      }
-      self.batch_offset = 0;
+    if !self.advance_past_semicolon(semicolon_offset) {
+      return None;
     }
 
     debug_assert!(
@@ -228,6 +593,101 @@ impl<'a> Scanner<'a> {
     Some(station_name)
   }
 
+  /// Sets `batch_offset` to the character past the semicolon at
+  /// `semicolon_offset`. If the semicolon character is the last character of
+  /// this batch, preemptively fetches the next batch of `BYTES_PER_BATCH`
+  /// bytes, returning `false` if EOF was reached.
+  #[must_use]
+  fn advance_past_semicolon(&mut self, semicolon_offset: u32) -> bool {
+    self.batch_offset = semicolon_offset + 1;
+    if semicolon_offset == BYTES_PER_BATCH as u32 - 1 {
+      if !self.read_next_assuming_available_if_single_thread() {
+        return false;
+      }
+      self.batch_offset = 0;
+    }
+    true
+  }
+
+  /// Finds and parses a temperature reading which is followed by another
+  /// semicolon rather than a newline, i.e. any but the last reading of a
+  /// record with several. Returns `None` if EOF was reached.
+  ///
+  /// The parse table expects readings to be followed by a newline, so the
+  /// reading is copied out of the buffer with a newline in place of the
+  /// semicolon.
+  fn find_next_column_reading(&mut self) -> Option<TemperatureReading> {
+    let buffer = self.buffer;
+    let temp_bytes = &buffer[self.batch_offset as usize..];
+    if !self.read_until_next_semicolon() {
+      return None;
+    }
+
+    let semicolon_offset = self.semicolon_mask.pop_lsb();
+    let temp_len = self.offset_to_ptr(semicolon_offset).addr() - temp_bytes.as_ptr().addr();
+    let temp_len = temp_len.min(MAX_TEMP_READING_LEN);
+    let mut encoding = [0; MAX_TEMP_READING_LEN + 1];
+    encoding[..temp_len].copy_from_slice(&temp_bytes[..temp_len]);
+    encoding[temp_len] = b'\n';
+    let reading = TemperatureReading::from_bytes_with_separator(&encoding, self.decimal_separator);
+
+    if !self.advance_past_semicolon(semicolon_offset) {
+      return None;
+    }
+    Some(reading)
+  }
+
+  /// Returns whether the first delimiter past `batch_offset` is a semicolon
+  /// rather than a newline, peeking at the next batch if there are none left
+  /// in this one. Returns `None` if EOF was reached.
+  fn next_delimiter_is_semicolon(&self) -> Option<bool> {
+    let (semicolon_mask, newline_mask) = if self.semicolon_mask | self.newline_mask != 0 {
+      (self.semicolon_mask, self.newline_mask)
+    } else if self.buffer.len() == BYTES_PER_BATCH {
+      return None;
+    } else {
+      Self::read_batch(&self.buffer[BYTES_PER_BATCH..], self.last_batch_mask)
+    };
+    Some(semicolon_mask.trailing_zeros() < newline_mask.trailing_zeros())
+  }
+
+  /// Advances `batch_offset` past the newline ending the current record,
+  /// dropping the semicolons of the fields before it, and returns `false` if
+  /// EOF was reached first.
+  #[must_use]
+  fn skip_to_next_line(&mut self) -> bool {
+    while self.newline_mask == 0 {
+      if !self.read_next() {
+        return false;
+      }
+    }
+    let newline_offset = self.newline_mask.pop_lsb();
+    self.semicolon_mask &= u64::MAX.unbounded_shl(newline_offset + 1);
+    self.batch_offset = newline_offset + 1;
+    true
+  }
+
+  /// Like `find_next_temp_reading`, but the reading may be followed by more
+  /// fields, which are skipped. Records without extra fields take the same
+  /// path as `find_next_temp_reading`, and the others are parsed like the
+  /// leading readings of `next_record`.
+  fn find_next_temp_reading_skipping_extra_fields(&mut self) -> Option<TemperatureReading> {
+    if !self.next_delimiter_is_semicolon()? {
+      return self.find_next_temp_reading();
+    }
+    let reading = self.find_next_column_reading()?;
+    self.skip_to_next_line().then_some(reading)
+  }
+
+  /// Finds and parses the reading ending the current record, according to
+  /// `extra_fields`.
+  fn find_last_temp_reading(&mut self) -> Option<TemperatureReading> {
+    match self.extra_fields {
+      ExtraFields::Forbid => self.find_next_temp_reading(),
+      ExtraFields::Skip => self.find_next_temp_reading_skipping_extra_fields(),
+    }
+  }
+
   /// Reads the next batch of characters from the file for a temperature
   /// reading without a delimiting newline character in the current batch.
   /// This method simply calls `read_next_assuming_available_if_single_thread`
@@ -260,6 +720,7 @@ impl<'a> Scanner<'a> {
   /// the next batch if no newline character was found in the current batch.
   /// Then the temperature encoding may be loaded into a u64 with an unaligned
   /// read from this copied buffer.
+  #[cfg(not(any(feature = "safe-fallback", miri)))]
   fn parse_temp_from_copied_buffer(&mut self, start_offset: u32) -> Option<TemperatureReading> {
     debug_assert!(BYTES_PER_BATCH >= std::mem::size_of::<u64>());
     // Offset in the current batch of the start of `temp_storage`, i.e. 8 bytes
@@ -288,11 +749,14 @@ impl<'a> Scanner<'a> {
       temp_storage[1] = unsafe { *(self.buffer.as_ptr() as *const u64) };
     }
 
-    Some(TemperatureReading::from_raw_ptr(unsafe {
-      temp_storage
-        .as_ptr()
-        .byte_add(start_offset as usize - TMP_OFFSET) as *const u8
-    }))
+    Some(TemperatureReading::from_raw_ptr_with_separator(
+      unsafe {
+        temp_storage
+          .as_ptr()
+          .byte_add(start_offset as usize - TMP_OFFSET) as *const u8
+      },
+      self.decimal_separator,
+    ))
   }
 
   /// Finds and parses the next temperature reading from the buffer, returning
@@ -302,6 +766,7 @@ impl<'a> Scanner<'a> {
   /// mode, when `buffer` may not span to the end of the file. In
   /// singlethreaded mode, every semicolon must be followed by a temperature
   /// reading + newline, so we can't possibly reach EOF in this method.
+  #[cfg(not(any(feature = "safe-fallback", miri)))]
   fn find_next_temp_reading(&mut self) -> Option<TemperatureReading> {
     let start_offset = self.batch_offset;
     // The pointer to the start of the temperature reading is the
@@ -320,7 +785,7 @@ impl<'a> Scanner<'a> {
         return None;
       }
 
-      TemperatureReading::from_raw_ptr(temp_start_ptr)
+      TemperatureReading::from_raw_ptr_with_separator(temp_start_ptr, self.decimal_separator)
     };
 
     // The offset of the next line is one past the newline character following
@@ -329,29 +794,173 @@ impl<'a> Scanner<'a> {
 
     Some(reading)
   }
+
+  /// Like the unsafe `find_next_temp_reading`, but parses the temperature
+  /// reading from a slice of the rest of the buffer, which always extends past
+  /// the following newline, so there is no page boundary to worry about.
+  #[cfg(any(feature = "safe-fallback", miri))]
+  fn find_next_temp_reading(&mut self) -> Option<TemperatureReading> {
+    let temp_bytes = self.offset_to_slice(self.batch_offset);
+    if self.newline_mask == 0 && !self.refresh_batch_for_trailing_temp() {
+      return None;
+    }
+    let reading = TemperatureReading::from_bytes_with_separator(temp_bytes, self.decimal_separator);
+
+    self.batch_offset = self.newline_mask.pop_lsb() + 1;
+
+    Some(reading)
+  }
+}
+
+impl<'a> Scanner<'a> {
+  /// Like `next`, but for records with several temperature readings, each
+  /// preceded by a semicolon, e.g. `Station;12.3;-4.5`. The readings are
+  /// parsed into `readings`, and the station name is returned. Every record
+  /// must have exactly `readings.len()` readings, which must be at least one.
+  ///
+  /// Records with several readings may be longer than `BUFFER_OVERLAP`, so
+  /// this should only be used to scan the whole input from the start.
+  pub fn next_record(&mut self, readings: &mut [TemperatureReading]) -> Option<&'a str> {
+    let record = self.find_next_record(readings);
+    if record.is_none() {
+      self.finished = true;
+    }
+    record
+  }
+
+  fn find_next_record(&mut self, readings: &mut [TemperatureReading]) -> Option<&'a str> {
+    let (last, columns) = readings
+      .split_last_mut()
+      .expect("Records must have at least one temperature reading");
+    let station_name = self.find_next_station_name()?;
+    for reading in columns {
+      *reading = self.find_next_column_reading()?;
+    }
+    *last = self.find_last_temp_reading()?;
+    Some(station_name)
+  }
 }
 
 impl<'a> Iterator for Scanner<'a> {
   type Item = (&'a str, TemperatureReading);
 
+  #[inline(always)]
   fn next(&mut self) -> Option<Self::Item> {
-    let station_name = self.find_next_station_name()?;
-    let temperature_reading = self.find_next_temp_reading()?;
+    let Some(station_name) = self.find_next_station_name() else {
+      self.finished = true;
+      return None;
+    };
+    let Some(temperature_reading) = self.find_last_temp_reading() else {
+      self.finished = true;
+      return None;
+    };
     Some((station_name, temperature_reading))
   }
 }
 
+/// A `Scanner` which fails with the offset of the first record whose station
+/// name is longer than `max_name_len` bytes, or whose reading is not followed
+/// by a newline within `MAX_TEMP_READING_LEN` bytes (and `MAX_EXTRA_FIELDS_LEN`
+/// bytes of skipped fields), instead of reading past the end of the record and
+/// yielding garbage. Only the lengths of records are checked, which is much
+/// cheaper than the full format check of `ValidatingScanner`.
+///
+/// Records are only checked up to the end of the scanner's input, so those
+/// running past the end of a chunk are left to the scanner.
+pub struct LengthCheckedScanner<'a> {
+  scanner: Scanner<'a>,
+  max_name_len: usize,
+  /// Set once iteration has returned `None` or an error.
+  finished: bool,
+}
+
+impl<'a> LengthCheckedScanner<'a> {
+  /// Checks the records `scanner` reads from here on. Fails if
+  /// `max_name_len` is longer than `MAX_STATION_NAME_LEN`, the longest name
+  /// `Scanner` can read.
+  pub fn new(scanner: Scanner<'a>, max_name_len: usize) -> BarseResult<Self> {
+    if max_name_len > MAX_STATION_NAME_LEN {
+      return Err(BarseError::new(format!(
+        "Maximum station name length must be at most {MAX_STATION_NAME_LEN} bytes, got \
+         {max_name_len}"
+      )));
+    }
+    Ok(Self {
+      scanner,
+      max_name_len,
+      finished: false,
+    })
+  }
+
+  /// Checks the lengths of the record at the start of `record`, which runs to
+  /// the end of the input.
+  fn check_record(&self, record: &[u8]) -> Result<(), RecordErrorKind> {
+    let name = &record[..record.len().min(self.max_name_len + 1)];
+    let semicolon = match name.iter().position(|&b| b == b';' || b == b'\n') {
+      Some(end) if name[end] == b';' => end,
+      Some(_) => return Err(RecordErrorKind::MissingSemicolon),
+      None if name.len() > self.max_name_len => return Err(RecordErrorKind::NameTooLong),
+      None => return Ok(()),
+    };
+
+    let max_fields_len = match self.scanner.extra_fields {
+      ExtraFields::Forbid => MAX_TEMP_READING_LEN,
+      ExtraFields::Skip => MAX_TEMP_READING_LEN + MAX_EXTRA_FIELDS_LEN,
+    };
+    let fields = &record[semicolon + 1..];
+    let fields = &fields[..fields.len().min(max_fields_len + 1)];
+    if fields.len() <= max_fields_len || fields.contains(&b'\n') {
+      return Ok(());
+    }
+    Err(match self.scanner.extra_fields {
+      ExtraFields::Forbid => RecordErrorKind::MalformedTemperature(
+        String::from_utf8_lossy(&fields[..max_fields_len]).into_owned(),
+      ),
+      ExtraFields::Skip => RecordErrorKind::ExtraFieldsTooLong,
+    })
+  }
+}
+
+impl<'a> Iterator for LengthCheckedScanner<'a> {
+  type Item = BarseResult<(&'a str, TemperatureReading)>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.finished {
+      return None;
+    }
+    let offset = self.scanner.bytes_consumed();
+    if let Err(kind) = self.check_record(&self.scanner.input[offset..]) {
+      self.finished = true;
+      return Some(Err(BarseError::MalformedRecord {
+        offset: offset as u64,
+        line: None,
+        kind,
+      }));
+    }
+    let record = self.scanner.next();
+    self.finished = record.is_none();
+    record.map(Ok)
+  }
+}
+
 #[cfg(test)]
 mod tests {
   use googletest::{gtest, prelude::*};
   use itertools::Itertools;
 
   use crate::{
+    aligned::AlignedInput,
+    error::{BarseError, RecordErrorKind},
     temperature_reading::TemperatureReading,
-    test_util::{random_input_file, simple_scanner_iter, AlignedBuffer},
+    test_util::{
+      random_input_file, random_input_file_with_padding_byte, simple_scanner_iter, AlignedBuffer,
+    },
   };
 
-  use super::Scanner;
+  use super::{
+    ExtraFields, LengthCheckedScanner, Scanner, BUFFER_OVERLAP, BUFFER_OVERLAP_WITH_EXTRA_FIELDS,
+    BYTES_PER_BATCH, MAX_EXTRA_FIELDS_LEN, MAX_STATION_NAME_LEN,
+  };
 
   #[gtest]
   fn test_iter_single_element() {
@@ -569,7 +1178,7 @@ mod tests {
     let input = random_input_file(13, 10_000, 1_000).unwrap();
 
     let scanner = Scanner::from_start(input.padded_slice());
-    let simple_scanner = simple_scanner_iter(input.padded_slice());
+    let simple_scanner = simple_scanner_iter(input.exact_slice());
     expect_eq!(scanner.collect_vec(), simple_scanner.collect_vec());
   }
 
@@ -579,10 +1188,277 @@ mod tests {
     let input = random_input_file(17, 400_000, 10_000).unwrap();
 
     let scanner = Scanner::from_start(input.padded_slice());
-    let simple_scanner = simple_scanner_iter(input.padded_slice());
+    let simple_scanner = simple_scanner_iter(input.exact_slice());
     expect_eq!(scanner.collect_vec(), simple_scanner.collect_vec());
   }
 
+  #[gtest]
+  fn test_iter_ignores_nonzero_padding() {
+    let data = b"Ab;20.8\nCd;1.9\n";
+    let mut buffer = AlignedBuffer { buffer: [0; 64] };
+    buffer.buffer[..data.len()].copy_from_slice(data);
+    for (i, b) in buffer.buffer[data.len()..].iter_mut().enumerate() {
+      *b = if i % 2 == 0 { b';' } else { b'\n' };
+    }
+
+    let mut scanner = Scanner::from_start_with_len(&buffer.buffer, data.len());
+    expect_that!(
+      scanner.next(),
+      some((eq("Ab"), eq(TemperatureReading::new(208))))
+    );
+    expect_that!(
+      scanner.next(),
+      some((eq("Cd"), eq(TemperatureReading::new(19))))
+    );
+    expect_that!(scanner.next(), none());
+  }
+
+  #[gtest]
+  fn test_against_small_nonzero_padding() {
+    let input = random_input_file_with_padding_byte(29, 10_000, 1_000, b'\n').unwrap();
+
+    let scanner = Scanner::from_start_with_len(input.padded_slice(), input.len());
+    let simple_scanner = simple_scanner_iter(input.exact_slice());
+    expect_eq!(scanner.collect_vec(), simple_scanner.collect_vec());
+  }
+
+  #[gtest]
+  fn test_iter_from_midpoint_ignores_nonzero_padding() {
+    let buffer = AlignedBuffer {
+      buffer: *b"city1;3.4\ncity2;\
+                 5.6\ncity3;7.8\nci\
+                 ti4;9.0\ncity6;0.\
+                 1\ncity7;2.3\ncity\
+                 8;4.5\n;1.2\n;3.4\n\
+                 ;\n;\n;\n;\n;\n;\n;\n;\n\
+                 ;\n;\n;\n;\n;\n;\n;\n;\n\
+                 ;\n;\n;\n;\n;\n;\n;\n;\n",
+    };
+
+    let mut scanner = Scanner::from_midpoint_with_len(&buffer.buffer, 70);
+    expect_that!(
+      scanner.next(),
+      some((eq("city8"), eq(TemperatureReading::new(45))))
+    );
+    expect_that!(scanner.next(), none());
+  }
+
+  #[gtest]
+  fn test_iter_from_midpoint_input_ends_in_overlap() {
+    let buffer = AlignedBuffer {
+      buffer: *b"city1;3.4\ncity2;\
+                 5.6\ncity3;7.8\n\0\0\
+                 \0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\
+                 \0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\
+                 \0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\
+                 \0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\
+                 \0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\
+                 \0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0",
+    };
+
+    let mut scanner = Scanner::from_midpoint_with_len(&buffer.buffer, 30);
+    expect_that!(scanner.next(), none());
+  }
+
+  /// Returns `len` bytes ending in a newline, e.g. the end of the records
+  /// the scanner over the previous slice finishes.
+  fn overlap_prefix(len: usize) -> String {
+    let records = "Tail;1.0\n".repeat(len.div_ceil(9));
+    records[records.len() - len..].to_owned()
+  }
+
+  /// Returns the offset of the first record of `text` which ends past its
+  /// first `overlap` bytes.
+  fn first_record_past_overlap(text: &str, overlap: usize) -> usize {
+    text
+      .split_inclusive('\n')
+      .scan(0, |end, line| {
+        *end += line.len();
+        Some(*end)
+      })
+      .take_while(|&end| end <= overlap)
+      .last()
+      .unwrap_or(0)
+  }
+
+  /// Returns the records of `text` which end past its first `BUFFER_OVERLAP`
+  /// bytes, which are those a scanner from the midpoint of `text` yields,
+  /// since the scanner over the previous slice yields the rest.
+  fn records_past_overlap(text: &str) -> Vec<(&str, TemperatureReading)> {
+    let start = first_record_past_overlap(text, BUFFER_OVERLAP);
+    simple_scanner_iter(&text.as_bytes()[start..]).collect()
+  }
+
+  /// Parses the first two fields of each line of `text`, ignoring the rest.
+  fn records_skipping_extra_fields(text: &str) -> Vec<(&str, TemperatureReading)> {
+    text
+      .lines()
+      .map(|line| {
+        let mut fields = line.split(';');
+        let station = fields.next().unwrap();
+        let temp = fields.next().unwrap().parse::<f32>().unwrap();
+        (
+          station,
+          TemperatureReading::new((temp * 10.).round() as i16),
+        )
+      })
+      .collect()
+  }
+
+  #[gtest]
+  fn test_from_midpoint_at_every_record_boundary() {
+    // The shortest and longest records, and records whose name or reading
+    // spans the end of the overlap for some boundaries.
+    let shapes = [(1, "0.0"), (7, "-12.3"), (50, "-99.9"), (50, "9.9")];
+    for (name_len, temp) in shapes {
+      let name = (0..name_len)
+        .map(|i| (b'a' + (i % 26) as u8) as char)
+        .collect::<String>();
+      let records = format!("{name};{temp}\n").repeat(4);
+      // Places the first record starting in the slice at every offset into
+      // the overlap, including right after a newline ending the overlap.
+      for boundary in 0..=BUFFER_OVERLAP {
+        let text = format!("{}{records}Last;4.5\n", overlap_prefix(boundary));
+        let input = AlignedInput::new(&text);
+        let records = Scanner::try_from_midpoint_with_len(input.padded_slice(), input.len())
+          .map(|scanner| scanner.collect_vec());
+        expect_that!(
+          records,
+          ok(eq(&records_past_overlap(&text))),
+          "boundary {boundary}, text {text:?}"
+        );
+      }
+    }
+  }
+
+  #[gtest]
+  fn test_from_midpoint_with_extra_fields_at_every_record_boundary() {
+    let longest_extra_fields = format!(";{}", "x".repeat(MAX_EXTRA_FIELDS_LEN - 1));
+    let shapes = [
+      (1, "0.0", ";"),
+      (7, "-12.3", ";55.3;a"),
+      (50, "-99.9", longest_extra_fields.as_str()),
+      (50, "9.9", ""),
+    ];
+    for (name_len, temp, extra_fields) in shapes {
+      let name = (0..name_len)
+        .map(|i| (b'a' + (i % 26) as u8) as char)
+        .collect::<String>();
+      let records = format!("{name};{temp}{extra_fields}\n").repeat(4);
+      for boundary in 0..=BUFFER_OVERLAP_WITH_EXTRA_FIELDS {
+        let text = format!("{}{records}Last;4.5;1\n", overlap_prefix(boundary));
+        let input = AlignedInput::new(&text);
+        let records = Scanner::try_from_midpoint_with_overlap(
+          input.padded_slice(),
+          input.len(),
+          BUFFER_OVERLAP_WITH_EXTRA_FIELDS,
+        )
+        .map(|scanner| scanner.with_extra_fields(ExtraFields::Skip).collect_vec());
+        let start = first_record_past_overlap(&text, BUFFER_OVERLAP_WITH_EXTRA_FIELDS);
+        expect_that!(
+          records,
+          ok(eq(&records_skipping_extra_fields(&text[start..]))),
+          "boundary {boundary}, text {text:?}"
+        );
+      }
+    }
+  }
+
+  #[gtest]
+  fn test_from_midpoint_no_newline_in_overlap() {
+    let texts = [
+      format!("{};1.0\nOslo;2.0\n", "a".repeat(BUFFER_OVERLAP + 10)),
+      // A semicolon, but no newline, in the last batch of the overlap.
+      format!(
+        "{};{}.0\nOslo;2.0\n",
+        "b".repeat(BUFFER_OVERLAP - 10),
+        "9".repeat(20)
+      ),
+    ];
+    for text in texts {
+      let input = AlignedInput::new(&text);
+      expect_that!(
+        Scanner::try_from_midpoint_with_len(input.padded_slice(), input.len()).map(|_| ()),
+        err(displays_as(contains_substring("No newline"))),
+        "text {text:?}"
+      );
+    }
+  }
+
+  #[gtest]
+  fn test_bytes_consumed() {
+    let input = random_input_file(0x8e1f, 10_000, 100).unwrap();
+    let line_ends = str::from_utf8(input.exact_slice())
+      .unwrap()
+      .split_inclusive('\n')
+      .scan(0, |end, line| {
+        *end += line.len();
+        Some(*end)
+      })
+      .collect_vec();
+
+    let mut scanner = Scanner::from_start_with_len(input.padded_slice(), input.len());
+    expect_eq!(scanner.bytes_consumed(), 0);
+    expect_eq!(scanner.remaining(), input.len());
+    for line_end in line_ends {
+      expect_that!(scanner.next(), some(anything()));
+      expect_eq!(scanner.bytes_consumed(), line_end);
+      expect_eq!(scanner.remaining(), input.len() - line_end);
+    }
+    expect_that!(scanner.next(), none());
+    expect_eq!(scanner.bytes_consumed(), input.len());
+    expect_eq!(scanner.remaining(), 0);
+  }
+
+  #[gtest]
+  fn test_bytes_consumed_from_midpoint_reaches_total() {
+    let input = random_input_file(0x51d3, 1_000, 100).unwrap();
+    let mut scanner = Scanner::from_midpoint_with_len(input.padded_slice(), input.len());
+    let mut consumed = scanner.bytes_consumed();
+    while scanner.next().is_some() {
+      expect_gt!(scanner.bytes_consumed(), consumed);
+      consumed = scanner.bytes_consumed();
+    }
+    expect_eq!(scanner.bytes_consumed(), input.len());
+  }
+
+  #[gtest]
+  fn test_invariants_hold_after_every_record() {
+    let input = random_input_file(0x2c9b, 5_000, 200).unwrap();
+    let check_records = |mut scanner: Scanner| {
+      scanner.check_invariants();
+      let mut records = 0;
+      while scanner.next().is_some() {
+        scanner.check_invariants();
+        records += 1;
+      }
+      scanner.check_invariants();
+      records
+    };
+
+    for extra_fields in [ExtraFields::Forbid, ExtraFields::Skip] {
+      let scanner = Scanner::from_start_with_len(input.padded_slice(), input.len())
+        .with_extra_fields(extra_fields);
+      expect_eq!(check_records(scanner), 5_000);
+    }
+    let scanner = Scanner::from_start_with_len(input.padded_slice(), input.len()).skip_header();
+    expect_eq!(check_records(scanner), 4_999);
+
+    // Scanners from the midpoint start at every batch boundary of the input,
+    // and run to the end of the input.
+    for offset in (0..input.len()).step_by(BYTES_PER_BATCH) {
+      let scanner =
+        Scanner::from_midpoint_with_len(&input.padded_slice()[offset..], input.len() - offset);
+      check_records(scanner);
+    }
+  }
+
+  #[gtest]
+  fn test_iter_empty_input() {
+    let mut scanner = Scanner::from_start_with_len(&[], 0);
+    expect_that!(scanner.next(), none());
+  }
+
   #[gtest]
   fn test_iter_from_midpoint_name_crosses_over() {
     let buffer = AlignedBuffer {
@@ -718,4 +1594,251 @@ mod tests {
     );
     expect_that!(scanner.next(), none());
   }
+
+  #[gtest]
+  fn test_try_from_start() {
+    let mut buffer = AlignedBuffer {
+      buffer: [0; 2 * BYTES_PER_BATCH],
+    };
+    buffer.buffer[..9].copy_from_slice(b"Oslo;1.0\n");
+
+    let scanner = Scanner::try_from_start(&buffer.buffer[..BYTES_PER_BATCH]);
+    expect_that!(
+      scanner.map(|scanner| scanner.take(1).collect_vec()),
+      ok(eq(&vec![("Oslo", TemperatureReading::new(10))]))
+    );
+    expect_that!(
+      Scanner::try_from_start(&buffer.buffer[1..BYTES_PER_BATCH + 1]).map(|_| ()),
+      err(displays_as(contains_substring("not aligned")))
+    );
+    expect_that!(
+      Scanner::try_from_start(&buffer.buffer[..BYTES_PER_BATCH - 1]).map(|_| ()),
+      err(displays_as(contains_substring("not a multiple")))
+    );
+  }
+
+  #[gtest]
+  fn test_skip_header() {
+    const RECORDS: &str = "Oslo;1.0\nAbha;-12.5\nHamburg;34.2\n";
+    // Headers ending on either side of batch boundaries.
+    for header_len in 1..=2 * BYTES_PER_BATCH + 1 {
+      let header = format!("{}\n", &"station;temperature".repeat(8)[..header_len - 1]);
+      let input = AlignedInput::new(format!("{header}{RECORDS}"));
+      let scanner = Scanner::from_start_with_len(input.padded_slice(), input.len()).skip_header();
+      expect_eq!(
+        scanner.collect_vec(),
+        simple_scanner_iter(RECORDS.as_bytes()).collect_vec(),
+        "header of {header_len} bytes"
+      );
+    }
+
+    for header in ["station;temperature\n", "station;temperature"] {
+      let input = AlignedInput::new(header);
+      let mut scanner =
+        Scanner::from_start_with_len(input.padded_slice(), input.len()).skip_header();
+      expect_that!(scanner.next(), none());
+      expect_eq!(scanner.remaining(), 0);
+    }
+  }
+
+  /// Small enough to run under Miri.
+  #[gtest]
+  fn test_records_at_every_offset() {
+    const RECORDS: &str = "Llanfairpwllgwyngyllgogerychwyrndrobwllllantysilio;-99.9\n\
+                           São Paulo;5.0\n\
+                           Abha;12.3\n";
+    // Shifts the records across batch boundaries with short leading records.
+    for shift in 0..2 * BYTES_PER_BATCH {
+      let text = format!(
+        "{}{};0.0\n{RECORDS}",
+        "a;0.0\n".repeat(shift / 6),
+        "x".repeat(1 + shift % 6)
+      );
+      let input = AlignedInput::new(&text);
+      let scanner = Scanner::from_start_with_len(input.padded_slice(), input.len());
+      expect_eq!(
+        scanner.collect_vec(),
+        simple_scanner_iter(text.as_bytes()).collect_vec(),
+        "records shifted by {shift} bytes"
+      );
+    }
+  }
+
+  #[gtest]
+  fn test_explicit_plus_sign_at_every_offset() {
+    const RECORDS: &str = "Llanfairpwllgwyngyllgogerychwyrndrobwllllantysilio;+99.9\n\
+                           São Paulo;+0.0\n\
+                           Abha;+9.9\n\
+                           Bangkok;-12.3\n";
+    for shift in 0..2 * BYTES_PER_BATCH {
+      let text = format!(
+        "{}{};+0.0\n{RECORDS}",
+        "a;+0.0\n".repeat(shift / 7),
+        "x".repeat(1 + shift % 7)
+      );
+      let input = AlignedInput::new(&text);
+      let scanner = Scanner::from_start_with_len(input.padded_slice(), input.len());
+      expect_eq!(
+        scanner.collect_vec(),
+        simple_scanner_iter(text.as_bytes()).collect_vec(),
+        "records shifted by {shift} bytes"
+      );
+    }
+  }
+
+  #[gtest]
+  fn test_skip_extra_fields_at_every_offset() {
+    let records = format!(
+      "Llanfairpwllgwyngyllgogerychwyrndrobwllllantysilio;-99.9;{}\n\
+       São Paulo;5.0;\n\
+       Abha;12.3;55.3;x\n\
+       Bangkok;-1.2\n\
+       Oslo;0.0;;;\n",
+      "x".repeat(MAX_EXTRA_FIELDS_LEN - 1)
+    );
+    // Shifts the records across batch boundaries with short leading records.
+    for shift in 0..2 * BYTES_PER_BATCH {
+      let text = format!(
+        "{}{};0.0;1\n{records}",
+        "a;0.0;1\n".repeat(shift / 8),
+        "x".repeat(1 + shift % 8)
+      );
+      let input = AlignedInput::new(&text);
+      let scanner = Scanner::from_start_with_len(input.padded_slice(), input.len())
+        .with_extra_fields(ExtraFields::Skip);
+      expect_eq!(
+        scanner.collect_vec(),
+        records_skipping_extra_fields(&text),
+        "records shifted by {shift} bytes"
+      );
+    }
+  }
+
+  #[gtest]
+  fn test_next_record_two_columns() {
+    const RECORDS: &str = "Llanfairpwllgwyngyllgogerychwyrndrobwllllantysilio;-99.9;99.9\n\
+                           São Paulo;5.0;-1.2\n\
+                           Abha;12.3;4.5\n";
+    // Shifts the records across batch boundaries with short leading records.
+    for shift in 0..2 * BYTES_PER_BATCH {
+      let text = format!(
+        "{}{};0.0;1.0\n{RECORDS}",
+        "a;0.0;1.0\n".repeat(shift / 10),
+        "x".repeat(1 + shift % 10)
+      );
+      let expected = text
+        .lines()
+        .map(|line| {
+          let (station, readings) = line.split_once(';').unwrap();
+          let readings = readings
+            .split(';')
+            .map(|temp| {
+              TemperatureReading::new((temp.parse::<f32>().unwrap() * 10.0).round() as i16)
+            })
+            .collect_vec();
+          (station, readings)
+        })
+        .collect_vec();
+
+      let input = AlignedInput::new(&text);
+      let mut scanner = Scanner::from_start_with_len(input.padded_slice(), input.len());
+      let mut readings = [TemperatureReading::new(0); 2];
+      let mut records = Vec::new();
+      while let Some(station) = scanner.next_record(&mut readings) {
+        records.push((station, readings.to_vec()));
+      }
+      expect_eq!(records, expected, "records shifted by {shift} bytes");
+      expect_eq!(scanner.remaining(), 0);
+    }
+  }
+
+  /// Scans `text` with a `LengthCheckedScanner`, returning the station and
+  /// reading of each record, or the offset and kind of the error it stopped
+  /// at.
+  fn length_checked_records(
+    text: &str,
+    max_name_len: usize,
+    extra_fields: ExtraFields,
+  ) -> Vec<Result<(String, i16), (u64, RecordErrorKind)>> {
+    let input = AlignedInput::new(text);
+    let scanner = Scanner::from_start_with_len(input.padded_slice(), input.len())
+      .with_extra_fields(extra_fields);
+    LengthCheckedScanner::new(scanner, max_name_len)
+      .unwrap()
+      .map(|record| match record {
+        Ok((station, temp)) => Ok((station.to_owned(), temp.reading())),
+        Err(BarseError::MalformedRecord { offset, kind, .. }) => Err((offset, kind)),
+        Err(err) => panic!("unexpected error {err}"),
+      })
+      .collect()
+  }
+
+  #[gtest]
+  fn test_length_checked_scanner_matches_scanner() {
+    let input = random_input_file(0x1e4c7, 10_000, 400).unwrap();
+    let scanner = Scanner::from_start_with_len(input.padded_slice(), input.len());
+    let records = LengthCheckedScanner::new(scanner, MAX_STATION_NAME_LEN)
+      .unwrap()
+      .collect::<Result<Vec<_>, _>>()
+      .unwrap();
+    expect_eq!(
+      records,
+      simple_scanner_iter(input.exact_slice()).collect_vec()
+    );
+  }
+
+  #[gtest]
+  fn test_length_checked_scanner_rejects_overlong_records() {
+    let oslo = Ok(("Oslo".to_owned(), 10));
+    let long_name = "a".repeat(MAX_STATION_NAME_LEN + 1);
+    let text = format!("Oslo;1.0\n{long_name};2.0\nAbha;3.0\n");
+    expect_eq!(
+      length_checked_records(&text, MAX_STATION_NAME_LEN, ExtraFields::Forbid),
+      [oslo.clone(), Err((9, RecordErrorKind::NameTooLong))]
+    );
+    // Long enough that the scanner would otherwise read past the record.
+    let text = format!("Oslo;1.0\n{}\n", "a".repeat(4 * BYTES_PER_BATCH));
+    expect_eq!(
+      length_checked_records(&text, MAX_STATION_NAME_LEN, ExtraFields::Forbid),
+      [oslo.clone(), Err((9, RecordErrorKind::NameTooLong))]
+    );
+
+    let text = "Gasselterboerveenschemond;-12.3\nAbha;3.0\n";
+    expect_eq!(
+      length_checked_records(text, 25, ExtraFields::Forbid),
+      [
+        Ok(("Gasselterboerveenschemond".to_owned(), -123)),
+        Ok(("Abha".to_owned(), 30))
+      ]
+    );
+    expect_eq!(
+      length_checked_records(text, 24, ExtraFields::Forbid),
+      [Err((0, RecordErrorKind::NameTooLong))]
+    );
+
+    expect_eq!(
+      length_checked_records("Oslo;1.0\nAbha 3.0\n", 50, ExtraFields::Forbid),
+      [oslo.clone(), Err((9, RecordErrorKind::MissingSemicolon))]
+    );
+    expect_eq!(
+      length_checked_records("Oslo;1.00000\n", 50, ExtraFields::Forbid),
+      [Err((
+        0,
+        RecordErrorKind::MalformedTemperature("1.000".to_owned())
+      ))]
+    );
+
+    let fields = ";1".repeat(MAX_EXTRA_FIELDS_LEN / 2);
+    expect_eq!(
+      length_checked_records(&format!("Oslo;-12.3{fields}\n"), 50, ExtraFields::Skip),
+      [Ok(("Oslo".to_owned(), -123))]
+    );
+    expect_eq!(
+      length_checked_records(&format!("Oslo;-12.3{fields};\n"), 50, ExtraFields::Skip),
+      [Err((0, RecordErrorKind::ExtraFieldsTooLong))]
+    );
+
+    let scanner = Scanner::from_start(&[]);
+    expect_true!(LengthCheckedScanner::new(scanner, MAX_STATION_NAME_LEN + 1).is_err());
+  }
 }
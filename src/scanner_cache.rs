@@ -1,7 +1,15 @@
 pub const BYTES_PER_BATCH: usize = 16;
 
 pub fn read_next_from_buffer(buffer: &[u8]) -> (u64, u64) {
-  let cache = unsafe { *(buffer.as_ptr() as *const u128) };
+  let cache = if cfg!(any(feature = "safe-fallback", miri)) {
+    u128::from_ne_bytes(
+      buffer[..BYTES_PER_BATCH]
+        .try_into()
+        .expect("Batch is BYTES_PER_BATCH bytes long"),
+    )
+  } else {
+    unsafe { *(buffer.as_ptr() as *const u128) }
+  };
   let semicolon_mask = char_mask(cache, b';');
   let newline_mask = char_mask(cache, b'\n');
   (semicolon_mask, newline_mask)
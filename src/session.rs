@@ -0,0 +1,501 @@
+//! A reusable aggregation session, for summarizing many inputs without paying
+//! to set up tables and worker threads for each of them.
+
+use std::{
+  iter,
+  ops::Deref,
+  panic::{self, AssertUnwindSafe},
+  sync::{
+    mpsc::{self, Receiver, Sender},
+    Arc,
+  },
+  thread::JoinHandle,
+  time::{Duration, Instant},
+};
+
+use crate::{
+  aggregator::TemperatureAggregator,
+  barse::{with_input_file, Table},
+  build_report::{BuildReport, Strategy},
+  build_table_mt::{add_sliced_records, collect_worker_results, thread_count},
+  error::{BarseError, BarseResult},
+  normalize::RecordChecks,
+  options::Options,
+  slicer::Slicer,
+  string_table::StringTable,
+  temperature_summary_table::TemperatureSummaryTable,
+  util::HasIter,
+};
+
+/// The work handed to a session worker for one input.
+struct Job {
+  slicer: Arc<Slicer>,
+  string_table: Arc<StringTable>,
+  summary_table: TemperatureSummaryTable,
+  checks: RecordChecks,
+  strict: bool,
+}
+
+/// A worker's table, handed back along with the number of records and
+/// missing readings it aggregated into the table.
+type JobResult = (TemperatureSummaryTable, BarseResult<(u64, u64)>);
+
+/// A worker thread of a session, which folds the chunks of each input it is
+/// sent into its own table of summaries.
+struct Worker {
+  jobs: Option<Sender<Job>>,
+  results: Receiver<JobResult>,
+  /// The worker's table, which the session holds while the worker is idle.
+  summary_table: Option<TemperatureSummaryTable>,
+  thread: Option<JoinHandle<()>>,
+}
+
+impl Worker {
+  fn spawn(capacity: usize) -> BarseResult<Self> {
+    let summary_table = TemperatureSummaryTable::new(capacity)?;
+    let (jobs, worker_jobs) = mpsc::channel::<Job>();
+    let (worker_results, results) = mpsc::channel();
+    let thread = std::thread::spawn(move || {
+      for job in worker_jobs {
+        let Job {
+          slicer,
+          string_table,
+          mut summary_table,
+          checks,
+          strict,
+        } = job;
+        let result = add_sliced_records(
+          &slicer,
+          &string_table,
+          &mut summary_table,
+          &TemperatureAggregator,
+          strict,
+          &checks,
+        );
+        // The input and string table must be released before the session
+        // learns that the job is done.
+        drop((slicer, string_table));
+        if worker_results.send((summary_table, result)).is_err() {
+          break;
+        }
+      }
+    });
+    Ok(Self {
+      jobs: Some(jobs),
+      results,
+      summary_table: Some(summary_table),
+      thread: Some(thread),
+    })
+  }
+
+  /// Hands the worker its table and the chunks of `slicer` to fold into it.
+  fn start(
+    &mut self,
+    slicer: &Arc<Slicer>,
+    string_table: &Arc<StringTable>,
+    options: &Options,
+  ) -> BarseResult {
+    let summary_table = self
+      .summary_table
+      .take()
+      .ok_or_else(|| BarseError::new("Session worker lost its table".to_owned()))?;
+    let job = Job {
+      slicer: slicer.clone(),
+      string_table: string_table.clone(),
+      summary_table,
+      checks: RecordChecks::new(options),
+      strict: options.strict,
+    };
+    let Some(jobs) = &self.jobs else {
+      return Err(BarseError::new("Session worker exited".to_owned()));
+    };
+    if let Err(mpsc::SendError(job)) = jobs.send(job) {
+      self.summary_table = Some(job.summary_table);
+      return Err(BarseError::new("Session worker exited".to_owned()));
+    }
+    Ok(())
+  }
+
+  /// Waits for the job `start`ed on the worker to finish. If the worker
+  /// panicked, a new one takes its place.
+  fn finish(&mut self, worker: usize, capacity: usize) -> BarseResult<(u64, u64)> {
+    match self.results.recv() {
+      Ok((summary_table, result)) => {
+        self.summary_table = Some(summary_table);
+        result
+      }
+      Err(mpsc::RecvError) => {
+        let payload = match self.thread.take().map(JoinHandle::join) {
+          Some(Err(payload)) => payload,
+          _ => Box::new("Session worker exited"),
+        };
+        *self = Self::spawn(capacity)?;
+        Err(BarseError::thread_panicked(worker, payload))
+      }
+    }
+  }
+}
+
+impl Drop for Worker {
+  fn drop(&mut self) {
+    // Closing the channel of jobs stops the thread once it is idle.
+    self.jobs = None;
+    if let Some(thread) = self.thread.take() {
+      let _ = thread.join();
+    }
+  }
+}
+
+/// The table of a `Session`, along with the report of the input just added
+/// to it.
+pub struct SummaryRef<'a> {
+  table: &'a Table,
+  report: BuildReport,
+}
+
+impl SummaryRef<'_> {
+  /// Describes how the last input was aggregated. `unique_stations` counts
+  /// every station in the table.
+  pub fn report(&self) -> &BuildReport {
+    &self.report
+  }
+}
+
+impl Deref for SummaryRef<'_> {
+  type Target = Table;
+
+  fn deref(&self) -> &Table {
+    self.table
+  }
+}
+
+/// Summarizes one input after another, reusing one table and a pool of
+/// worker threads across all of them. Building a table for each input maps
+/// and initializes fresh tables and spawns new threads, which dominates the
+/// time taken to summarize small inputs.
+///
+/// Every input is split across the session's workers, which share one table
+/// of station names, so `options.strategy` and
+/// `options.single_threaded_threshold` don't apply. The workers are stopped
+/// when the session is dropped.
+pub struct Session {
+  options: Options,
+  table: Table,
+  workers: Vec<Worker>,
+}
+
+impl Session {
+  /// Constructs a session with an empty table, which summarizes inputs with
+  /// `options` on `options.threads` threads, or one per available core. The
+  /// calling thread is one of them.
+  pub fn new(options: &Options) -> BarseResult<Self> {
+    options.validate()?;
+    let threads = options.threads.unwrap_or_else(|| {
+      std::thread::available_parallelism()
+        .map(|nonzero| nonzero.get())
+        .unwrap_or(1)
+    });
+    let workers = (1..threads)
+      .map(|_| Worker::spawn(options.table_capacity))
+      .collect::<BarseResult<_>>()?;
+    Ok(Self {
+      options: options.clone(),
+      table: Table::with_capacity(options.table_capacity)?,
+      workers,
+    })
+  }
+
+  /// The table of every input added since the session was last cleared.
+  pub fn table(&self) -> &Table {
+    &self.table
+  }
+
+  /// Removes every station from the table.
+  pub fn clear(&mut self) {
+    self.table.clear();
+  }
+
+  /// Summarizes the file at `input_path`, replacing the results of earlier
+  /// inputs.
+  pub fn aggregate(&mut self, input_path: &str) -> BarseResult<SummaryRef<'_>> {
+    self.clear();
+    self.accumulate(input_path)
+  }
+
+  /// Adds the readings of the file at `input_path` to the results of earlier
+  /// inputs. If the file can't be summarized, the table is cleared, since
+  /// some of its records may already have been added.
+  pub fn accumulate(&mut self, input_path: &str) -> BarseResult<SummaryRef<'_>> {
+    let start = Instant::now();
+    let report = with_input_file(input_path, |input, len| {
+      let map_time = start.elapsed();
+      let report = self.accumulate_from_bytes(input, len)?;
+      Ok(BuildReport { map_time, ..report })
+    });
+    match report {
+      Ok(report) => Ok(SummaryRef {
+        table: &self.table,
+        report,
+      }),
+      Err(err) => {
+        self.clear();
+        Err(err)
+      }
+    }
+  }
+
+  /// Adds the readings of the first `len` bytes of `input`, which is padded
+  /// to a multiple of `SCANNER_CACHE_SIZE`, to the table. The caller clears
+  /// the table if this fails.
+  fn accumulate_from_bytes(&mut self, input: &[u8], len: usize) -> BarseResult<BuildReport> {
+    let start = Instant::now();
+    let options = &self.options;
+    let threads = thread_count(len, options).min(self.workers.len() + 1);
+    // SAFETY: Every job started below finishes before this returns.
+    let slicer = Arc::new(
+      unsafe { Slicer::new(input, len, options.chunk_size) }
+        .with_decimal_separator(options.decimal_separator)
+        .with_skip_header(options.skip_header)
+        .with_extra_fields(options.extra_fields),
+    );
+    let string_table = self.table.string_table().clone();
+    let workers = &mut self.workers[..threads - 1];
+    let started = workers
+      .iter_mut()
+      .map(|worker| worker.start(&slicer, &string_table, options))
+      .collect::<Vec<_>>();
+    drop(string_table);
+
+    // The calling thread folds its share of the chunks straight into the
+    // table. A panic is caught rather than unwound, since the workers are
+    // still reading from `input`.
+    let checks = RecordChecks::new(options);
+    let table = &mut self.table;
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+      table.add_sliced_records(&slicer, &TemperatureAggregator, options.strict, &checks)
+    }))
+    .unwrap_or_else(|payload| Err(BarseError::thread_panicked(0, payload)));
+
+    // Every started job finishes before the results are collected.
+    let results = collect_worker_results(iter::once(result).chain(
+      started.into_iter().enumerate().map(|(worker, started)| {
+        started.and_then(|()| workers[worker].finish(worker + 1, options.table_capacity))
+      }),
+    ));
+
+    // Workers' tables are emptied even if a worker failed, so they can be
+    // reused for the next input.
+    let merge_start = Instant::now();
+    self.table.drain_from(
+      workers
+        .iter_mut()
+        .filter_map(|worker| worker.summary_table.as_mut()),
+    );
+    let (records, missing_records) = results?.into_iter().fold(
+      (0, 0),
+      |(records, missing), (worker_records, worker_missing)| {
+        (records + worker_records, missing + worker_missing)
+      },
+    );
+    let hugepages = self.table.hugepages()
+      && workers.iter().all(|worker| {
+        worker
+          .summary_table
+          .as_ref()
+          .is_some_and(TemperatureSummaryTable::hugepages)
+      });
+    Ok(BuildReport {
+      strategy: Strategy::MultiThreaded,
+      threads,
+      bytes: len as u64,
+      records,
+      missing_records,
+      unique_stations: self.table.iter().count(),
+      chunks: slicer.chunks_taken(),
+      hugepages,
+      map_time: Duration::ZERO,
+      wall_time: start.elapsed(),
+      merge_time: merge_start.elapsed(),
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{fs, path::PathBuf};
+
+  use googletest::prelude::*;
+
+  use crate::{
+    barse::build_with_options_and_map_time, error::BarseError, normalize::NameBuf,
+    options::Options, scanner::BUFFER_OVERLAP, session::Session,
+    temperature_reading::TemperatureReading, test_util::random_input_file, util::HasIter,
+  };
+
+  fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("barse_{}_session_{name}", std::process::id()))
+  }
+
+  /// Writes `count` small inputs of varying sizes and numbers of stations,
+  /// returning their paths.
+  fn write_inputs(name: &str, count: u64) -> Vec<String> {
+    (0..count)
+      .map(|i| {
+        let input = random_input_file(0x5e55 + i, 500 + 700 * i, 1 + 40 * i as u32).unwrap();
+        let path = temp_path(&format!("{name}_{i}.txt"));
+        fs::write(&path, input.exact_slice()).unwrap();
+        path.to_str().unwrap().to_owned()
+      })
+      .collect()
+  }
+
+  fn options(strict: bool) -> Options {
+    Options {
+      threads: Some(3),
+      min_chunk_size: 1,
+      chunk_size: 4 * BUFFER_OVERLAP,
+      strict,
+      missing_reading: Some(TemperatureReading::new(0)),
+      ..Options::default()
+    }
+  }
+
+  #[gtest]
+  fn test_session_matches_one_shot_builds() {
+    let paths = write_inputs("aggregate", 12);
+    for strict in [false, true] {
+      let options = options(strict);
+      let mut session = Session::new(&options).unwrap();
+      // Each file is summarized twice, to check that clearing the table
+      // leaves nothing behind.
+      for path in paths.iter().chain(&paths) {
+        let (expected, expected_report) = build_with_options_and_map_time(path, &options).unwrap();
+        let summary = session.aggregate(path).unwrap();
+        expect_eq!(summary.fingerprint(), expected.fingerprint(), "{path}");
+        expect_eq!(summary.iter().count(), expected.iter().count(), "{path}");
+        expect_eq!(summary.report().records, expected_report.records, "{path}");
+        expect_eq!(
+          summary.report().missing_records,
+          expected_report.missing_records,
+          "{path}"
+        );
+        expect_eq!(
+          summary.report().unique_stations,
+          expected_report.unique_stations,
+          "{path}"
+        );
+      }
+    }
+    for path in paths {
+      fs::remove_file(path).unwrap();
+    }
+  }
+
+  #[gtest]
+  fn test_session_accumulate_merges_files() {
+    let paths = write_inputs("accumulate", 8);
+    let concatenated = temp_path("accumulate_all.txt");
+    let contents = paths
+      .iter()
+      .map(|path| fs::read(path).unwrap())
+      .collect::<Vec<_>>()
+      .concat();
+    fs::write(&concatenated, contents).unwrap();
+    let options = options(false);
+    let (expected, expected_report) =
+      build_with_options_and_map_time(concatenated.to_str().unwrap(), &options).unwrap();
+
+    let mut session = Session::new(&options).unwrap();
+    let mut records = session.aggregate(&paths[0]).unwrap().report().records;
+    for path in &paths[1..] {
+      records += session.accumulate(path).unwrap().report().records;
+    }
+    expect_eq!(session.table().fingerprint(), expected.fingerprint());
+    expect_eq!(records, expected_report.records);
+
+    session.clear();
+    expect_eq!(session.table().iter().count(), 0);
+
+    for path in paths
+      .iter()
+      .map(String::as_str)
+      .chain(concatenated.to_str())
+    {
+      fs::remove_file(path).unwrap();
+    }
+  }
+
+  #[gtest]
+  fn test_session_clears_table_on_error() {
+    let paths = write_inputs("error", 2);
+    let malformed = temp_path("error_malformed.txt");
+    fs::write(&malformed, "Oslo;1.0\nRome;2.0\nLima;x\nOslo;3.0\n").unwrap();
+    let malformed = malformed.to_str().unwrap();
+    let options = options(true);
+    let mut session = Session::new(&options).unwrap();
+
+    session.accumulate(&paths[0]).unwrap();
+    let result = session.accumulate(malformed);
+    expect_true!(matches!(
+      result.as_ref().map_err(BarseError::without_context),
+      Err(BarseError::MalformedRecord { .. })
+    ));
+    expect_eq!(session.table().iter().count(), 0);
+
+    let (expected, _) = build_with_options_and_map_time(&paths[1], &options).unwrap();
+    expect_eq!(
+      session.aggregate(&paths[1]).unwrap().fingerprint(),
+      expected.fingerprint()
+    );
+
+    for path in paths.iter().map(String::as_str).chain([malformed]) {
+      fs::remove_file(path).unwrap();
+    }
+  }
+
+  #[gtest]
+  fn test_session_replaces_panicked_workers() {
+    fn normalize<'a>(name: &'a str, _: &'a mut NameBuf) -> &'a str {
+      if name == "Panic" {
+        panic!("cannot normalize {name}");
+      }
+      name
+    }
+
+    let paths = write_inputs("panic", 2);
+    let panicking = temp_path("panic_station.txt");
+    fs::write(&panicking, "Oslo;1.0\nPanic;2.0\n").unwrap();
+    let panicking = panicking.to_str().unwrap();
+    let options = Options {
+      normalize: Some(normalize),
+      ..options(false)
+    };
+    let mut session = Session::new(&options).unwrap();
+
+    session.aggregate(&paths[0]).unwrap();
+    match session
+      .aggregate(panicking)
+      .as_ref()
+      .map_err(BarseError::without_context)
+    {
+      Err(BarseError::ThreadPanicked { message, .. }) => {
+        expect_eq!(message, "cannot normalize Panic")
+      }
+      Err(err) => fail!("unexpected error {err}").and_log_failure(),
+      Ok(_) => fail!("expected the build to fail").and_log_failure(),
+    }
+    expect_eq!(session.table().iter().count(), 0);
+
+    for path in &paths {
+      let (expected, _) = build_with_options_and_map_time(path, &options).unwrap();
+      expect_eq!(
+        session.aggregate(path).unwrap().fingerprint(),
+        expected.fingerprint()
+      );
+    }
+
+    for path in paths.iter().map(String::as_str).chain([panicking]) {
+      fs::remove_file(path).unwrap();
+    }
+  }
+}
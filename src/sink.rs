@@ -0,0 +1,70 @@
+//! Consumers of the summaries in a table, which are handed one station at a
+//! time rather than a collection of every station.
+
+use std::io::Write;
+
+use crate::{
+  barse::WeatherStation, error::BarseResult, output_format::write_brc_station,
+  temperature_summary::TemperatureSummary,
+};
+
+/// Receives the summary of each station in a table from `drain_into`, one
+/// station at a time. Closures taking a name and summary are sinks.
+pub trait Sink {
+  fn on_station(&mut self, name: &str, summary: &TemperatureSummary) -> BarseResult;
+}
+
+impl<F: FnMut(&str, &TemperatureSummary) -> BarseResult> Sink for F {
+  fn on_station(&mut self, name: &str, summary: &TemperatureSummary) -> BarseResult {
+    self(name, summary)
+  }
+}
+
+/// Writes the stations it receives to `out` in the `Brc` format, in the
+/// order they are received, as `{station=min/mean/max, ...}`. Each station is
+/// formatted straight into `out`.
+pub struct BrcSink<W: Write> {
+  out: W,
+  include_count: bool,
+  stations: usize,
+}
+
+impl<W: Write> BrcSink<W> {
+  /// Constructs a sink writing to `out`. If `include_count` is set, the
+  /// number of readings is appended to each station as ` (n)`.
+  pub fn new(out: W, include_count: bool) -> Self {
+    Self {
+      out,
+      include_count,
+      stations: 0,
+    }
+  }
+
+  /// The number of stations written so far.
+  pub fn stations(&self) -> usize {
+    self.stations
+  }
+
+  /// Closes the summary, returning `out`.
+  pub fn finish(mut self) -> BarseResult<W> {
+    if self.stations == 0 {
+      write!(self.out, "{{")?;
+    }
+    writeln!(self.out, "}}")?;
+    Ok(self.out)
+  }
+}
+
+impl<W: Write> Sink for BrcSink<W> {
+  fn on_station(&mut self, name: &str, summary: &TemperatureSummary) -> BarseResult {
+    let station = WeatherStation::new(name, *summary);
+    write_brc_station(
+      &station,
+      self.stations == 0,
+      self.include_count,
+      &mut self.out,
+    )?;
+    self.stations += 1;
+    Ok(())
+  }
+}
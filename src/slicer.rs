@@ -1,41 +1,340 @@
 use std::{
   slice,
-  sync::atomic::{AtomicUsize, Ordering},
+  sync::atomic::{AtomicU64, Ordering},
 };
 
-use crate::scanner::{Scanner, BUFFER_OVERLAP};
-
-const CHUNK_SIZE: usize = 2 * 1024 * 1024;
+use crate::{
+  error::{BarseError, BarseResult},
+  scanner::{ExtraFields, Scanner, BUFFER_OVERLAP, SCANNER_CACHE_SIZE},
+  temperature_reading::DecimalSeparator,
+  validating_scanner::ValidatingScanner,
+};
 
 pub struct Slicer {
   buffer: &'static [u8],
-  cur_offset: AtomicUsize,
+  /// The length of the input in `buffer`, excluding padding.
+  len: usize,
+  /// The number of bytes handed out per slice, excluding the overlap.
+  chunk_size: usize,
+  /// The offset of the next chunk to hand out. This is a `u64` so that
+  /// reservations past the end of the input, one per consumer, can't overflow
+  /// on 32-bit targets, where inputs may approach `usize::MAX`.
+  cur_offset: AtomicU64,
+  decimal_separator: DecimalSeparator,
+  /// If set, the scanner over the first chunk skips the first record.
+  skip_header: bool,
+  /// Whether records may have fields after their reading, which also sets
+  /// how far chunks overlap.
+  extra_fields: ExtraFields,
 }
 
 impl Slicer {
+  /// Every chunk after the first starts scanning after the last newline in
+  /// its first `BUFFER_OVERLAP` bytes, so no record may be longer than
+  /// `BUFFER_OVERLAP` bytes. `try_next_chunk` reports chunks for which this
+  /// doesn't hold.
+  ///
   /// Safety:
   /// The caller must guarantee that the lifetime of `buffer` outlives
   /// `Scanner`.
-  pub unsafe fn new(buffer: &[u8]) -> Self {
+  pub unsafe fn new(buffer: &[u8], len: usize, chunk_size: usize) -> Self {
+    debug_assert!(len <= buffer.len());
+    debug_assert!(buffer.len().is_multiple_of(SCANNER_CACHE_SIZE));
+    debug_assert!(chunk_size != 0 && chunk_size.is_multiple_of(BUFFER_OVERLAP));
     Self {
+      // SAFETY: The caller guarantees `buffer` outlives the slicer and its
+      // scanners.
       buffer: unsafe { slice::from_raw_parts(buffer.as_ptr(), buffer.len()) },
-      cur_offset: AtomicUsize::new(0),
+      len,
+      chunk_size,
+      cur_offset: AtomicU64::new(0),
+      decimal_separator: DecimalSeparator::Dot,
+      skip_header: false,
+      extra_fields: ExtraFields::Forbid,
+    }
+  }
+
+  /// Hands out scanners which parse temperature readings using
+  /// `decimal_separator`.
+  pub fn with_decimal_separator(self, decimal_separator: DecimalSeparator) -> Self {
+    Self {
+      decimal_separator,
+      ..self
     }
   }
 
-  pub fn next_slice(&self) -> Option<Scanner<'_>> {
-    let offset = self.cur_offset.fetch_add(CHUNK_SIZE, Ordering::Relaxed);
-    if offset >= self.buffer.len() {
-      self.cur_offset.fetch_sub(CHUNK_SIZE, Ordering::Relaxed);
+  /// If `skip_header` is set, the first record of the input, e.g. a header
+  /// line, is skipped by the scanner over the first chunk.
+  pub fn with_skip_header(self, skip_header: bool) -> Self {
+    Self {
+      skip_header,
+      ..self
+    }
+  }
+
+  /// Hands out scanners which skip fields after the reading of each record
+  /// with `ExtraFields::Skip`. Chunks then overlap by
+  /// `extra_fields.buffer_overlap()` bytes, which bounds the length of
+  /// records instead of `BUFFER_OVERLAP`.
+  pub fn with_extra_fields(self, extra_fields: ExtraFields) -> Self {
+    Self {
+      extra_fields,
+      ..self
+    }
+  }
+
+  /// Returns the number of slices handed out so far. Once every consumer has
+  /// been exhausted, all failed reservations in `reserve_chunk` have been
+  /// undone, so this is exactly the number of chunks the input was split into.
+  pub fn chunks_taken(&self) -> usize {
+    (self.cur_offset.load(Ordering::Relaxed) / self.chunk_size as u64) as usize
+  }
+
+  /// Claims the next chunk, returning its offset in the buffer and a scanner
+  /// over its records. Returns an error if the chunk starts within a record
+  /// longer than `BUFFER_OVERLAP` bytes, which valid input never has, so that
+  /// its scanner has no record boundary to start from.
+  pub fn try_next_chunk(&self) -> Option<BarseResult<(u64, Scanner<'_>)>> {
+    self.reserve_chunk().map(|(offset, slice, slice_len)| {
+      let scanner = if offset == 0 && self.skip_header {
+        Scanner::from_start_with_len(slice, slice_len).skip_header()
+      } else if offset == 0 {
+        Scanner::from_start_with_len(slice, slice_len)
+      } else {
+        let overlap = self.extra_fields.buffer_overlap();
+        Scanner::try_from_midpoint_with_overlap(slice, slice_len, overlap).map_err(|_| {
+          BarseError::new(format!(
+            "The chunk at byte {offset} starts within a record longer than {overlap} bytes"
+          ))
+        })?
+      };
+      Ok((
+        offset,
+        scanner
+          .with_decimal_separator(self.decimal_separator)
+          .with_extra_fields(self.extra_fields),
+      ))
+    })
+  }
+
+  /// Like `try_next_chunk`, but returns a scanner which checks the format of
+  /// every record, reporting errors at their offset in the whole buffer.
+  pub fn next_validating_slice(&self) -> Option<ValidatingScanner<'_>> {
+    self.reserve_chunk().map(|(offset, slice, slice_len)| {
+      let scanner = ValidatingScanner::from_chunk(
+        &slice[..slice_len],
+        offset,
+        offset + slice_len as u64 == self.len as u64,
+        self.extra_fields,
+      )
+      .with_decimal_separator(self.decimal_separator);
+      if offset == 0 && self.skip_header {
+        scanner.skip_header()
+      } else {
+        scanner
+      }
+    })
+  }
+
+  /// Claims the next chunk, returning its offset, the slice of the buffer
+  /// spanning it and its overlap, and the length of the input in the slice.
+  ///
+  /// The final chunk may hold no more than the overlap's bytes of input,
+  /// and its slice may even be shorter than the overlap if the padding
+  /// of the buffer ends first. Every record in it is then read by the
+  /// scanner over the previous chunk, so the scanners over it yield nothing
+  /// without reading its overlap.
+  fn reserve_chunk(&self) -> Option<(u64, &[u8], usize)> {
+    let chunk_size = self.chunk_size as u64;
+    let reserved = self.cur_offset.fetch_add(chunk_size, Ordering::Relaxed);
+    if reserved >= self.len as u64 {
+      self.cur_offset.fetch_sub(chunk_size, Ordering::Relaxed);
       None
     } else {
-      let end = (offset + CHUNK_SIZE + BUFFER_OVERLAP).min(self.buffer.len());
+      // In bounds of the input, so this fits in a `usize`.
+      let offset = reserved as usize;
+      let overlap = self.extra_fields.buffer_overlap();
+      let end = (offset + self.chunk_size + overlap).min(self.buffer.len());
+      // The scanner over this chunk finishes the last record which starts in
+      // the chunk, so unless the input ends first, the slice must extend a
+      // full overlap past the chunk for that record to fit.
+      debug_assert!(end >= (offset + self.chunk_size + overlap).min(self.len));
       let slice = &self.buffer[offset..end];
-      if offset == 0 {
-        Some(Scanner::from_start(slice))
-      } else {
-        Some(Scanner::from_midpoint(slice))
+      let slice_len = self.len.min(end) - offset;
+      // The next chunk's scanner skips past the first newline in its
+      // overlap, which is only sound if every chunk starts on a batch
+      // boundary.
+      debug_assert!(offset.is_multiple_of(SCANNER_CACHE_SIZE));
+      Some((reserved, slice, slice_len))
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use googletest::prelude::*;
+  use itertools::Itertools;
+
+  use crate::{
+    aligned::AlignedInput,
+    scanner::{Scanner, BUFFER_OVERLAP},
+    slicer::Slicer,
+    temperature_reading::TemperatureReading,
+  };
+
+  const CHUNK_SIZES: [usize; 3] = [BUFFER_OVERLAP, 2 * BUFFER_OVERLAP, 5 * BUFFER_OVERLAP];
+  const TEMPS: [&str; 4] = ["-99.9", "1.2", "-3.4", "56.7"];
+
+  fn full_scan(input: &AlignedInput) -> Vec<(String, TemperatureReading)> {
+    Scanner::from_start_with_len(input.padded_slice(), input.len())
+      .map(|(station, temp)| (station.to_owned(), temp))
+      .collect()
+  }
+
+  fn chunked_scan(input: &AlignedInput, chunk_size: usize) -> Vec<(String, TemperatureReading)> {
+    let slicer = unsafe { Slicer::new(input.padded_slice(), input.len(), chunk_size) };
+    std::iter::from_fn(|| slicer.try_next_chunk())
+      .flat_map(|chunk| chunk.unwrap().1)
+      .map(|(station, temp)| (station.to_owned(), temp))
+      .collect()
+  }
+
+  /// Returns records with names of every length from 1 to 50 bytes, preceded
+  /// by a record `shift` bytes longer than the shortest possible record.
+  fn records(shift: usize) -> Vec<String> {
+    std::iter::once(format!("{};0.0\n", "s".repeat(shift + 1)))
+      .chain((1..=50).map(|len| {
+        let name = (0..len)
+          .map(|i| (b'a' + (i % 26) as u8) as char)
+          .collect::<String>();
+        format!("{name};{}\n", TEMPS[len % TEMPS.len()])
+      }))
+      .collect()
+  }
+
+  /// Returns records totaling `len` bytes, which must be more than 5.
+  fn filler_records(mut len: usize) -> String {
+    let mut records = String::new();
+    while len > 45 {
+      records.push_str(&format!("{};0.0\n", "f".repeat(35)));
+      len -= 40;
+    }
+    records.push_str(&format!("{};0.0\n", "f".repeat(len - 5)));
+    records
+  }
+
+  fn expect_chunks_match_full_scan(text: &str) {
+    let input = AlignedInput::new(text);
+    let expected = full_scan(&input);
+    for chunk_size in CHUNK_SIZES {
+      expect_eq!(
+        chunked_scan(&input, chunk_size),
+        expected,
+        "chunk_size {chunk_size}, input {text:?}"
+      );
+    }
+  }
+
+  #[gtest]
+  fn test_chunks_match_full_scan_at_every_alignment() {
+    for shift in 0..BUFFER_OVERLAP {
+      expect_chunks_match_full_scan(&records(shift).concat());
+    }
+  }
+
+  #[gtest]
+  fn test_chunks_match_full_scan_for_every_input_length() {
+    // Truncating after every record makes the input end at every offset
+    // relative to the chunk boundaries.
+    for shift in [0, 7, 31] {
+      let records = records(shift);
+      for count in 0..=records.len() {
+        expect_chunks_match_full_scan(&records[..count].concat());
+      }
+    }
+  }
+
+  #[gtest]
+  fn test_record_spanning_chunk_edge() {
+    let chunk_size = 2 * BUFFER_OVERLAP;
+    let record = "Spanning;12.3\n";
+    for offset_into_record in 0..record.len() {
+      // Pad with records so that the chunk edge falls `offset_into_record`
+      // bytes into `record`.
+      let text = format!(
+        "{}{record}After;-4.5\n",
+        filler_records(chunk_size - offset_into_record)
+      );
+      assert_eq!(text.find(record), Some(chunk_size - offset_into_record));
+
+      let input = AlignedInput::new(&text);
+      let chunks = chunked_scan(&input, chunk_size);
+      expect_eq!(chunks, full_scan(&input));
+      expect_eq!(
+        chunks
+          .iter()
+          .filter(|(station, _)| station == "Spanning")
+          .collect_vec(),
+        vec![&("Spanning".to_owned(), TemperatureReading::new(123))]
+      );
+    }
+  }
+
+  #[gtest]
+  fn test_tiny_final_chunk() {
+    for chunk_size in CHUNK_SIZES {
+      // The final chunk holds `tail_len` bytes, which for the shortest tails
+      // leaves a slice shorter than `BUFFER_OVERLAP` if the padding is.
+      for tail_len in 1..=BUFFER_OVERLAP + 1 {
+        let text = filler_records(2 * chunk_size + tail_len);
+        let input = AlignedInput::new(&text);
+        let expected = full_scan(&input);
+        expect_eq!(
+          chunked_scan(&input, chunk_size),
+          expected,
+          "chunk_size {chunk_size}, tail_len {tail_len}"
+        );
+
+        let slicer = unsafe { Slicer::new(input.padded_slice(), input.len(), chunk_size) };
+        let validated = std::iter::from_fn(|| slicer.next_validating_slice())
+          .flatten()
+          .map(|record| record.map(|(station, temp)| (station.to_owned(), temp)))
+          .collect::<Result<Vec<_>, _>>();
+        expect_that!(
+          validated,
+          ok(eq(&expected)),
+          "chunk_size {chunk_size}, tail_len {tail_len}"
+        );
+        expect_eq!(
+          slicer.chunks_taken(),
+          input.len().div_ceil(chunk_size),
+          "chunk_size {chunk_size}, tail_len {tail_len}"
+        );
       }
     }
   }
+
+  #[gtest]
+  fn test_chunk_starting_in_overlong_record() {
+    // The second chunk's overlap lies entirely within the long name.
+    let text = format!(
+      "Oslo;1.0\n{};1.0\nBergen;2.0\n",
+      "a".repeat(2 * BUFFER_OVERLAP)
+    );
+    let input = AlignedInput::new(&text);
+    let slicer = unsafe { Slicer::new(input.padded_slice(), input.len(), BUFFER_OVERLAP) };
+    let chunks = std::iter::from_fn(|| slicer.try_next_chunk())
+      .map(|chunk| chunk.map(|(offset, _)| offset))
+      .collect_vec();
+    expect_that!(
+      chunks,
+      elements_are![
+        ok(eq(&0)),
+        err(displays_as(contains_substring(format!(
+          "chunk at byte {BUFFER_OVERLAP} starts within a record"
+        )))),
+        ok(eq(&(2 * BUFFER_OVERLAP as u64))),
+      ]
+    );
+  }
 }
@@ -0,0 +1,235 @@
+use crate::{
+  error::BarseResult,
+  hugepage_backed_table::HugepageBackedTable,
+  inline_string::InlineString,
+  str_hash::{hash_bits_for_capacity, str_hash_with_bits, TABLE_SIZE},
+  temperature_reading::TemperatureReading,
+  temperature_summary::TemperatureSummary,
+  util::{likely, prefetch, HasIter},
+};
+
+/// The number of readings `add_readings_batch` hashes and prefetches ahead of
+/// inserting them.
+const BATCH_LEN: usize = 16;
+
+/// A weather station table laid out as a struct of arrays: the station names
+/// are kept in one array, and their summaries in another at the same indices.
+/// Probing for a station only streams through the compact name array, and the
+/// summary is only touched once the station's bucket is found, or to check
+/// whether a bucket whose name doesn't match is empty.
+///
+/// Otherwise this behaves like `WeatherStationTable`, which interleaves each
+/// name with its summary.
+pub struct SoaWeatherStationTable {
+  keys: HugepageBackedTable<InlineString>,
+  summaries: HugepageBackedTable<TemperatureSummary>,
+  /// log2 of the capacity of the table.
+  hash_bits: u32,
+}
+
+impl SoaWeatherStationTable {
+  pub fn new() -> BarseResult<Self> {
+    Self::with_capacity(TABLE_SIZE)
+  }
+
+  /// Constructs a table with `capacity` buckets, which must be a power of two.
+  pub fn with_capacity(capacity: usize) -> BarseResult<Self> {
+    Ok(Self {
+      hash_bits: hash_bits_for_capacity(capacity)?,
+      keys: HugepageBackedTable::new(capacity)?,
+      summaries: HugepageBackedTable::new(capacity)?,
+    })
+  }
+
+  pub fn capacity(&self) -> usize {
+    self.keys.size()
+  }
+
+  pub fn hugepages(&self) -> bool {
+    self.keys.hugepages() && self.summaries.hugepages()
+  }
+
+  /// Copies the name and summary of every station in the table, in table
+  /// order, so that the results outlive both the table and the input it was
+  /// built from.
+  pub fn to_owned_summary(&self) -> Vec<(String, TemperatureSummary)> {
+    self
+      .iter()
+      .map(|(station, summary)| (station.to_owned(), *summary))
+      .collect()
+  }
+
+  /// Adds `reading` to the summary of `station`, which must be in a padded
+  /// buffer. Panics if `station` is longer than 50 bytes, or if the table is
+  /// full.
+  pub fn add_reading(&mut self, station: &str, reading: TemperatureReading) {
+    let idx = self.station_index(station);
+    let idx = self.find_index(station, idx);
+    self.summaries.entry_at_mut(idx).add_reading(reading);
+  }
+
+  /// Adds every reading in `batch`, like calling `add_reading` on each in
+  /// order. The home buckets of up to `BATCH_LEN` stations are found and
+  /// their names prefetched before any of them is inserted into, so that
+  /// their cache misses overlap.
+  pub fn add_readings_batch(&mut self, batch: &[(&str, TemperatureReading)]) {
+    for batch in batch.chunks(BATCH_LEN) {
+      let mut indices = [0; BATCH_LEN];
+      for (idx, &(station, _)) in indices.iter_mut().zip(batch) {
+        *idx = self.station_index(station);
+        prefetch(self.keys.entry_at(*idx));
+      }
+      for (&idx, &(station, reading)) in indices.iter().zip(batch) {
+        let idx = self.find_index(station, idx);
+        self.summaries.entry_at_mut(idx).add_reading(reading);
+      }
+    }
+  }
+
+  fn station_index(&self, station: &str) -> usize {
+    str_hash_with_bits(station.as_bytes(), self.hash_bits) as usize
+  }
+
+  /// Returns true if the bucket at `index` holds `station`, claiming the
+  /// bucket for `station` if it is empty. A bucket is in use iff its summary
+  /// has a reading, since an empty station name is indistinguishable from an
+  /// uninitialized key.
+  fn matches_key_or_initialize(&mut self, station: &str, index: usize) -> bool {
+    if likely(*self.keys.entry_at(index) == station) {
+      true
+    } else if self.summaries.entry_at(index).count == 0 {
+      self.keys.entry_at_mut(index).initialize_station(station);
+      true
+    } else {
+      false
+    }
+  }
+
+  /// Finds the index of the bucket for `station`, whose home bucket is `idx`.
+  fn find_index(&mut self, station: &str, idx: usize) -> usize {
+    if likely(self.matches_key_or_initialize(station, idx)) {
+      return idx;
+    }
+
+    let mask = self.capacity() - 1;
+    let offset = (1..self.capacity())
+      .find(|&i| self.matches_key_or_initialize(station, (idx + i) & mask))
+      .expect("No empty bucket found, table is full");
+    (idx + offset) & mask
+  }
+}
+
+impl<'a> HasIter<'a> for SoaWeatherStationTable {
+  type Item = (&'a str, &'a TemperatureSummary);
+
+  fn iter(&'a self) -> impl Iterator<Item = Self::Item> {
+    (0..self.capacity()).filter_map(|index| {
+      let summary = self.summaries.entry_at(index);
+      (summary.count != 0).then(|| (self.keys.entry_at(index).as_str(), summary))
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::panic::AssertUnwindSafe;
+
+  use googletest::prelude::*;
+  use itertools::Itertools;
+
+  use crate::{
+    aligned::AlignedInput, barse::Table, scanner::Scanner, soa_table::SoaWeatherStationTable,
+    temperature_reading::TemperatureReading, temperature_summary::TemperatureSummary,
+    test_util::random_input_file, util::HasIter,
+  };
+
+  fn sorted_fields(
+    summary: Vec<(String, TemperatureSummary)>,
+  ) -> Vec<(String, (TemperatureReading, TemperatureReading, i64, u32))> {
+    summary
+      .into_iter()
+      .map(|(station, summary)| {
+        let fields = (summary.min, summary.max, summary.total, summary.count);
+        (station, fields)
+      })
+      .sorted()
+      .collect()
+  }
+
+  #[gtest]
+  fn test_matches_interleaved_table() {
+    // 500 stations in 1024 buckets, so many stations collide.
+    let input = random_input_file(0x50a7ab, 20_000, 500).unwrap();
+    let records = Scanner::from_start_with_len(input.padded_slice(), input.len()).collect_vec();
+
+    let mut table = Table::with_capacity(1024).unwrap();
+    let mut soa_table = SoaWeatherStationTable::with_capacity(1024).unwrap();
+    let mut batched_soa_table = SoaWeatherStationTable::with_capacity(1024).unwrap();
+    for &(station, temp) in &records {
+      table.add_reading(station, temp);
+      soa_table.add_reading(station, temp);
+    }
+    for batch in records.chunks(37) {
+      batched_soa_table.add_readings_batch(batch);
+    }
+
+    let expected = sorted_fields(table.to_owned_summary());
+    expect_eq!(expected.len(), 500);
+    expect_eq!(sorted_fields(soa_table.to_owned_summary()), expected);
+    expect_eq!(
+      sorted_fields(batched_soa_table.to_owned_summary()),
+      expected
+    );
+    expect_eq!(soa_table.iter().count(), 500);
+  }
+
+  #[gtest]
+  fn test_empty_station_name() {
+    let input = AlignedInput::new(";1.0\n;3.0\nOslo;2.0\n");
+    let mut table = SoaWeatherStationTable::with_capacity(2).unwrap();
+    for (station, temp) in Scanner::from_start_with_len(input.padded_slice(), input.len()) {
+      table.add_reading(station, temp);
+    }
+    expect_that!(
+      sorted_fields(table.to_owned_summary()),
+      elements_are![
+        eq(&(
+          "".to_owned(),
+          (
+            TemperatureReading::new(10),
+            TemperatureReading::new(30),
+            40,
+            2
+          )
+        )),
+        eq(&(
+          "Oslo".to_owned(),
+          (
+            TemperatureReading::new(20),
+            TemperatureReading::new(20),
+            20,
+            1
+          )
+        )),
+      ]
+    );
+  }
+
+  #[gtest]
+  fn test_over_length_name_panics() {
+    let name = "a".repeat(51);
+    let mut table = SoaWeatherStationTable::with_capacity(16).unwrap();
+    let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+      table.add_reading(&name, TemperatureReading::new(123))
+    }));
+    expect_true!(result.is_err());
+
+    // The bucket isn't claimed, so the table is left as it was.
+    expect_eq!(table.iter().count(), 0);
+    table.add_reading(&name[..50], TemperatureReading::new(123));
+    expect_that!(
+      table.iter().map(|(station, _)| station).collect_vec(),
+      elements_are![eq(&&name[..50])]
+    );
+  }
+}
@@ -0,0 +1,149 @@
+use std::{fmt::Debug, sync::Arc};
+
+use crate::{
+  error::{BarseError, BarseResult},
+  normalize::NameBuf,
+  scanner::MAX_STATION_NAME_LEN,
+  string_table::StringTable,
+};
+
+/// Restricts a build to the stations named in an allow-list, and those whose
+/// names start with one of a set of prefixes. Records of other stations are
+/// dropped as they are scanned, before their readings touch the table.
+///
+/// Filters apply to the station names in the input, before
+/// `Options::normalize`.
+#[derive(Clone, Default)]
+pub struct StationFilter {
+  /// The allowed station names, hashed like the weather station tables, or
+  /// `None` if no names were given.
+  names: Option<Arc<StringTable>>,
+  prefixes: Vec<String>,
+}
+
+impl StationFilter {
+  /// Constructs a filter allowing exactly the stations in `names`, failing if
+  /// any are longer than `MAX_STATION_NAME_LEN` bytes.
+  pub fn new<S: AsRef<str>>(names: impl IntoIterator<Item = S>) -> BarseResult<Self> {
+    let names = names.into_iter().collect::<Vec<_>>();
+    if names.is_empty() {
+      return Ok(Self::default());
+    }
+    // At most half full, so lookups of other names rarely scan far.
+    let table = StringTable::new((2 * names.len()).next_power_of_two())?;
+    // The table reads past the end of the names, so they are copied into a
+    // padded buffer first.
+    let mut name_buf = NameBuf::new();
+    for name in &names {
+      let name = name.as_ref();
+      if name.len() > MAX_STATION_NAME_LEN {
+        return Err(BarseError::new(format!(
+          "Filtered station name \"{name}\" is longer than {MAX_STATION_NAME_LEN} bytes"
+        )));
+      }
+      name_buf.clear();
+      name_buf.push_str(name);
+      table.find_entry_index(name_buf.as_str());
+    }
+    Ok(Self {
+      names: Some(Arc::new(table)),
+      prefixes: Vec::new(),
+    })
+  }
+
+  /// Also allows the stations whose names start with any of `prefixes`.
+  pub fn with_prefixes<S: Into<String>>(mut self, prefixes: impl IntoIterator<Item = S>) -> Self {
+    self.prefixes.extend(prefixes.into_iter().map(Into::into));
+    self
+  }
+
+  /// The names in the allow-list, in no particular order.
+  pub fn names(&self) -> impl Iterator<Item = &str> {
+    self.names.iter().flat_map(|table| {
+      (0..table.capacity())
+        .map(|idx| table.entry_at(idx))
+        .filter(|entry| entry.initialized())
+        .map(|entry| entry.value_str())
+    })
+  }
+
+  pub fn prefixes(&self) -> &[String] {
+    &self.prefixes
+  }
+
+  /// Returns true if the readings of `station` should be aggregated.
+  /// `station` must point into the padded input buffer.
+  pub(crate) fn matches(&self, station: &str) -> bool {
+    self
+      .names
+      .as_ref()
+      .is_some_and(|table| table.contains(station))
+      || self
+        .prefixes
+        .iter()
+        .any(|prefix| station.starts_with(prefix.as_str()))
+  }
+}
+
+impl Debug for StationFilter {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let mut names = self.names().collect::<Vec<_>>();
+    names.sort_unstable();
+    f.debug_struct("StationFilter")
+      .field("names", &names)
+      .field("prefixes", &self.prefixes)
+      .finish()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use googletest::prelude::*;
+
+  use crate::{error::BarseError, normalize::NameBuf, station_filter::StationFilter};
+
+  fn matches(filter: &StationFilter, station: &str) -> bool {
+    let mut name = NameBuf::new();
+    name.push_str(station);
+    filter.matches(name.as_str())
+  }
+
+  #[gtest]
+  fn test_station_filter() {
+    let filter = StationFilter::new(["Oslo", "Rome", "Oslo"])
+      .unwrap()
+      .with_prefixes(["US/", "Ber"]);
+    for station in ["Oslo", "Rome", "US/NYC", "US/", "Berlin", "Bern"] {
+      expect_true!(matches(&filter, station), "{station}");
+    }
+    for station in ["Osl", "Oslo2", "rome", "US", "UK/London", "Lima", ""] {
+      expect_false!(matches(&filter, station), "{station}");
+    }
+    expect_eq!(
+      format!("{filter:?}"),
+      "StationFilter { names: [\"Oslo\", \"Rome\"], prefixes: [\"US/\", \"Ber\"] }"
+    );
+
+    let names = (0..100).map(|i| format!("station{i}")).collect::<Vec<_>>();
+    let filter = StationFilter::new(&names).unwrap();
+    expect_eq!(filter.names().count(), 100);
+    expect_true!(names.iter().all(|name| matches(&filter, name)));
+    expect_false!(matches(&filter, "station100"));
+  }
+
+  #[gtest]
+  fn test_empty_station_filter_matches_nothing() {
+    let filter = StationFilter::new(Vec::<String>::new()).unwrap();
+    expect_false!(matches(&filter, "Oslo"));
+    expect_false!(matches(&filter, ""));
+    expect_true!(matches(&filter.with_prefixes([""]), "Oslo"));
+  }
+
+  #[gtest]
+  fn test_station_filter_name_too_long() {
+    expect_true!(matches!(
+      StationFilter::new(["a".repeat(51)]),
+      Err(BarseError::Other(_))
+    ));
+  }
+}
@@ -1,24 +1,55 @@
-use std::arch::x86_64::{
-  __m256i, _mm256_and_si256, _mm256_loadu_si256, _mm256_testz_si256, _mm256_xor_si256,
+use std::{
+  arch::x86_64::{
+    __m256i, _mm256_cmpeq_epi8, _mm256_loadu_si256, _mm256_movemask_epi8, _mm256_or_si256,
+    _mm256_testz_si256, _mm256_xor_si256,
+  },
+  cmp::Ordering,
 };
 
-#[cfg(not(feature = "multithreaded"))]
-use crate::inline_string::InlineString;
-#[cfg(feature = "multithreaded")]
-use crate::inline_string_mt::InlineString;
 use crate::util::{unaligned_read_would_cross_page_boundary, unlikely};
 
 const M256_BYTES: usize = 32;
 
-fn cmp_str_slow(inline_str: &InlineString, other: &str) -> bool {
+/// The storage of an inline string, and the length of the string at its
+/// start. The storage is at least `M256_BYTES` long and at least as long as
+/// the string, so `M256_BYTES` may be loaded from its start, or from anywhere
+/// ending within the string, without reading out of bounds. It is at most
+/// `2 * M256_BYTES` long, so two overlapping loads cover any string in it.
+#[derive(Clone, Copy)]
+pub struct InlineStorage<'a> {
+  bytes: &'a [u8],
+  len: usize,
+}
+
+impl<'a> InlineStorage<'a> {
+  /// Returns `None` if `bytes` can't be compared `M256_BYTES` at a time with
+  /// at most two loads, in which case the string must be compared some other
+  /// way.
+  ///
+  /// # Safety
+  /// `len` must be at most `N`.
+  pub unsafe fn new<const N: usize>(bytes: &'a [u8; N], len: usize) -> Option<Self> {
+    debug_assert!(len <= N);
+    (M256_BYTES..=2 * M256_BYTES)
+      .contains(&N)
+      .then_some(Self { bytes, len })
+  }
+
+  fn value(&self) -> &'a [u8] {
+    unsafe { self.bytes.get_unchecked(..self.len) }
+  }
+}
+
+fn cmp_str_slow(inline_str: InlineStorage, other: &str) -> bool {
   // Manually compare strings to avoid calling libc::strcmp
-  (0..inline_str.len()).all(|i| inline_str.value()[i] == other.as_bytes()[i])
+  (0..inline_str.len).all(|i| inline_str.value()[i] == other.as_bytes()[i])
 }
 
 fn foreign_str_unknown_bytes_mask(len: usize) -> __m256i {
   debug_assert!((0..=M256_BYTES).contains(&len));
 
-  const MASK_BYTES: [u8; 63] = [
+  // 64 bytes so that the 32-byte load for `len == 0` stays in bounds.
+  const MASK_BYTES: [u8; 64] = [
     0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, //
     0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, //
     0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, //
@@ -26,68 +57,217 @@ fn foreign_str_unknown_bytes_mask(len: usize) -> __m256i {
     0, 0, 0, 0, 0, 0, 0, 0, //
     0, 0, 0, 0, 0, 0, 0, 0, //
     0, 0, 0, 0, 0, 0, 0, 0, //
-    0, 0, 0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0, 0, 0, 0,
   ];
   unsafe {
     _mm256_loadu_si256(MASK_BYTES.get_unchecked(M256_BYTES - len..).as_ptr() as *const __m256i)
   }
 }
 
+/// Returns true if `a` and `b` are equal in the bytes selected by `mask`.
 #[target_feature(enable = "avx2")]
-fn cmp_si256(a: __m256i, b: __m256i) -> bool {
+fn cmp_si256_masked(a: __m256i, b: __m256i, mask: __m256i) -> bool {
   let xor = _mm256_xor_si256(a, b);
-  _mm256_testz_si256(xor, xor) != 0
+  _mm256_testz_si256(xor, mask) != 0
 }
 
+/// Compares strings of at most `M256_BYTES` bytes with a single load of each,
+/// masking off the bytes past their length on both sides.
 #[target_feature(enable = "avx2")]
-fn cmp_str_fast_avx(inline_str: &InlineString, other: &str) -> bool {
-  let len = inline_str.len();
+fn cmp_str_fast_avx(inline_str: InlineStorage, other: &str) -> bool {
+  let len = inline_str.len;
 
-  let inline_str_val =
-    unsafe { _mm256_loadu_si256(inline_str.value_str().as_ptr() as *const __m256i) };
-  let mask = foreign_str_unknown_bytes_mask(len);
+  let inline_str_val = unsafe { _mm256_loadu_si256(inline_str.bytes.as_ptr() as *const __m256i) };
   let foreign_str_val = unsafe { _mm256_loadu_si256(other.as_ptr() as *const __m256i) };
-  let other_str_val = _mm256_and_si256(foreign_str_val, mask);
+  cmp_si256_masked(
+    inline_str_val,
+    foreign_str_val,
+    foreign_str_unknown_bytes_mask(len),
+  )
+}
+
+/// Compares strings longer than `M256_BYTES` with two overlapping loads, of
+/// the first and last `M256_BYTES` bytes of each string, e.g. bytes 0..32 and
+/// 18..50 of a 50-byte name. Both loads lie within the strings, so unlike
+/// `cmp_str_fast_avx` no bytes need to be masked off, and the foreign string
+/// can't be read past a page boundary.
+#[target_feature(enable = "avx2")]
+fn cmp_str_long_avx(inline_str: InlineStorage, other: &str) -> bool {
+  let len = inline_str.len;
+  debug_assert!(len > M256_BYTES);
+  debug_assert_eq!(len, other.len());
 
-  cmp_si256(inline_str_val, other_str_val)
+  let load = |bytes: &[u8], offset: usize| unsafe {
+    _mm256_loadu_si256(bytes.as_ptr().add(offset) as *const __m256i)
+  };
+  let (inline_bytes, other_bytes) = (inline_str.value(), other.as_bytes());
+  let tail = len - M256_BYTES;
+  let head_diff = _mm256_xor_si256(load(inline_bytes, 0), load(other_bytes, 0));
+  let tail_diff = _mm256_xor_si256(load(inline_bytes, tail), load(other_bytes, tail));
+  let diff = _mm256_or_si256(head_diff, tail_diff);
+  _mm256_testz_si256(diff, diff) != 0
 }
 
-pub fn inline_str_eq_foreign_str(inline_str: &InlineString, other: &str) -> bool {
-  let len = inline_str.len();
-  if unlikely(len != other.len()) {
-    false
+/// How `inline_str_eq_foreign_str` compares two strings of length `len`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CmpPath {
+  /// A single masked load with `cmp_str_fast_avx`.
+  Short,
+  /// Two overlapping loads with `cmp_str_long_avx`.
+  Long,
+  /// Byte by byte with `cmp_str_slow`, since a 32-byte load of the foreign
+  /// string could cross into an unmapped page. The pointer of an empty string
+  /// may not point to memory at all, e.g. that of `String::new()`.
+  Scalar,
+}
+
+fn cmp_path(len: usize, other: &str) -> CmpPath {
+  if unlikely(len > M256_BYTES) {
+    CmpPath::Long
   } else if unlikely(
-    len > M256_BYTES || unaligned_read_would_cross_page_boundary::<__m256i>(other.as_ptr()),
+    len == 0 || unaligned_read_would_cross_page_boundary::<__m256i>(other.as_ptr()),
   ) {
-    cmp_str_slow(inline_str, other)
+    CmpPath::Scalar
+  } else {
+    CmpPath::Short
+  }
+}
+
+/// Returns the index of the first byte among those selected by `mask` that
+/// differs between the `M256_BYTES` bytes at `offset` of the storage of `a`
+/// and `b`.
+#[target_feature(enable = "avx2")]
+fn first_difference_avx(
+  a: InlineStorage,
+  b: InlineStorage,
+  offset: usize,
+  mask: u32,
+) -> Option<usize> {
+  let load = |inline_str: InlineStorage| unsafe {
+    _mm256_loadu_si256(inline_str.bytes.as_ptr().add(offset) as *const __m256i)
+  };
+  let equal_bytes = _mm256_movemask_epi8(_mm256_cmpeq_epi8(load(a), load(b))) as u32;
+  let differing_bytes = !equal_bytes & mask;
+  (differing_bytes != 0).then(|| offset + differing_bytes.trailing_zeros() as usize)
+}
+
+/// Orders two inline strings like their byte slices: by their first
+/// differing byte, or by length if one is a prefix of the other. The strings
+/// are compared `M256_BYTES` bytes at a time, and strings longer than that
+/// with a second load of the last `M256_BYTES` bytes of the shorter one,
+/// which overlaps the first load like in `cmp_str_long_avx`. Since the
+/// overlapping bytes are equal if the second load is reached, its first
+/// differing byte is the first differing byte of the strings.
+pub fn inline_str_cmp_inline_str(a: InlineStorage, b: InlineStorage) -> Ordering {
+  let len = a.len.min(b.len);
+  let head_mask = if len >= M256_BYTES {
+    u32::MAX
+  } else {
+    (1 << len) - 1
+  };
+  let mut first_difference = unsafe { first_difference_avx(a, b, 0, head_mask) };
+  if first_difference.is_none() && len > M256_BYTES {
+    first_difference = unsafe { first_difference_avx(a, b, len - M256_BYTES, u32::MAX) };
+  }
+  match first_difference {
+    Some(idx) => a.bytes[idx].cmp(&b.bytes[idx]),
+    None => a.len.cmp(&b.len),
+  }
+}
+
+/// Compares the first `len` bytes of the storage of two inline strings. The
+/// storage of both is at least `M256_BYTES` bytes, so unlike foreign strings
+/// they can always be loaded in full, and the bytes past `len`, which may hold
+/// anything, are masked off or not loaded at all.
+#[target_feature(enable = "avx2")]
+fn cmp_inline_strs_avx(a: InlineStorage, b: InlineStorage, len: usize) -> bool {
+  let load = |inline_str: InlineStorage, offset: usize| unsafe {
+    _mm256_loadu_si256(inline_str.bytes.as_ptr().add(offset) as *const __m256i)
+  };
+  if len <= M256_BYTES {
+    cmp_si256_masked(load(a, 0), load(b, 0), foreign_str_unknown_bytes_mask(len))
   } else {
-    unsafe { cmp_str_fast_avx(inline_str, other) }
+    let tail = len - M256_BYTES;
+    let head_diff = _mm256_xor_si256(load(a, 0), load(b, 0));
+    let tail_diff = _mm256_xor_si256(load(a, tail), load(b, tail));
+    let diff = _mm256_or_si256(head_diff, tail_diff);
+    _mm256_testz_si256(diff, diff) != 0
+  }
+}
+
+/// Returns true if the two strings have the same length and contents,
+/// regardless of the bytes stored past their lengths.
+pub fn inline_str_eq_inline_str(a: InlineStorage, b: InlineStorage) -> bool {
+  let len = a.len;
+  if len != b.len {
+    return false;
+  }
+  unsafe { cmp_inline_strs_avx(a, b, len) }
+}
+
+pub fn inline_str_eq_foreign_str(inline_str: InlineStorage, other: &str) -> bool {
+  let len = inline_str.len;
+  if unlikely(len != other.len()) {
+    return false;
+  }
+  match cmp_path(len, other) {
+    CmpPath::Short => unsafe { cmp_str_fast_avx(inline_str, other) },
+    CmpPath::Long => unsafe { cmp_str_long_avx(inline_str, other) },
+    CmpPath::Scalar => cmp_str_slow(inline_str, other),
   }
 }
 
 #[cfg(test)]
 mod tests {
   use googletest::prelude::*;
+  use itertools::Itertools;
+  use rand::{rngs::StdRng, seq::IndexedRandom, Rng, SeedableRng};
 
-  #[cfg(not(feature = "multithreaded"))]
+  use crate::generate::default_stations;
   use crate::inline_string::InlineString;
-  #[cfg(feature = "multithreaded")]
-  use crate::inline_string_mt::InlineString;
-  use crate::str_cmp_x86::inline_str_eq_foreign_str;
+  use crate::str_cmp_x86::{
+    cmp_path, inline_str_cmp_inline_str, inline_str_eq_foreign_str, CmpPath, InlineStorage,
+  };
+
+  fn inline(contents: &str) -> InlineString {
+    InlineString::try_from(contents).unwrap()
+  }
+
+  fn storage(inline_str: &InlineString) -> InlineStorage<'_> {
+    inline_str.storage().unwrap()
+  }
+
+  /// Expects `inline_str_cmp_inline_str` to order every pair of `names` like
+  /// their bytes.
+  fn expect_cmp_matches_slice_cmp(names: &[String]) {
+    let inline_names = names.iter().map(|name| inline(name)).collect_vec();
+    for ((a, inline_a), (b, inline_b)) in names
+      .iter()
+      .zip(&inline_names)
+      .cartesian_product(names.iter().zip(&inline_names))
+    {
+      expect_eq!(
+        inline_str_cmp_inline_str(storage(inline_a), storage(inline_b)),
+        a.as_bytes().cmp(b.as_bytes()),
+        "Comparing {a:?} and {b:?}"
+      );
+    }
+  }
 
   #[gtest]
   fn test_cmp_eq() {
     expect_true!(inline_str_eq_foreign_str(
-      &InlineString::new("test word"),
+      storage(&inline("test word")),
       "test word"
     ));
-    expect_true!(inline_str_eq_foreign_str(&InlineString::new("a"), "a"));
+    expect_true!(inline_str_eq_foreign_str(storage(&inline("")), ""));
+    expect_true!(inline_str_eq_foreign_str(storage(&inline("a")), "a"));
     expect_true!(inline_str_eq_foreign_str(
-      &InlineString::new("This sentence is 32 letters long"),
+      storage(&inline("This sentence is 32 letters long")),
       "This sentence is 32 letters long"
     ));
     expect_true!(inline_str_eq_foreign_str(
-      &InlineString::new("This sentence is more than 32 letters long"),
+      storage(&inline("This sentence is more than 32 letters long")),
       "This sentence is more than 32 letters long"
     ));
   }
@@ -95,16 +275,101 @@ mod tests {
   #[gtest]
   fn test_cmp_ne() {
     expect_false!(inline_str_eq_foreign_str(
-      &InlineString::new("test word"),
+      storage(&inline("test word")),
       "test word two"
     ));
     expect_false!(inline_str_eq_foreign_str(
-      &InlineString::new("test word"),
+      storage(&inline("test word")),
       "test"
     ));
     expect_false!(inline_str_eq_foreign_str(
-      &InlineString::new("test word"),
+      storage(&inline("test word")),
       "word test"
     ));
   }
+
+  #[gtest]
+  fn test_cmp_empty_string() {
+    // `String::new()` has a dangling pointer, which must not be loaded from.
+    let empty = String::new();
+    expect_eq!(cmp_path(0, &empty), CmpPath::Scalar);
+    expect_true!(inline_str_eq_foreign_str(storage(&inline("")), &empty));
+    expect_false!(inline_str_eq_foreign_str(storage(&inline("a")), &empty));
+  }
+
+  #[gtest]
+  fn test_cmp_long_names() {
+    let name = "A station name of exactly fifty characters, padded";
+    expect_eq!(name.len(), 50);
+    let inline_name = inline(name);
+    expect_eq!(cmp_path(name.len(), name), CmpPath::Long);
+    expect_true!(inline_str_eq_foreign_str(storage(&inline_name), name));
+
+    // Differ only in the first load, in both loads, and only in the second.
+    for differing_byte in [0, 17, 18, 31, 32, 49] {
+      let mut other = name.as_bytes().to_vec();
+      other[differing_byte] ^= 1;
+      let other = str::from_utf8(&other).unwrap();
+      expect_that!(
+        inline_str_eq_foreign_str(storage(&inline_name), other),
+        eq(false),
+        "differing byte {differing_byte}"
+      );
+    }
+
+    let name = &name[..33];
+    let inline_name = inline(name);
+    expect_eq!(cmp_path(name.len(), name), CmpPath::Long);
+    expect_true!(inline_str_eq_foreign_str(storage(&inline_name), name));
+    expect_false!(inline_str_eq_foreign_str(
+      storage(&inline_name),
+      "A station name of exactly fifty X"
+    ));
+  }
+
+  #[gtest]
+  fn test_cmp_orders_stations_like_slices() {
+    let mut names = default_stations()
+      .unwrap()
+      .into_iter()
+      .map(|station| station.name)
+      .filter(|name| name.len() <= 50)
+      .collect_vec();
+    names.sort_unstable();
+    // Neighbors in sorted order share the longest prefixes.
+    for neighbors in names.chunks(100) {
+      expect_cmp_matches_slice_cmp(neighbors);
+    }
+    let mut rng = StdRng::seed_from_u64(0x5ca1ab1e);
+    expect_cmp_matches_slice_cmp(&names.choose_multiple(&mut rng, 300).cloned().collect_vec());
+  }
+
+  #[gtest]
+  fn test_cmp_orders_random_strings_like_slices() {
+    // Few distinct characters, so that strings often share long prefixes,
+    // including multi-byte characters and bytes with the high bit set.
+    const CHARS: [char; 5] = ['a', 'b', '\u{7f}', 'é', '\u{ffff}'];
+    let mut rng = StdRng::seed_from_u64(0x0bd3c7);
+    let mut names = (0..300)
+      .map(|_| {
+        let len = rng.random_range(0..=50);
+        let mut name = String::new();
+        loop {
+          let c = *CHARS.choose(&mut rng).unwrap();
+          if name.len() + c.len_utf8() > len {
+            break name;
+          }
+          name.push(c);
+        }
+      })
+      .collect_vec();
+    // Every prefix of a long name, which must order before the name.
+    let long_name = names.iter().max_by_key(|name| name.len()).unwrap().clone();
+    names.extend(
+      long_name
+        .char_indices()
+        .map(|(idx, _)| long_name[..idx].to_owned()),
+    );
+    expect_cmp_matches_slice_cmp(&names);
+  }
 }
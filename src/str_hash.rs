@@ -6,6 +6,13 @@ pub const HASH_BITS: u32 = 15;
 pub const HASH_BITS: u32 = 20;
 pub const TABLE_SIZE: usize = 1 << HASH_BITS;
 
+use std::{
+  collections::HashMap,
+  hash::{BuildHasher, Hasher},
+};
+
+use crate::error::{BarseError, BarseResult};
+
 #[cfg(feature = "multithreaded")]
 pub const HASH_MAGIC: u64 = 0x10000200400002;
 #[cfg(not(feature = "multithreaded"))]
@@ -13,12 +20,12 @@ pub const HASH_MAGIC: u64 = 0x800400001001;
 
 #[cfg(any(test, not(target_feature = "avx2")))]
 mod generic_hasher {
+  #[cfg(not(any(feature = "safe-fallback", miri)))]
   use std::ptr::read_unaligned;
 
-  use crate::{
-    str_hash::{HASH_BITS, HASH_MAGIC},
-    util::{unaligned_read_would_cross_page_boundary, unlikely},
-  };
+  use crate::str_hash::HASH_MAGIC;
+  #[cfg(not(any(feature = "safe-fallback", miri)))]
+  use crate::util::{unaligned_read_would_cross_page_boundary, unlikely};
 
   fn read_str_to_u128_slow(s: &[u8]) -> u128 {
     s.iter()
@@ -39,16 +46,34 @@ mod generic_hasher {
   }
 
   fn scramble_u64(v: u64) -> u64 {
-    v.wrapping_mul(HASH_MAGIC) >> (64 - HASH_BITS)
+    v.wrapping_mul(HASH_MAGIC)
   }
 
+  #[cfg(test)]
   pub fn str_hash(bytes: &[u8]) -> u64 {
+    str_hash_wide(bytes) >> (64 - crate::str_hash::HASH_BITS)
+  }
+
+  #[cfg(not(any(feature = "safe-fallback", miri)))]
+  fn read_str_to_u128(bytes: &[u8]) -> u128 {
     let ptr = bytes.as_ptr();
-    let v = if unlikely(unaligned_read_would_cross_page_boundary::<u128>(ptr)) {
+    // The pointer of an empty string may not point to memory at all, e.g.
+    // that of `""`.
+    if unlikely(bytes.is_empty() || unaligned_read_would_cross_page_boundary::<u128>(ptr)) {
       read_str_to_u128_slow(bytes)
     } else {
       unsafe { read_unaligned(ptr as *const u128) }
-    };
+    }
+  }
+
+  /// Never reads past the end of `bytes`, unlike the unaligned read above.
+  #[cfg(any(feature = "safe-fallback", miri))]
+  fn read_str_to_u128(bytes: &[u8]) -> u128 {
+    read_str_to_u128_slow(bytes)
+  }
+
+  pub fn str_hash_wide(bytes: &[u8]) -> u64 {
+    let v = read_str_to_u128(bytes);
 
     let v = mask_above(v, bytes.len());
     let v = compress_u128_to_u64(v);
@@ -75,14 +100,99 @@ mod generic_hasher {
   }
 }
 
+/// Returns the number of hash bits needed to index a table of `capacity`
+/// buckets, which must be a power of two of at least 2.
+pub fn hash_bits_for_capacity(capacity: usize) -> BarseResult<u32> {
+  if capacity < 2 || !capacity.is_power_of_two() {
+    return Err(BarseError::new(format!(
+      "Table capacity must be a power of two of at least 2, got {capacity}"
+    )));
+  }
+  Ok(capacity.ilog2())
+}
+
+/// Returns the full 64-bit scrambled hash of `bytes`, of which `str_hash`
+/// takes the high `HASH_BITS` bits.
 #[cfg(target_feature = "avx2")]
-pub fn str_hash(bytes: &[u8]) -> u64 {
-  crate::str_hash_x86::str_hash_fast(bytes)
+fn str_hash_wide(bytes: &[u8]) -> u64 {
+  crate::str_hash_x86::str_hash_fast_wide(bytes)
 }
 
 #[cfg(not(target_feature = "avx2"))]
+fn str_hash_wide(bytes: &[u8]) -> u64 {
+  generic_hasher::str_hash_wide(bytes)
+}
+
 pub fn str_hash(bytes: &[u8]) -> u64 {
-  generic_hasher::str_hash(bytes)
+  str_hash_with_bits(bytes, HASH_BITS)
+}
+
+/// Like `str_hash`, but returns a hash of `bits` bits, for tables with a
+/// capacity other than `TABLE_SIZE`. `bits` must be in `1..=64`.
+pub fn str_hash_with_bits(bytes: &[u8], bits: u32) -> u64 {
+  debug_assert!((1..=64).contains(&bits));
+  str_hash_wide(bytes) >> (64 - bits)
+}
+
+/// The number of leading bytes of a station name which contribute to its hash.
+pub const HASHED_PREFIX_LEN: usize = 16;
+
+/// Returns the pairs of distinct names in `stations` which share their first
+/// `HASHED_PREFIX_LEN` bytes, and so always collide in the station tables,
+/// pairing each colliding name with the first name with its prefix. A dataset
+/// with no such pairs is safe for `str_hash`.
+pub fn check_prefix_collisions(stations: &[&str]) -> Vec<(String, String)> {
+  let mut prefixes = HashMap::new();
+  stations
+    .iter()
+    .filter_map(|&station| {
+      let prefix = &station.as_bytes()[..station.len().min(HASHED_PREFIX_LEN)];
+      let first = *prefixes.entry(prefix).or_insert(station);
+      (first != station).then(|| (first.to_owned(), station.to_owned()))
+    })
+    .collect()
+}
+
+/// A `BuildHasher` for maps keyed by station name, which hashes keys with the
+/// same function as the weather station tables. Only the first 16 bytes of
+/// each key contribute to its hash, so keys sharing a 16-byte prefix collide.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BuildStringHash;
+
+impl BuildHasher for BuildStringHash {
+  type Hasher = StringHasher;
+
+  fn build_hasher(&self) -> Self::Hasher {
+    StringHasher { hash: 0 }
+  }
+}
+
+pub struct StringHasher {
+  hash: u64,
+}
+
+impl Hasher for StringHasher {
+  fn write(&mut self, bytes: &[u8]) {
+    // `str_hash_wide` reads 16 bytes regardless of the length of `bytes`,
+    // which is only safe for strings in the padded input buffer, so copy keys
+    // into a buffer of their own.
+    let mut buffer = [0; 16];
+    let len = bytes.len().min(16);
+    buffer[..len].copy_from_slice(&bytes[..len]);
+    self.hash = self.hash.rotate_left(5) ^ str_hash_wide(&buffer[..len]);
+  }
+
+  fn write_u8(&mut self, i: u8) {
+    // `str` hashes its bytes followed by a 0xff terminator, which would
+    // otherwise go through `write`.
+    self.hash = self.hash.rotate_left(5) ^ i as u64;
+  }
+
+  fn finish(&self) -> u64 {
+    // Only the high bits of `str_hash_wide` are well mixed, but hash maps
+    // index buckets with the low bits.
+    self.hash ^ self.hash.rotate_left(32)
+  }
 }
 
 #[cfg(test)]
@@ -95,7 +205,7 @@ mod tests {
     Rng, SeedableRng,
   };
 
-  use crate::str_hash::{generic_hasher, str_hash};
+  use crate::str_hash::{check_prefix_collisions, generic_hasher, str_hash};
 
   #[gtest]
   fn test_str_hash_different_positions() {
@@ -144,4 +254,34 @@ mod tests {
       assert_eq!(fast_hash, slow_hash);
     }
   }
+
+  #[gtest]
+  fn test_check_prefix_collisions() {
+    expect_that!(
+      check_prefix_collisions(&[
+        "Oslo",
+        "Abha",
+        "Oslo",
+        "San Francisco",
+        "Santa Cruz de la Sierra"
+      ]),
+      is_empty()
+    );
+
+    let pair = |first: &str, second: &str| (first.to_owned(), second.to_owned());
+    expect_eq!(
+      check_prefix_collisions(&[
+        "Santa Cruz de Tenerife",
+        "Oslo",
+        "Santa Cruz de la Sierra",
+        "Santa Cruz de Tenerife",
+        "Santa Cruz de Te",
+        "Santa Cruz de Tenerife Norte",
+      ]),
+      vec![
+        pair("Santa Cruz de Tenerife", "Santa Cruz de Te"),
+        pair("Santa Cruz de Tenerife", "Santa Cruz de Tenerife Norte"),
+      ]
+    );
+  }
 }
@@ -22,10 +22,7 @@ fn read_str_to_m128_slow(s: &[u8]) -> __m128i {
 
 #[target_feature(enable = "sse2")]
 fn mask_char_and_above(v: __m128i, len: usize) -> __m128i {
-  debug_assert!(
-    (2..=16).contains(&len),
-    "len is outside the range 2..=16: {len}"
-  );
+  debug_assert!(len <= 16, "len is outside the range 0..=16: {len}");
   const MASK_REGION: [u8; 32] = [
     0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, //
     0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, //
@@ -45,12 +42,21 @@ fn compress_m128_to_u64(v: __m128i) -> u64 {
 }
 
 fn scramble_u64(v: u64) -> u64 {
-  v.wrapping_mul(HASH_MAGIC) >> (64 - HASH_BITS)
+  v.wrapping_mul(HASH_MAGIC)
 }
 
 pub fn str_hash_fast(bytes: &[u8]) -> u64 {
+  str_hash_fast_wide(bytes) >> (64 - HASH_BITS)
+}
+
+/// Returns the full 64-bit hash, of which `str_hash_fast` takes the high
+/// `HASH_BITS` bits.
+pub fn str_hash_fast_wide(bytes: &[u8]) -> u64 {
   let ptr = bytes.as_ptr();
-  let v = if unlikely(unaligned_read_would_cross_page_boundary::<__m128i>(ptr)) {
+  // The pointer of an empty string may not point to memory at all, e.g.
+  // that of `""`.
+  let v = if unlikely(bytes.is_empty() || unaligned_read_would_cross_page_boundary::<__m128i>(ptr))
+  {
     read_str_to_m128_slow(bytes)
   } else {
     unsafe { _mm_loadu_si128(ptr as *const __m128i) }
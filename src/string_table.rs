@@ -1,38 +1,95 @@
+// Without the `multithreaded` feature, string tables only hold the names of
+// station filters.
+#![cfg_attr(not(feature = "multithreaded"), allow(dead_code))]
+
+#[cfg(feature = "diagnostics")]
+use std::sync::atomic::{AtomicU64, Ordering};
+
 use crate::{
-  error::BarseResult, hugepage_backed_table::HugepageBackedTable, inline_string_mt::InlineString,
-  str_hash::str_hash,
+  error::BarseResult,
+  hugepage_backed_table::HugepageBackedTable,
+  inline_string_mt::InlineString,
+  str_hash::{hash_bits_for_capacity, str_hash_with_bits},
 };
 
-pub struct StringTable<const SIZE: usize> {
-  table: HugepageBackedTable<InlineString, SIZE>,
+pub struct StringTable {
+  table: HugepageBackedTable<InlineString>,
+  /// log2 of the capacity of `table`.
+  hash_bits: u32,
+  /// The number of buckets examined by `scan_for_entry`. This is only updated
+  /// on collisions, so threads rarely contend on it.
+  #[cfg(feature = "diagnostics")]
+  scan_probes: AtomicU64,
 }
 
-impl<const SIZE: usize> StringTable<SIZE> {
-  pub fn new() -> BarseResult<Self> {
+impl StringTable {
+  /// Constructs a table with `capacity` buckets, which must be a power of two.
+  pub fn new(capacity: usize) -> BarseResult<Self> {
     Ok(Self {
-      table: HugepageBackedTable::new()?,
+      hash_bits: hash_bits_for_capacity(capacity)?,
+      // A zeroed `InlineString` is already an unclaimed string, so the
+      // buckets are left for the first thread claiming them to touch.
+      table: HugepageBackedTable::new_lazy(capacity)?,
+      #[cfg(feature = "diagnostics")]
+      scan_probes: AtomicU64::new(0),
     })
   }
 
+  pub fn capacity(&self) -> usize {
+    self.table.size()
+  }
+
+  #[cfg(feature = "diagnostics")]
+  pub fn scan_probes(&self) -> u64 {
+    self.scan_probes.load(Ordering::Relaxed)
+  }
+
+  pub fn hugepages(&self) -> bool {
+    self.table.hugepages()
+  }
+
+  pub fn prefault(&self) {
+    self.table.prefault();
+  }
+
+  pub const fn mapped_len_for_capacity(capacity: usize) -> usize {
+    HugepageBackedTable::<InlineString>::mapped_len_for_size(capacity)
+  }
+
+  pub fn mapped_len(&self) -> usize {
+    self.table.mapped_len()
+  }
+
   pub fn entry_at(&self, index: usize) -> &InlineString {
     self.table.entry_at(index)
   }
 
   fn station_hash(&self, station: &str) -> u64 {
-    str_hash(station.as_bytes())
+    str_hash_with_bits(station.as_bytes(), self.hash_bits)
   }
 
   fn station_index(&self, station: &str) -> usize {
-    self.station_hash(station) as usize % SIZE
+    self.station_hash(station) as usize
   }
 
   fn scan_for_entry(&self, station: &str, start_idx: usize) -> usize {
-    (1..SIZE)
-      .map(|i| (start_idx + i) % SIZE)
-      .find(|&idx| self.table.entry_at(idx).eq_or_initialize(station))
-      .expect("No empty bucket found, table is full")
+    let mask = self.capacity() - 1;
+    let offset = (1..self.capacity())
+      .find(|&i| {
+        self
+          .table
+          .entry_at((start_idx + i) & mask)
+          .eq_or_initialize(station)
+      })
+      .expect("No empty bucket found, table is full");
+    #[cfg(feature = "diagnostics")]
+    self.scan_probes.fetch_add(offset as u64, Ordering::Relaxed);
+    (start_idx + offset) & mask
   }
 
+  /// Returns the index of the entry for `station`, inserting it if it isn't
+  /// in the table yet. Panics if `station` is longer than 50 bytes, or if the
+  /// table is full.
   pub fn find_entry_index(&self, station: &str) -> usize {
     let idx = self.station_index(station);
     let entry = self.entry_at(idx);
@@ -42,4 +99,74 @@ impl<const SIZE: usize> StringTable<SIZE> {
       self.scan_for_entry(station, idx)
     }
   }
+
+  /// Removes every name from the table, keeping its allocation. Only the
+  /// buckets which were claimed are written.
+  #[cfg(feature = "multithreaded")]
+  pub fn clear(&mut self) {
+    for idx in 0..self.capacity() {
+      if self.entry_at(idx).initialized() {
+        self.table.reset_at(idx);
+      }
+    }
+  }
+
+  /// Returns true if `station` is in the table, without inserting it. This
+  /// may miss names which are being inserted concurrently.
+  pub fn contains(&self, station: &str) -> bool {
+    let mask = self.capacity() - 1;
+    let start_idx = self.station_index(station);
+    (0..self.capacity())
+      .map(|i| self.entry_at((start_idx + i) & mask))
+      .take_while(|entry| entry.initialized())
+      .any(|entry| entry == station)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{collections::HashMap, sync::Barrier, thread};
+
+  use googletest::prelude::*;
+
+  use super::StringTable;
+
+  #[gtest]
+  fn test_concurrent_find_entry_index() {
+    const THREADS: usize = 8;
+    // With 200 names in 256 buckets, many names collide, so threads race to
+    // initialize the same buckets and to scan past each other's.
+    let names = (0..200).map(|i| format!("station{i}")).collect::<Vec<_>>();
+    let table = StringTable::new(256).unwrap();
+    let barrier = Barrier::new(THREADS);
+
+    let indices = thread::scope(|scope| {
+      let threads = (0..THREADS)
+        .map(|thread| {
+          let (table, barrier, names) = (&table, &barrier, &names);
+          scope.spawn(move || {
+            barrier.wait();
+            // Each thread starts interning at a different name.
+            (0..names.len())
+              .map(|i| {
+                let name = &names[(i + thread * 25) % names.len()];
+                (name.as_str(), table.find_entry_index(name))
+              })
+              .collect::<HashMap<_, _>>()
+          })
+        })
+        .collect::<Vec<_>>();
+      threads
+        .into_iter()
+        .map(|thread| thread.join().unwrap())
+        .collect::<Vec<_>>()
+    });
+
+    for thread_indices in &indices {
+      expect_eq!(thread_indices, &indices[0]);
+    }
+    for (name, &idx) in &indices[0] {
+      expect_eq!(table.entry_at(idx).value_str(), *name);
+    }
+  }
 }
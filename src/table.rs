@@ -1,24 +1,218 @@
-use std::fmt::Debug;
+use std::{collections::HashMap, fmt::Debug};
 
 use crate::{
+  barse::{fingerprint, select_by_avg},
   error::BarseResult,
   hugepage_backed_table::HugepageBackedTable,
-  str_hash::str_hash,
-  table_entry::Entry,
+  inline_string::InlineString,
+  normalize::NameNormalizer,
+  sink::Sink,
+  str_hash::{hash_bits_for_capacity, str_hash_with_bits, TABLE_SIZE},
+  table_entry::{Entry, KeyMatch},
+  table_stats::{MemoryFootprint, TableStats},
   temperature_reading::TemperatureReading,
   temperature_summary::TemperatureSummary,
-  util::{likely, HasIter},
+  util::{likely, prefetch, unlikely, HasIter},
 };
 
-pub struct WeatherStationTable<const SIZE: usize> {
-  table: HugepageBackedTable<Entry, SIZE>,
+/// The number of readings `add_readings_batch` hashes and prefetches ahead of
+/// inserting them.
+const BATCH_LEN: usize = 16;
+
+pub struct WeatherStationTable {
+  table: HugepageBackedTable<Entry>,
+  /// log2 of the capacity of `table`.
+  hash_bits: u32,
+  /// The buckets of the stations in the table, in the order the stations
+  /// claimed them.
+  first_seen: Vec<usize>,
+  /// The number of buckets examined by `scan_for_entry`.
+  #[cfg(feature = "diagnostics")]
+  scan_probes: u64,
 }
 
-impl<const SIZE: usize> WeatherStationTable<SIZE> {
+impl WeatherStationTable {
   pub fn new() -> BarseResult<Self> {
-    Ok(Self {
-      table: HugepageBackedTable::new()?,
-    })
+    Self::with_capacity(TABLE_SIZE)
+  }
+
+  /// Constructs a table with `capacity` buckets, which must be a power of two.
+  pub fn with_capacity(capacity: usize) -> BarseResult<Self> {
+    let hash_bits = hash_bits_for_capacity(capacity)?;
+    Ok(Self::from_table(
+      hash_bits,
+      HugepageBackedTable::new(capacity)?,
+    ))
+  }
+
+  /// Like `new`, but the buckets are left zeroed until a station claims them,
+  /// rather than all initialized upfront, so the pages of the table are only
+  /// touched once a station hashes into them.
+  pub fn new_lazy() -> BarseResult<Self> {
+    Self::with_capacity_lazy(TABLE_SIZE)
+  }
+
+  /// Like `with_capacity`, but initializes buckets lazily like `new_lazy`.
+  pub fn with_capacity_lazy(capacity: usize) -> BarseResult<Self> {
+    let hash_bits = hash_bits_for_capacity(capacity)?;
+    Ok(Self::from_table(
+      hash_bits,
+      HugepageBackedTable::new_lazy(capacity)?,
+    ))
+  }
+
+  fn from_table(hash_bits: u32, table: HugepageBackedTable<Entry>) -> Self {
+    Self {
+      hash_bits,
+      table,
+      first_seen: Vec::new(),
+      #[cfg(feature = "diagnostics")]
+      scan_probes: 0,
+    }
+  }
+
+  pub fn capacity(&self) -> usize {
+    self.table.size()
+  }
+
+  /// Returns the name and summary of the station in bucket `index`, or
+  /// `None` if the bucket is empty.
+  pub fn station_at(&self, index: usize) -> Option<(&str, &TemperatureSummary)> {
+    let entry = self.entry_at(index);
+    (!entry.is_default()).then(|| entry.to_iter_pair())
+  }
+
+  pub fn stats(&self) -> TableStats {
+    #[cfg(feature = "diagnostics")]
+    let lookups = self.iter().map(|(_, summary)| summary.count as u64).sum();
+    TableStats {
+      capacity: self.capacity(),
+      stations: self.iter().count(),
+      #[cfg(feature = "diagnostics")]
+      lookups,
+      // Every lookup examines its home bucket before scanning.
+      #[cfg(feature = "diagnostics")]
+      probes: lookups + self.scan_probes,
+    }
+  }
+
+  /// Returns the summary of every reading in the table, across all stations.
+  pub fn global_summary(&self) -> TemperatureSummary {
+    TemperatureSummary::merge_all(self.iter().map(|(_, summary)| summary))
+  }
+
+  pub fn hugepages(&self) -> bool {
+    self.table.hugepages()
+  }
+
+  /// Faults in every page of the table, so that a build timed afterwards
+  /// doesn't pay for the page faults of first touching the table.
+  pub fn prefault(&self) {
+    self.table.prefault();
+  }
+
+  /// Returns the memory `with_capacity(capacity)` reserves, without
+  /// allocating a table.
+  pub const fn memory_footprint_for_capacity(capacity: usize) -> MemoryFootprint {
+    MemoryFootprint {
+      capacity,
+      entry_bytes: std::mem::size_of::<Entry>(),
+      key_bytes: std::mem::size_of::<InlineString>(),
+      summary_bytes: std::mem::size_of::<TemperatureSummary>(),
+      mapped_bytes: HugepageBackedTable::<Entry>::mapped_len_for_size(capacity),
+    }
+  }
+
+  /// Returns the memory reserved by this table.
+  pub fn memory_footprint(&self) -> MemoryFootprint {
+    MemoryFootprint {
+      mapped_bytes: self.table.mapped_len(),
+      ..Self::memory_footprint_for_capacity(self.capacity())
+    }
+  }
+
+  /// Returns the name and summary of every station in the table, in the
+  /// order the stations first appeared in the input. Stations merged from
+  /// another table appear in that table's order, after the stations already
+  /// in this table. A station whose bucket was claimed by `bucket_index_of`
+  /// is ordered by that claim, and skipped until it has readings.
+  pub fn iter_by_appearance(&self) -> impl Iterator<Item = (&str, &TemperatureSummary)> {
+    self
+      .first_seen
+      .iter()
+      .filter_map(|&idx| self.station_at(idx))
+  }
+
+  /// Hands the name and summary of every station in the table to `sink`, in
+  /// the order of `iter_by_appearance`, stopping at the first error. The
+  /// table is left as it is.
+  pub fn drain_into<S: Sink + ?Sized>(&self, sink: &mut S) -> BarseResult {
+    self
+      .iter_by_appearance()
+      .try_for_each(|(station, summary)| sink.on_station(station, summary))
+  }
+
+  /// Copies the name and summary of every station in the table, in table
+  /// order, so that the results outlive both the table and the input it was
+  /// built from.
+  pub fn to_owned_summary(&self) -> Vec<(String, TemperatureSummary)> {
+    self
+      .iter()
+      .map(|(station, summary)| (station.to_owned(), *summary))
+      .collect()
+  }
+
+  /// Merges the summaries of every station in `other` into this table.
+  pub fn merge(&mut self, other: &Self) -> BarseResult {
+    self.merge_summaries(other.iter_by_appearance())
+  }
+
+  /// Merges each summary into the summary of its station in this table. The
+  /// summaries must not be empty.
+  pub fn merge_summaries<'a>(
+    &mut self,
+    summaries: impl IntoIterator<Item = (&'a str, &'a TemperatureSummary)>,
+  ) -> BarseResult {
+    // Names are copied into a padded buffer before being looked up in this
+    // table.
+    let mut names = NameNormalizer::new(&|name: &str, out: &mut String| out.push_str(name));
+    for (station, summary) in summaries {
+      let station = names.normalize(station)?;
+      let idx = self.station_index(station);
+      self.find_entry(station, idx).merge(summary);
+    }
+    Ok(())
+  }
+
+  /// Returns the names and averages of the `n` stations with the highest
+  /// average readings, hottest first, with ties broken by name.
+  pub fn top_by_avg(&self, n: usize) -> Vec<(String, TemperatureReading)> {
+    select_by_avg(self.iter(), n, true)
+  }
+
+  /// Returns the names and averages of the `n` stations with the lowest
+  /// average readings, coldest first, with ties broken by name.
+  pub fn bottom_by_avg(&self, n: usize) -> Vec<(String, TemperatureReading)> {
+    select_by_avg(self.iter(), n, false)
+  }
+
+  /// Returns a 64-bit fingerprint of the stations and summaries in the
+  /// table, which is equal for logically equal tables, regardless of their
+  /// capacities or where each station landed in the table, and almost surely
+  /// differs otherwise.
+  pub fn fingerprint(&self) -> u64 {
+    fingerprint(self.iter())
+  }
+
+  /// Returns true if both tables hold the same stations with equal summaries,
+  /// regardless of their capacities or where each station landed in the
+  /// table.
+  pub fn logically_eq(&self, other: &Self) -> bool {
+    let other_stations: HashMap<_, _> = other.iter().collect();
+    self.iter().count() == other_stations.len()
+      && self
+        .iter()
+        .all(|(station, summary)| other_stations.get(station) == Some(&summary))
   }
 
   fn entry_at(&self, index: usize) -> &Entry {
@@ -29,39 +223,110 @@ impl<const SIZE: usize> WeatherStationTable<SIZE> {
     self.table.entry_at_mut(index)
   }
 
-  fn scan_for_entry(&mut self, station: &str, start_idx: usize) -> &mut Entry {
-    let idx = (1..SIZE)
-      .map(|i| (start_idx + i) % SIZE)
-      .find(|&idx| self.entry_at_mut(idx).matches_key_or_initialize(station))
+  fn scan_for_entry(&mut self, station: &str, start_idx: usize) -> (usize, KeyMatch) {
+    let mask = self.capacity() - 1;
+    let (offset, key_match) = (1..self.capacity())
+      .find_map(|i| {
+        match self
+          .entry_at_mut((start_idx + i) & mask)
+          .find_or_claim(station)
+        {
+          KeyMatch::Other => None,
+          key_match => Some((i, key_match)),
+        }
+      })
       .expect("No empty bucket found, table is full");
-    self.entry_at_mut(idx)
+    #[cfg(feature = "diagnostics")]
+    {
+      self.scan_probes += offset as u64;
+    }
+    ((start_idx + offset) & mask, key_match)
   }
 
+  /// Adds `reading` to the summary of `station`, which must be in a padded
+  /// buffer. Panics if `station` is longer than 50 bytes, or if the table is
+  /// full.
   pub fn add_reading(&mut self, station: &str, reading: TemperatureReading) {
-    self.find_entry(station).add_reading(reading);
+    let idx = self.station_index(station);
+    self.find_entry(station, idx).add_reading(reading);
+  }
+
+  /// Adds every reading in `batch`, like calling `add_reading` on each in
+  /// order. The home buckets of up to `BATCH_LEN` stations are found and
+  /// prefetched before any of them is inserted into, so that their cache
+  /// misses overlap. Like `add_reading`, the station names must be in a
+  /// padded buffer.
+  pub fn add_readings_batch(&mut self, batch: &[(&str, TemperatureReading)]) {
+    for batch in batch.chunks(BATCH_LEN) {
+      let mut indices = [0; BATCH_LEN];
+      for (idx, &(station, _)) in indices.iter_mut().zip(batch) {
+        *idx = self.station_index(station);
+        prefetch(self.entry_at(*idx));
+      }
+      for (&idx, &(station, reading)) in indices.iter().zip(batch) {
+        self.find_entry(station, idx).add_reading(reading);
+      }
+    }
   }
 
   fn station_hash(&self, station: &str) -> u64 {
-    str_hash(station.as_bytes())
+    str_hash_with_bits(station.as_bytes(), self.hash_bits)
   }
 
   fn station_index(&self, station: &str) -> usize {
-    self.station_hash(station) as usize % SIZE
+    self.station_hash(station) as usize
   }
 
-  fn find_entry(&mut self, station: &str) -> &mut Entry {
+  /// Returns the index of the bucket holding `station`, claiming one for it
+  /// if it isn't in the table yet, e.g. to keep a side table of per-station
+  /// data indexed in step with this one. Like `add_reading`, `station` must
+  /// be in a padded buffer.
+  ///
+  /// A station keeps its bucket once claimed, but isn't listed by `iter`
+  /// until it has a reading. The empty station name is the exception: its
+  /// bucket is indistinguishable from an unused one until it has a reading,
+  /// so it may be claimed by another station in the meantime.
+  pub fn bucket_index_of(&mut self, station: &str) -> usize {
     let idx = self.station_index(station);
+    let (idx, key_match) = self.find_index(station, idx);
+    // The empty station's bucket isn't kept until it has a reading, so it is
+    // only noted once `find_entry` claims it again.
+    if key_match == KeyMatch::Claimed && !station.is_empty() {
+      self.note_claimed(idx);
+    }
+    idx
+  }
 
-    if likely(self.entry_at_mut(idx).matches_key_or_initialize(station)) {
-      return self.entry_at_mut(idx);
+  /// Finds the index of the entry for `station`, whose home bucket is `idx`,
+  /// and whether the station claimed it.
+  fn find_index(&mut self, station: &str, idx: usize) -> (usize, KeyMatch) {
+    let key_match = self.entry_at_mut(idx).find_or_claim(station);
+    if likely(key_match != KeyMatch::Other) {
+      return (idx, key_match);
     }
 
     // Otherwise we have to search for a bucket.
     self.scan_for_entry(station, idx)
   }
+
+  /// Finds the entry for `station`, whose home bucket is `idx`, noting the
+  /// bucket in `first_seen` if the station claimed it.
+  fn find_entry(&mut self, station: &str, idx: usize) -> &mut Entry {
+    let (idx, key_match) = self.find_index(station, idx);
+    if unlikely(key_match == KeyMatch::Claimed) {
+      self.note_claimed(idx);
+    }
+    self.entry_at_mut(idx)
+  }
+
+  #[cold]
+  #[inline(never)]
+  fn note_claimed(&mut self, idx: usize) {
+    self.first_seen.push(idx);
+  }
 }
 
-impl<'a, const SIZE: usize> HasIter<'a> for WeatherStationTable<SIZE> {
+impl<'a> HasIter<'a> for WeatherStationTable {
   type Item = (&'a str, &'a TemperatureSummary);
 
   fn iter(&'a self) -> impl Iterator<Item = Self::Item> {
@@ -72,26 +337,26 @@ impl<'a, const SIZE: usize> HasIter<'a> for WeatherStationTable<SIZE> {
   }
 }
 
-impl<const SIZE: usize> Debug for WeatherStationTable<SIZE> {
+impl Debug for WeatherStationTable {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     write!(f, "")
   }
 }
 
-struct WeatherStationIterator<'a, const SIZE: usize> {
-  table: &'a WeatherStationTable<SIZE>,
+struct WeatherStationIterator<'a> {
+  table: &'a WeatherStationTable,
   index: usize,
 }
 
-impl<'a, const SIZE: usize> Iterator for WeatherStationIterator<'a, SIZE> {
+impl<'a> Iterator for WeatherStationIterator<'a> {
   type Item = (&'a str, &'a TemperatureSummary);
 
   fn next(&mut self) -> Option<Self::Item> {
-    while self.index < SIZE {
-      let entry = self.table.entry_at(self.index);
+    while self.index < self.table.capacity() {
+      let station = self.table.station_at(self.index);
       self.index += 1;
-      if !entry.is_default() {
-        return Some(entry.to_iter_pair());
+      if station.is_some() {
+        return station;
       }
     }
     None
@@ -100,22 +365,27 @@ impl<'a, const SIZE: usize> Iterator for WeatherStationIterator<'a, SIZE> {
 
 #[cfg(test)]
 mod tests {
+  use std::panic::AssertUnwindSafe;
+
   use googletest::prelude::*;
   use itertools::Itertools;
 
   use crate::{
+    hugepage_backed_table::HUGEPAGE_SIZE,
+    scanner::Scanner,
     table::{TemperatureSummary, WeatherStationTable},
     temperature_reading::TemperatureReading,
+    test_util::random_input_file,
     util::HasIter,
   };
 
-  fn new_table<const SIZE: usize>() -> WeatherStationTable<SIZE> {
-    WeatherStationTable::new().unwrap()
+  fn new_table(capacity: usize) -> WeatherStationTable {
+    WeatherStationTable::with_capacity(capacity).unwrap()
   }
 
   #[gtest]
   fn test_insert() {
-    let mut table = new_table::<16>();
+    let mut table = new_table(16);
     table.add_reading("station1", TemperatureReading::new(123));
 
     let mut iter = table.iter();
@@ -133,9 +403,27 @@ mod tests {
     );
   }
 
+  #[gtest]
+  fn test_over_length_name_panics() {
+    let name = "a".repeat(51);
+    let mut table = new_table(16);
+    let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+      table.add_reading(&name, TemperatureReading::new(123))
+    }));
+    expect_true!(result.is_err());
+
+    // The bucket isn't claimed, so the table is left as it was.
+    expect_eq!(table.iter().count(), 0);
+    table.add_reading(&name[..50], TemperatureReading::new(123));
+    expect_that!(
+      table.iter().map(|(station, _)| station).collect_vec(),
+      elements_are![eq(&&name[..50])]
+    );
+  }
+
   #[gtest]
   fn test_insert_two_stations() {
-    let mut table = new_table::<16>();
+    let mut table = new_table(16);
     table.add_reading("station1", TemperatureReading::new(123));
     table.add_reading("station2", TemperatureReading::new(456));
 
@@ -167,7 +455,7 @@ mod tests {
 
   #[gtest]
   fn test_insert_station_twice() {
-    let mut table = new_table::<16>();
+    let mut table = new_table(16);
     table.add_reading("station1", TemperatureReading::new(123));
     table.add_reading("station1", TemperatureReading::new(456));
 
@@ -185,4 +473,294 @@ mod tests {
       )]
     );
   }
+
+  #[gtest]
+  fn test_insert_empty_station() {
+    let mut table = new_table(16);
+    table.add_reading("", TemperatureReading::new(123));
+    table.add_reading("station1", TemperatureReading::new(456));
+    table.add_reading("", TemperatureReading::new(-7));
+
+    let elements = table.iter().collect_vec();
+    expect_that!(
+      elements,
+      unordered_elements_are![
+        (
+          eq(&""),
+          derefs_to(pat!(TemperatureSummary {
+            min: &TemperatureReading::new(-7),
+            max: &TemperatureReading::new(123),
+            total: &116,
+            count: &2,
+          }))
+        ),
+        (
+          eq(&"station1"),
+          derefs_to(pat!(TemperatureSummary {
+            min: &TemperatureReading::new(456),
+            max: &TemperatureReading::new(456),
+            total: &456,
+            count: &1,
+          }))
+        )
+      ]
+    );
+  }
+
+  #[gtest]
+  fn test_iter_by_appearance() {
+    let names = |table: &WeatherStationTable| {
+      table
+        .iter_by_appearance()
+        .map(|(station, _)| station.to_owned())
+        .collect_vec()
+    };
+    let mut table = new_table(16);
+    // A claimed bucket is skipped until it has readings, but keeps its place.
+    table.bucket_index_of("station9");
+    for station in ["station3", "station1", "station3", "station2", "station1"] {
+      table.add_reading(station, TemperatureReading::new(10));
+    }
+    expect_that!(
+      names(&table),
+      elements_are![eq("station3"), eq("station1"), eq("station2")]
+    );
+
+    let mut other = new_table(16);
+    for station in ["station4", "station2", "station9"] {
+      other.add_reading(station, TemperatureReading::new(20));
+    }
+    table.merge(&other).unwrap();
+    expect_that!(
+      names(&table),
+      elements_are![
+        eq("station9"),
+        eq("station3"),
+        eq("station1"),
+        eq("station2"),
+        eq("station4")
+      ]
+    );
+    expect_eq!(names(&table).len(), table.iter().count());
+
+    let mut visited = Vec::new();
+    table
+      .drain_into(&mut |station: &str, summary: &TemperatureSummary| {
+        visited.push((station.to_owned(), summary.count));
+        Ok(())
+      })
+      .unwrap();
+    expect_that!(
+      visited,
+      elements_are![
+        eq(&("station9".to_owned(), 1)),
+        eq(&("station3".to_owned(), 2)),
+        eq(&("station1".to_owned(), 2)),
+        eq(&("station2".to_owned(), 2)),
+        eq(&("station4".to_owned(), 1))
+      ]
+    );
+  }
+
+  #[gtest]
+  fn test_bucket_index_of() {
+    // Every bucket of the table ends up claimed, so the stations collide.
+    let mut table = new_table(4);
+    let stations = ["station1", "station2", "station3"];
+    let indices = stations.map(|station| table.bucket_index_of(station));
+    expect_that!(indices.iter().unique().collect_vec(), len(eq(3)));
+    expect_that!(table.iter().collect_vec(), is_empty());
+
+    // Claimed buckets aren't handed to other stations before their first
+    // reading.
+    table.add_reading("station4", TemperatureReading::new(12));
+    let station4 = table.bucket_index_of("station4");
+    expect_that!(indices, not(contains(eq(station4))));
+    for (station, index) in stations.into_iter().zip(indices) {
+      expect_eq!(table.bucket_index_of(station), index);
+    }
+
+    table.add_reading("station2", TemperatureReading::new(34));
+    let (station, summary) = table.entry_at(indices[1]).to_iter_pair();
+    expect_eq!(station, "station2");
+    expect_eq!(
+      (summary.max, summary.count),
+      (TemperatureReading::new(34), 1)
+    );
+    expect_that!(
+      table.iter().map(|(station, _)| station).collect_vec(),
+      unordered_elements_are![eq(&"station2"), eq(&"station4")]
+    );
+  }
+
+  #[gtest]
+  fn test_add_readings_batch_matches_add_reading() {
+    let input = random_input_file(0x5eb7c4, 10_000, 500).unwrap();
+    let records = Scanner::from_start_with_len(input.padded_slice(), input.len()).collect_vec();
+
+    let mut table = new_table(1024);
+    for &(station, temp) in &records {
+      table.add_reading(station, temp);
+    }
+    let mut batched_table = new_table(1024);
+    for batch in records.chunks(37) {
+      batched_table.add_readings_batch(batch);
+    }
+
+    let sorted = |table: &WeatherStationTable| {
+      table
+        .iter()
+        .map(|(station, summary)| {
+          let fields = (summary.min, summary.max, summary.total, summary.count);
+          (station.to_owned(), fields)
+        })
+        .sorted()
+        .collect_vec()
+    };
+    expect_eq!(sorted(&batched_table), sorted(&table));
+    expect_eq!(batched_table.iter().count(), 500);
+  }
+
+  #[gtest]
+  fn test_build_after_prefault() {
+    let input = random_input_file(0x9ef4, 10_000, 500).unwrap();
+    let records = Scanner::from_start_with_len(input.padded_slice(), input.len()).collect_vec();
+
+    let mut table = new_table(1024);
+    let mut prefaulted = [
+      new_table(1024),
+      WeatherStationTable::with_capacity_lazy(1024).unwrap(),
+    ];
+    for prefaulted in &prefaulted {
+      prefaulted.prefault();
+    }
+    for &(station, temp) in &records {
+      table.add_reading(station, temp);
+      for prefaulted in &mut prefaulted {
+        prefaulted.add_reading(station, temp);
+      }
+    }
+    expect_eq!(table.iter().count(), 500);
+    for prefaulted in &prefaulted {
+      expect_true!(prefaulted.logically_eq(&table));
+    }
+  }
+
+  #[gtest]
+  fn test_lazy_table_matches_eager_table() {
+    let input = random_input_file(0x1a2e, 10_000, 500).unwrap();
+    let mut records = Scanner::from_start_with_len(input.padded_slice(), input.len()).collect_vec();
+    // Stations whose min or max would be wrong if their summaries were left
+    // zeroed, including the empty name, which matches an unclaimed bucket.
+    records.extend([
+      ("hot", TemperatureReading::new(12)),
+      ("hot", TemperatureReading::new(34)),
+      ("cold", TemperatureReading::new(-12)),
+      ("", TemperatureReading::new(5)),
+    ]);
+
+    let mut table = new_table(1024);
+    let mut lazy_table = WeatherStationTable::with_capacity_lazy(1024).unwrap();
+    for &(station, temp) in &records {
+      table.add_reading(station, temp);
+      lazy_table.add_reading(station, temp);
+    }
+    expect_true!(lazy_table.logically_eq(&table));
+    let min_max = |name: &str| {
+      lazy_table
+        .iter()
+        .find(|&(station, _)| station == name)
+        .map(|(_, summary)| (summary.min, summary.max))
+    };
+    expect_eq!(
+      min_max("hot"),
+      Some((TemperatureReading::new(12), TemperatureReading::new(34)))
+    );
+    expect_eq!(
+      min_max("cold"),
+      Some((TemperatureReading::new(-12), TemperatureReading::new(-12)))
+    );
+
+    let mut merged = WeatherStationTable::with_capacity_lazy(1024).unwrap();
+    merged.merge(&table).unwrap();
+    expect_true!(merged.logically_eq(&table));
+  }
+
+  #[gtest]
+  fn test_owned_summary_outlives_table() {
+    let input = random_input_file(0x0e7b1d, 1_000, 50).unwrap();
+    let mut table = new_table(256);
+    for (station, temp) in Scanner::from_start_with_len(input.padded_slice(), input.len()) {
+      table.add_reading(station, temp);
+    }
+    let expected = table
+      .iter()
+      .map(|(station, summary)| (station.to_owned(), summary.count))
+      .collect_vec();
+
+    let summary = table.to_owned_summary();
+    drop(table);
+    drop(input);
+    expect_eq!(
+      summary
+        .iter()
+        .map(|(station, summary)| (station.clone(), summary.count))
+        .collect_vec(),
+      expected
+    );
+    expect_eq!(
+      summary
+        .iter()
+        .map(|(_, summary)| summary.count)
+        .sum::<u32>(),
+      1_000
+    );
+  }
+
+  #[gtest]
+  fn test_logically_eq() {
+    let input = random_input_file(0x10e9a1, 1_000, 50).unwrap();
+    let readings = Scanner::from_start_with_len(input.padded_slice(), input.len()).collect_vec();
+    let build = |capacity: usize, readings: &[(&str, TemperatureReading)], reverse: bool| {
+      let mut table = new_table(capacity);
+      let mut add =
+        |&(station, temp): &(&str, TemperatureReading)| table.add_reading(station, temp);
+      if reverse {
+        readings.iter().rev().for_each(&mut add);
+      } else {
+        readings.iter().for_each(&mut add);
+      }
+      table
+    };
+
+    let table = build(64, &readings, false);
+    let reversed = build(256, &readings, true);
+    expect_true!(table.logically_eq(&reversed));
+    expect_true!(reversed.logically_eq(&table));
+
+    let mut changed = readings.clone();
+    let (station, temp) = changed[0];
+    changed[0] = (station, TemperatureReading::new(temp.reading() + 1));
+    let changed = build(64, &changed, true);
+    expect_false!(table.logically_eq(&changed));
+    expect_false!(changed.logically_eq(&table));
+  }
+
+  #[gtest]
+  fn test_memory_footprint_matches_mapping() {
+    for capacity in [16, 1 << 15, 1 << 20] {
+      let footprint = new_table(capacity).memory_footprint();
+      expect_eq!(
+        footprint,
+        WeatherStationTable::memory_footprint_for_capacity(capacity)
+      );
+      expect_eq!(footprint.capacity, capacity);
+      expect_eq!(footprint.entry_bytes, 72);
+      expect_eq!(footprint.key_bytes + footprint.summary_bytes, 72);
+      expect_eq!(
+        footprint.mapped_bytes,
+        (capacity * 72).next_multiple_of(HUGEPAGE_SIZE)
+      );
+    }
+  }
 }
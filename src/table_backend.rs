@@ -0,0 +1,148 @@
+use std::ptr::NonNull;
+
+#[cfg(feature = "mmap")]
+use memmap2::{MmapMut, MmapOptions};
+
+#[cfg(not(feature = "mmap"))]
+use crate::{aligned_alloc::AlignedAlloc, hugepage_backed_table::HUGEPAGE_SIZE};
+use crate::{error::BarseResult, util::PAGE_SIZE};
+
+/// Allocates the memory backing a `HugepageBackedTable`. Each table owns its
+/// own backend, which makes exactly one allocation over the table's lifetime.
+///
+/// # Safety
+///
+/// Tables trust the allocation without checking it, so a successful
+/// `allocate(len, align)` must return a pointer to `len` bytes that:
+///
+/// - are valid for reads and writes, and not accessed by anything else until
+///   they are passed to `deallocate`,
+/// - are aligned to at least `align`, and
+/// - are all zero, since tables hand out references to their zeroed elements.
+pub unsafe trait TableBackend {
+  /// Allocates `len` zero-initialized bytes aligned to at least `align`,
+  /// which is a power of two no larger than the smallest page size.
+  fn allocate(&mut self, len: usize, align: usize) -> BarseResult<NonNull<u8>>;
+
+  /// Frees the allocation of `len` bytes at `ptr`.
+  ///
+  /// Safety:
+  /// `ptr` and `len` must describe the allocation returned by `allocate` on
+  /// this backend, which must not be accessed afterwards.
+  unsafe fn deallocate(&mut self, ptr: NonNull<u8>, len: usize);
+
+  /// Returns true if the kernel accepted the advice to back the allocation
+  /// with hugepages. It may still use regular pages, e.g. if no hugepages are
+  /// free, so this doesn't guarantee that the allocation is backed by them.
+  fn hugepages(&self) -> bool {
+    false
+  }
+
+  /// Faults in every page of the allocation of `len` bytes at `ptr`, so that
+  /// accesses to it don't pay for page faults. By default, one byte of each
+  /// page is read, which for memory the kernel zero-fills on demand may only
+  /// map a shared zero page until the page is first written.
+  ///
+  /// Safety:
+  /// `ptr` and `len` must describe the allocation returned by `allocate` on
+  /// this backend.
+  unsafe fn prefault(&self, ptr: NonNull<u8>, len: usize) {
+    unsafe { touch_pages(ptr, len) };
+  }
+}
+
+/// Reads one byte of each page of the `len` bytes at `ptr`.
+///
+/// Safety:
+/// `ptr` must be valid for reads of `len` bytes.
+unsafe fn touch_pages(ptr: NonNull<u8>, len: usize) {
+  for offset in (0..len).step_by(PAGE_SIZE) {
+    // SAFETY: `offset` is within the `len` bytes at `ptr`. The read is
+    // volatile so that it isn't optimized out.
+    unsafe { ptr.add(offset).read_volatile() };
+  }
+}
+
+/// Maps anonymous memory with `memmap2`, advising the kernel to back it with
+/// transparent hugepages on Linux.
+#[cfg(feature = "mmap")]
+#[derive(Default)]
+pub struct MmapBackend {
+  map: Option<MmapMut>,
+  /// True if the kernel accepted the advice to back the mapping with
+  /// hugepages.
+  hugepages: bool,
+}
+
+// SAFETY: `map_anon` maps `len` zero-filled, page-aligned bytes, which are
+// owned by `self.map` until `deallocate`.
+#[cfg(feature = "mmap")]
+unsafe impl TableBackend for MmapBackend {
+  fn allocate(&mut self, len: usize, _align: usize) -> BarseResult<NonNull<u8>> {
+    debug_assert!(self.map.is_none());
+    // Mappings are page-aligned, which satisfies any `align`.
+    let mut map = MmapOptions::new().len(len).map_anon()?;
+    // Hugepages are only an optimization, so fall back to regular pages if
+    // the kernel rejects the advice.
+    #[cfg(target_os = "linux")]
+    {
+      self.hugepages = map.advise(memmap2::Advice::HugePage).is_ok();
+    }
+    let ptr = NonNull::from(&mut map[..]).cast();
+    self.map = Some(map);
+    Ok(ptr)
+  }
+
+  unsafe fn deallocate(&mut self, _ptr: NonNull<u8>, _len: usize) {
+    self.map = None;
+  }
+
+  fn hugepages(&self) -> bool {
+    self.hugepages
+  }
+
+  /// Populates the mapping's page tables for writing on Linux, which faults
+  /// in real pages without touching their contents, falling back to reading
+  /// each page on kernels older than 5.14.
+  unsafe fn prefault(&self, ptr: NonNull<u8>, len: usize) {
+    #[cfg(target_os = "linux")]
+    if let Some(map) = &self.map
+      && map.advise(memmap2::Advice::PopulateWrite).is_ok()
+    {
+      return;
+    }
+    unsafe { touch_pages(ptr, len) };
+  }
+}
+
+/// Allocates from the heap, aligned to `HUGEPAGE_SIZE` like a mapping would
+/// be.
+#[cfg(not(feature = "mmap"))]
+#[derive(Default)]
+pub struct HeapBackend {
+  alloc: Option<AlignedAlloc>,
+}
+
+// SAFETY: `AlignedAlloc::zeroed` allocates `len` zeroed bytes aligned to at
+// least `align`, which are owned by `self.alloc` until `deallocate`.
+#[cfg(not(feature = "mmap"))]
+unsafe impl TableBackend for HeapBackend {
+  fn allocate(&mut self, len: usize, align: usize) -> BarseResult<NonNull<u8>> {
+    debug_assert!(self.alloc.is_none());
+    let mut alloc = AlignedAlloc::zeroed(len, align.max(HUGEPAGE_SIZE))?;
+    let ptr = NonNull::from(&mut alloc[..]).cast();
+    self.alloc = Some(alloc);
+    Ok(ptr)
+  }
+
+  unsafe fn deallocate(&mut self, _ptr: NonNull<u8>, _len: usize) {
+    self.alloc = None;
+  }
+}
+
+/// The backend of tables which don't choose one.
+#[cfg(feature = "mmap")]
+pub type DefaultBackend = MmapBackend;
+/// The backend of tables which don't choose one.
+#[cfg(not(feature = "mmap"))]
+pub type DefaultBackend = HeapBackend;
@@ -1,8 +1,22 @@
 use crate::{
-  hugepage_backed_table::InPlaceInitializable, inline_string::InlineString,
-  temperature_reading::TemperatureReading, temperature_summary::TemperatureSummary, util::likely,
+  hugepage_backed_table::InPlaceInitializable,
+  inline_string::InlineString,
+  temperature_reading::TemperatureReading,
+  temperature_summary::TemperatureSummary,
+  util::{likely, unlikely},
 };
 
+/// How `Entry::find_or_claim` matched a station against an entry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyMatch {
+  /// The entry already held the station.
+  Found,
+  /// The entry was unused, and was claimed for the station.
+  Claimed,
+  /// The entry holds another station.
+  Other,
+}
+
 #[derive(Default, Clone)]
 pub struct Entry {
   key: InlineString,
@@ -10,36 +24,59 @@ pub struct Entry {
 }
 
 impl Entry {
-  fn initialize_key(&mut self, station: &str) {
-    self.key.initialize(station);
-  }
-
   pub fn add_reading(&mut self, reading: TemperatureReading) {
-    debug_assert!(!self.is_default());
     self.temp_summary.add_reading(reading);
   }
 
-  pub fn matches_key_or_initialize(&mut self, station: &str) -> bool {
-    if likely(self.key.eq_foreign_str(station)) {
-      true
-    } else if self.is_default() {
-      self.initialize_key(station);
-      true
+  pub fn merge(&mut self, summary: &TemperatureSummary) {
+    self.temp_summary.merge(summary);
+  }
+
+  /// Returns whether this entry holds `station`, claiming it for `station`
+  /// if it is unused. The summary is initialized when the entry is claimed,
+  /// so entries of lazily initialized tables may be left zeroed until then.
+  /// Panics without claiming the entry if `station` is too long, as
+  /// described in `InlineString::initialize_station`.
+  pub fn find_or_claim(&mut self, station: &str) -> KeyMatch {
+    if likely(self.key == station) {
+      // A zeroed key also matches the empty station name, without the entry
+      // having been claimed.
+      if unlikely(station.is_empty()) && self.is_default() {
+        self.temp_summary.initialize();
+        return KeyMatch::Claimed;
+      }
+      KeyMatch::Found
+    } else if !self.is_claimed() {
+      self.key.initialize_station(station);
+      self.temp_summary.initialize();
+      KeyMatch::Claimed
     } else {
-      false
+      KeyMatch::Other
     }
   }
 
+  /// Returns true if this entry has no readings, in which case it is left
+  /// out of iteration.
   pub fn is_default(&self) -> bool {
-    self.key.is_default()
+    self.temp_summary.count == 0
+  }
+
+  /// Returns true if this entry has been claimed by a station. Keys may be
+  /// claimed without adding a reading, so an entry is claimed if its key is
+  /// nonempty, or if it has a reading. The key alone can't be used for this,
+  /// since an empty station name is indistinguishable from an uninitialized
+  /// key.
+  fn is_claimed(&self) -> bool {
+    !self.key.is_empty() || !self.is_default()
   }
 
   pub fn to_iter_pair(&self) -> (&str, &TemperatureSummary) {
-    (self.key.value_str(), &self.temp_summary)
+    (self.key.as_str(), &self.temp_summary)
   }
 }
 
-impl InPlaceInitializable for Entry {
+// SAFETY: Both the key and summary of an entry are valid when zeroed.
+unsafe impl InPlaceInitializable for Entry {
   fn initialize(&mut self) {
     self.temp_summary.initialize();
   }
@@ -0,0 +1,82 @@
+use std::fmt::Display;
+
+#[cfg(feature = "diagnostics")]
+use crate::error::{BarseError, BarseResult};
+
+/// Statistics about the occupancy of a weather station table.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TableStats {
+  /// The number of buckets in the table.
+  pub capacity: usize,
+  /// The number of distinct stations stored in the table.
+  pub stations: usize,
+  /// The number of station lookups performed, i.e. the number of records
+  /// inserted into the table.
+  #[cfg(feature = "diagnostics")]
+  pub lookups: u64,
+  /// The total number of buckets examined across all lookups. Each lookup
+  /// examines at least one bucket.
+  #[cfg(feature = "diagnostics")]
+  pub probes: u64,
+}
+
+/// The memory reserved by a weather station table, which is allocated up front
+/// for every bucket whether or not it holds a station.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MemoryFootprint {
+  /// The number of buckets in the table.
+  pub capacity: usize,
+  /// The bytes of each bucket, including any padding between its station name
+  /// and summary.
+  pub entry_bytes: usize,
+  /// The bytes of the station name stored in each bucket.
+  pub key_bytes: usize,
+  /// The bytes of the temperature summary stored in each bucket.
+  pub summary_bytes: usize,
+  /// The total bytes mapped for the table. Each mapping is rounded up to a
+  /// multiple of the 2 MiB hugepage size, so this is at least `capacity *
+  /// entry_bytes`.
+  pub mapped_bytes: usize,
+}
+
+#[cfg(feature = "diagnostics")]
+impl TableStats {
+  /// The average number of buckets examined per lookup, which is 1 for a
+  /// table with no collisions.
+  pub fn average_probe_length(&self) -> f64 {
+    if self.lookups == 0 {
+      0.
+    } else {
+      self.probes as f64 / self.lookups as f64
+    }
+  }
+
+  /// Returns an error if the average probe length exceeds `threshold`, which
+  /// indicates that the station hash function is distributing the stations in
+  /// the input poorly.
+  pub fn warn_if_degraded(&self, threshold: f64) -> BarseResult {
+    let average_probe_length = self.average_probe_length();
+    if average_probe_length > threshold {
+      return Err(BarseError::new(format!(
+        "Average probe length {average_probe_length:.2} exceeds {threshold:.2} \
+           ({} stations in {} buckets)",
+        self.stations, self.capacity
+      )));
+    }
+    Ok(())
+  }
+}
+
+impl Display for TableStats {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    writeln!(f, "table capacity: {}", self.capacity)?;
+    write!(f, "table stations: {}", self.stations)?;
+    #[cfg(feature = "diagnostics")]
+    write!(
+      f,
+      "\naverage probe length: {:.3}",
+      self.average_probe_length()
+    )?;
+    Ok(())
+  }
+}
@@ -0,0 +1,256 @@
+use std::f64::consts::PI;
+
+use crate::temperature_reading::TemperatureReading;
+
+/// The compression used by `TDigestSummary::default`.
+pub const DEFAULT_COMPRESSION: f64 = 100.;
+
+/// A cluster of readings, represented by their mean.
+#[derive(Clone, Copy, Debug)]
+struct Centroid {
+  mean: f64,
+  weight: u64,
+}
+
+/// Estimates quantiles of a stream of temperature readings with a t-digest,
+/// which clusters readings into centroids that are small near the extremes
+/// and large near the median, so tail quantiles stay accurate in bounded
+/// memory. Digests from different threads can be merged like
+/// `TemperatureSummary`s.
+#[derive(Clone, Debug)]
+pub struct TDigestSummary {
+  /// Bounds the number of centroids, which is at most about `compression`.
+  /// Higher values trade memory for accuracy.
+  compression: f64,
+  /// Merged centroids, sorted by mean.
+  centroids: Vec<Centroid>,
+  /// Centroids which haven't been merged into `centroids` yet.
+  unmerged: Vec<Centroid>,
+  count: u64,
+  min: TemperatureReading,
+  max: TemperatureReading,
+}
+
+impl TDigestSummary {
+  /// Constructs an empty digest. `compression` must be at least 1.
+  pub fn new(compression: f64) -> Self {
+    assert!(compression >= 1., "Invalid compression {compression}");
+    Self {
+      compression,
+      centroids: Vec::new(),
+      unmerged: Vec::new(),
+      count: 0,
+      min: TemperatureReading::new(i16::MAX),
+      max: TemperatureReading::new(i16::MIN),
+    }
+  }
+
+  /// The number of readings added to the digest.
+  pub fn count(&self) -> u64 {
+    self.count
+  }
+
+  pub fn add_reading(&mut self, temp: TemperatureReading) {
+    self.min = self.min.min(temp);
+    self.max = self.max.max(temp);
+    self.count += 1;
+    self.add_unmerged(Centroid {
+      mean: temp.reading() as f64,
+      weight: 1,
+    });
+  }
+
+  pub fn merge(&mut self, other: &Self) {
+    self.min = self.min.min(other.min);
+    self.max = self.max.max(other.max);
+    self.count += other.count;
+    for &centroid in other.centroids.iter().chain(&other.unmerged) {
+      self.add_unmerged(centroid);
+    }
+  }
+
+  /// Returns the estimated reading at quantile `q`, which is clamped to
+  /// `[0, 1]`, or `None` if the digest is empty. Quantiles 0 and 1 are the
+  /// exact min and max.
+  pub fn quantile(&self, q: f64) -> Option<TemperatureReading> {
+    if self.count == 0 {
+      return None;
+    }
+    let centroids = if self.unmerged.is_empty() {
+      self.centroids.clone()
+    } else {
+      let mut digest = self.clone();
+      digest.compress();
+      digest.centroids
+    };
+
+    // Each centroid is treated as if its readings were spread evenly around
+    // its mean, with the mean at the centroid's midpoint rank. Ranks between
+    // midpoints are linearly interpolated.
+    let (min, max) = (self.min.reading() as f64, self.max.reading() as f64);
+    let rank = q.clamp(0., 1.) * self.count as f64;
+    let mut prev = (0., min);
+    let mut seen = 0.;
+    for centroid in &centroids {
+      let next = (seen + centroid.weight as f64 / 2., centroid.mean);
+      if rank < next.0 {
+        return Some(to_reading(interpolate(prev, next, rank)));
+      }
+      prev = next;
+      seen += centroid.weight as f64;
+    }
+    Some(to_reading(interpolate(prev, (seen, max), rank)))
+  }
+
+  fn add_unmerged(&mut self, centroid: Centroid) {
+    self.unmerged.push(centroid);
+    if self.unmerged.len() as f64 >= 4. * self.compression {
+      self.compress();
+    }
+  }
+
+  /// Merges `unmerged` into `centroids`, combining adjacent centroids as long
+  /// as they span no more than one unit of the scale function.
+  fn compress(&mut self) {
+    let mut centroids = std::mem::take(&mut self.centroids);
+    centroids.append(&mut self.unmerged);
+    centroids.sort_unstable_by(|a, b| a.mean.total_cmp(&b.mean));
+
+    let total = self.count as f64;
+    let mut merged = Vec::with_capacity(self.compression.ceil() as usize);
+    let mut centroids = centroids.into_iter();
+    let Some(mut current) = centroids.next() else {
+      return;
+    };
+    let mut weight_before = 0;
+    let mut weight_limit = total * self.next_quantile_limit(0.);
+    for centroid in centroids {
+      let weight = current.weight + centroid.weight;
+      if (weight_before + weight) as f64 <= weight_limit {
+        current.mean += (centroid.mean - current.mean) * centroid.weight as f64 / weight as f64;
+        current.weight = weight;
+      } else {
+        weight_before += current.weight;
+        weight_limit = total * self.next_quantile_limit(weight_before as f64 / total);
+        merged.push(current);
+        current = centroid;
+      }
+    }
+    merged.push(current);
+    self.centroids = merged;
+  }
+
+  /// Returns the quantile one unit of the scale function
+  /// `k(q) = compression / (2 pi) * asin(2q - 1)` past `q`.
+  fn next_quantile_limit(&self, q: f64) -> f64 {
+    let normalizer = self.compression / (2. * PI);
+    let k = normalizer * (2. * q - 1.).asin() + 1.;
+    if k >= self.compression / 4. {
+      1.
+    } else {
+      ((k / normalizer).sin() + 1.) / 2.
+    }
+  }
+}
+
+impl Default for TDigestSummary {
+  fn default() -> Self {
+    Self::new(DEFAULT_COMPRESSION)
+  }
+}
+
+fn interpolate((x0, y0): (f64, f64), (x1, y1): (f64, f64), x: f64) -> f64 {
+  if x1 <= x0 {
+    y1
+  } else {
+    y0 + (y1 - y0) * (x - x0) / (x1 - x0)
+  }
+}
+
+fn to_reading(reading: f64) -> TemperatureReading {
+  TemperatureReading::new(reading.round() as i16)
+}
+
+#[cfg(test)]
+mod tests {
+  use googletest::prelude::*;
+
+  use crate::{tdigest_summary::TDigestSummary, temperature_reading::TemperatureReading};
+
+  /// Returns `count` readings spread unevenly over the whole domain.
+  fn readings(seed: u64, count: usize) -> Vec<TemperatureReading> {
+    let mut state = seed;
+    (0..count)
+      .map(|_| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        // Summing two uniform values skews the readings toward the middle.
+        let reading = (state % 1000) as i16 + ((state >> 32) % 1000) as i16 - 999;
+        TemperatureReading::new(reading)
+      })
+      .collect()
+  }
+
+  /// Returns the fraction of `sorted` below `estimate`, and the fraction at or
+  /// below it.
+  fn rank_range(sorted: &[TemperatureReading], estimate: TemperatureReading) -> (f64, f64) {
+    let below = sorted.partition_point(|&reading| reading < estimate);
+    let at_or_below = sorted.partition_point(|&reading| reading <= estimate);
+    let len = sorted.len() as f64;
+    (below as f64 / len, at_or_below as f64 / len)
+  }
+
+  fn expect_quantiles_near(digest: &TDigestSummary, sorted: &[TemperatureReading]) {
+    for q in [0.5, 0.9, 0.99] {
+      let estimate = digest.quantile(q).unwrap();
+      let (low, high) = rank_range(sorted, estimate);
+      expect_that!(q, ge(low - 0.01), "q = {q}, estimate = {estimate}");
+      expect_that!(q, le(high + 0.01), "q = {q}, estimate = {estimate}");
+    }
+    expect_eq!(digest.quantile(0.), sorted.first().copied());
+    expect_eq!(digest.quantile(1.), sorted.last().copied());
+  }
+
+  #[gtest]
+  fn test_quantiles_match_sort() {
+    let readings = readings(0x7d1e5, 5_000);
+    let mut digest = TDigestSummary::default();
+    for &reading in &readings {
+      digest.add_reading(reading);
+    }
+
+    let mut sorted = readings;
+    sorted.sort();
+    expect_eq!(digest.count(), 5_000);
+    expect_quantiles_near(&digest, &sorted);
+  }
+
+  #[gtest]
+  fn test_merged_quantiles_match_sort() {
+    let readings = readings(0x3c0ffee, 8_000);
+    let mut digest = TDigestSummary::default();
+    for chunk in readings.chunks(1_000) {
+      let mut chunk_digest = TDigestSummary::default();
+      for &reading in chunk {
+        chunk_digest.add_reading(reading);
+      }
+      digest.merge(&chunk_digest);
+    }
+
+    let mut sorted = readings;
+    sorted.sort();
+    expect_eq!(digest.count(), 8_000);
+    expect_quantiles_near(&digest, &sorted);
+  }
+
+  #[gtest]
+  fn test_empty_and_single() {
+    let mut digest = TDigestSummary::default();
+    expect_eq!(digest.quantile(0.5), None);
+    digest.add_reading(TemperatureReading::new(-123));
+    for q in [0., 0.5, 1.] {
+      expect_eq!(digest.quantile(q), Some(TemperatureReading::new(-123)));
+    }
+  }
+}
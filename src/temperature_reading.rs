@@ -1,4 +1,6 @@
-use std::{fmt::Display, ptr::read_unaligned};
+use std::{collections::HashMap, fmt::Display, ptr::read_unaligned};
+
+use crate::util::unlikely;
 
 // Min and max possible temperature readings per the spec (-99.9 degrees to
 // 99.9 degrees).
@@ -19,9 +21,29 @@ const PARSE_MAGIC: u64 = 0xd6df3436fe286720;
 /// (e.g. X.X).
 pub const MIN_TEMP_READING_LEN: usize = 3;
 /// The highest number of bytes possible in a valid temperature string encoding
-/// (e.g. -XX.X).
+/// (e.g. -XX.X or +XX.X).
 pub const MAX_TEMP_READING_LEN: usize = 5;
 
+/// The character separating the integer and fractional parts of temperature
+/// readings in the input.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DecimalSeparator {
+  /// `12.3`, as in the reference input.
+  #[default]
+  Dot,
+  /// `12,3`, as in European-formatted data.
+  Comma,
+}
+
+impl DecimalSeparator {
+  pub const fn byte(self) -> u8 {
+    match self {
+      Self::Dot => b'.',
+      Self::Comma => b',',
+    }
+  }
+}
+
 /// Converts an integer encoding of a temperature reading to its string
 /// representation in the file.
 const fn int_val_to_str_encoding(val: i16) -> u64 {
@@ -69,7 +91,50 @@ const fn int_val_to_str_encoding(val: i16) -> u64 {
 /// Translates a temperature string value held in a u64 in little endian order
 /// to the index in the parse table.
 const fn parse_table_idx(float_string_encoding: u64) -> usize {
-  (float_string_encoding.wrapping_mul(PARSE_MAGIC) >> (u64::BITS - PARSE_TABLE_SHIFT)) as usize
+  parse_table_idx_with_magic(float_string_encoding, PARSE_MAGIC, PARSE_TABLE_SHIFT)
+}
+
+/// Like `parse_table_idx`, for a parse table of `1 << shift` entries indexed
+/// by `magic`.
+const fn parse_table_idx_with_magic(float_string_encoding: u64, magic: u64, shift: u32) -> usize {
+  (float_string_encoding.wrapping_mul(magic) >> (u64::BITS - shift)) as usize
+}
+
+/// Checks that `magic` maps every possible temperature reading to a unique
+/// index in a parse table of `1 << shift` entries, as `PARSE_MAGIC` does for
+/// `PARSE_TABLE_SHIFT`. Returns the pairs of readings which map to the same
+/// index otherwise, pairing each colliding reading with the lowest reading at
+/// its index. `shift` must be between 1 and 64.
+pub fn check_parse_magic(magic: u64, shift: u32) -> Result<(), Vec<(i16, i16)>> {
+  assert!((1..=u64::BITS).contains(&shift), "Invalid shift {shift}");
+  let mut table = HashMap::new();
+  let collisions: Vec<_> = (MIN_TEMP..=MAX_TEMP)
+    .filter_map(|val| {
+      let idx = parse_table_idx_with_magic(int_val_to_str_encoding(val), magic, shift);
+      let first = *table.entry(idx).or_insert(val);
+      (first != val).then_some((first, val))
+    })
+    .collect();
+  if collisions.is_empty() {
+    Ok(())
+  } else {
+    Err(collisions)
+  }
+}
+
+/// Replaces the ',' in the masked encoding of a temperature reading with a
+/// '.', so it can be looked up in the parse table.
+const fn comma_to_dot(encoding: u64) -> u64 {
+  const LSB: u64 = 0x0101_0101_0101_0101;
+  const MSB: u64 = 0x8080_8080_8080_8080;
+  // Bytes which were ',' are now zero. The usual zero-byte test can only
+  // misfire in bytes above a zero byte, and every byte following the ',' is
+  // either a digit, the newline, or masked-off zero, none of which are 0x01
+  // after the xor.
+  let commas = encoding ^ (b',' as u64 * LSB);
+  let comma_msbs = commas.wrapping_sub(LSB) & !commas & MSB;
+  // ',' (0x2c) and '.' (0x2e) only differ in bit 1.
+  encoding ^ (comma_msbs >> 6)
 }
 
 /// Builds a parse table which maps string encodings of temperatures to their
@@ -95,6 +160,12 @@ const PARSE_TABLE: [TemperatureReading; PARSE_TABLE_SIZE] = build_parse_table();
 /// Represents a temperature reading from the input file, ranging from -99.9 to
 /// 99.9 (2001 possible values).
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(
+  feature = "serde",
+  derive(serde::Serialize, serde::Deserialize),
+  serde(transparent)
+)]
+#[repr(transparent)]
 pub struct TemperatureReading {
   /// Fixed-point representation of the temperature reading, i.e. 10 *
   /// temperature reading.
@@ -108,10 +179,50 @@ impl TemperatureReading {
 
   /// Parses a temperature reading directly from the file buffer starting at
   /// `str_ptr`. Requires that the temperature reading is followed by a newline
-  /// character.
+  /// character. Positive readings may have an explicit '+' sign, e.g. `+12.3`.
   pub fn from_raw_ptr(str_ptr: *const u8) -> Self {
+    Self::from_raw_ptr_with_separator(str_ptr, DecimalSeparator::Dot)
+  }
+
+  /// Like `from_raw_ptr`, but for temperature readings using `separator` as
+  /// the decimal separator.
+  pub fn from_raw_ptr_with_separator(str_ptr: *const u8, separator: DecimalSeparator) -> Self {
     let encoding = unsafe { read_unaligned(str_ptr as *const u64) }.to_le();
-    Self::u64_encoding_to_self(encoding)
+    Self::u64_encoding_to_self(encoding, separator)
+  }
+
+  /// Like `from_raw_ptr_with_separator`, but reads the temperature reading
+  /// from the start of `bytes`, which must contain the following newline
+  /// character. Bytes past the end of `bytes` are treated as zero.
+  pub fn from_bytes_with_separator(bytes: &[u8], separator: DecimalSeparator) -> Self {
+    let mut encoding = [0; std::mem::size_of::<u64>()];
+    let len = bytes.len().min(encoding.len());
+    encoding[..len].copy_from_slice(&bytes[..len]);
+    Self::u64_encoding_to_self(u64::from_le_bytes(encoding), separator)
+  }
+
+  /// Parses `text`, which must be of the form `[-+]?\d?\d<separator>\d`,
+  /// optionally followed by ASCII whitespace, e.g. spaces or tabs, returning
+  /// `None` if it is malformed. Unlike `from_raw_ptr`, this does not require
+  /// the reading to be followed by a newline.
+  pub fn parse_checked(text: &[u8], separator: DecimalSeparator) -> Option<Self> {
+    let (negative, digits) = match text.trim_ascii_end() {
+      [b'-', digits @ ..] => (true, digits),
+      [b'+', digits @ ..] => (false, digits),
+      digits => (false, digits),
+    };
+    let (int, frac) = match digits {
+      [int @ .., sep, frac] if *sep == separator.byte() => (int, *frac),
+      _ => return None,
+    };
+    if !(1..=2).contains(&int.len()) || !int.iter().chain([&frac]).all(u8::is_ascii_digit) {
+      return None;
+    }
+    let reading = int
+      .iter()
+      .chain([&frac])
+      .fold(0, |reading, digit| reading * 10 + (digit - b'0') as i16);
+    Some(Self::new(if negative { -reading } else { reading }))
   }
 
   pub const fn reading(&self) -> i16 {
@@ -122,8 +233,19 @@ impl TemperatureReading {
   /// the file in little-endian order to a TemperatureReading. `encoding` is
   /// expected to contain a newline character (`b'\n'`) at some byte index
   /// 3 - 5, since temperature readings are always proceeded by a newline
-  /// character.
-  fn u64_encoding_to_self(encoding: u64) -> Self {
+  /// character. If the reading starts with an explicit '+' sign, the newline
+  /// is instead at byte index 4 - 6, i.e. 3 - 5 once the sign is skipped.
+  fn u64_encoding_to_self(encoding: u64, separator: DecimalSeparator) -> Self {
+    // The parse table only holds encodings without a '+' sign, so skip it.
+    // Shifting moves the newline of a reading like `+12.3` to byte index 4,
+    // and shifts zero into the top byte, which is masked off below. Explicit
+    // signs are rare, so this is a branch rather than a conditional move,
+    // keeping the shift off the path from the load to the table lookup.
+    let encoding = if unlikely(encoding as u8 == b'+') {
+      encoding >> 8
+    } else {
+      encoding
+    };
     let mask = if encoding.to_le_bytes()[3] == b'\n' {
       // If the character at index 3 in `encoding` is a newline, mask off byte
       // indices 4 - 7 since those may contain arbitrary values from the next
@@ -145,17 +267,27 @@ impl TemperatureReading {
     };
     // `val` is a unique integer value for each possible temperature reading.
     let val = encoding & mask;
+    let val = match separator {
+      DecimalSeparator::Dot => val,
+      DecimalSeparator::Comma => comma_to_dot(val),
+    };
 
     // Look up the parsed temperature reading from a precomputed lookup table.
-    unsafe { *PARSE_TABLE.get_unchecked(parse_table_idx(val)) }
+    let idx = parse_table_idx(val);
+    if cfg!(any(feature = "safe-fallback", miri)) {
+      PARSE_TABLE[idx]
+    } else {
+      unsafe { *PARSE_TABLE.get_unchecked(idx) }
+    }
   }
 }
 
 impl Display for TemperatureReading {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     let neg = if self.reading < 0 { "-" } else { "" };
-    let tens = self.reading.abs() / 10;
-    let ones = self.reading.abs() % 10;
+    // `unsigned_abs`, since `i16::MIN.abs()` overflows.
+    let tens = self.reading.unsigned_abs() / 10;
+    let ones = self.reading.unsigned_abs() % 10;
     write!(f, "{neg}{tens}.{ones}")
   }
 }
@@ -167,7 +299,9 @@ mod tests {
   use itertools::Itertools;
 
   use crate::temperature_reading::{
-    int_val_to_str_encoding, parse_table_idx, TemperatureReading, PARSE_TABLE,
+    check_parse_magic, int_val_to_str_encoding, parse_table_idx, parse_table_idx_with_magic,
+    DecimalSeparator, TemperatureReading, MAX_TEMP, MIN_TEMP, PARSE_MAGIC, PARSE_TABLE,
+    PARSE_TABLE_SHIFT,
   };
 
   fn int_val_to_str(val: i16) -> String {
@@ -217,6 +351,28 @@ mod tests {
     }
   }
 
+  #[test]
+  fn test_check_parse_magic() {
+    assert_eq!(check_parse_magic(PARSE_MAGIC, PARSE_TABLE_SHIFT), Ok(()));
+
+    // Too small a table must have collisions.
+    let collisions = check_parse_magic(PARSE_MAGIC, 10).unwrap_err();
+    assert!(collisions.len() >= (MIN_TEMP..=MAX_TEMP).len() - (1 << 10));
+    for (first, second) in collisions {
+      assert!(first < second, "{first} {second}");
+      assert_eq!(
+        parse_table_idx_with_magic(int_val_to_str_encoding(first), PARSE_MAGIC, 10),
+        parse_table_idx_with_magic(int_val_to_str_encoding(second), PARSE_MAGIC, 10)
+      );
+    }
+
+    // A magic of 1 keeps the high bits of the encodings, which are always
+    // zero, so every reading collides with the first.
+    let collisions = check_parse_magic(1, PARSE_TABLE_SHIFT).unwrap_err();
+    assert_eq!(collisions.len(), (MIN_TEMP..=MAX_TEMP).len() - 1);
+    assert!(collisions.iter().all(|&(first, _)| first == MIN_TEMP));
+  }
+
   #[test]
   fn test_parse() {
     for val in -999..=999 {
@@ -230,4 +386,93 @@ mod tests {
       );
     }
   }
+
+  #[test]
+  fn test_parse_comma() {
+    for val in -999..=999 {
+      let s = format!("{}\nab\n", int_val_to_str(val).replace('.', ","));
+      assert_eq!(
+        TemperatureReading::from_raw_ptr_with_separator(s.as_ptr(), DecimalSeparator::Comma),
+        TemperatureReading::new(val),
+        "Parsing {s}"
+      );
+    }
+  }
+
+  #[test]
+  fn test_parse_from_bytes() {
+    for val in -999..=999 {
+      let s = int_val_to_str(val);
+      for line in [format!("{s}\n"), format!("{s}\nab;1.0\n")] {
+        assert_eq!(
+          TemperatureReading::from_bytes_with_separator(line.as_bytes(), DecimalSeparator::Dot),
+          TemperatureReading::new(val),
+          "Parsing {line:?}"
+        );
+      }
+    }
+  }
+
+  #[test]
+  fn test_parse_explicit_plus_sign() {
+    for (text, val) in [("+0.0", 0), ("+9.9", 99), ("+99.9", 999)] {
+      let line = format!("{text}\nab;-1.0\n");
+      assert_eq!(
+        parse_temp_reading_magic(&line),
+        TemperatureReading::new(val),
+        "Parsing {text}"
+      );
+      assert_eq!(
+        TemperatureReading::from_bytes_with_separator(line.as_bytes(), DecimalSeparator::Dot),
+        TemperatureReading::new(val),
+        "Parsing {text}"
+      );
+      let comma_line = line.replace('.', ",");
+      assert_eq!(
+        TemperatureReading::from_raw_ptr_with_separator(
+          comma_line.as_ptr(),
+          DecimalSeparator::Comma
+        ),
+        TemperatureReading::new(val),
+        "Parsing {comma_line:?}"
+      );
+      assert_eq!(
+        TemperatureReading::parse_checked(text.as_bytes(), DecimalSeparator::Dot),
+        Some(TemperatureReading::new(val)),
+        "Parsing {text}"
+      );
+    }
+    for text in ["+-1.0", "-+1.0", "++1.0", "+", "+.0", "+123.4"] {
+      assert_eq!(
+        TemperatureReading::parse_checked(text.as_bytes(), DecimalSeparator::Dot),
+        None,
+        "Parsing {text:?}"
+      );
+    }
+  }
+
+  #[test]
+  fn test_parse_checked_trailing_whitespace() {
+    for text in ["12.3", "12.3 ", "12.3\t", "12.3 \t  ", "12.3\r"] {
+      assert_eq!(
+        TemperatureReading::parse_checked(text.as_bytes(), DecimalSeparator::Dot),
+        Some(TemperatureReading::new(123)),
+        "Parsing {text:?}"
+      );
+    }
+    for text in [" 12.3", "12 .3", "12. 3", "- 1.0", " ", ""] {
+      assert_eq!(
+        TemperatureReading::parse_checked(text.as_bytes(), DecimalSeparator::Dot),
+        None,
+        "Parsing {text:?}"
+      );
+    }
+  }
+
+  #[test]
+  fn test_display_extreme_readings() {
+    assert_eq!(TemperatureReading::new(i16::MAX).to_string(), "3276.7");
+    assert_eq!(TemperatureReading::new(i16::MIN).to_string(), "-3276.8");
+    assert_eq!(TemperatureReading::new(-9).to_string(), "-0.9");
+  }
 }
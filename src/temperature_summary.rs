@@ -1,11 +1,26 @@
-use crate::{hugepage_backed_table::InPlaceInitializable, temperature_reading::TemperatureReading};
+use std::fmt::Display;
 
-#[derive(Debug, Clone, Copy)]
+use crate::{
+  error::{BarseError, BarseResult},
+  hugepage_backed_table::InPlaceInitializable,
+  temperature_reading::TemperatureReading,
+};
+
+/// The fields are laid out as `min`, `max`, `count`, `total` in 16 bytes, so
+/// that summaries can be merged two at a time with AVX2.
+///
+/// Single-threaded and multithreaded builds share this type. Worker threads
+/// each fold readings into their own table of summaries rather than updating
+/// shared summaries atomically, so partial summaries from any builder are
+/// combined with `merge`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(C)]
 pub struct TemperatureSummary {
   pub min: TemperatureReading,
   pub max: TemperatureReading,
-  pub total: i64,
   pub count: u32,
+  pub total: i64,
 }
 
 impl TemperatureSummary {
@@ -17,10 +32,19 @@ impl TemperatureSummary {
     self.max
   }
 
+  /// Returns the mean reading, rounded to a tenth of a degree exactly as the
+  /// reference Java implementation of the challenge does: the mean is
+  /// computed in double precision, then rounded with ties toward positive
+  /// infinity. Since the division is inexact, some means which lie exactly
+  /// halfway between two tenths round down.
+  ///
+  /// The mean lies between the lowest and highest readings, so it fits in an
+  /// `i16` for any summary built from readings, even at the bounds of `i16`.
+  /// Summaries with an inconsistent `total` and `count` saturate instead.
   pub fn avg(&self) -> TemperatureReading {
-    let rounding_offset = self.count as i64 / 2;
-    let avg = (self.total + rounding_offset).div_euclid(self.count as i64);
-    debug_assert!((i16::MIN as i64..=i16::MAX as i64).contains(&avg));
+    debug_assert!(self.count > 0);
+    let mean = (self.total as f64 / 10.) / self.count as f64;
+    let avg = round_half_up(mean * 10.);
     TemperatureReading::new(avg as i16)
   }
 
@@ -31,15 +55,70 @@ impl TemperatureSummary {
     self.count += 1;
   }
 
+  /// Removes `temp`, which must have been added to this summary, e.g. to
+  /// correct historical data. The min and max can't be restored without
+  /// rescanning the readings, so removing a reading equal to either of them
+  /// fails and leaves the summary unchanged, unless it is the only reading.
+  /// Use `subtract_reading_unchecked` to remove it anyway.
+  pub fn remove_reading(&mut self, temp: TemperatureReading) -> BarseResult {
+    if self.count == 0 || temp < self.min || temp > self.max {
+      return Err(BarseError::new(format!(
+        "Cannot remove reading {temp}, which is not in the summary"
+      )));
+    }
+    if self.count == 1 {
+      *self = Self::default();
+      return Ok(());
+    }
+    if temp == self.min || temp == self.max {
+      return Err(BarseError::new(format!(
+        "Cannot remove reading {temp}, which is the min or max of the summary, without \
+         rescanning the readings"
+      )));
+    }
+    self.subtract_reading_unchecked(temp);
+    Ok(())
+  }
+
+  /// Removes `temp` from the total and count, leaving the min and max as they
+  /// were, even if `temp` was the only reading attaining them.
+  pub fn subtract_reading_unchecked(&mut self, temp: TemperatureReading) {
+    debug_assert!(self.count > 0);
+    self.total -= temp.reading() as i64;
+    self.count -= 1;
+  }
+
   pub fn merge(&mut self, other: &Self) {
     self.min = self.min.min(other.min);
     self.max = self.max.max(other.max);
     self.total += other.total;
     self.count += other.count;
   }
+
+  /// Merges all of `summaries` into one summary.
+  pub fn merge_all<'a>(summaries: impl IntoIterator<Item = &'a TemperatureSummary>) -> Self {
+    summaries
+      .into_iter()
+      .fold(Self::default(), |mut merged, summary| {
+        merged.merge(summary);
+        merged
+      })
+  }
 }
 
-impl InPlaceInitializable for TemperatureSummary {
+/// Rounds `x` to the nearest integer, with ties toward positive infinity, like
+/// Java's `Math.round`.
+pub(crate) fn round_half_up(x: f64) -> f64 {
+  let floor = x.floor();
+  if x - floor >= 0.5 {
+    floor + 1.
+  } else {
+    floor
+  }
+}
+
+// SAFETY: A `TemperatureSummary` is made of integers, for which zero is valid.
+unsafe impl InPlaceInitializable for TemperatureSummary {
   fn initialize(&mut self) {
     self.min = TemperatureReading::new(i16::MAX);
     self.max = TemperatureReading::new(i16::MIN);
@@ -59,11 +138,84 @@ impl Default for TemperatureSummary {
   }
 }
 
+/// Formats the summary as `min/avg/max`, each with one decimal, as in the
+/// output of the reference implementation of the challenge.
+impl Display for TemperatureSummary {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}/{}/{}", self.min(), self.avg(), self.max())
+  }
+}
+
 #[cfg(test)]
 mod tests {
   use googletest::prelude::*;
 
-  use crate::{temperature_reading::TemperatureReading, temperature_summary::TemperatureSummary};
+  use crate::{
+    error::BarseError, temperature_reading::TemperatureReading,
+    temperature_summary::TemperatureSummary,
+  };
+
+  fn summary(total: i64, count: u32) -> TemperatureSummary {
+    TemperatureSummary {
+      total,
+      count,
+      ..TemperatureSummary::default()
+    }
+  }
+
+  #[gtest]
+  fn test_avg_rounds_ties_toward_positive_infinity() {
+    expect_eq!(summary(293, 2).avg(), TemperatureReading::new(147));
+    expect_eq!(summary(-5, 2).avg(), TemperatureReading::new(-2));
+    expect_eq!(summary(-1997, 2).avg(), TemperatureReading::new(-998));
+    expect_eq!(summary(-1, 6).avg(), TemperatureReading::new(0));
+  }
+
+  #[gtest]
+  fn test_avg_matches_double_precision_reference() {
+    // -297.3 / 6 is exactly -49.55, but the nearest double is slightly below
+    // it, so the reference implementation rounds down.
+    expect_eq!(summary(-2973, 6).avg(), TemperatureReading::new(-496));
+  }
+
+  #[gtest]
+  fn test_display() {
+    let mut summary = TemperatureSummary::default();
+    for reading in [-53, 120, 4, 0] {
+      summary.add_reading(TemperatureReading::new(reading));
+    }
+    expect_eq!(summary.to_string(), "-5.3/1.8/12.0");
+  }
+
+  #[gtest]
+  fn test_extreme_readings() {
+    for reading in [i16::MIN, i16::MIN + 1, i16::MAX - 1, i16::MAX] {
+      let mut summary = TemperatureSummary::default();
+      for _ in 0..1_000_000 {
+        summary.add_reading(TemperatureReading::new(reading));
+      }
+      expect_eq!(summary.avg(), TemperatureReading::new(reading));
+    }
+
+    let mut summary = TemperatureSummary::default();
+    for _ in 0..1000 {
+      summary.add_reading(TemperatureReading::new(i16::MIN));
+      summary.add_reading(TemperatureReading::new(i16::MAX));
+    }
+    expect_eq!(summary.to_string(), "-3276.8/0.0/3276.7");
+  }
+
+  #[gtest]
+  fn test_avg_of_inconsistent_summary_saturates() {
+    expect_eq!(
+      summary(i64::MAX, 1).avg(),
+      TemperatureReading::new(i16::MAX)
+    );
+    expect_eq!(
+      summary(i64::MIN, 1).avg(),
+      TemperatureReading::new(i16::MIN)
+    );
+  }
 
   #[gtest]
   fn test_merge_default() {
@@ -129,4 +281,63 @@ mod tests {
       })
     );
   }
+
+  #[gtest]
+  fn test_add_then_remove_reading() {
+    let mut summary = TemperatureSummary::default();
+    for reading in [-100, 250, 30] {
+      summary.add_reading(TemperatureReading::new(reading));
+    }
+    let before = summary;
+
+    summary.add_reading(TemperatureReading::new(125));
+    expect_that!(summary.remove_reading(TemperatureReading::new(125)), ok(()));
+    expect_that!(
+      summary,
+      pat!(TemperatureSummary {
+        min: TemperatureReading::new(-100),
+        max: TemperatureReading::new(250),
+        total: before.total,
+        count: before.count,
+      })
+    );
+
+    // The min and max can't be removed, since the next smallest or largest
+    // reading is unknown.
+    for extreme in [-100, 250, 251] {
+      expect_true!(matches!(
+        summary.remove_reading(TemperatureReading::new(extreme)),
+        Err(BarseError::Other(_))
+      ));
+    }
+    expect_eq!((summary.total, summary.count), (before.total, before.count));
+
+    summary.subtract_reading_unchecked(TemperatureReading::new(250));
+    expect_that!(
+      summary,
+      pat!(TemperatureSummary {
+        min: TemperatureReading::new(-100),
+        max: TemperatureReading::new(250),
+        total: -70,
+        count: 2,
+      })
+    );
+  }
+
+  #[gtest]
+  fn test_remove_only_reading() {
+    let mut summary = TemperatureSummary::default();
+    summary.add_reading(TemperatureReading::new(42));
+    expect_that!(summary.remove_reading(TemperatureReading::new(42)), ok(()));
+    expect_that!(
+      summary,
+      pat!(TemperatureSummary {
+        min: TemperatureReading::new(i16::MAX),
+        max: TemperatureReading::new(i16::MIN),
+        total: 0,
+        count: 0,
+      })
+    );
+    expect_true!(summary.remove_reading(TemperatureReading::new(42)).is_err());
+  }
 }
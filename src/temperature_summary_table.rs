@@ -1,30 +1,63 @@
 use crate::{
-  error::BarseResult, hugepage_backed_table::HugepageBackedTable,
-  temperature_reading::TemperatureReading, temperature_summary::TemperatureSummary,
+  aggregator::{InPlaceInitializable, Merge},
+  error::BarseResult,
+  hugepage_backed_table::HugepageBackedTable,
+  temperature_reading::TemperatureReading,
+  temperature_summary::TemperatureSummary,
 };
 
-pub struct TemperatureSummaryTable<const SIZE: usize> {
-  table: HugepageBackedTable<TemperatureSummary, SIZE>,
+pub struct TemperatureSummaryTable<S = TemperatureSummary> {
+  table: HugepageBackedTable<S>,
 }
 
-impl<const SIZE: usize> TemperatureSummaryTable<SIZE> {
-  pub fn new() -> BarseResult<Self> {
+impl<S: Merge + InPlaceInitializable> TemperatureSummaryTable<S> {
+  pub fn new(capacity: usize) -> BarseResult<Self> {
     Ok(Self {
-      table: HugepageBackedTable::new()?,
+      table: HugepageBackedTable::new(capacity)?,
     })
   }
 
-  pub fn entry_at(&self, index: usize) -> &TemperatureSummary {
+  pub fn hugepages(&self) -> bool {
+    self.table.hugepages()
+  }
+
+  pub fn prefault(&self) {
+    self.table.prefault();
+  }
+
+  pub const fn mapped_len_for_capacity(capacity: usize) -> usize {
+    HugepageBackedTable::<S>::mapped_len_for_size(capacity)
+  }
+
+  pub fn mapped_len(&self) -> usize {
+    self.table.mapped_len()
+  }
+
+  pub fn entry_at(&self, index: usize) -> &S {
     self.table.entry_at(index)
   }
 
-  pub fn add_reading_at_index(&mut self, temp: TemperatureReading, index: usize) {
-    self.table.entry_at_mut(index).add_reading(temp);
+  pub fn entry_at_mut(&mut self, index: usize) -> &mut S {
+    self.table.entry_at_mut(index)
+  }
+
+  /// Returns the summary at `index` to the state `new` leaves it in.
+  pub fn reset_at(&mut self, index: usize) {
+    self.table.reset_at(index);
+  }
+
+  pub fn merge_at_index(&mut self, summary: &S, index: usize) {
+    self.table.entry_at_mut(index).merge(summary);
   }
 
   pub fn merge(&mut self, other: Self) {
-    for i in 0..SIZE {
-      self.table.entry_at_mut(i).merge(other.entry_at(i));
-    }
+    debug_assert_eq!(self.table.size(), other.table.size());
+    S::merge_slices(self.table.elements_mut(), other.table.elements());
+  }
+}
+
+impl TemperatureSummaryTable {
+  pub fn add_reading_at_index(&mut self, temp: TemperatureReading, index: usize) {
+    self.table.entry_at_mut(index).add_reading(temp);
   }
 }
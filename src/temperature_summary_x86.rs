@@ -0,0 +1,103 @@
+use std::arch::x86_64::{
+  __m256i, _mm256_add_epi32, _mm256_add_epi64, _mm256_blend_epi16, _mm256_blend_epi32,
+  _mm256_loadu_si256, _mm256_max_epi16, _mm256_min_epi16, _mm256_storeu_si256,
+};
+
+use crate::temperature_summary::TemperatureSummary;
+
+const _: () = assert!(std::mem::size_of::<TemperatureSummary>() == 16);
+
+/// Merges each of `src` into the summary at the same index of `dst`, two
+/// summaries per 256-bit vector.
+///
+/// Each 128-bit lane holds one summary, laid out as 16-bit `min` and `max`,
+/// a 32-bit `count` and a 64-bit `total`. The lanes are merged with a packed
+/// 16-bit min, 16-bit max, 32-bit add and 64-bit add, and the results are
+/// blended together so that each field comes from the operation matching its
+/// width.
+pub fn merge_summaries(dst: &mut [TemperatureSummary], src: &[TemperatureSummary]) {
+  debug_assert_eq!(dst.len(), src.len());
+  let mut dst_pairs = dst.chunks_exact_mut(2);
+  let mut src_pairs = src.chunks_exact(2);
+  for (dst_pair, src_pair) in (&mut dst_pairs).zip(&mut src_pairs) {
+    // SAFETY: Each pair spans 32 bytes, and unaligned loads and stores are
+    // used.
+    unsafe {
+      let merged = merge_pair(
+        _mm256_loadu_si256(dst_pair.as_ptr() as *const __m256i),
+        _mm256_loadu_si256(src_pair.as_ptr() as *const __m256i),
+      );
+      _mm256_storeu_si256(dst_pair.as_mut_ptr() as *mut __m256i, merged);
+    }
+  }
+  for (summary, other) in dst_pairs
+    .into_remainder()
+    .iter_mut()
+    .zip(src_pairs.remainder())
+  {
+    summary.merge(other);
+  }
+}
+
+/// Merges the two summaries in `a` with the two in `b`.
+fn merge_pair(a: __m256i, b: __m256i) -> __m256i {
+  // SAFETY: This module is only compiled with the avx2 target feature.
+  unsafe {
+    let totals = _mm256_add_epi64(a, b);
+    let counts = _mm256_add_epi32(a, b);
+    let mins = _mm256_min_epi16(a, b);
+    let maxs = _mm256_max_epi16(a, b);
+    // The blend masks select 32-bit and 16-bit elements within each 128-bit
+    // lane: `count` is the second 32-bit element, `min` and `max` the first
+    // two 16-bit elements.
+    let merged = _mm256_blend_epi32::<0b0010_0010>(totals, counts);
+    let merged = _mm256_blend_epi16::<0b0000_0001>(merged, mins);
+    _mm256_blend_epi16::<0b0000_0010>(merged, maxs)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use googletest::prelude::*;
+  use rand::{rngs::StdRng, Rng, SeedableRng};
+
+  use crate::{
+    temperature_reading::TemperatureReading, temperature_summary::TemperatureSummary,
+    temperature_summary_x86::merge_summaries,
+  };
+
+  fn random_summary(rng: &mut StdRng) -> TemperatureSummary {
+    // Some summaries are left empty, as most buckets of a table are.
+    if rng.random_bool(0.2) {
+      return TemperatureSummary::default();
+    }
+    let min = rng.random_range(-999..=999);
+    TemperatureSummary {
+      min: TemperatureReading::new(min),
+      max: TemperatureReading::new(rng.random_range(min..=999)),
+      count: rng.random_range(1..1 << 30),
+      total: rng.random_range(-(1 << 40)..1 << 40),
+    }
+  }
+
+  #[gtest]
+  fn test_matches_scalar_merge() {
+    let mut rng = StdRng::seed_from_u64(0x51d3e);
+    // Odd lengths leave a summary to merge past the last pair.
+    for len in [0, 1, 2, 7, 1000, 1001] {
+      let mut dst = (0..len)
+        .map(|_| random_summary(&mut rng))
+        .collect::<Vec<_>>();
+      let src = (0..len)
+        .map(|_| random_summary(&mut rng))
+        .collect::<Vec<_>>();
+
+      let mut expected = dst.clone();
+      for (summary, other) in expected.iter_mut().zip(&src) {
+        summary.merge(other);
+      }
+      merge_summaries(&mut dst, &src);
+      expect_eq!(dst, expected, "{len} summaries");
+    }
+  }
+}
@@ -1,128 +1,66 @@
-use std::{cmp::Ordering, collections::HashMap, fmt::Display};
-
-use crate::{test_util::random_input_file, util::HasIter};
+use crate::{
+  aligned::AlignedInput,
+  build_report::{BuildReport, Strategy},
+  options::Options,
+  reference::{weather_stations, TemperatureSummary, WeatherStation},
+  temperature_reading::DecimalSeparator,
+  test_util::{random_input_file, random_input_file_with_padding_byte},
+  util::HasIter,
+};
 use googletest::prelude::*;
 use itertools::Itertools;
 
 #[cfg(not(feature = "multithreaded"))]
-use crate::build_table::build_temperature_reading_table_from_bytes;
+use crate::build_table::{
+  build_temperature_reading_table_from_bytes,
+  build_temperature_reading_table_from_bytes_with_options,
+};
 #[cfg(feature = "multithreaded")]
-use crate::build_table_mt::build_temperature_reading_table_from_bytes;
-
-struct TemperatureSummary {
-  min: i32,
-  max: i32,
-  total: i64,
-  count: u32,
-}
-
-impl TemperatureSummary {
-  fn min(&self) -> f32 {
-    self.min as f32 / 10.0
-  }
-
-  fn max(&self) -> f32 {
-    self.max as f32 / 10.0
-  }
-
-  fn avg(&self) -> f32 {
-    let rounded_total = self.total + (self.count / 2) as i64;
-    rounded_total.div_euclid(self.count as i64) as f32 / 10.0
-  }
-
-  fn add_reading(&mut self, temp: f32) {
-    let temp = (temp * 10.0).round() as i32;
-    self.min = self.min.min(temp);
-    self.max = self.max.max(temp);
-    self.total += temp as i64;
-    self.count += 1;
-  }
-}
-
-impl Default for TemperatureSummary {
-  fn default() -> Self {
-    Self {
-      min: i32::MAX,
-      max: i32::MIN,
-      total: 0,
-      count: 0,
-    }
-  }
-}
+use crate::build_table_mt::{
+  build_temperature_reading_table_from_bytes,
+  build_temperature_reading_table_from_bytes_with_options,
+};
 
-pub struct WeatherStation {
-  name: String,
-  summary: TemperatureSummary,
+pub(crate) fn expected_temperature_reading_summaries(
+  input_bytes: &str,
+) -> impl Iterator<Item = String> {
+  expected_weather_stations(input_bytes).map(|station| format!("{station}"))
 }
 
-impl PartialEq for WeatherStation {
-  fn eq(&self, other: &Self) -> bool {
-    self.name.eq(&other.name)
-  }
+fn expected_weather_stations(input_bytes: &str) -> impl Iterator<Item = WeatherStation> {
+  weather_stations(input_bytes, false).unwrap().into_iter()
 }
 
-impl Eq for WeatherStation {}
-
-impl PartialOrd for WeatherStation {
-  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-    Some(self.cmp(other))
-  }
-}
-
-impl Ord for WeatherStation {
-  fn cmp(&self, other: &Self) -> Ordering {
-    self.name.cmp(&other.name)
-  }
-}
-
-impl Display for WeatherStation {
-  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-    write!(
-      f,
-      "{}={:.1}/{:.1}/{:.1}",
-      self.name,
-      self.summary.min(),
-      self.summary.avg(),
-      self.summary.max()
-    )
-  }
-}
-
-fn expected_temperature_reading_summaries(input_bytes: &str) -> impl Iterator<Item = String> {
-  input_bytes
-    .split('\n')
-    .filter(|line| !line.is_empty())
-    .fold(
-      HashMap::<String, TemperatureSummary>::new(),
-      |mut map, line| {
-        let (station, temp) = line
-          .split_once(';')
-          .unwrap_or_else(|| panic!("No ';' found in \"{line}\""));
-        map
-          .entry(station.to_owned())
-          .or_default()
-          .add_reading(temp.parse().unwrap());
-        map
-      },
-    )
-    .into_iter()
-    .map(|(station, summary)| WeatherStation {
-      name: station,
-      summary,
-    })
+pub(crate) fn barse_temperature_reading_summaries(
+  input: &AlignedInput,
+) -> impl Iterator<Item = String> {
+  build_temperature_reading_table_from_bytes(input.padded_slice(), input.len())
+    .unwrap()
+    .iter()
+    .map(|(station, summary)| crate::barse::WeatherStation::new(station, *summary))
     .sorted_unstable()
     .map(|station| format!("{station}"))
+    .collect_vec()
+    .into_iter()
 }
 
-fn barse_temperature_reading_summaries(input_bytes: &[u8]) -> impl Iterator<Item = String> {
-  build_temperature_reading_table_from_bytes(input_bytes)
-    .unwrap()
+pub(crate) fn barse_temperature_reading_summaries_with_options(
+  input: &AlignedInput,
+  options: &Options,
+) -> (Vec<String>, BuildReport) {
+  let (table, report) = build_temperature_reading_table_from_bytes_with_options(
+    input.padded_slice(),
+    input.len(),
+    options,
+  )
+  .unwrap();
+  let summaries = table
     .iter()
     .map(|(station, summary)| crate::barse::WeatherStation::new(station, *summary))
     .sorted_unstable()
     .map(|station| format!("{station}"))
-    .collect_vec()
-    .into_iter()
+    .collect_vec();
+  (summaries, report)
 }
 
 fn assert_equal_outputs<I1, I2>(iter1: I1, iter2: I2)
@@ -154,7 +92,7 @@ where
 fn test_fuzz_10_000_x_10() {
   let input = random_input_file(0x12312312, 10_000, 10).unwrap();
   assert_equal_outputs(
-    barse_temperature_reading_summaries(input.padded_slice()),
+    barse_temperature_reading_summaries(&input),
     expected_temperature_reading_summaries(str::from_utf8(input.exact_slice()).unwrap()),
   );
 }
@@ -163,7 +101,7 @@ fn test_fuzz_10_000_x_10() {
 fn test_fuzz_100_000_x_100() {
   let input = random_input_file(0x43f9e1, 100_000, 100).unwrap();
   assert_equal_outputs(
-    barse_temperature_reading_summaries(input.padded_slice()),
+    barse_temperature_reading_summaries(&input),
     expected_temperature_reading_summaries(str::from_utf8(input.exact_slice()).unwrap()),
   );
 }
@@ -173,7 +111,504 @@ fn test_fuzz_100_000_x_100() {
 fn test_fuzz_10_000_000_x_10_000() {
   let input = random_input_file(0x09f8eab1, 10_000_000, 10_000).unwrap();
   assert_equal_outputs(
-    barse_temperature_reading_summaries(input.padded_slice()),
+    barse_temperature_reading_summaries(&input),
+    expected_temperature_reading_summaries(str::from_utf8(input.exact_slice()).unwrap()),
+  );
+}
+
+#[gtest]
+fn test_fuzz_below_single_threaded_threshold() {
+  let input = random_input_file(0x7c2b05, 100_000, 100).unwrap();
+  let options = Options {
+    single_threaded_threshold: usize::MAX,
+    ..Options::default()
+  };
+  let (summaries, report) = barse_temperature_reading_summaries_with_options(&input, &options);
+  expect_eq!(report.strategy, Strategy::SingleThreaded);
+  expect_eq!(report.threads, 1);
+  assert_equal_outputs(
+    summaries,
+    expected_temperature_reading_summaries(str::from_utf8(input.exact_slice()).unwrap()),
+  );
+}
+
+#[gtest]
+fn test_fuzz_above_single_threaded_threshold() {
+  let input = random_input_file(0x7c2b05, 100_000, 100).unwrap();
+  let options = Options {
+    single_threaded_threshold: 0,
+    min_chunk_size: 1,
+    ..Options::default()
+  };
+  let (summaries, report) = barse_temperature_reading_summaries_with_options(&input, &options);
+  #[cfg(feature = "multithreaded")]
+  expect_eq!(report.strategy, Strategy::MultiThreaded);
+  #[cfg(not(feature = "multithreaded"))]
+  expect_eq!(report.strategy, Strategy::SingleThreaded);
+  assert_equal_outputs(
+    summaries,
     expected_temperature_reading_summaries(str::from_utf8(input.exact_slice()).unwrap()),
   );
 }
+
+#[gtest]
+fn test_single_threaded_threshold_sides_agree() {
+  let input = random_input_file(0x1d88f3, 100_000, 1_000).unwrap();
+  let (below, _) = barse_temperature_reading_summaries_with_options(
+    &input,
+    &Options {
+      single_threaded_threshold: usize::MAX,
+      ..Options::default()
+    },
+  );
+  let (above, _) = barse_temperature_reading_summaries_with_options(
+    &input,
+    &Options {
+      single_threaded_threshold: 0,
+      min_chunk_size: 1,
+      ..Options::default()
+    },
+  );
+  assert_equal_outputs(below, above);
+}
+
+#[cfg(feature = "multithreaded")]
+#[gtest]
+fn test_thread_count_capped_by_min_chunk_size() {
+  let input = random_input_file(0x3e1f, 10_000, 10).unwrap();
+  let (_, report) = barse_temperature_reading_summaries_with_options(
+    &input,
+    &Options {
+      single_threaded_threshold: 0,
+      min_chunk_size: input.len(),
+      ..Options::default()
+    },
+  );
+  expect_eq!(report.strategy, Strategy::MultiThreaded);
+  expect_eq!(report.threads, 1);
+}
+
+#[cfg(feature = "multithreaded")]
+#[gtest]
+fn test_fuzz_per_thread_tables() {
+  let input = random_input_file(0x2f7e61, 100_000, 1_000).unwrap();
+  let (summaries, report) = barse_temperature_reading_summaries_with_options(
+    &input,
+    &Options {
+      min_chunk_size: 1,
+      chunk_size: 4096,
+      threads: Some(4),
+      strategy: Some(Strategy::PerThreadTables),
+      ..Options::default()
+    },
+  );
+  expect_eq!(report.strategy, Strategy::PerThreadTables);
+  expect_eq!(report.threads, 4);
+  assert_equal_outputs(
+    summaries,
+    expected_temperature_reading_summaries(str::from_utf8(input.exact_slice()).unwrap()),
+  );
+}
+
+#[gtest]
+fn test_fuzz_nonzero_padding() {
+  for padding in [b'\n', b';', b'0'] {
+    let input = random_input_file_with_padding_byte(0x5e1c7, 10_000, 100, padding).unwrap();
+    assert_equal_outputs(
+      barse_temperature_reading_summaries(&input),
+      expected_temperature_reading_summaries(str::from_utf8(input.exact_slice()).unwrap()),
+    );
+  }
+}
+
+#[gtest]
+fn test_comma_decimal_separator_matches_dot() {
+  let input = random_input_file(0x2c2e, 100_000, 1_000).unwrap();
+  let text = str::from_utf8(input.exact_slice()).unwrap();
+  let comma_text = text
+    .lines()
+    .map(|line| {
+      let (station, temp) = line.rsplit_once(';').unwrap();
+      format!("{station};{}\n", temp.replace('.', ","))
+    })
+    .collect::<String>();
+  let comma_input = AlignedInput::new(&comma_text);
+
+  for single_threaded_threshold in [usize::MAX, 0] {
+    let (summaries, _) = barse_temperature_reading_summaries_with_options(
+      &comma_input,
+      &Options {
+        single_threaded_threshold,
+        min_chunk_size: 1,
+        decimal_separator: DecimalSeparator::Comma,
+        ..Options::default()
+      },
+    );
+    assert_equal_outputs(summaries, expected_temperature_reading_summaries(text));
+  }
+}
+
+#[gtest]
+fn test_report_counts_match_simple_parser() {
+  let input = random_input_file(0x2f0d6b, 100_000, 1_000).unwrap();
+  let text = str::from_utf8(input.exact_slice()).unwrap();
+  for single_threaded_threshold in [usize::MAX, 0] {
+    let (_, report) = barse_temperature_reading_summaries_with_options(
+      &input,
+      &Options {
+        single_threaded_threshold,
+        min_chunk_size: 1,
+        ..Options::default()
+      },
+    );
+    expect_eq!(report.bytes, input.len() as u64);
+    expect_eq!(report.records, text.lines().count() as u64);
+    expect_eq!(
+      report.unique_stations,
+      expected_temperature_reading_summaries(text).count()
+    );
+    expect_ge!(report.chunks, 1);
+  }
+}
+
+#[cfg(feature = "multithreaded")]
+#[gtest]
+fn test_streaming_partials_merge_to_final_table() {
+  use crate::{build_table_mt::build_streaming, temperature_summary::TemperatureSummary};
+  use std::{collections::HashMap, sync::Mutex};
+
+  let input = random_input_file(0x6a4d21, 300_000, 1_000).unwrap();
+  let merged = Mutex::new(HashMap::<String, TemperatureSummary>::new());
+  let chunks = Mutex::new(Vec::new());
+  let table = build_streaming(input.padded_slice(), input.len(), |partial| {
+    chunks
+      .lock()
+      .unwrap()
+      .push((partial.offset, partial.records));
+    let mut merged = merged.lock().unwrap();
+    for (station, summary) in partial.stations {
+      merged
+        .entry(station.to_owned())
+        .or_default()
+        .merge(&summary);
+    }
+  })
+  .unwrap();
+
+  let chunks = chunks.into_inner().unwrap();
+  expect_gt!(chunks.len(), 1);
+  expect_eq!(
+    chunks.iter().map(|(_, records)| records).sum::<u64>(),
+    str::from_utf8(input.exact_slice()).unwrap().lines().count() as u64
+  );
+
+  let merged = merged.into_inner().unwrap();
+  expect_eq!(merged.len(), table.iter().count());
+  for (station, summary) in table.iter() {
+    let partial = merged.get(station);
+    expect_that!(partial, some(anything()), "Missing station {station}");
+    if let Some(partial) = partial {
+      expect_eq!(
+        (partial.min, partial.max, partial.total, partial.count),
+        (summary.min, summary.max, summary.total, summary.count),
+        "Station {station}"
+      );
+    }
+  }
+}
+
+#[gtest]
+fn test_empty_station_name() {
+  let text = ";12.3\nHamburg;4.5\n;-6.7\nBulawayo;8.9\n;0.0\n";
+  let input = AlignedInput::new(text);
+  for single_threaded_threshold in [usize::MAX, 0] {
+    let (summaries, report) = barse_temperature_reading_summaries_with_options(
+      &input,
+      &Options {
+        single_threaded_threshold,
+        min_chunk_size: 1,
+        ..Options::default()
+      },
+    );
+    expect_eq!(report.unique_stations, 3);
+    assert_equal_outputs(summaries, expected_temperature_reading_summaries(text));
+  }
+}
+
+#[gtest]
+fn test_skip_header() {
+  let input = random_input_file(0x4ead3f, 10_000, 100).unwrap();
+  let text = str::from_utf8(input.exact_slice()).unwrap();
+  let with_header = AlignedInput::new(format!("station;temperature\n{text}"));
+  for single_threaded_threshold in [usize::MAX, 0] {
+    for strict in [false, true] {
+      let (summaries, report) = barse_temperature_reading_summaries_with_options(
+        &with_header,
+        &Options {
+          single_threaded_threshold,
+          min_chunk_size: 1,
+          chunk_size: 4096,
+          strict,
+          skip_header: true,
+          ..Options::default()
+        },
+      );
+      expect_eq!(report.records, text.lines().count() as u64);
+      assert_equal_outputs(summaries, expected_temperature_reading_summaries(text));
+    }
+  }
+}
+
+#[gtest]
+fn test_build_config_sizes_table_for_expected_stations() {
+  use crate::{options::BuildConfig, str_hash::TABLE_SIZE};
+
+  let input = random_input_file(0x5e1a7d, 100_000, 100).unwrap();
+  let text = str::from_utf8(input.exact_slice()).unwrap();
+  let config = BuildConfig {
+    expected_stations: Some(4 * TABLE_SIZE),
+    expected_bytes: Some(input.len() as u64),
+    threads: Some(4),
+  };
+  for single_threaded_threshold in [usize::MAX, 0] {
+    let options = Options {
+      single_threaded_threshold,
+      min_chunk_size: 1,
+      ..config.options()
+    };
+    let (table, _) = build_temperature_reading_table_from_bytes_with_options(
+      input.padded_slice(),
+      input.len(),
+      &options,
+    )
+    .unwrap();
+    let stats = table.stats();
+    expect_gt!(stats.capacity, TABLE_SIZE);
+    expect_eq!(
+      stats.stations,
+      expected_temperature_reading_summaries(text).count()
+    );
+
+    let summaries = table
+      .iter()
+      .map(|(station, summary)| crate::barse::WeatherStation::new(station, *summary))
+      .sorted_unstable()
+      .map(|station| format!("{station}"))
+      .collect_vec();
+    assert_equal_outputs(summaries, expected_temperature_reading_summaries(text));
+  }
+}
+
+#[cfg(feature = "diagnostics")]
+#[gtest]
+fn test_colliding_stations_trip_probe_length_warning() {
+  use crate::{error::BarseError, str_hash::str_hash_with_bits};
+
+  const HASH_BITS: u32 = 10;
+  const STATIONS: usize = 64;
+  const ROUNDS: usize = 16;
+
+  // Station names which all hash to the same bucket, so each new station has
+  // to probe past every station inserted before it.
+  let colliding = (0..)
+    .map(|i| format!("Station{i}"))
+    .filter(|name| str_hash_with_bits(name.as_bytes(), HASH_BITS) == 0)
+    .take(STATIONS)
+    .collect_vec();
+  let text: String = colliding
+    .iter()
+    .cycle()
+    .take(STATIONS * ROUNDS)
+    .map(|name| format!("{name};1.0\n"))
+    .collect();
+  let input = AlignedInput::new(&text);
+
+  for single_threaded_threshold in [usize::MAX, 0] {
+    let (table, _) = build_temperature_reading_table_from_bytes_with_options(
+      input.padded_slice(),
+      input.len(),
+      &Options {
+        single_threaded_threshold,
+        min_chunk_size: 1,
+        table_capacity: 1 << HASH_BITS,
+        ..Options::default()
+      },
+    )
+    .unwrap();
+    let stats = table.stats();
+    expect_eq!(stats.stations, STATIONS);
+    expect_eq!(stats.lookups, (STATIONS * ROUNDS) as u64);
+    expect_gt!(stats.average_probe_length(), 16.);
+    expect_true!(matches!(
+      stats.warn_if_degraded(4.),
+      Err(BarseError::Other(_))
+    ));
+  }
+
+  let input = random_input_file(0x3a4b9c, 100_000, 100).unwrap();
+  let table =
+    build_temperature_reading_table_from_bytes(input.padded_slice(), input.len()).unwrap();
+  let stats = table.stats();
+  expect_eq!(stats.lookups, 100_000);
+  expect_that!(stats.warn_if_degraded(4.), ok(anything()));
+}
+
+#[gtest]
+fn test_counts_from_merged_tables() {
+  use crate::output_format::OutputFormat;
+
+  let input = random_input_file(0x6d1c0a, 100_000, 100).unwrap();
+  let text = str::from_utf8(input.exact_slice()).unwrap();
+  let (table, report) = build_temperature_reading_table_from_bytes_with_options(
+    input.padded_slice(),
+    input.len(),
+    &Options {
+      single_threaded_threshold: 0,
+      min_chunk_size: 1,
+      chunk_size: 64 * 1024,
+      threads: Some(4),
+      ..Options::default()
+    },
+  )
+  .unwrap();
+  #[cfg(feature = "multithreaded")]
+  expect_eq!(report.strategy, Strategy::MultiThreaded);
+  #[cfg(not(feature = "multithreaded"))]
+  expect_eq!(report.strategy, Strategy::SingleThreaded);
+
+  let mut out = Vec::new();
+  OutputFormat::Brc
+    .write_stations(
+      table
+        .iter()
+        .map(|(station, summary)| crate::barse::WeatherStation::new(station, *summary))
+        .sorted_unstable(),
+      true,
+      &mut out,
+    )
+    .unwrap();
+
+  let expected = format!(
+    "{{{}}}\n",
+    expected_weather_stations(text)
+      .map(|station| format!("{station} ({})", station.summary.count))
+      .join(", ")
+  );
+  expect_eq!(str::from_utf8(&out).unwrap(), expected);
+}
+
+#[gtest]
+fn test_global_summary_matches_simple_parser() {
+  let input = random_input_file(0x2f8e11, 100_000, 1_000).unwrap();
+  let text = str::from_utf8(input.exact_slice()).unwrap();
+  let expected = text
+    .lines()
+    .map(|line| {
+      let (_, temp) = line.rsplit_once(';').unwrap();
+      (temp.parse::<f32>().unwrap() * 10.0).round() as i32
+    })
+    .fold(TemperatureSummary::default(), |mut summary, temp| {
+      summary.min = summary.min.min(temp);
+      summary.max = summary.max.max(temp);
+      summary.total += temp as i64;
+      summary.count += 1;
+      summary
+    });
+
+  for single_threaded_threshold in [usize::MAX, 0] {
+    let (table, _) = build_temperature_reading_table_from_bytes_with_options(
+      input.padded_slice(),
+      input.len(),
+      &Options {
+        single_threaded_threshold,
+        min_chunk_size: 1,
+        ..Options::default()
+      },
+    )
+    .unwrap();
+    let global = table.global_summary();
+    expect_eq!(
+      (
+        global.min().reading() as i32,
+        global.max().reading() as i32,
+        global.total,
+        global.count
+      ),
+      (expected.min, expected.max, expected.total, expected.count)
+    );
+    expect_eq!(
+      format!("{:.1}", global.avg().reading() as f64 / 10.0),
+      format!("{:.1}", expected.avg())
+    );
+  }
+}
+
+#[gtest]
+fn test_real_stations_sharing_hashed_prefix() {
+  use crate::{
+    generate::{default_stations, Generator},
+    str_hash::{check_prefix_collisions, str_hash_with_bits, HASH_BITS},
+  };
+
+  let stations = default_stations().unwrap();
+  let names = stations
+    .iter()
+    .map(|station| station.name.as_str())
+    .collect_vec();
+  // Every name sharing its hashed prefix with another, e.g. the dozen "São
+  // Sebastião ..." stations, which can only be told apart by probing.
+  let pairs = check_prefix_collisions(&names);
+  for (first, other) in &pairs {
+    expect_eq!(
+      str_hash_with_bits(first.as_bytes(), HASH_BITS),
+      str_hash_with_bits(other.as_bytes(), HASH_BITS)
+    );
+  }
+  let colliding = pairs
+    .into_iter()
+    .flat_map(|(first, other)| [first, other])
+    .unique()
+    .collect_vec();
+  expect_gt!(colliding.len(), 100);
+
+  let colliding_stations = stations
+    .iter()
+    .filter(|station| colliding.contains(&station.name))
+    .cloned()
+    .collect_vec();
+  let mut text = Vec::new();
+  Generator::new(&colliding_stations, colliding_stations.len(), 0x1b2c)
+    .unwrap()
+    .write(50_000, &mut text)
+    .unwrap();
+  // The official generator's most extreme readings.
+  for name in &colliding {
+    text.extend(format!("{name};-99.9\n{name};99.9\n").bytes());
+  }
+  let text = String::from_utf8(text).unwrap();
+  let input = AlignedInput::new(&text);
+
+  #[cfg(feature = "multithreaded")]
+  let strategies = [
+    Strategy::SingleThreaded,
+    Strategy::MultiThreaded,
+    Strategy::PerThreadTables,
+  ];
+  #[cfg(not(feature = "multithreaded"))]
+  let strategies = [Strategy::SingleThreaded];
+  for strategy in strategies {
+    let (summaries, report) = barse_temperature_reading_summaries_with_options(
+      &input,
+      &Options {
+        min_chunk_size: 1,
+        chunk_size: 4096,
+        threads: Some(4),
+        strategy: Some(strategy),
+        ..Options::default()
+      },
+    );
+    expect_eq!(report.strategy, strategy);
+    expect_eq!(summaries.len(), colliding.len());
+    assert_equal_outputs(summaries, expected_temperature_reading_summaries(&text));
+  }
+}
@@ -0,0 +1,299 @@
+//! Property-based differential tests, which build the table from generated
+//! inputs with adversarial structure and compare it with the simple parser.
+//! Failing inputs are shrunk by proptest, and the smallest failing input is
+//! written to a temporary file for debugging.
+
+use std::{fs, path::PathBuf};
+
+use googletest::prelude::{expect_eq, expect_true, gtest};
+use itertools::Itertools;
+use proptest::{collection::vec, prelude::*, test_runner::TestCaseError};
+
+use crate::{
+  aligned::AlignedInput,
+  hugepage_backed_table::HUGEPAGE_SIZE,
+  options::Options,
+  reference::weather_stations_with_extra_fields,
+  scanner::{ExtraFields, MAX_EXTRA_FIELDS_LEN, MAX_STATION_NAME_LEN},
+  test_against_simple_parser::barse_temperature_reading_summaries_with_options,
+};
+#[cfg(feature = "multithreaded")]
+use crate::{options::DEFAULT_CHUNK_SIZE, scanner::BUFFER_OVERLAP};
+
+/// The shortest record, with a one-byte name and a three-byte reading.
+const MIN_RECORD_LEN: usize = "a;0.0\n".len();
+/// The longest filler record, with a name of `MAX_STATION_NAME_LEN` bytes.
+const MAX_FILLER_RECORD_LEN: usize = MAX_STATION_NAME_LEN + MIN_RECORD_LEN - 1;
+
+/// An input of records of `stations`, given as indices into `stations` and
+/// readings in tenths of a degree. The `head` records are followed by filler
+/// records, which position the start of the `tail` records `back` bytes
+/// before a multiple of `boundary`, if it is nonzero.
+///
+/// The generated records are followed by each of `extra_fields` in turn, if
+/// any, which are skipped with `ExtraFields::Skip`.
+#[derive(Clone, Debug)]
+struct Case {
+  stations: Vec<String>,
+  head: Vec<(usize, i16)>,
+  tail: Vec<(usize, i16)>,
+  boundary: usize,
+  back: usize,
+  extra_fields: Vec<String>,
+  skip_extra_fields: bool,
+}
+
+impl Case {
+  fn text(&self) -> String {
+    let head = self.records(&self.head);
+    let filler = filler(self.filler_len(head.len()));
+    let tail = self.records(&self.tail);
+    format!("{head}{filler}{tail}")
+  }
+
+  fn records(&self, records: &[(usize, i16)]) -> String {
+    let extra_fields = self.extra_fields.iter().map(String::as_str);
+    records
+      .iter()
+      .zip(extra_fields.chain(std::iter::once("")).cycle())
+      .map(|(&(station, reading), extra_fields)| {
+        format!(
+          "{};{}{extra_fields}\n",
+          self.stations[station],
+          format_reading(reading)
+        )
+      })
+      .collect()
+  }
+
+  fn extra_fields_mode(&self) -> ExtraFields {
+    if self.skip_extra_fields {
+      ExtraFields::Skip
+    } else {
+      ExtraFields::Forbid
+    }
+  }
+
+  /// Returns the number of filler bytes needed after `head_len` bytes of
+  /// records for the tail to start `back` bytes before a boundary. Filler
+  /// records are at least `MIN_RECORD_LEN` bytes, so a boundary which would
+  /// need less filler than that is skipped.
+  fn filler_len(&self, head_len: usize) -> usize {
+    if self.boundary == 0 {
+      return 0;
+    }
+    let mut tail_start = self.boundary - self.back;
+    while tail_start < head_len || (1..MIN_RECORD_LEN).contains(&(tail_start - head_len)) {
+      tail_start += self.boundary;
+    }
+    tail_start - head_len
+  }
+}
+
+/// Formats `reading`, in tenths of a degree, as the input does.
+fn format_reading(reading: i16) -> String {
+  let sign = if reading < 0 { "-" } else { "" };
+  let reading = reading.unsigned_abs();
+  format!("{sign}{}.{}", reading / 10, reading % 10)
+}
+
+/// Returns `len` bytes of records, which must be zero or at least
+/// `MIN_RECORD_LEN`. The filler stations' names are made of 'z's, so they
+/// are distinct from the generated stations, but repeat throughout the
+/// filler.
+fn filler(mut len: usize) -> String {
+  debug_assert!(len == 0 || len >= MIN_RECORD_LEN);
+  let mut filler = String::with_capacity(len);
+  while len > 0 {
+    let record_len = if len <= MAX_FILLER_RECORD_LEN {
+      len
+    } else {
+      // Leave enough for at least one more record.
+      MAX_FILLER_RECORD_LEN.min(len - MIN_RECORD_LEN)
+    };
+    let name_len = record_len - (MIN_RECORD_LEN - 1);
+    filler.push_str(&"z".repeat(name_len));
+    filler.push_str(";0.0\n");
+    len -= record_len;
+  }
+  filler
+}
+
+/// Generates station names which are prefixes of one 50-byte name, over a
+/// two-letter alphabet, favoring the shortest and longest lengths.
+fn stations() -> impl Strategy<Value = Vec<String>> {
+  let name = vec(prop_oneof![Just('a'), Just('b')], MAX_STATION_NAME_LEN)
+    .prop_map(|chars| chars.into_iter().collect::<String>());
+  let lengths = vec(
+    prop_oneof![
+      Just(1),
+      Just(MAX_STATION_NAME_LEN - 1),
+      Just(MAX_STATION_NAME_LEN),
+      1..=MAX_STATION_NAME_LEN,
+    ],
+    1..8,
+  );
+  (name, lengths).prop_map(|(name, lengths)| {
+    lengths
+      .into_iter()
+      .map(|len| name[..len].to_owned())
+      .unique()
+      .collect()
+  })
+}
+
+/// Generates the fields following a reading, including their leading
+/// semicolons, favoring empty fields and the longest allowed.
+fn extra_fields() -> impl Strategy<Value = String> {
+  prop_oneof![
+    Just(";".to_owned()),
+    Just(";55.3".to_owned()),
+    Just(format!(";{}", "x".repeat(MAX_EXTRA_FIELDS_LEN - 1))),
+    "(;[a-z0-9.]{0,15}){1,4}",
+  ]
+}
+
+/// Generates readings in tenths of a degree, favoring the extremes and zero.
+fn reading() -> impl Strategy<Value = i16> {
+  prop_oneof![Just(-999), Just(0), Just(999), -999..=999i16]
+}
+
+fn case() -> impl Strategy<Value = Case> {
+  let boundary = prop_oneof![
+    2 => Just(0),
+    3 => Just(64),
+    1 => Just(128),
+    1 => Just(HUGEPAGE_SIZE),
+  ];
+  // Records only have extra fields if they are skipped.
+  let extra_fields = prop_oneof![
+    2 => Just((Vec::new(), false)),
+    1 => Just((Vec::new(), true)),
+    2 => vec(extra_fields(), 1..4).prop_map(|fields| (fields, true)),
+  ];
+  (
+    stations(),
+    boundary,
+    0..=MAX_FILLER_RECORD_LEN,
+    extra_fields,
+  )
+    .prop_flat_map(
+      |(stations, boundary, back, (extra_fields, skip_extra_fields))| {
+        // Stations are drawn at random, so duplicates are interleaved
+        // throughout both the head and the tail.
+        let records = || vec((0..stations.len(), reading()), 0..100);
+        (records(), records()).prop_map(move |(head, tail)| Case {
+          stations: stations.clone(),
+          head,
+          tail,
+          boundary,
+          back,
+          extra_fields: extra_fields.clone(),
+          skip_extra_fields,
+        })
+      },
+    )
+}
+
+/// Writes `text` to a temporary file, returning its path.
+fn write_failing_input(text: &str) -> PathBuf {
+  let path = std::env::temp_dir().join(format!(
+    "barse_{}_differential_failure.txt",
+    std::process::id()
+  ));
+  fs::write(&path, text).expect("Failed to write the failing input");
+  path
+}
+
+/// Inputs this short are built on the calling thread by default, so the
+/// multithreaded build is forced, with chunks at the hugepage boundaries and
+/// with chunks only a few batches long.
+#[cfg(feature = "multithreaded")]
+fn multithreaded_builds(input: &AlignedInput, options: &Options) -> Vec<(String, Vec<String>)> {
+  [DEFAULT_CHUNK_SIZE, 4 * BUFFER_OVERLAP]
+    .into_iter()
+    .map(|chunk_size| {
+      let options = Options {
+        single_threaded_threshold: 0,
+        min_chunk_size: 1,
+        chunk_size,
+        ..options.clone()
+      };
+      let (summaries, _) = barse_temperature_reading_summaries_with_options(input, &options);
+      (format!("{chunk_size}-byte chunks"), summaries)
+    })
+    .collect()
+}
+
+#[cfg(not(feature = "multithreaded"))]
+fn multithreaded_builds(_input: &AlignedInput, _options: &Options) -> Vec<(String, Vec<String>)> {
+  Vec::new()
+}
+
+fn check_case(case: &Case) -> Result<(), TestCaseError> {
+  let text = case.text();
+  let input = AlignedInput::new(&text);
+  let extra_fields = case.extra_fields_mode();
+  let expected = weather_stations_with_extra_fields(&text, false, extra_fields)
+    .expect("Generated inputs are well-formed")
+    .into_iter()
+    .map(|station| format!("{station}"))
+    .collect_vec();
+
+  let options = Options {
+    extra_fields,
+    ..Options::default()
+  };
+  let strict_options = Options {
+    strict: true,
+    ..options.clone()
+  };
+  let builds = [
+    (
+      "default".to_owned(),
+      barse_temperature_reading_summaries_with_options(&input, &options).0,
+    ),
+    (
+      "strict".to_owned(),
+      barse_temperature_reading_summaries_with_options(&input, &strict_options).0,
+    ),
+  ]
+  .into_iter()
+  .chain(multithreaded_builds(&input, &options));
+  for (build, summaries) in builds {
+    if summaries != expected {
+      let path = write_failing_input(&text);
+      return Err(TestCaseError::fail(format!(
+        "The {build} build differs from the simple parser on the input written to {}",
+        path.display()
+      )));
+    }
+  }
+  Ok(())
+}
+
+proptest! {
+  #![proptest_config(ProptestConfig::with_cases(64))]
+
+  #[test]
+  fn test_differential_against_simple_parser(case in case()) {
+    check_case(&case)?;
+  }
+}
+
+#[gtest]
+fn test_filler_positions_tail_before_boundary() {
+  let case = Case {
+    stations: vec!["a".repeat(MAX_STATION_NAME_LEN)],
+    head: vec![(0, -999); 3],
+    tail: vec![(0, 999)],
+    boundary: 64,
+    back: 7,
+    extra_fields: Vec::new(),
+    skip_extra_fields: false,
+  };
+  let text = case.text();
+  let tail = format!("{};99.9\n", case.stations[0]);
+  expect_true!(text.ends_with(&tail));
+  expect_eq!((text.len() - tail.len() + case.back) % case.boundary, 0);
+}
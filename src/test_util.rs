@@ -1,83 +1,149 @@
+use std::sync::OnceLock;
+#[cfg(feature = "tracing")]
 use std::{
-  alloc::{alloc, dealloc, Layout},
-  slice,
+  collections::HashMap,
+  sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex,
+  },
 };
 
-use brc::build_input::{get_weather_stations, output_lines};
-use rand::{rngs::StdRng, SeedableRng};
-
 use crate::{
-  error::BarseResult, scanner::SCANNER_CACHE_SIZE, temperature_reading::TemperatureReading,
+  aligned::AlignedInput,
+  error::BarseResult,
+  generate::{default_stations, Generator, Station},
+  temperature_reading::TemperatureReading,
 };
 
-const ALIGNMENT: usize = SCANNER_CACHE_SIZE;
-
 #[repr(align(32))]
 pub struct AlignedBuffer<const N: usize> {
   pub buffer: [u8; N],
 }
 
-pub struct AlignedInput {
-  bytes: *mut u8,
-  len: usize,
-}
-impl AlignedInput {
-  pub fn new(src: &str) -> Self {
-    let len = src.len().next_multiple_of(ALIGNMENT);
-    let layout = Layout::from_size_align(len, ALIGNMENT).unwrap();
-    let bytes = unsafe { alloc(layout) };
-    unsafe {
-      libc::memset(bytes as *mut libc::c_void, 0, len);
-      bytes.copy_from(src.as_bytes().as_ptr(), src.len());
-    }
-    Self {
-      bytes,
-      len: src.len(),
-    }
-  }
-
-  pub fn exact_slice(&self) -> &[u8] {
-    unsafe { slice::from_raw_parts(self.bytes, self.len) }
-  }
-
-  pub fn padded_slice(&self) -> &[u8] {
-    unsafe { slice::from_raw_parts(self.bytes, self.len.next_multiple_of(SCANNER_CACHE_SIZE)) }
-  }
-}
-impl Drop for AlignedInput {
-  fn drop(&mut self) {
-    let layout = Layout::from_size_align(self.len, ALIGNMENT).unwrap();
-    unsafe {
-      dealloc(self.bytes, layout);
-    }
-  }
-}
-
 pub fn random_input_file(
   seed: u64,
   records: u64,
   unique_stations: u32,
 ) -> BarseResult<AlignedInput> {
-  const WEATHER_STATIONS_PATH: &str = "data/weather_stations.csv";
+  random_input_file_with_padding_byte(seed, records, unique_stations, 0)
+}
 
-  let mut rng = StdRng::seed_from_u64(seed);
-  let stations = get_weather_stations(WEATHER_STATIONS_PATH).unwrap();
+pub fn random_input_file_with_padding_byte(
+  seed: u64,
+  records: u64,
+  unique_stations: u32,
+  padding: u8,
+) -> BarseResult<AlignedInput> {
+  static STATIONS: OnceLock<Vec<Station>> = OnceLock::new();
+  let stations = STATIONS.get_or_init(|| default_stations().unwrap());
 
-  Ok(AlignedInput::new(
-    &output_lines(&stations, records, unique_stations, &mut rng)?
-      .collect::<std::result::Result<Vec<_>, _>>()?
-      .join(""),
-  ))
+  let mut output = Vec::new();
+  Generator::new(stations, unique_stations as usize, seed)?.write(records, &mut output)?;
+  Ok(AlignedInput::new_with_padding_byte(output, padding))
 }
 
 pub fn simple_scanner_iter(buffer: &[u8]) -> impl Iterator<Item = (&str, TemperatureReading)> {
   str::from_utf8(buffer)
     .unwrap()
     .split('\n')
-    .filter(|line| !line.is_empty() && !line.starts_with(0 as char))
+    .filter(|line| !line.is_empty())
     .map(|line| {
       let (station, temp) = line.split_once(';').unwrap();
       let temp = (temp.parse::<f32>().unwrap() * 10.).round() as i16;
       (station, TemperatureReading::new(temp))
     })
 }
+
+/// The name and fields of a span or event seen by a `CapturingSubscriber`.
+#[cfg(feature = "tracing")]
+#[derive(Clone, Debug)]
+pub struct TraceRecord {
+  /// The name of the span, or the message of the event.
+  pub name: String,
+  pub is_span: bool,
+  /// The value of each field, formatted with `Debug`, or verbatim for
+  /// strings.
+  pub fields: HashMap<String, String>,
+}
+
+/// A `tracing` subscriber which records every span and event on the threads
+/// it is the default subscriber of.
+#[cfg(feature = "tracing")]
+#[derive(Clone, Default)]
+pub struct CapturingSubscriber {
+  records: Arc<Mutex<Vec<TraceRecord>>>,
+  next_span_id: Arc<AtomicU64>,
+}
+
+#[cfg(feature = "tracing")]
+impl CapturingSubscriber {
+  pub fn spans(&self, name: &str) -> Vec<TraceRecord> {
+    self.records_matching(name, true)
+  }
+
+  pub fn events(&self, message: &str) -> Vec<TraceRecord> {
+    self.records_matching(message, false)
+  }
+
+  fn records_matching(&self, name: &str, is_span: bool) -> Vec<TraceRecord> {
+    self
+      .records
+      .lock()
+      .unwrap()
+      .iter()
+      .filter(|record| record.name == name && record.is_span == is_span)
+      .cloned()
+      .collect()
+  }
+
+  fn push(&self, name: &str, is_span: bool, record: impl FnOnce(&mut FieldVisitor<'_>)) {
+    let mut fields = HashMap::new();
+    record(&mut FieldVisitor(&mut fields));
+    let name = fields.remove("message").unwrap_or_else(|| name.to_owned());
+    self.records.lock().unwrap().push(TraceRecord {
+      name,
+      is_span,
+      fields,
+    });
+  }
+}
+
+#[cfg(feature = "tracing")]
+impl tracing::Subscriber for CapturingSubscriber {
+  fn enabled(&self, _metadata: &tracing::Metadata<'_>) -> bool {
+    true
+  }
+
+  fn new_span(&self, span: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+    self.push(span.metadata().name(), true, |visitor| span.record(visitor));
+    tracing::span::Id::from_u64(self.next_span_id.fetch_add(1, Ordering::Relaxed) + 1)
+  }
+
+  fn record(&self, _span: &tracing::span::Id, _values: &tracing::span::Record<'_>) {}
+
+  fn record_follows_from(&self, _span: &tracing::span::Id, _follows: &tracing::span::Id) {}
+
+  fn event(&self, event: &tracing::Event<'_>) {
+    self.push(event.metadata().name(), false, |visitor| {
+      event.record(visitor)
+    });
+  }
+
+  fn enter(&self, _span: &tracing::span::Id) {}
+
+  fn exit(&self, _span: &tracing::span::Id) {}
+}
+
+#[cfg(feature = "tracing")]
+struct FieldVisitor<'a>(&'a mut HashMap<String, String>);
+
+#[cfg(feature = "tracing")]
+impl tracing::field::Visit for FieldVisitor<'_> {
+  fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+    self.0.insert(field.name().to_owned(), value.to_owned());
+  }
+
+  fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+    self.0.insert(field.name().to_owned(), format!("{value:?}"));
+  }
+}
@@ -1,3 +1,5 @@
+use std::cmp::Ordering;
+
 #[inline(always)]
 #[cold]
 fn cold_path() {}
@@ -22,11 +24,108 @@ pub fn unlikely(b: bool) -> bool {
   }
 }
 
+/// Hints to the CPU that the cache line holding `value` will be read soon.
+#[inline(always)]
+pub fn prefetch<T>(value: &T) {
+  #[cfg(target_arch = "x86_64")]
+  unsafe {
+    use std::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+    _mm_prefetch::<_MM_HINT_T0>(std::ptr::from_ref(value).cast());
+  }
+  #[cfg(not(target_arch = "x86_64"))]
+  let _ = value;
+}
+
+/// The smallest page size of the target. Every real page boundary is a
+/// multiple of it, so checking against a smaller size than the real one is
+/// safe, just slower. Apple silicon only has 16 KiB pages.
+#[cfg(all(target_vendor = "apple", target_arch = "aarch64"))]
+pub const PAGE_SIZE: usize = 16 * 1024;
+/// The smallest page size of the target. Every real page boundary is a
+/// multiple of it, so checking against a smaller size than the real one is
+/// safe, just slower.
+#[cfg(not(all(target_vendor = "apple", target_arch = "aarch64")))]
+pub const PAGE_SIZE: usize = 4096;
+
+#[cfg(any(target_feature = "avx2", not(any(feature = "safe-fallback", miri))))]
 pub fn unaligned_read_would_cross_page_boundary<T>(start_ptr: *const u8) -> bool {
-  const PAGE_SIZE: usize = 4096;
   (start_ptr as usize) % PAGE_SIZE > PAGE_SIZE - std::mem::size_of::<T>()
 }
 
+/// Copies `src` to the start of `dst` without calling libc's `memcpy`, whose
+/// call overhead dominates copies as short as station names. Panics if `src`
+/// is longer than `dst`.
+///
+/// `src` is copied in 8-byte words, the last of which overlaps the words
+/// before it, and strings shorter than a word are copied with two
+/// overlapping 4-, 2- or 1-byte copies. Only the first `src.len()` bytes of
+/// `dst` are written.
+#[inline(always)]
+pub fn copy_no_libc<const N: usize>(dst: &mut [u8; N], src: &[u8]) {
+  assert!(src.len() <= N);
+  let len = src.len();
+  let (src, dst) = (src.as_ptr(), dst.as_mut_ptr());
+  unsafe {
+    if len >= 8 {
+      // The loop runs a constant number of times, with each copy guarded by
+      // its own branch. LLVM replaces a loop of copies bounded by `len` with
+      // a call to `memcpy`.
+      for word in 0..N / 8 {
+        if word < len / 8 {
+          copy_word::<u64>(src, dst, word * 8);
+        }
+      }
+      copy_word::<u64>(src, dst, len - 8);
+    } else if len >= 4 {
+      copy_word::<u32>(src, dst, 0);
+      copy_word::<u32>(src, dst, len - 4);
+    } else if len >= 2 {
+      copy_word::<u16>(src, dst, 0);
+      copy_word::<u16>(src, dst, len - 2);
+    } else if len == 1 {
+      copy_word::<u8>(src, dst, 0);
+    }
+  }
+}
+
+/// Copies the `T` at byte `offset` of `src` to byte `offset` of `dst`.
+///
+/// # Safety
+/// `src` and `dst` must be valid for `offset + size_of::<T>()` bytes.
+#[inline(always)]
+unsafe fn copy_word<T>(src: *const u8, dst: *mut u8, offset: usize) {
+  unsafe {
+    let word = src.add(offset).cast::<T>().read_unaligned();
+    dst.add(offset).cast::<T>().write_unaligned(word);
+  }
+}
+
+/// Compares strings by their UTF-16 code units, like Java's
+/// `String::compareTo`. This agrees with byte order, except that characters
+/// outside the Basic Multilingual Plane, which are encoded as surrogate pairs
+/// in UTF-16, order before characters in `U+E000..=U+FFFF`.
+pub fn utf16_cmp(a: &str, b: &str) -> Ordering {
+  let (a_bytes, b_bytes) = (a.as_bytes(), b.as_bytes());
+  match a_bytes.iter().zip(b_bytes).position(|(a, b)| a != b) {
+    None => a_bytes.len().cmp(&b_bytes.len()),
+    Some(idx) => {
+      // The strings are equal before `idx`, so the bytes at `idx` either both
+      // begin a character, or both continue characters with the same leading
+      // bytes, which order the same way in UTF-8 and UTF-16. The orders only
+      // differ between a 4-byte sequence, starting with 0xf0..=0xf4, and a
+      // 3-byte sequence of `U+E000..=U+FFFF`, starting with 0xee or 0xef.
+      let (a, b) = (a_bytes[idx], b_bytes[idx]);
+      let is_supplementary = |byte: u8| byte >= 0xf0;
+      let is_high_bmp = |byte: u8| byte == 0xee || byte == 0xef;
+      if (is_supplementary(a) && is_high_bmp(b)) || (is_high_bmp(a) && is_supplementary(b)) {
+        b.cmp(&a)
+      } else {
+        a.cmp(&b)
+      }
+    }
+  }
+}
+
 pub trait HasIter<'a> {
   type Item: 'a;
 
@@ -50,3 +149,96 @@ impl BitVector for u64 {
     offset
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use std::cmp::Ordering;
+
+  use googletest::prelude::*;
+  use itertools::Itertools;
+
+  use crate::util::{copy_no_libc, utf16_cmp};
+
+  #[cfg(any(target_feature = "avx2", not(any(feature = "safe-fallback", miri))))]
+  fn expect_reads_stop_at_page_end<T>() {
+    use crate::util::unaligned_read_would_cross_page_boundary;
+
+    // 16 KiB is a multiple of the page size of every target, so it is always
+    // the end of a page.
+    const PAGE_END: usize = 16 * 1024;
+    let size = std::mem::size_of::<T>();
+    for offset in 1..=size {
+      let ptr = std::ptr::without_provenance::<u8>(PAGE_END - offset);
+      expect_eq!(
+        unaligned_read_would_cross_page_boundary::<T>(ptr),
+        offset < size,
+        "{size}-byte read {offset} bytes before the end of the page"
+      );
+    }
+    expect_false!(unaligned_read_would_cross_page_boundary::<T>(
+      std::ptr::without_provenance(PAGE_END)
+    ));
+  }
+
+  #[cfg(any(target_feature = "avx2", not(any(feature = "safe-fallback", miri))))]
+  #[gtest]
+  fn test_reads_crossing_16k_page_end() {
+    expect_reads_stop_at_page_end::<u64>();
+    expect_reads_stop_at_page_end::<u128>();
+    expect_reads_stop_at_page_end::<[u8; 32]>();
+  }
+
+  #[gtest]
+  fn test_utf16_cmp_matches_utf16_encoding() {
+    let strings = [
+      "",
+      "A",
+      "Ab",
+      "B",
+      "a",
+      "\u{7f}",
+      "\u{80}",
+      "\u{7ff}",
+      "\u{800}",
+      "\u{d7ff}",
+      "\u{e000}",
+      "\u{ff21}",
+      "\u{ffff}",
+      "\u{10000}",
+      "\u{1f600}",
+      "\u{10ffff}",
+      "\u{1f600}a",
+      "\u{ff21}a",
+    ];
+    for (a, b) in strings.iter().cartesian_product(strings.iter()) {
+      expect_eq!(
+        utf16_cmp(a, b),
+        a.encode_utf16().cmp(b.encode_utf16()),
+        "Comparing {a:?} and {b:?}"
+      );
+    }
+  }
+
+  #[gtest]
+  fn test_copy_no_libc() {
+    let src = (1..=52).collect::<Vec<u8>>();
+    for len in 0..=52 {
+      let mut dst = [0xff; 52];
+      copy_no_libc(&mut dst, &src[..len]);
+      expect_eq!(&dst[..len], &src[..len], "Copying {len} bytes");
+      expect_that!(&dst[len..], each(eq(&0xff)), "Copying {len} bytes");
+    }
+  }
+
+  #[gtest]
+  fn test_copy_no_libc_rejects_long_sources() {
+    let result = std::panic::catch_unwind(|| copy_no_libc(&mut [0; 8], &[1; 9]));
+    expect_true!(result.is_err());
+  }
+
+  #[gtest]
+  fn test_utf16_cmp_differs_from_byte_order() {
+    expect_eq!("\u{1f600}".cmp("\u{ff21}"), Ordering::Greater);
+    expect_eq!(utf16_cmp("\u{1f600}", "\u{ff21}"), Ordering::Less);
+  }
+}
@@ -0,0 +1,209 @@
+use std::{
+  fmt::Display,
+  fs::File,
+  io::{BufRead, BufReader},
+};
+
+use crate::{
+  error::{BarseError, BarseResult, RecordErrorKind},
+  scanner::ExtraFields,
+  temperature_reading::DecimalSeparator,
+  validating_scanner::validate_record,
+};
+
+/// The number of violations `validate_file` reports by default.
+pub const DEFAULT_MAX_ERRORS: usize = 10;
+const READ_BUFFER_SIZE: usize = 1 << 20;
+
+/// Options controlling how an input is validated.
+#[derive(Clone, Debug)]
+pub struct ValidateOptions {
+  /// Stop reading the input after this many violations, or read all of it if
+  /// 0.
+  pub max_errors: usize,
+  pub decimal_separator: DecimalSeparator,
+  /// Whether fields after the temperature reading are allowed.
+  pub extra_fields: ExtraFields,
+}
+
+impl Default for ValidateOptions {
+  fn default() -> Self {
+    Self {
+      max_errors: DEFAULT_MAX_ERRORS,
+      decimal_separator: DecimalSeparator::Dot,
+      extra_fields: ExtraFields::Forbid,
+    }
+  }
+}
+
+/// The result of checking the format of every record of an input.
+#[derive(Debug, Default)]
+pub struct ValidationReport {
+  /// The number of records read, including malformed ones.
+  pub records: u64,
+  /// The `MalformedRecord` errors of the malformed records found, with their
+  /// line numbers, in the order they appear in the input.
+  pub violations: Vec<BarseError>,
+  /// True if reading stopped after `max_errors` violations, in which case
+  /// there may be more past them.
+  pub stopped_early: bool,
+}
+
+impl ValidationReport {
+  pub fn is_valid(&self) -> bool {
+    self.violations.is_empty()
+  }
+}
+
+impl Display for ValidationReport {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    for violation in &self.violations {
+      writeln!(f, "{violation}")?;
+    }
+    write!(
+      f,
+      "{} records, {} violations",
+      self.records,
+      self.violations.len()
+    )?;
+    if self.stopped_early {
+      write!(f, " (stopped at the limit, there may be more)")?;
+    }
+    Ok(())
+  }
+}
+
+/// Checks the format of every record read from `input`, reporting the first
+/// `options.max_errors` malformed records, or all of them if it is 0. Unlike the table builders, this
+/// reads the input through a buffer, so works on pipes.
+pub fn validate_reader<R: BufRead>(
+  mut input: R,
+  options: &ValidateOptions,
+) -> BarseResult<ValidationReport> {
+  let mut report = ValidationReport::default();
+  let mut record = Vec::new();
+  let mut offset = 0;
+  while options.max_errors == 0 || report.violations.len() < options.max_errors {
+    record.clear();
+    let len = input.read_until(b'\n', &mut record)?;
+    if len == 0 {
+      return Ok(report);
+    }
+
+    report.records += 1;
+    let result = match record.strip_suffix(b"\n") {
+      Some(record) => {
+        validate_record(record, options.decimal_separator, options.extra_fields).map(|_| ())
+      }
+      None => Err(RecordErrorKind::MissingNewline),
+    };
+    if let Err(kind) = result {
+      report.violations.push(BarseError::MalformedRecord {
+        offset,
+        line: Some(report.records),
+        kind,
+      });
+    }
+    offset += len as u64;
+  }
+
+  report.stopped_early = !input.fill_buf()?.is_empty();
+  Ok(report)
+}
+
+/// Like `validate_reader`, over the file at `path`. The violations are
+/// attributed to the file.
+pub fn validate_file(path: &str, options: &ValidateOptions) -> BarseResult<ValidationReport> {
+  let mut report = File::open(path)
+    .map_err(BarseError::from)
+    .and_then(|file| validate_reader(BufReader::with_capacity(READ_BUFFER_SIZE, file), options))
+    .map_err(|err| err.in_file(path))?;
+  report.violations = report
+    .violations
+    .into_iter()
+    .map(|violation| violation.in_file(path))
+    .collect();
+  Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{fs, path::PathBuf};
+
+  use googletest::prelude::*;
+
+  use crate::{
+    error::{BarseError, RecordErrorKind},
+    validate::{validate_file, validate_reader, ValidateOptions},
+  };
+
+  fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("barse_{}_{name}", std::process::id()))
+  }
+
+  fn violations(input: &str, max_errors: usize) -> Vec<(u64, Option<u64>, RecordErrorKind)> {
+    let options = ValidateOptions {
+      max_errors,
+      ..ValidateOptions::default()
+    };
+    validate_reader(input.as_bytes(), &options)
+      .unwrap()
+      .violations
+      .into_iter()
+      .map(|violation| match violation {
+        BarseError::MalformedRecord { offset, line, kind } => (offset, line, kind),
+        err => panic!("Unexpected error {err}"),
+      })
+      .collect()
+  }
+
+  #[gtest]
+  fn test_reports_violations() {
+    let input = "Oslo;1.0\nAbha;12..\nHamburg;34.2\nBulawayo 8.9\nPalembang;38.8";
+    expect_eq!(
+      violations(input, 10),
+      vec![
+        (
+          9,
+          Some(2),
+          RecordErrorKind::MalformedTemperature("12..".to_owned())
+        ),
+        (32, Some(4), RecordErrorKind::MissingSemicolon),
+        (45, Some(5), RecordErrorKind::MissingNewline),
+      ]
+    );
+    expect_eq!(violations(input, 1).len(), 1);
+    expect_eq!(violations(input, 0).len(), 3);
+    expect_that!(violations("Oslo;1.0\n", 10), is_empty());
+  }
+
+  #[gtest]
+  fn test_validate_file() {
+    let input_path = temp_path("validate.txt");
+    fs::write(
+      &input_path,
+      format!("Oslo;1.0\n{};1.0\nAbha;1.0\n", "a".repeat(51)),
+    )
+    .unwrap();
+    let input_path = input_path.to_str().unwrap();
+    let report = validate_file(
+      input_path,
+      &ValidateOptions {
+        max_errors: 1,
+        ..ValidateOptions::default()
+      },
+    )
+    .unwrap();
+    fs::remove_file(input_path).unwrap();
+
+    expect_eq!(report.records, 2);
+    expect_true!(report.stopped_early);
+    expect_eq!(
+      report.to_string(),
+      format!(
+        "error: {input_path} @ byte 9, line 2: station name too long\n\
+         2 records, 1 violations (stopped at the limit, there may be more)"
+      )
+    );
+  }
+}
@@ -0,0 +1,315 @@
+use crate::{
+  error::{BarseError, BarseResult, RecordErrorKind},
+  scanner::{ExtraFields, MAX_EXTRA_FIELDS_LEN, MAX_STATION_NAME_LEN},
+  temperature_reading::{DecimalSeparator, TemperatureReading},
+};
+
+/// A scanner which checks the format of every record, yielding an error with
+/// the offset of the first malformed record instead of undefined results.
+/// This is much slower than `Scanner`, which assumes the input is well-formed.
+///
+/// Like `Scanner`, the station names yielded point into the input, so the
+/// input must be padded for them to be inserted into the station tables.
+pub struct ValidatingScanner<'a> {
+  input: &'a [u8],
+  /// The offset of `input` in the whole input.
+  base: u64,
+  /// The offset in `input` of the start of the next record.
+  pos: usize,
+  /// True if `input` runs to the end of the whole input, in which case a
+  /// trailing record without a newline is malformed. Otherwise, the trailing
+  /// record belongs to the scanner over the next chunk.
+  at_eof: bool,
+  decimal_separator: DecimalSeparator,
+  extra_fields: ExtraFields,
+  /// Set once iteration has returned `None` or an error.
+  finished: bool,
+}
+
+impl<'a> ValidatingScanner<'a> {
+  /// Constructs a scanner over the whole input, excluding padding.
+  pub fn from_start(input: &'a [u8]) -> Self {
+    Self {
+      input,
+      base: 0,
+      pos: 0,
+      at_eof: true,
+      decimal_separator: DecimalSeparator::Dot,
+      extra_fields: ExtraFields::Forbid,
+      finished: false,
+    }
+  }
+
+  /// Constructs a scanner over a chunk of the input at offset `base`, which
+  /// yields exactly the records a `Scanner` over the same chunk would: the
+  /// first `extra_fields.buffer_overlap()` bytes of chunks after the first
+  /// overlap with the previous chunk, and records are read from after the
+  /// last newline in the overlap.
+  pub fn from_chunk(chunk: &'a [u8], base: u64, at_eof: bool, extra_fields: ExtraFields) -> Self {
+    let mut scanner = Self {
+      base,
+      at_eof,
+      extra_fields,
+      ..Self::from_start(chunk)
+    };
+    if base == 0 {
+      return scanner;
+    }
+    let overlap = extra_fields.buffer_overlap();
+    if chunk.len() <= overlap {
+      // Like `Scanner::from_midpoint_with_len`, the scanner over the previous
+      // chunk reads every remaining record.
+      scanner.finished = true;
+      return scanner;
+    }
+    match chunk[..overlap].iter().rposition(|&b| b == b'\n') {
+      Some(newline) => scanner.pos = newline + 1,
+      // The record spanning the overlap is too long, which the scanner over
+      // the previous chunk reports from the start of the record.
+      None => scanner.finished = true,
+    }
+    scanner
+  }
+
+  /// Skips the first record, up to and including the first newline, e.g. to
+  /// discard a header line. This must be called before iterating.
+  pub fn skip_header(self) -> Self {
+    let pos = self.input[self.pos..]
+      .iter()
+      .position(|&b| b == b'\n')
+      .map_or(self.input.len(), |len| self.pos + len + 1);
+    Self { pos, ..self }
+  }
+
+  /// Parses temperature readings using `decimal_separator`.
+  pub fn with_decimal_separator(self, decimal_separator: DecimalSeparator) -> Self {
+    Self {
+      decimal_separator,
+      ..self
+    }
+  }
+
+  /// Accepts and ignores fields after the reading of each record with
+  /// `ExtraFields::Skip`.
+  pub fn with_extra_fields(self, extra_fields: ExtraFields) -> Self {
+    Self {
+      extra_fields,
+      ..self
+    }
+  }
+
+  fn error(&mut self, offset: usize, kind: RecordErrorKind) -> Option<<Self as Iterator>::Item> {
+    self.finished = true;
+    Some(Err(BarseError::MalformedRecord {
+      offset: self.base + offset as u64,
+      line: None,
+      kind,
+    }))
+  }
+
+  fn parse_record(&mut self, start: usize, end: usize) -> Option<<Self as Iterator>::Item> {
+    match validate_record(
+      &self.input[start..end],
+      self.decimal_separator,
+      self.extra_fields,
+    ) {
+      Ok(record) => Some(Ok(record)),
+      Err(kind) => self.error(start, kind),
+    }
+  }
+}
+
+/// Checks the format of a single record, excluding its trailing newline,
+/// returning the station name and reading it holds. With `ExtraFields::Skip`,
+/// the reading ends at the next semicolon, if any, and the fields after it are
+/// only checked for their length.
+pub(crate) fn validate_record(
+  record: &[u8],
+  decimal_separator: DecimalSeparator,
+  extra_fields: ExtraFields,
+) -> Result<(&str, TemperatureReading), RecordErrorKind> {
+  let semicolon = record
+    .iter()
+    .position(|&b| b == b';')
+    .ok_or(RecordErrorKind::MissingSemicolon)?;
+  let (name, temp) = (&record[..semicolon], &record[semicolon + 1..]);
+  if name.len() > MAX_STATION_NAME_LEN {
+    return Err(RecordErrorKind::NameTooLong);
+  }
+  let name = str::from_utf8(name).map_err(|_| RecordErrorKind::InvalidUtf8)?;
+  let temp = match (extra_fields, temp.iter().position(|&b| b == b';')) {
+    (ExtraFields::Skip, Some(semicolon)) => {
+      if temp.len() - semicolon > MAX_EXTRA_FIELDS_LEN {
+        return Err(RecordErrorKind::ExtraFieldsTooLong);
+      }
+      &temp[..semicolon]
+    }
+    _ => temp,
+  };
+  let temp = TemperatureReading::parse_checked(temp, decimal_separator).ok_or_else(|| {
+    RecordErrorKind::MalformedTemperature(String::from_utf8_lossy(temp).into_owned())
+  })?;
+  Ok((name, temp))
+}
+
+impl<'a> Iterator for ValidatingScanner<'a> {
+  type Item = BarseResult<(&'a str, TemperatureReading)>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.finished || self.pos == self.input.len() {
+      self.finished = true;
+      return None;
+    }
+
+    let start = self.pos;
+    let overlap_start = self
+      .input
+      .len()
+      .saturating_sub(self.extra_fields.buffer_overlap());
+    match self.input[start..].iter().position(|&b| b == b'\n') {
+      Some(len) => {
+        self.pos = start + len + 1;
+        self.parse_record(start, start + len)
+      }
+      None if self.at_eof => self.error(start, RecordErrorKind::MissingNewline),
+      // The next chunk's scanner starts after the last newline in its
+      // overlap, i.e. at `start`, unless `start` precedes the overlap, in
+      // which case the record is too long to fit in it.
+      None if start >= overlap_start => {
+        self.finished = true;
+        None
+      }
+      None => self.error(start, RecordErrorKind::NameTooLong),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use googletest::prelude::*;
+
+  use crate::{
+    error::{BarseError, RecordErrorKind},
+    scanner::{ExtraFields, MAX_EXTRA_FIELDS_LEN},
+    temperature_reading::{DecimalSeparator, TemperatureReading},
+    validating_scanner::ValidatingScanner,
+  };
+
+  fn first_error(input: &str) -> Option<(u64, RecordErrorKind)> {
+    ValidatingScanner::from_start(input.as_bytes()).find_map(|record| match record {
+      Err(BarseError::MalformedRecord { offset, kind, .. }) => Some((offset, kind)),
+      _ => None,
+    })
+  }
+
+  #[gtest]
+  fn test_valid_records() {
+    let records = ValidatingScanner::from_start("Oslo;-3.4\n;0.0\nAbha;12.3\n".as_bytes())
+      .map(|record| record.map(|(name, temp)| (name, temp.reading())))
+      .collect::<Result<Vec<_>, _>>();
+    expect_that!(
+      records,
+      ok(elements_are![
+        eq(&("Oslo", -34)),
+        eq(&("", 0)),
+        eq(&("Abha", 123))
+      ])
+    );
+
+    let comma_records = ValidatingScanner::from_start("Oslo;-3,4\n".as_bytes())
+      .with_decimal_separator(DecimalSeparator::Comma)
+      .collect::<Result<Vec<_>, _>>();
+    expect_that!(
+      comma_records,
+      ok(elements_are![eq(&("Oslo", TemperatureReading::new(-34)))])
+    );
+  }
+
+  #[gtest]
+  fn test_trailing_whitespace() {
+    let records =
+      ValidatingScanner::from_start("Berlin;12.3 \nOslo;-3.4\t\nAbha;0.0 \t \n".as_bytes())
+        .map(|record| record.map(|(name, temp)| (name, temp.reading())))
+        .collect::<Result<Vec<_>, _>>();
+    expect_that!(
+      records,
+      ok(elements_are![
+        eq(&("Berlin", 123)),
+        eq(&("Oslo", -34)),
+        eq(&("Abha", 0))
+      ])
+    );
+    // Whitespace is only trimmed from the end of the reading.
+    expect_eq!(
+      first_error("Berlin; 12.3\n"),
+      Some((0, RecordErrorKind::MalformedTemperature(" 12.3".to_owned())))
+    );
+  }
+
+  #[gtest]
+  fn test_malformed_records() {
+    expect_eq!(
+      first_error("Oslo;1.0\nAbha;12..\n"),
+      Some((9, RecordErrorKind::MalformedTemperature("12..".to_owned())))
+    );
+    expect_eq!(
+      first_error("Oslo;1.0\nAbha 12.0\n"),
+      Some((9, RecordErrorKind::MissingSemicolon))
+    );
+    expect_eq!(
+      first_error(&format!("{};1.0\n", "a".repeat(51))),
+      Some((0, RecordErrorKind::NameTooLong))
+    );
+    expect_eq!(
+      first_error("Oslo;1.0\nAbha;1.0"),
+      Some((9, RecordErrorKind::MissingNewline))
+    );
+    expect_eq!(
+      ValidatingScanner::from_start(b"Oslo;1.0\nAb\xffha;1.0\n")
+        .find_map(Result::err)
+        .map(|err| err.to_string()),
+      Some("error: byte 9: station name is not valid UTF-8".to_owned())
+    );
+  }
+
+  #[gtest]
+  fn test_extra_fields() {
+    let longest = format!(";{}", "x".repeat(MAX_EXTRA_FIELDS_LEN - 1));
+    let input = format!("Oslo;-3.4;55.3\nAbha;12.3{longest}\nBern;1.0;\nRome;2.0\n");
+    let records = ValidatingScanner::from_start(input.as_bytes())
+      .with_extra_fields(ExtraFields::Skip)
+      .map(|record| record.map(|(name, temp)| (name, temp.reading())))
+      .collect::<Result<Vec<_>, _>>();
+    expect_that!(
+      records,
+      ok(elements_are![
+        eq(&("Oslo", -34)),
+        eq(&("Abha", 123)),
+        eq(&("Bern", 10)),
+        eq(&("Rome", 20))
+      ])
+    );
+
+    let first_error = |input: &str| {
+      ValidatingScanner::from_start(input.as_bytes())
+        .with_extra_fields(ExtraFields::Skip)
+        .find_map(Result::err)
+        .map(|err| err.to_string())
+    };
+    expect_eq!(
+      first_error(&format!("Oslo;1.0\nAbha;1.0{longest}x\n")),
+      Some("error: byte 9: extra fields too long".to_owned())
+    );
+    expect_eq!(
+      first_error("Oslo;1.0;\nAbha;;1.0\n"),
+      Some("error: byte 10: malformed temperature \"\"".to_owned())
+    );
+    // Extra fields are malformed readings unless they are skipped.
+    expect_eq!(
+      ValidatingScanner::from_start(b"Oslo;1.0;55.3\n")
+        .find_map(Result::err)
+        .map(|err| err.to_string()),
+      Some("error: byte 0: malformed temperature \"1.0;55.3\"".to_owned())
+    );
+  }
+}
@@ -0,0 +1,290 @@
+//! Incremental aggregation of an input file which is appended to while it is
+//! being summarized, e.g. a live log of measurements.
+
+use std::{
+  fs::File,
+  io::{Read, Seek, SeekFrom},
+};
+
+use crate::{
+  barse::{PaddedInput, Table},
+  error::{BarseError, BarseResult},
+  options::Options,
+};
+
+/// What `IncrementalAggregator::poll_file` found in the file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Poll {
+  /// The file hasn't grown since the last poll.
+  Unchanged,
+  /// Bytes were appended to the file, and every complete record among them
+  /// was folded into the table.
+  Appended,
+  /// The file shrank, so it was truncated or replaced. The table was reset
+  /// and rebuilt from the start of the file.
+  Reset,
+}
+
+/// A table of summaries which is kept up to date with an append-only input,
+/// by folding in the bytes appended since the last update rather than
+/// rebuilding from the whole input.
+///
+/// Appended bytes needn't end on a record boundary. A trailing partial record
+/// is held back until the rest of it is ingested, so the table only ever
+/// holds whole records.
+pub struct IncrementalAggregator {
+  table: Table,
+  options: Options,
+  /// The bytes of the input after the last complete record.
+  partial_record: Vec<u8>,
+  /// The number of bytes of the input ingested so far, including
+  /// `partial_record`.
+  ingested: u64,
+}
+
+impl IncrementalAggregator {
+  /// Constructs an aggregator of an empty input, which builds the records of
+  /// each ingested chunk with `options`. `options.skip_header` only skips the
+  /// first record of the input.
+  pub fn new(options: &Options) -> BarseResult<Self> {
+    options.validate()?;
+    Ok(Self {
+      table: Table::with_capacity(options.table_capacity)?,
+      options: options.clone(),
+      partial_record: Vec::new(),
+      ingested: 0,
+    })
+  }
+
+  /// The table of every complete record ingested so far.
+  pub fn table(&self) -> &Table {
+    &self.table
+  }
+
+  /// The number of bytes of the input ingested so far.
+  pub fn ingested(&self) -> u64 {
+    self.ingested
+  }
+
+  /// The offset in the input of the end of the last complete record, from
+  /// which the next chunk of records starts.
+  pub fn parsed_offset(&self) -> u64 {
+    self.ingested - self.partial_record.len() as u64
+  }
+
+  /// Forgets every record ingested so far, as if the input were empty.
+  pub fn reset(&mut self) -> BarseResult {
+    *self = Self::new(&self.options)?;
+    Ok(())
+  }
+
+  /// Folds `bytes`, which were appended to the input after every byte
+  /// ingested so far, into the table. Only the records completed by `bytes`
+  /// are parsed. If the build of these records fails, e.g. on a malformed
+  /// record with `options.strict`, none of them are folded in, and the
+  /// aggregator is left as it was.
+  pub fn ingest_new_bytes(&mut self, bytes: &[u8]) -> BarseResult {
+    let Some(last_newline) = bytes.iter().rposition(|&b| b == b'\n') else {
+      self.partial_record.extend_from_slice(bytes);
+      self.ingested += bytes.len() as u64;
+      return Ok(());
+    };
+    let (completed, partial) = bytes.split_at(last_newline + 1);
+
+    let options = Options {
+      skip_header: self.options.skip_header && self.parsed_offset() == 0,
+      ..self.options.clone()
+    };
+    let mut records = std::mem::take(&mut self.partial_record);
+    records.extend_from_slice(completed);
+    let built = PaddedInput::new(&records)
+      .and_then(|input| input.build(&options))
+      .and_then(|(chunk_table, _)| self.table.merge(&chunk_table));
+    if let Err(err) = built {
+      // Restore the partial record that `records` started with.
+      records.truncate(records.len() - completed.len());
+      self.partial_record = records;
+      return Err(err);
+    }
+
+    self.partial_record = partial.to_vec();
+    self.ingested += bytes.len() as u64;
+    Ok(())
+  }
+
+  /// Re-opens the file at `input_path` and ingests the bytes appended to it
+  /// since the last poll. If the file is shorter than the bytes already
+  /// ingested, it was truncated or rotated, so the aggregator is reset and
+  /// the whole file is ingested.
+  pub fn poll_file(&mut self, input_path: &str) -> BarseResult<Poll> {
+    self
+      .poll_file_inner(input_path)
+      .map_err(|err| err.in_file(input_path))
+  }
+
+  fn poll_file_inner(&mut self, input_path: &str) -> BarseResult<Poll> {
+    let mut file = File::open(input_path)?;
+    let len = file.metadata()?.len();
+    let poll = if len < self.ingested {
+      self.reset()?;
+      Poll::Reset
+    } else if len == self.ingested {
+      return Ok(Poll::Unchanged);
+    } else {
+      Poll::Appended
+    };
+
+    let new_len = usize::try_from(len - self.ingested).map_err(|_| {
+      BarseError::new(format!(
+        "{} appended bytes don't fit in memory",
+        len - self.ingested
+      ))
+    })?;
+    let mut bytes = vec![0; new_len];
+    file.seek(SeekFrom::Start(self.ingested))?;
+    file.read_exact(&mut bytes)?;
+    self.ingest_new_bytes(&bytes)?;
+    Ok(poll)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::fs;
+
+  use googletest::prelude::*;
+  use itertools::Itertools;
+
+  use crate::{
+    error::BarseError,
+    options::Options,
+    print_summary::{write_table_summary, SummaryOptions},
+    test_util::random_input_file,
+    util::HasIter,
+    watch::{IncrementalAggregator, Poll},
+  };
+
+  fn summary(aggregator: &IncrementalAggregator) -> String {
+    let mut out = Vec::new();
+    write_table_summary(aggregator.table(), &SummaryOptions::default(), &mut out).unwrap();
+    String::from_utf8(out).unwrap()
+  }
+
+  #[gtest]
+  fn test_ingest_in_arbitrary_chunks() {
+    let input = random_input_file(0x6b1c20, 2_000, 100).unwrap();
+    let input = input.exact_slice();
+    let options = Options {
+      table_capacity: 256,
+      ..Options::default()
+    };
+    let mut whole = IncrementalAggregator::new(&options).unwrap();
+    whole.ingest_new_bytes(input).unwrap();
+
+    for chunk_len in [1, 7, 64, 1000, 4096] {
+      let mut aggregator = IncrementalAggregator::new(&options).unwrap();
+      for chunk in input.chunks(chunk_len) {
+        aggregator.ingest_new_bytes(chunk).unwrap();
+        expect_le!(aggregator.parsed_offset(), aggregator.ingested());
+      }
+      expect_eq!(aggregator.ingested(), input.len() as u64);
+      expect_eq!(aggregator.parsed_offset(), input.len() as u64);
+      expect_eq!(
+        summary(&aggregator),
+        summary(&whole),
+        "chunks of {chunk_len}"
+      );
+    }
+  }
+
+  #[gtest]
+  fn test_partial_record_is_held_back() {
+    let mut aggregator = IncrementalAggregator::new(&Options::default()).unwrap();
+    aggregator
+      .ingest_new_bytes(b"Hamburg;12.0\nBulawayo;8")
+      .unwrap();
+    expect_eq!(aggregator.parsed_offset(), 13);
+    expect_eq!(summary(&aggregator), "{Hamburg=12.0/12.0/12.0}\n");
+
+    aggregator.ingest_new_bytes(b".9\nHamburg;3").unwrap();
+    expect_eq!(aggregator.parsed_offset(), 26);
+    expect_eq!(
+      summary(&aggregator),
+      "{Bulawayo=8.9/8.9/8.9, Hamburg=12.0/12.0/12.0}\n"
+    );
+
+    aggregator.ingest_new_bytes(b"4.2\n").unwrap();
+    expect_eq!(
+      summary(&aggregator),
+      "{Bulawayo=8.9/8.9/8.9, Hamburg=12.0/23.1/34.2}\n"
+    );
+  }
+
+  #[gtest]
+  fn test_header_is_only_skipped_once() {
+    let options = Options {
+      skip_header: true,
+      ..Options::default()
+    };
+    let mut aggregator = IncrementalAggregator::new(&options).unwrap();
+    aggregator.ingest_new_bytes(b"station;temp").unwrap();
+    aggregator.ingest_new_bytes(b"erature\nOslo;1.0\n").unwrap();
+    aggregator.ingest_new_bytes(b"Oslo;3.0\n").unwrap();
+    expect_eq!(summary(&aggregator), "{Oslo=1.0/2.0/3.0}\n");
+  }
+
+  #[gtest]
+  fn test_malformed_chunk_is_not_ingested() {
+    let options = Options {
+      strict: true,
+      ..Options::default()
+    };
+    let mut aggregator = IncrementalAggregator::new(&options).unwrap();
+    aggregator.ingest_new_bytes(b"Oslo;1.0\nOs").unwrap();
+    expect_true!(matches!(
+      aggregator.ingest_new_bytes(b"lo;2.0\nOslo;abc\n"),
+      Err(BarseError::MalformedRecord { .. })
+    ));
+    expect_eq!((aggregator.parsed_offset(), aggregator.ingested()), (9, 11));
+
+    aggregator.ingest_new_bytes(b"lo;3.0\n").unwrap();
+    expect_eq!(summary(&aggregator), "{Oslo=1.0/2.0/3.0}\n");
+  }
+
+  #[gtest]
+  fn test_poll_file_appends_and_rotation() {
+    let input_path = std::env::temp_dir().join(format!("barse_watch_{}.txt", std::process::id()));
+    let path = input_path.to_str().unwrap();
+    let mut aggregator = IncrementalAggregator::new(&Options::default()).unwrap();
+
+    fs::write(path, "Hamburg;12.0\nBulawayo;8").unwrap();
+    expect_that!(aggregator.poll_file(path), ok(eq(&Poll::Appended)));
+    expect_that!(aggregator.poll_file(path), ok(eq(&Poll::Unchanged)));
+    fs::write(path, "Hamburg;12.0\nBulawayo;8.9\nHamburg;34.2\n").unwrap();
+    expect_that!(aggregator.poll_file(path), ok(eq(&Poll::Appended)));
+    expect_eq!(
+      summary(&aggregator),
+      "{Bulawayo=8.9/8.9/8.9, Hamburg=12.0/23.1/34.2}\n"
+    );
+
+    // Rotated to a shorter file.
+    fs::write(path, "Oslo;-3.0\n").unwrap();
+    expect_that!(aggregator.poll_file(path), ok(eq(&Poll::Reset)));
+    expect_eq!(summary(&aggregator), "{Oslo=-3.0/-3.0/-3.0}\n");
+    expect_eq!(aggregator.ingested(), 10);
+
+    fs::remove_file(path).unwrap();
+    expect_that!(
+      aggregator.poll_file(path).map_err(|err| err.to_string()),
+      err(contains_substring(path))
+    );
+    expect_eq!(
+      aggregator
+        .table()
+        .iter()
+        .map(|(station, summary)| (station.to_owned(), summary.count))
+        .collect_vec(),
+      vec![("Oslo".to_owned(), 1)]
+    );
+  }
+}
@@ -0,0 +1,125 @@
+//! Builds the static library, then compiles the C program in `tests/capi/`
+//! against it and `include/barse.h` and runs it, to check that the C API
+//! works across the ABI. The C compiler is taken from `CC`, or `cc` by
+//! default.
+
+#![cfg(feature = "capi")]
+
+use std::{
+  env, fs,
+  path::{Path, PathBuf},
+  process::{Command, Output},
+  sync::OnceLock,
+};
+
+use googletest::prelude::*;
+
+/// The native libraries the standard library links against on Linux, from
+/// `rustc --print native-static-libs`.
+const NATIVE_LIBS: [&str; 7] = [
+  "-lgcc_s",
+  "-lutil",
+  "-lrt",
+  "-lpthread",
+  "-lm",
+  "-ldl",
+  "-lc",
+];
+
+fn temp_path(name: &str) -> PathBuf {
+  env::temp_dir().join(format!("barse_capi_{}_{name}", std::process::id()))
+}
+
+/// Builds the static library once per run, returning its path. It is built
+/// in its own target directory, since the build of this test holds the lock
+/// on the default one.
+fn static_lib() -> &'static Path {
+  static STATIC_LIB: OnceLock<PathBuf> = OnceLock::new();
+  STATIC_LIB.get_or_init(|| {
+    let target_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("capi");
+    let output = Command::new(env::var("CARGO").unwrap_or_else(|_| "cargo".to_owned()))
+      .args([
+        "rustc",
+        "--lib",
+        "--crate-type",
+        "staticlib",
+        "--features",
+        "capi",
+      ])
+      .arg("--manifest-path")
+      .arg(Path::new(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml"))
+      .arg("--target-dir")
+      .arg(&target_dir)
+      .output()
+      .unwrap();
+    assert!(
+      output.status.success(),
+      "Failed to build the static library:\n{}",
+      String::from_utf8_lossy(&output.stderr)
+    );
+    target_dir.join("debug/libbarse.a")
+  })
+}
+
+/// Compiles the C program, returning the path of the executable.
+fn compile(name: &str) -> PathBuf {
+  let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+  let exe = temp_path(name);
+  let output = Command::new(env::var("CC").unwrap_or_else(|_| "cc".to_owned()))
+    .arg("-Wall")
+    .arg("-Werror")
+    .arg("-I")
+    .arg(manifest_dir.join("include"))
+    .arg(manifest_dir.join("tests/capi/summarize.c"))
+    .arg(static_lib())
+    .args(NATIVE_LIBS)
+    .arg("-o")
+    .arg(&exe)
+    .output()
+    .unwrap();
+  assert!(
+    output.status.success(),
+    "Failed to compile the C program:\n{}",
+    String::from_utf8_lossy(&output.stderr)
+  );
+  exe
+}
+
+fn run(name: &str, input_path: &Path) -> Output {
+  let exe = compile(name);
+  let output = Command::new(&exe).arg(input_path).output().unwrap();
+  fs::remove_file(&exe).unwrap();
+  output
+}
+
+#[gtest]
+fn test_summarize_file() {
+  let input_path = temp_path("input.txt");
+  fs::write(
+    &input_path,
+    "Hamburg;12.0\nBulawayo;8.9\nSão Paulo;-3.4\nHamburg;34.2\nBulawayo;-0.1\n",
+  )
+  .unwrap();
+  let output = run("summarize", &input_path);
+  fs::remove_file(&input_path).unwrap();
+
+  expect_eq!(str::from_utf8(&output.stderr).unwrap(), "");
+  expect_eq!(output.status.code(), Some(0));
+  expect_eq!(
+    str::from_utf8(&output.stdout).unwrap(),
+    "Bulawayo=-1/44/89 2\nHamburg=120/231/342 2\nSão Paulo=-34/-34/-34 1\n"
+  );
+}
+
+#[gtest]
+fn test_missing_file() {
+  let input_path = temp_path("missing.txt");
+  let output = run("missing", &input_path);
+
+  expect_eq!(output.status.code(), Some(2));
+  expect_eq!(str::from_utf8(&output.stdout).unwrap(), "");
+  expect_that!(
+    str::from_utf8(&output.stderr).unwrap(),
+    starts_with(format!("error: {}: ", input_path.display()))
+  );
+}
@@ -0,0 +1,36 @@
+/*
+ * Summarizes the file given as the only argument through the C API, printing
+ * each station as "name=min/avg/max count", with temperatures in tenths of a
+ * degree. Prints the error message and exits with the status code on failure.
+ */
+
+#include <stdio.h>
+
+#include "barse.h"
+
+int main(int argc, char **argv) {
+  if (argc != 2) {
+    fprintf(stderr, "usage: %s <input>\n", argv[0]);
+    return 1;
+  }
+
+  struct BarseSummary *summaries = NULL;
+  size_t len = 0;
+  int32_t status = barse_summarize_file(argv[1], &summaries, &len);
+  if (status != BARSE_OK) {
+    fprintf(stderr, "%s\n", barse_last_error());
+    return status;
+  }
+  if (barse_last_error() != NULL) {
+    fprintf(stderr, "barse_last_error is not null after success\n");
+    return 1;
+  }
+
+  for (size_t i = 0; i < len; i++) {
+    const struct BarseSummary *summary = &summaries[i];
+    printf("%.*s=%d/%d/%d %u\n", (int)summary->name_len, (const char *)summary->name,
+           summary->min, summary->avg, summary->max, summary->count);
+  }
+  barse_free_summary(summaries, len);
+  return 0;
+}
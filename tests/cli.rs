@@ -0,0 +1,672 @@
+use std::{
+  fs,
+  io::Write,
+  path::PathBuf,
+  process::{Command, Output, Stdio},
+};
+
+use googletest::prelude::*;
+
+const MISSING_INPUT: &str = "/nonexistent/measurements.txt";
+
+fn temp_path(name: &str) -> PathBuf {
+  std::env::temp_dir().join(format!("barse_cli_{}_{name}", std::process::id()))
+}
+
+/// Writes a deterministic input of `records` records over a few stations.
+fn generate_input(name: &str, records: usize) -> PathBuf {
+  const STATIONS: [&str; 5] = ["Abha", "Bulawayo", "Hamburg", "Oslo", "Palembang"];
+  let mut state = 0x2545f4914f6cdd1du64;
+  let input = (0..records)
+    .map(|_| {
+      state ^= state << 13;
+      state ^= state >> 7;
+      state ^= state << 17;
+      let station = STATIONS[(state % STATIONS.len() as u64) as usize];
+      let reading = (state >> 32) % 1999;
+      let reading = reading as i64 - 999;
+      format!(
+        "{station};{}{}.{}\n",
+        if reading < 0 { "-" } else { "" },
+        reading.abs() / 10,
+        reading.abs() % 10
+      )
+    })
+    .collect::<String>();
+  let input_path = temp_path(name);
+  fs::write(&input_path, input).unwrap();
+  input_path
+}
+
+fn barse(args: &[&str]) -> Output {
+  Command::new(env!("CARGO_BIN_EXE_barse"))
+    .args(args)
+    .output()
+    .unwrap()
+}
+
+fn barse_with_env(args: &[&str], env: &[(&str, &str)]) -> Output {
+  Command::new(env!("CARGO_BIN_EXE_barse"))
+    .args(args)
+    .envs(env.iter().copied())
+    .output()
+    .unwrap()
+}
+
+#[gtest]
+fn test_summary_goes_to_stdout() {
+  let input_path = temp_path("summary.txt");
+  fs::write(&input_path, "Hamburg;12.0\nBulawayo;8.9\nHamburg;34.2\n").unwrap();
+  let output = barse(&["--input", input_path.to_str().unwrap()]);
+  fs::remove_file(&input_path).unwrap();
+
+  expect_eq!(output.status.code(), Some(0));
+  expect_eq!(
+    str::from_utf8(&output.stdout).unwrap(),
+    "{Bulawayo=8.9/8.9/8.9, Hamburg=12.0/23.1/34.2}\n"
+  );
+  expect_eq!(str::from_utf8(&output.stderr).unwrap(), "");
+}
+
+#[gtest]
+fn test_missing_file_is_io_error() {
+  let output = barse(&["--input", MISSING_INPUT]);
+
+  expect_eq!(output.status.code(), Some(2));
+  expect_eq!(str::from_utf8(&output.stdout).unwrap(), "");
+  expect_that!(
+    str::from_utf8(&output.stderr).unwrap(),
+    starts_with(format!("error: {MISSING_INPUT}: "))
+  );
+}
+
+#[gtest]
+fn test_corrupt_file_is_data_error() {
+  let input_path = temp_path("corrupt.txt");
+  fs::write(&input_path, "Oslo;1.0\nAbha;12..\n").unwrap();
+  let input_path = input_path.to_str().unwrap();
+  let output = barse(&["--input", input_path, "--strict"]);
+  fs::remove_file(input_path).unwrap();
+
+  expect_eq!(output.status.code(), Some(3));
+  expect_eq!(str::from_utf8(&output.stdout).unwrap(), "");
+  expect_eq!(
+    str::from_utf8(&output.stderr).unwrap(),
+    format!("error: {input_path} @ byte 9, line 2: malformed temperature \"12..\"\n")
+  );
+}
+
+#[gtest]
+fn test_usage_error() {
+  let output = barse(&["--sort-by", "hottest"]);
+
+  expect_eq!(output.status.code(), Some(1));
+  expect_eq!(str::from_utf8(&output.stdout).unwrap(), "");
+  expect_that!(
+    str::from_utf8(&output.stderr).unwrap(),
+    contains_substring("hottest")
+  );
+}
+
+#[gtest]
+fn test_help_goes_to_stdout() {
+  let output = barse(&["--help"]);
+
+  expect_eq!(output.status.code(), Some(0));
+  expect_that!(
+    str::from_utf8(&output.stdout).unwrap(),
+    contains_substring("--input")
+  );
+  expect_eq!(str::from_utf8(&output.stderr).unwrap(), "");
+}
+
+#[gtest]
+fn test_parallelism_flags_agree() {
+  let input_path = generate_input("parallelism.txt", 20_000);
+  let input_path = input_path.to_str().unwrap();
+  let expected = barse(&["--input", input_path]);
+  expect_eq!(expected.status.code(), Some(0));
+
+  let mut flag_sets = vec![
+    vec!["--threads", "0"],
+    vec!["--threads", "3"],
+    vec!["--chunk-size", "512K"],
+    vec!["--chunk-size", "128"],
+    vec!["--strategy", "single"],
+  ];
+  if cfg!(feature = "multithreaded") {
+    flag_sets.extend([
+      vec!["--strategy", "shared-table", "--chunk-size", "1k"],
+      vec!["--strategy", "per-thread-table", "--chunk-size", "1k"],
+      vec!["--strategy", "per-thread-table", "--threads", "2"],
+    ]);
+  }
+  for flags in flag_sets {
+    let output = barse(&[&["--input", input_path], flags.as_slice()].concat());
+    expect_eq!(output.status.code(), Some(0), "{flags:?}");
+    expect_eq!(output.stdout, expected.stdout, "{flags:?}");
+  }
+  fs::remove_file(input_path).unwrap();
+}
+
+#[gtest]
+fn test_invalid_parallelism_flags_are_usage_errors() {
+  let mut flag_sets = vec![
+    vec!["--chunk-size", "32"],
+    vec!["--chunk-size", "100"],
+    vec!["--chunk-size", "2X"],
+    vec!["--threads", "-1"],
+    vec!["--strategy", "round-robin"],
+  ];
+  if cfg!(not(feature = "multithreaded")) {
+    flag_sets.push(vec!["--strategy", "shared-table"]);
+  }
+  for flags in flag_sets {
+    // The input is never opened, so the missing file isn't reported.
+    let output = barse(&[&["--input", MISSING_INPUT], flags.as_slice()].concat());
+    expect_eq!(output.status.code(), Some(1), "{flags:?}");
+    expect_eq!(str::from_utf8(&output.stdout).unwrap(), "");
+    expect_that!(
+      str::from_utf8(&output.stderr).unwrap(),
+      not(contains_substring("nonexistent"))
+    );
+  }
+}
+
+#[gtest]
+fn test_profile_out() {
+  let input_path = generate_input("profiled.txt", 1000);
+  let profile_path = temp_path("profile.svg");
+  let output = barse(&[
+    "--input",
+    input_path.to_str().unwrap(),
+    "--profile-out",
+    profile_path.to_str().unwrap(),
+  ]);
+  fs::remove_file(&input_path).unwrap();
+
+  if cfg!(all(feature = "profiled", unix)) {
+    expect_eq!(output.status.code(), Some(0));
+    expect_true!(fs::remove_file(&profile_path).is_ok());
+  } else {
+    expect_eq!(output.status.code(), Some(1));
+    expect_eq!(str::from_utf8(&output.stdout).unwrap(), "");
+    expect_that!(
+      str::from_utf8(&output.stderr).unwrap(),
+      contains_substring("profiled")
+    );
+    expect_false!(profile_path.exists());
+  }
+}
+
+#[gtest]
+fn test_watch_prints_summary_once_until_input_changes() {
+  let input_path = generate_input("watch.txt", 1000);
+  let input = input_path.to_str().unwrap();
+  let summary = barse(&["--input", input]);
+  let output = barse(&[
+    "watch",
+    "--input",
+    input,
+    "--interval",
+    "1ms",
+    "--count",
+    "3",
+  ]);
+  let output_path = temp_path("watch_summary.txt");
+  let to_file = barse(&[
+    "watch",
+    "--input",
+    input,
+    "--interval",
+    "1ms",
+    "--count",
+    "1",
+    "--output",
+    output_path.to_str().unwrap(),
+  ]);
+  let input_len = fs::metadata(&input_path).unwrap().len();
+  fs::remove_file(&input_path).unwrap();
+
+  expect_eq!(output.status.code(), Some(0));
+  expect_eq!(
+    str::from_utf8(&output.stdout).unwrap(),
+    format!(
+      "==> {input} ({input_len} bytes) <==\n{}",
+      str::from_utf8(&summary.stdout).unwrap()
+    )
+  );
+  expect_eq!(to_file.status.code(), Some(0));
+  expect_eq!(str::from_utf8(&to_file.stdout).unwrap(), "");
+  expect_eq!(fs::read(&output_path).unwrap(), summary.stdout);
+  fs::remove_file(&output_path).unwrap();
+}
+
+#[gtest]
+fn test_watch_missing_input() {
+  let output = barse(&["watch", "--input", MISSING_INPUT, "--count", "1"]);
+  expect_eq!(output.status.code(), Some(2));
+  expect_that!(
+    str::from_utf8(&output.stderr).unwrap(),
+    contains_substring(MISSING_INPUT)
+  );
+}
+
+#[gtest]
+fn test_parquet_output() {
+  let input_path = generate_input("parquet.txt", 1000);
+  let parquet_path = temp_path("summary.parquet");
+  let output = barse(&[
+    "--input",
+    input_path.to_str().unwrap(),
+    "--format",
+    "parquet",
+    "--output",
+    parquet_path.to_str().unwrap(),
+  ]);
+  let to_stdout = barse(&[
+    "--input",
+    input_path.to_str().unwrap(),
+    "--format",
+    "parquet",
+  ]);
+  fs::remove_file(&input_path).unwrap();
+
+  expect_eq!(to_stdout.status.code(), Some(1));
+  expect_eq!(str::from_utf8(&to_stdout.stdout).unwrap(), "");
+  if cfg!(feature = "arrow") {
+    expect_eq!(output.status.code(), Some(0));
+    expect_that!(
+      str::from_utf8(&to_stdout.stderr).unwrap(),
+      contains_substring("--output")
+    );
+    let parquet = fs::read(&parquet_path).unwrap();
+    fs::remove_file(&parquet_path).unwrap();
+    expect_true!(parquet.starts_with(b"PAR1") && parquet.ends_with(b"PAR1"));
+  } else {
+    expect_eq!(output.status.code(), Some(1));
+    expect_that!(
+      str::from_utf8(&output.stderr).unwrap(),
+      contains_substring("`arrow` feature")
+    );
+    expect_false!(parquet_path.exists());
+  }
+}
+
+#[gtest]
+fn test_report_with_perf_counters() {
+  let input_path = generate_input("perf_counters.txt", 1000);
+  let output = barse(&["--input", input_path.to_str().unwrap(), "--report"]);
+  fs::remove_file(&input_path).unwrap();
+
+  // The counters are often unavailable, e.g. in containers, which must not
+  // fail the run.
+  expect_eq!(output.status.code(), Some(0));
+  let stderr = str::from_utf8(&output.stderr).unwrap();
+  if cfg!(all(feature = "perf-counters", target_os = "linux")) {
+    expect_that!(
+      stderr,
+      contains_substring("instructions: ").or(contains_substring("perf counters are unavailable"))
+    );
+  } else {
+    expect_that!(stderr, not(contains_substring("instructions: ")));
+  }
+}
+
+#[gtest]
+fn test_quiet_writes_only_the_summary() {
+  let input_path = temp_path("quiet.txt");
+  fs::write(&input_path, "Hamburg;12.0\nBulawayo;8.9\nHamburg;34.2\n").unwrap();
+  let output = barse(&[
+    "--input",
+    input_path.to_str().unwrap(),
+    "--quiet",
+    "--global",
+  ]);
+  fs::remove_file(&input_path).unwrap();
+
+  expect_eq!(output.status.code(), Some(0));
+  expect_eq!(
+    str::from_utf8(&output.stdout).unwrap(),
+    "{Bulawayo=8.9/8.9/8.9, Hamburg=12.0/23.1/34.2}\n"
+  );
+  expect_eq!(str::from_utf8(&output.stderr).unwrap(), "");
+}
+
+#[gtest]
+fn test_quiet_conflicts_with_reports() {
+  for flag in ["--report", "--stats"] {
+    let output = barse(&["--input", MISSING_INPUT, "--quiet", flag]);
+    expect_eq!(output.status.code(), Some(1), "{flag}");
+    expect_eq!(str::from_utf8(&output.stdout).unwrap(), "");
+  }
+}
+
+#[gtest]
+fn test_stats_go_to_stderr() {
+  let input_path = temp_path("stats.txt");
+  fs::write(&input_path, "Hamburg;12.0\nBulawayo;8.9\nHamburg;34.2\n").unwrap();
+  let output = barse(&["--input", input_path.to_str().unwrap(), "--stats"]);
+  fs::remove_file(&input_path).unwrap();
+
+  expect_eq!(output.status.code(), Some(0));
+  expect_eq!(
+    str::from_utf8(&output.stdout).unwrap(),
+    "{Bulawayo=8.9/8.9/8.9, Hamburg=12.0/23.1/34.2}\n"
+  );
+  let stderr = str::from_utf8(&output.stderr).unwrap();
+  expect_that!(stderr, contains_substring("records: 3\n"));
+  expect_that!(stderr, contains_substring("unique stations: 2\n"));
+  expect_that!(stderr, contains_substring("table stations: 2"));
+}
+
+// The arguments, environment variables, and expected error of each case.
+type Case<'a> = (&'a [&'a str], &'a [(&'a str, &'a str)], Option<&'a str>);
+
+#[gtest]
+fn test_option_precedence() {
+  let input_path = temp_path("precedence.txt");
+  fs::write(&input_path, "Hamburg;12.0\nBulawayo;8.9\n").unwrap();
+  let config_path = temp_path("precedence.toml");
+  fs::write(
+    &config_path,
+    "# Invalid, to show which source wins.\nchunk_size = 100\n",
+  )
+  .unwrap();
+  let input_path = input_path.to_str().unwrap();
+  let config_path = config_path.to_str().unwrap();
+
+  let cases: [Case; 5] = [
+    (&[], &[], None),
+    (
+      &["--config", config_path],
+      &[],
+      Some("chunk_size from the config file"),
+    ),
+    (
+      &[],
+      &[("BARSE_CONFIG", config_path)],
+      Some("chunk_size from the config file"),
+    ),
+    (
+      &["--config", config_path],
+      &[("BARSE_CHUNK_SIZE", "512K")],
+      None,
+    ),
+    (
+      &["--chunk-size", "100"],
+      &[("BARSE_CHUNK_SIZE", "512K")],
+      Some("chunk_size from the command line"),
+    ),
+  ];
+  for (args, env, error) in cases {
+    let output = barse_with_env(&[&["--input", input_path], args].concat(), env);
+    let stderr = str::from_utf8(&output.stderr).unwrap();
+    match error {
+      Some(error) => {
+        expect_eq!(output.status.code(), Some(1), "{args:?} {env:?}");
+        expect_that!(stderr, contains_substring(error), "{args:?} {env:?}");
+      }
+      None => {
+        expect_eq!(output.status.code(), Some(0), "{args:?} {env:?}: {stderr}");
+        expect_eq!(
+          str::from_utf8(&output.stdout).unwrap(),
+          "{Bulawayo=8.9/8.9/8.9, Hamburg=12.0/12.0/12.0}\n"
+        );
+      }
+    }
+  }
+
+  let output = barse_with_env(
+    &["--input", input_path],
+    &[("BARSE_CHUNK_SIZE", "100"), ("BARSE_THREADS", "2")],
+  );
+  expect_eq!(output.status.code(), Some(1));
+  expect_that!(
+    str::from_utf8(&output.stderr).unwrap(),
+    contains_substring("chunk_size from the environment, threads from the environment")
+  );
+
+  fs::write(config_path, "threads = \"many\"\n").unwrap();
+  let output = barse(&["--input", input_path, "--config", config_path]);
+  expect_eq!(output.status.code(), Some(1));
+  expect_that!(
+    str::from_utf8(&output.stderr).unwrap(),
+    contains_substring(format!(
+      "{config_path}: line 1: expected a non-negative integer"
+    ))
+  );
+
+  fs::remove_file(input_path).unwrap();
+  fs::remove_file(config_path).unwrap();
+}
+
+#[gtest]
+fn test_generate_is_deterministic() {
+  let paths = [temp_path("generated-1.txt"), temp_path("generated-2.txt")];
+  for (path, threads) in paths.iter().zip(["1", "4"]) {
+    let output = barse(&[
+      "generate",
+      "--rows",
+      "200000",
+      "--seed",
+      "42",
+      "--threads",
+      threads,
+      "--out",
+      path.to_str().unwrap(),
+    ]);
+    expect_eq!(output.status.code(), Some(0));
+  }
+  let generated = fs::read(&paths[0]).unwrap();
+  expect_eq!(generated, fs::read(&paths[1]).unwrap());
+  expect_eq!(generated.iter().filter(|&&b| b == b'\n').count(), 200_000);
+
+  let output = barse(&["--input", paths[0].to_str().unwrap(), "--strict"]);
+  expect_eq!(output.status.code(), Some(0));
+  for path in paths {
+    fs::remove_file(path).unwrap();
+  }
+}
+
+#[gtest]
+fn test_bench_suppresses_summary() {
+  let input_path = generate_input("bench.txt", 1_000);
+  let input_path = input_path.to_str().unwrap();
+  let output = barse(&[
+    "bench",
+    "--input",
+    input_path,
+    "--iterations",
+    "1",
+    "--warmup",
+    "0",
+    "--format",
+    "json",
+  ]);
+  fs::remove_file(input_path).unwrap();
+
+  expect_eq!(output.status.code(), Some(0));
+  let stdout = str::from_utf8(&output.stdout).unwrap();
+  expect_that!(stdout, starts_with("{\"strategy\":"));
+  expect_that!(stdout, contains_substring("\"records\":1000,"));
+  expect_that!(stdout, not(contains_substring("Abha=")));
+
+  let output = barse(&["bench", "--input", MISSING_INPUT, "--iterations", "0"]);
+  expect_eq!(output.status.code(), Some(1));
+}
+
+#[gtest]
+fn test_verify_against_reference_parser() {
+  let input_path = generate_input("verify_reference.txt", 10_000);
+  let input_path = input_path.to_str().unwrap();
+  let mut strategies = vec!["single"];
+  if cfg!(feature = "multithreaded") {
+    strategies.push("shared-table");
+  }
+  for strategy in strategies {
+    let output = barse(&["--input", input_path, "--verify", "--strategy", strategy]);
+    expect_eq!(output.status.code(), Some(0), "{strategy}");
+    expect_that!(output.stdout, is_empty(), "{strategy}");
+    expect_eq!(
+      str::from_utf8(&output.stderr).unwrap(),
+      "OK: 5 stations match\n",
+      "{strategy}"
+    );
+  }
+  fs::remove_file(input_path).unwrap();
+}
+
+#[gtest]
+fn test_verify_reports_violations() {
+  let input_path = temp_path("verify.txt");
+  fs::write(
+    &input_path,
+    "Oslo;1.0\nAbha;12..\nHamburg;34.2\nBulawayo 8.9\nPalembang;38.8\n",
+  )
+  .unwrap();
+  let input_path = input_path.to_str().unwrap();
+  let output = barse(&["verify", "--input", input_path]);
+  let limited_output = barse(&["verify", "--input", input_path, "--max-errors", "1"]);
+  let unlimited_output = barse(&["verify", "--input", input_path, "--max-errors", "0"]);
+  fs::remove_file(input_path).unwrap();
+
+  expect_eq!(output.status.code(), Some(3));
+  expect_eq!(
+    str::from_utf8(&output.stdout).unwrap(),
+    format!(
+      "error: {input_path} @ byte 9, line 2: malformed temperature \"12..\"\n\
+       error: {input_path} @ byte 32, line 4: missing ';'\n\
+       5 records, 2 violations\n"
+    )
+  );
+  expect_eq!(limited_output.status.code(), Some(3));
+  expect_that!(
+    str::from_utf8(&limited_output.stdout).unwrap(),
+    ends_with("2 records, 1 violations (stopped at the limit, there may be more)\n")
+  );
+  expect_eq!(unlimited_output.status.code(), Some(3));
+  expect_eq!(unlimited_output.stdout, output.stdout);
+}
+
+#[gtest]
+fn test_verify_reads_stdin() {
+  let mut child = Command::new(env!("CARGO_BIN_EXE_barse"))
+    .args(["verify", "--input", "-"])
+    .stdin(Stdio::piped())
+    .stdout(Stdio::piped())
+    .spawn()
+    .unwrap();
+  child
+    .stdin
+    .take()
+    .unwrap()
+    .write_all(b"Oslo;1.0\nAbha;12.3\n")
+    .unwrap();
+  let output = child.wait_with_output().unwrap();
+
+  expect_eq!(output.status.code(), Some(0));
+  expect_eq!(
+    str::from_utf8(&output.stdout).unwrap(),
+    "2 records, 0 violations\n"
+  );
+}
+
+#[gtest]
+fn test_input_dir() {
+  let input_path = generate_input("input_dir.txt", 20_000);
+  let input = fs::read_to_string(&input_path).unwrap();
+  let split = input[..input.len() / 2].rfind('\n').unwrap() + 1;
+  let dir = temp_path("input_dir");
+  fs::create_dir_all(&dir).unwrap();
+  fs::write(dir.join("shard0.txt"), &input[..split]).unwrap();
+  fs::write(dir.join("shard1.txt"), &input[split..]).unwrap();
+  fs::write(dir.join("notes.md"), "Not;a record\n").unwrap();
+  let empty_dir = temp_path("empty_input_dir");
+  fs::create_dir_all(&empty_dir).unwrap();
+
+  let expected = barse(&["--input", input_path.to_str().unwrap()]);
+  let output = barse(&["--input-dir", dir.to_str().unwrap(), "--input-ext", "txt"]);
+  let empty_output = barse(&["--input-dir", empty_dir.to_str().unwrap()]);
+  let conflicting = barse(&["--input-dir", dir.to_str().unwrap(), "--input", "x.txt"]);
+  fs::remove_file(&input_path).unwrap();
+  fs::remove_dir_all(&dir).unwrap();
+  fs::remove_dir_all(&empty_dir).unwrap();
+
+  expect_eq!(expected.status.code(), Some(0));
+  expect_eq!(output.status.code(), Some(0));
+  expect_eq!(output.stdout, expected.stdout);
+  expect_eq!(empty_output.status.code(), Some(0));
+  expect_eq!(str::from_utf8(&empty_output.stdout).unwrap(), "{}\n");
+  expect_eq!(conflicting.status.code(), Some(1));
+}
+
+#[gtest]
+fn test_min_count_drops_stations_with_few_readings() {
+  let input_path = temp_path("min_count.txt");
+  fs::write(
+    &input_path,
+    "Hamburg;12.0\nBulawayo;8.9\nHamburg;34.2\nOslo;1.0\n",
+  )
+  .unwrap();
+  let input_path = input_path.to_str().unwrap();
+  let output = barse(&["--input", input_path, "--min-count", "2"]);
+  let global_output = barse(&["--input", input_path, "--min-count", "2", "--global"]);
+  fs::remove_file(input_path).unwrap();
+
+  expect_eq!(output.status.code(), Some(0));
+  expect_eq!(
+    str::from_utf8(&output.stdout).unwrap(),
+    "{Hamburg=12.0/23.1/34.2}\n"
+  );
+  // The dropped stations still count towards the global summary.
+  expect_that!(
+    str::from_utf8(&global_output.stderr).unwrap(),
+    starts_with("records=4, stations=3")
+  );
+}
+
+#[gtest]
+fn test_station_filter_flags() {
+  let input_path = temp_path("station_filter.txt");
+  fs::write(
+    &input_path,
+    "US/NYC;12.0\nBulawayo;8.9\nUS/LA;34.2\nOslo;1.0\nOslo;3.0\n",
+  )
+  .unwrap();
+  let input_path = input_path.to_str().unwrap();
+  let output = barse(&[
+    "--input",
+    input_path,
+    "--station",
+    "Oslo",
+    "--station-prefix",
+    "US/",
+  ]);
+  let names_output = barse(&[
+    "--input",
+    input_path,
+    "--station",
+    "Oslo",
+    "--station",
+    "Bulawayo",
+  ]);
+  let too_long = "a".repeat(51);
+  let too_long_output = barse(&["--input", input_path, "--station", &too_long]);
+  fs::remove_file(input_path).unwrap();
+
+  expect_eq!(output.status.code(), Some(0));
+  expect_eq!(
+    str::from_utf8(&output.stdout).unwrap(),
+    "{Oslo=1.0/2.0/3.0, US/LA=34.2/34.2/34.2, US/NYC=12.0/12.0/12.0}\n"
+  );
+  expect_eq!(
+    str::from_utf8(&names_output.stdout).unwrap(),
+    "{Bulawayo=8.9/8.9/8.9, Oslo=1.0/2.0/3.0}\n"
+  );
+  expect_eq!(too_long_output.status.code(), Some(1));
+  expect_that!(
+    str::from_utf8(&too_long_output.stderr).unwrap(),
+    contains_substring("is longer than 50 bytes")
+  );
+}
@@ -0,0 +1,166 @@
+//! End-to-end tests of the binary, which run it on a generated input and
+//! compare its output with the golden outputs in `tests/golden/`. Run with
+//! `BARSE_UPDATE_GOLDEN=1` to rewrite the golden outputs after an intended
+//! change to the output.
+
+use std::{
+  fs,
+  path::{Path, PathBuf},
+  process::{Command, Output},
+};
+
+use barse::{
+  generate::{Generator, Station},
+  print_summary::write_summary,
+};
+use googletest::prelude::*;
+
+const SEED: u64 = 0x901de2;
+const ROWS: u64 = 10_000;
+const MISSING_INPUT: &str = "/nonexistent/measurements.txt";
+
+fn temp_path(name: &str) -> PathBuf {
+  std::env::temp_dir().join(format!("barse_golden_{}_{name}", std::process::id()))
+}
+
+/// Writes the generated input to a temporary file. The stations are fixed
+/// here, rather than taken from the bundled station list, so that the golden
+/// outputs don't change with the list.
+fn generate_input(name: &str) -> PathBuf {
+  let stations = [
+    ("Abha", 18.0),
+    ("Bulawayo", 18.9),
+    ("Hamburg", 9.7),
+    ("Llanfairpwllgwyngyllgogerychwyrndrobwllllantysilio", 9.5),
+    ("Oslo", 5.7),
+    ("Palembang", 27.3),
+    ("São Paulo", 19.7),
+    ("Yellowknife", -4.3),
+  ]
+  .map(|(name, mean)| Station {
+    name: name.to_owned(),
+    mean,
+  });
+  let mut input = Vec::new();
+  Generator::new(&stations, stations.len(), SEED)
+    .unwrap()
+    .write(ROWS, &mut input)
+    .unwrap();
+  let input_path = temp_path(name);
+  fs::write(&input_path, input).unwrap();
+  input_path
+}
+
+fn barse(args: &[&str]) -> Output {
+  Command::new(env!("CARGO_BIN_EXE_barse"))
+    .args(args)
+    .output()
+    .unwrap()
+}
+
+fn golden_path(name: &str) -> PathBuf {
+  Path::new(env!("CARGO_MANIFEST_DIR"))
+    .join("tests/golden")
+    .join(name)
+}
+
+/// Returns the golden output `name`, first replacing it with `actual` if
+/// `BARSE_UPDATE_GOLDEN` is set.
+fn golden(name: &str, actual: &str) -> String {
+  let path = golden_path(name);
+  if std::env::var_os("BARSE_UPDATE_GOLDEN").is_some() {
+    fs::write(&path, actual).unwrap();
+  }
+  fs::read_to_string(&path).unwrap()
+}
+
+/// Runs the binary with `args` followed by `--input` and the generated input,
+/// returning its output.
+fn barse_on_generated_input(name: &str, args: &[&str]) -> Output {
+  let input_path = generate_input(name);
+  let output = barse(&[args, &["--input", input_path.to_str().unwrap()]].concat());
+  fs::remove_file(&input_path).unwrap();
+  output
+}
+
+#[gtest]
+fn test_default_args() {
+  let output = barse_on_generated_input("default.txt", &[]);
+  let stdout = str::from_utf8(&output.stdout).unwrap();
+
+  expect_eq!(output.status.code(), Some(0));
+  expect_eq!(stdout, golden("default.txt", stdout));
+  expect_eq!(str::from_utf8(&output.stderr).unwrap(), "");
+}
+
+#[gtest]
+fn test_library_matches_binary() {
+  let input_path = generate_input("library.txt");
+  let mut out = Vec::new();
+  let written = write_summary(input_path.to_str().unwrap(), &mut out);
+  fs::remove_file(&input_path).unwrap();
+
+  expect_that!(written, ok(anything()));
+  expect_eq!(
+    str::from_utf8(&out).unwrap(),
+    fs::read_to_string(golden_path("default.txt")).unwrap()
+  );
+}
+
+#[gtest]
+fn test_two_threads() {
+  // The input is small enough to be processed on one thread by default, so
+  // the multithreaded build is forced, with chunks small enough for both
+  // threads to get several.
+  #[cfg(feature = "multithreaded")]
+  let args = [
+    "--threads",
+    "2",
+    "--strategy",
+    "shared-table",
+    "--chunk-size",
+    "4K",
+  ];
+  #[cfg(not(feature = "multithreaded"))]
+  let args = ["--threads", "2"];
+  let output = barse_on_generated_input("threads.txt", &args);
+
+  expect_eq!(output.status.code(), Some(0));
+  expect_eq!(
+    str::from_utf8(&output.stdout).unwrap(),
+    fs::read_to_string(golden_path("default.txt")).unwrap()
+  );
+}
+
+#[gtest]
+fn test_csv_format() {
+  let output = barse_on_generated_input("csv.txt", &["--format", "csv"]);
+  let stdout = str::from_utf8(&output.stdout).unwrap();
+
+  expect_eq!(output.status.code(), Some(0));
+  expect_eq!(stdout, golden("csv.txt", stdout));
+}
+
+#[gtest]
+fn test_empty_file() {
+  let input_path = temp_path("empty.txt");
+  fs::write(&input_path, "").unwrap();
+  let output = barse(&["--input", input_path.to_str().unwrap()]);
+  fs::remove_file(&input_path).unwrap();
+  let stdout = str::from_utf8(&output.stdout).unwrap();
+
+  expect_eq!(output.status.code(), Some(0));
+  expect_eq!(stdout, golden("empty.txt", stdout));
+}
+
+#[gtest]
+fn test_missing_file() {
+  let output = barse(&["--input", MISSING_INPUT]);
+
+  expect_eq!(output.status.code(), Some(2));
+  expect_eq!(str::from_utf8(&output.stdout).unwrap(), "");
+  expect_that!(
+    str::from_utf8(&output.stderr).unwrap(),
+    starts_with(format!("error: {MISSING_INPUT}: "))
+  );
+}
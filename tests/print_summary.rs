@@ -0,0 +1,64 @@
+use std::{fs, path::PathBuf};
+
+use barse::{
+  options::Options,
+  print_summary::{print_summary, write_summary, write_summary_with_options, SummaryOptions},
+};
+use googletest::prelude::*;
+
+/// Includes a 50-byte name, the longest allowed, and a multi-byte character.
+const RECORDS: &str = "Hamburg;12.0\n\
+                       Bulawayo;8.9\n\
+                       Llanfairpwllgwyngyllgogerychwyrndrobwllllantysilio;-5.5\n\
+                       Palembang;38.8\n\
+                       São Paulo;25.3\n\
+                       Hamburg;34.2\n";
+const EXPECTED: &str = "{Bulawayo=8.9/8.9/8.9, Hamburg=12.0/23.1/34.2, \
+                        Llanfairpwllgwyngyllgogerychwyrndrobwllllantysilio=-5.5/-5.5/-5.5, \
+                        Palembang=38.8/38.8/38.8, São Paulo=25.3/25.3/25.3}\n";
+
+fn temp_path(name: &str) -> PathBuf {
+  std::env::temp_dir().join(format!("barse_print_summary_{}_{name}", std::process::id()))
+}
+
+#[gtest]
+fn test_print_summary_of_temp_file() {
+  let input_path = temp_path("small.txt");
+  fs::write(&input_path, RECORDS).unwrap();
+  let input_path = input_path.to_str().unwrap();
+
+  let printed = print_summary(input_path);
+  let mut out = Vec::new();
+  let written = write_summary(input_path, &mut out);
+  fs::remove_file(input_path).unwrap();
+
+  expect_that!(printed, ok(anything()));
+  expect_that!(written, ok(anything()));
+  expect_eq!(str::from_utf8(&out).unwrap(), EXPECTED);
+}
+
+#[gtest]
+fn test_summary_of_large_temp_file() {
+  // Repeating the records leaves every minimum, mean and maximum unchanged,
+  // while making the input large enough to be split between threads.
+  let input_path = temp_path("large.txt");
+  fs::write(&input_path, RECORDS.repeat(20_000)).unwrap();
+  let input_path = input_path.to_str().unwrap();
+
+  let mut build = Options::default();
+  build.threads = Some(4);
+  build.single_threaded_threshold = 0;
+  let mut out = Vec::new();
+  let written = write_summary_with_options(
+    input_path,
+    &SummaryOptions {
+      build,
+      ..SummaryOptions::default()
+    },
+    &mut out,
+  );
+  fs::remove_file(input_path).unwrap();
+
+  expect_that!(written, ok(anything()));
+  expect_eq!(str::from_utf8(&out).unwrap(), EXPECTED);
+}
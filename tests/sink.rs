@@ -0,0 +1,143 @@
+use std::{
+  alloc::{GlobalAlloc, Layout, System},
+  cell::Cell,
+  fs,
+  path::PathBuf,
+};
+
+use barse::{
+  barse::build_with_config,
+  generate::{default_stations, Generator},
+  options::BuildConfig,
+  print_summary::{write_table_summary, SortKey, SummaryOptions},
+  sink::BrcSink,
+};
+use googletest::prelude::*;
+
+/// Counts the allocations made by each thread, so that tests running
+/// concurrently don't see each other's allocations.
+struct CountingAllocator;
+
+thread_local! {
+  static ALLOCATIONS: Cell<u64> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+  unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+    let _ = ALLOCATIONS.try_with(|allocations| allocations.set(allocations.get() + 1));
+    unsafe { System.alloc(layout) }
+  }
+
+  unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+    unsafe { System.dealloc(ptr, layout) }
+  }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Returns the number of allocations made by the calling thread while
+/// running `f`.
+fn allocations_during(f: impl FnOnce()) -> u64 {
+  let before = ALLOCATIONS.with(Cell::get);
+  f();
+  ALLOCATIONS.with(Cell::get) - before
+}
+
+fn temp_path(name: &str) -> PathBuf {
+  std::env::temp_dir().join(format!("barse_sink_{}_{name}", std::process::id()))
+}
+
+/// Returns the stations of a summary in the `Brc` format, in name order.
+fn sorted_stations(summary: &str) -> Vec<&str> {
+  let mut stations = summary
+    .trim_end()
+    .trim_start_matches('{')
+    .trim_end_matches('}')
+    .split(", ")
+    .collect::<Vec<_>>();
+  stations.sort_unstable();
+  stations
+}
+
+#[gtest]
+fn test_brc_sink_allocates_nothing_per_station() {
+  let input_path = temp_path("allocations.txt");
+  let mut input = Vec::new();
+  Generator::new(&default_stations().unwrap(), 1000, 0x51_4c)
+    .unwrap()
+    .write(50_000, &mut input)
+    .unwrap();
+  fs::write(&input_path, input).unwrap();
+  let (table, report) =
+    build_with_config(input_path.to_str().unwrap(), &BuildConfig::default()).unwrap();
+  fs::remove_file(&input_path).unwrap();
+
+  let mut out = Vec::with_capacity(1 << 20);
+  let mut stations = 0;
+  let allocations = allocations_during(|| {
+    let mut sink = BrcSink::new(&mut out, true);
+    table.drain_into(&mut sink).unwrap();
+    stations = sink.stations();
+    sink.finish().unwrap();
+  });
+  expect_eq!(allocations, 0);
+  expect_eq!(stations, report.unique_stations);
+
+  // The sink writes the same stations as the sorted summary.
+  let mut sorted = Vec::new();
+  let options = SummaryOptions {
+    include_count: true,
+    ..SummaryOptions::default()
+  };
+  write_table_summary(&table, &options, &mut sorted).unwrap();
+  let (out, sorted) = (
+    str::from_utf8(&out).unwrap(),
+    str::from_utf8(&sorted).unwrap(),
+  );
+  expect_eq!(sorted_stations(out), sorted_stations(sorted));
+  expect_ne!(out, sorted);
+
+  // Writing an unsorted summary goes through the sink.
+  let mut unsorted = Vec::with_capacity(1 << 20);
+  let options = SummaryOptions {
+    sort_by: SortKey::Unsorted,
+    ..options
+  };
+  let allocations =
+    allocations_during(|| write_table_summary(&table, &options, &mut unsorted).unwrap());
+  expect_eq!(allocations, 0);
+  expect_eq!(str::from_utf8(&unsorted).unwrap(), out);
+}
+
+#[gtest]
+fn test_closure_sink_stops_at_first_error() {
+  let input_path = temp_path("closure.txt");
+  fs::write(&input_path, "Oslo;1.0\nRome;2.0\nLima;3.0\n").unwrap();
+  let (table, _) =
+    build_with_config(input_path.to_str().unwrap(), &BuildConfig::default()).unwrap();
+  fs::remove_file(&input_path).unwrap();
+
+  let mut visited = 0;
+  let mut sink = |_: &str, _: &_| {
+    visited += 1;
+    if visited == 2 {
+      Err(barse::BarseError::new("enough".to_owned()))
+    } else {
+      Ok(())
+    }
+  };
+  let result = table.drain_into(&mut sink);
+  expect_that!(result, err(anything()));
+  expect_eq!(visited, 2);
+
+  let mut names = Vec::new();
+  table
+    .drain_into(&mut |name: &str, _: &_| {
+      names.push(name.to_owned());
+      Ok(())
+    })
+    .unwrap();
+  names.sort_unstable();
+  expect_that!(names, elements_are![eq("Lima"), eq("Oslo"), eq("Rome")]);
+}